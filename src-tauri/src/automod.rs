@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use twitch_api::eventsub::automod::message::{
+    AutomodMessage, AutomodMessageFragment, AutomodMessageHoldV1Payload,
+};
+
+/// Twitch expires held messages on its own after a few minutes, so a queue
+/// this long only builds up when update events are being missed.
+const MAX_HELD_PER_CHANNEL: usize = 200;
+
+/// A chat message AutoMod is holding for review.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct HeldMessage {
+    pub message_id: String,
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+    pub text: String,
+    pub fragments: Vec<HeldFragment>,
+    /// The AutoMod category that flagged it, e.g. `swearing`.
+    pub category: String,
+    /// How strongly it matched the category, 0 (weakest) to 4.
+    pub level: u8,
+    pub held_at: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HeldFragment {
+    Text { text: String },
+    Emote { text: String, id: String },
+    Cheermote { text: String },
+}
+
+impl HeldMessage {
+    pub fn new(held: &AutomodMessageHoldV1Payload) -> Self {
+        HeldMessage {
+            message_id: held.message_id.to_string(),
+            broadcaster_user_id: held.broadcaster_user_id.to_string(),
+            broadcaster_user_login: held.broadcaster_user_login.to_string(),
+            user_id: held.user_id.to_string(),
+            user_login: held.user_login.to_string(),
+            user_name: held.user_name.to_string(),
+            text: held.message.text.clone(),
+            fragments: fragments(&held.message),
+            category: serde_json::to_value(&held.category)
+                .ok()
+                .and_then(|value| value.as_str().map(str::to_owned))
+                .unwrap_or_default(),
+            level: held.level,
+            held_at: held.held_at.to_string(),
+        }
+    }
+}
+
+fn fragments(message: &AutomodMessage) -> Vec<HeldFragment> {
+    message
+        .fragments
+        .iter()
+        .map(|fragment| match fragment {
+            AutomodMessageFragment::Emote { text, emote } => HeldFragment::Emote {
+                text: text.clone(),
+                id: emote.id.to_string(),
+            },
+            AutomodMessageFragment::Cheermote { text, .. } => {
+                HeldFragment::Cheermote { text: text.clone() }
+            }
+            other => HeldFragment::Text {
+                text: other.text().to_owned(),
+            },
+        })
+        .collect()
+}
+
+/// Held messages per broadcaster id, oldest first.
+#[derive(Clone, Default)]
+pub struct AutomodQueue(Arc<Mutex<HashMap<String, Vec<HeldMessage>>>>);
+
+impl AutomodQueue {
+    /// Adds `message` and returns the channel's queue.
+    pub fn hold(&self, message: HeldMessage) -> Vec<HeldMessage> {
+        let mut channels = self.0.lock().unwrap();
+        let queue = channels
+            .entry(message.broadcaster_user_id.clone())
+            .or_default();
+        queue.retain(|held| held.message_id != message.message_id);
+        queue.push(message);
+        if queue.len() > MAX_HELD_PER_CHANNEL {
+            let excess = queue.len() - MAX_HELD_PER_CHANNEL;
+            queue.drain(..excess);
+        }
+        queue.clone()
+    }
+
+    /// Drops `message_id` once it's been approved, denied or expired, wherever
+    /// that happened, returning it and the channel's remaining queue.
+    pub fn resolve(&self, message_id: &str) -> Option<(HeldMessage, Vec<HeldMessage>)> {
+        let mut channels = self.0.lock().unwrap();
+        channels.values_mut().find_map(|queue| {
+            let position = queue
+                .iter()
+                .position(|held| held.message_id == message_id)?;
+            let resolved = queue.remove(position);
+            Some((resolved, queue.clone()))
+        })
+    }
+
+    pub fn pending(&self, broadcaster_id: &str) -> Vec<HeldMessage> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(broadcaster_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Forgets a channel's queue when we stop watching it, since its update
+    /// events stop arriving too.
+    pub fn clear_channel(&self, channel_login: &str) {
+        self.0.lock().unwrap().retain(|_, queue| {
+            queue.first().is_some_and(|held| {
+                !held
+                    .broadcaster_user_login
+                    .eq_ignore_ascii_case(channel_login)
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(channel: &str, id: &str) -> HeldMessage {
+        HeldMessage {
+            message_id: id.to_string(),
+            broadcaster_user_id: channel.to_string(),
+            broadcaster_user_login: channel.to_string(),
+            user_id: "42".to_string(),
+            user_login: "viewer".to_string(),
+            user_name: "Viewer".to_string(),
+            text: "held text".to_string(),
+            fragments: Vec::new(),
+            category: "swearing".to_string(),
+            level: 2,
+            held_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn holds_per_channel_and_resolves_from_anywhere() {
+        let queue = AutomodQueue::default();
+        queue.hold(held("forsen", "a"));
+        queue.hold(held("forsen", "b"));
+        queue.hold(held("xqc", "c"));
+
+        let (resolved, remaining) = queue.resolve("a").unwrap();
+        assert_eq!(resolved.message_id, "a");
+        assert_eq!(remaining, vec![held("forsen", "b")]);
+        assert!(queue.resolve("a").is_none());
+        assert_eq!(queue.pending("xqc"), vec![held("xqc", "c")]);
+
+        queue.clear_channel("XQC");
+        assert!(queue.pending("xqc").is_empty());
+        assert_eq!(queue.pending("forsen").len(), 1);
+    }
+
+    #[test]
+    fn redelivered_holds_are_not_duplicated_and_queue_is_bounded() {
+        let queue = AutomodQueue::default();
+        queue.hold(held("forsen", "a"));
+        assert_eq!(queue.hold(held("forsen", "a")).len(), 1);
+
+        for i in 0..MAX_HELD_PER_CHANNEL {
+            queue.hold(held("forsen", &i.to_string()));
+        }
+        let pending = queue.pending("forsen");
+        assert_eq!(pending.len(), MAX_HELD_PER_CHANNEL);
+        assert_eq!(pending[0].message_id, "0");
+    }
+
+    #[test]
+    fn parses_held_payload() {
+        let payload: AutomodMessageHoldV1Payload = serde_json::from_value(serde_json::json!({
+            "broadcaster_user_id": "129546453",
+            "broadcaster_user_login": "nerixyz",
+            "broadcaster_user_name": "nerixyz",
+            "user_id": "489584266",
+            "user_login": "uint128",
+            "user_name": "uint128",
+            "message_id": "332e99ac-e19c-4368-a15b-793e8266b51f",
+            "message": {
+                "text": "bad Kappa",
+                "fragments": [
+                    {"type": "text", "text": "bad ", "cheermote": null, "emote": null},
+                    {
+                        "type": "emote",
+                        "text": "Kappa",
+                        "cheermote": null,
+                        "emote": {"id": "25", "emote_set_id": "0"}
+                    }
+                ]
+            },
+            "category": "swearing",
+            "level": 4,
+            "held_at": "2024-10-19T20:11:16.799750627Z"
+        }))
+        .unwrap();
+
+        let message = HeldMessage::new(&payload);
+        assert_eq!(message.category, "swearing");
+        assert_eq!(message.level, 4);
+        assert_eq!(
+            message.fragments,
+            vec![
+                HeldFragment::Text {
+                    text: "bad ".to_string()
+                },
+                HeldFragment::Emote {
+                    text: "Kappa".to_string(),
+                    id: "25".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{
+    badgemanager::BadgeManager,
+    emote::providers::http::{fetch_json, FetchOutcome, ProviderFetchError},
+    emotemanager::EmoteManager,
+    highlight::HighlightSet,
+    types::{AppSettings, BackfillSettings, ChannelMessage, ChannelMessageType},
+};
+
+const BACKFILL_PROVIDER: &str = "RecentMessages";
+/// Message ids remembered per channel for de-duplication. Comfortably above
+/// the largest backfill the service hands out.
+const SEEN_IDS_PER_CHANNEL: usize = 2_000;
+
+#[derive(Debug, Deserialize)]
+struct RecentMessagesResponse {
+    #[serde(default)]
+    messages: Vec<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Message ids already shown per channel, shared by the live EventSub loop and
+/// the join backfill so neither emits a message the other already did.
+#[derive(Clone, Default)]
+pub struct SeenMessageIds(Arc<Mutex<HashMap<String, SeenChannel>>>);
+
+#[derive(Default)]
+struct SeenChannel {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl SeenMessageIds {
+    /// Returns `false` if `message_id` was already seen in `channel_login`.
+    pub fn insert(&self, channel_login: &str, message_id: &str) -> bool {
+        let mut channels = self.0.lock().unwrap();
+        let channel = channels.entry(channel_login.to_lowercase()).or_default();
+        if !channel.ids.insert(message_id.to_owned()) {
+            return false;
+        }
+        channel.order.push_back(message_id.to_owned());
+        while channel.order.len() > SEEN_IDS_PER_CHANNEL {
+            if let Some(oldest) = channel.order.pop_front() {
+                channel.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// A chat message decoded from an IRCv3 `PRIVMSG` line.
+#[derive(Debug, Clone)]
+pub struct Privmsg {
+    pub message_id: String,
+    pub sent_at_ms: i64,
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    pub chatter_user_id: String,
+    pub chatter_user_login: String,
+    pub chatter_user_name: String,
+    pub text: String,
+    pub color: String,
+    pub message_type: ChannelMessageType,
+    /// `(set_id, id, info)` for each entry of the `badges` tag.
+    pub badges: Vec<(String, String, String)>,
+    /// `(emote_id, name)` for each distinct Twitch emote in the text.
+    pub emotes: Vec<(String, String)>,
+}
+
+impl Privmsg {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = IrcLine::parse(line)?;
+        if line.command != "PRIVMSG" {
+            return None;
+        }
+        let tag = |key: &str| line.tags.get(key).cloned().unwrap_or_default();
+        let message_id = tag("id");
+        if message_id.is_empty() {
+            return None;
+        }
+
+        let raw_text = line.trailing?;
+        let text = raw_text
+            .strip_prefix("\u{1}ACTION ")
+            .map(|action| action.strip_suffix('\u{1}').unwrap_or(action))
+            .unwrap_or(raw_text)
+            .to_owned();
+        let chatter_user_login = line
+            .prefix
+            .and_then(|prefix| prefix.split('!').next())
+            .unwrap_or_default()
+            .to_owned();
+        let chatter_user_name = match tag("display-name") {
+            name if name.is_empty() => chatter_user_login.clone(),
+            name => name,
+        };
+        let broadcaster_user_login = line
+            .params
+            .first()
+            .map(|channel| channel.trim_start_matches('#').to_lowercase())
+            .unwrap_or_default();
+
+        let badge_info: HashMap<&str, &str> = line
+            .tags
+            .get("badge-info")
+            .map(|info| info.split(',').filter_map(|b| b.split_once('/')).collect())
+            .unwrap_or_default();
+        let badges = line
+            .tags
+            .get("badges")
+            .map(|badges| {
+                badges
+                    .split(',')
+                    .filter_map(|badge| badge.split_once('/'))
+                    .map(|(set_id, id)| {
+                        let info = badge_info.get(set_id).copied().unwrap_or_default();
+                        (set_id.to_owned(), id.to_owned(), info.to_owned())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let message_type = match tag("msg-id").as_str() {
+            "highlighted-message" => ChannelMessageType::ChannelPointsHighlighted,
+            "skip-subs-mode-message" => ChannelMessageType::ChannelPointsSubOnly,
+            "user-intro" => ChannelMessageType::UserIntro,
+            "gigantified-emote-message" => ChannelMessageType::PowerUpsGigantifiedEmote,
+            "animated-message" => ChannelMessageType::PowerUpsMessageEffect,
+            _ => ChannelMessageType::Text,
+        };
+
+        Some(Privmsg {
+            message_id,
+            sent_at_ms: tag("tmi-sent-ts").parse().unwrap_or_default(),
+            broadcaster_user_id: tag("room-id"),
+            broadcaster_user_login,
+            chatter_user_id: tag("user-id"),
+            chatter_user_login,
+            chatter_user_name,
+            emotes: emotes_by_range(&tag("emotes"), &text),
+            text,
+            color: tag("color"),
+            message_type,
+            badges,
+        })
+    }
+}
+
+/// Resolves the `emotes` tag (`id:start-end,start-end/id:...`, code point
+/// offsets) to emote names taken from `text`.
+fn emotes_by_range(tag: &str, text: &str) -> Vec<(String, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut emotes = Vec::new();
+    for entry in tag.split('/').filter(|entry| !entry.is_empty()) {
+        let Some((id, ranges)) = entry.split_once(':') else {
+            continue;
+        };
+        let name = ranges.split(',').find_map(|range| {
+            let (start, end) = range.split_once('-')?;
+            let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+            (start <= end && end < chars.len()).then(|| chars[start..=end].iter().collect())
+        });
+        match name {
+            Some(name) => emotes.push((id.to_owned(), name)),
+            None => debug!(id, ranges, "ignoring out-of-range emote tag"),
+        }
+    }
+    emotes
+}
+
+struct IrcLine<'a> {
+    tags: HashMap<&'a str, String>,
+    prefix: Option<&'a str>,
+    command: &'a str,
+    params: Vec<&'a str>,
+    trailing: Option<&'a str>,
+}
+
+impl<'a> IrcLine<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let mut tags = HashMap::new();
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (raw_tags, remainder) = tagged.split_once(' ')?;
+            for tag in raw_tags.split(';') {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                tags.insert(key, unescape_tag_value(value));
+            }
+            rest = remainder.trim_start();
+        }
+
+        let mut prefix = None;
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (value, remainder) = prefixed.split_once(' ')?;
+            prefix = Some(value);
+            rest = remainder.trim_start();
+        }
+
+        let (command, mut rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        if command.is_empty() {
+            return None;
+        }
+        let mut params = Vec::new();
+        let mut trailing = None;
+        while !rest.is_empty() {
+            if let Some(value) = rest.strip_prefix(':') {
+                trailing = Some(value);
+                break;
+            }
+            let (param, remainder) = rest.split_once(' ').unwrap_or((rest, ""));
+            params.push(param);
+            rest = remainder.trim_start();
+        }
+
+        Some(IrcLine {
+            tags,
+            prefix,
+            command,
+            params,
+            trailing,
+        })
+    }
+}
+
+fn unescape_tag_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// One HTTP client shared by every join's backfill, rebuilt only after the
+/// timeouts it was built with change.
+#[derive(Clone, Default)]
+pub struct BackfillClient(Arc<Mutex<Option<(ClientTimeouts, reqwest::Client)>>>);
+
+/// `(connect, request)` timeouts.
+type ClientTimeouts = (Duration, Duration);
+
+impl BackfillClient {
+    fn get(&self, settings: &AppSettings) -> Result<reqwest::Client, reqwest::Error> {
+        let timeouts = (
+            settings.providers.http_connect_timeout(),
+            settings.backfill.request_timeout(),
+        );
+        let mut cached = self.0.lock().unwrap();
+        if let Some((built_with, client)) = cached.as_ref() {
+            if *built_with == timeouts {
+                return Ok(client.clone());
+            }
+        }
+        let client = reqwest::Client::builder()
+            .connect_timeout(timeouts.0)
+            .timeout(timeouts.1)
+            .build()?;
+        *cached = Some((timeouts, client.clone()));
+        Ok(client)
+    }
+}
+
+/// Fetches the raw IRC lines the recent-messages service holds for `login`.
+async fn fetch_recent_lines(
+    client: &reqwest::Client,
+    settings: &BackfillSettings,
+    login: &str,
+) -> Result<Vec<String>, ProviderFetchError> {
+    let url = format!(
+        "{}/{}?limit={}",
+        settings.base_url.trim_end_matches('/'),
+        login,
+        settings.message_limit
+    );
+    let response =
+        match fetch_json::<RecentMessagesResponse>(client, BACKFILL_PROVIDER, login, &url, None)
+            .await?
+        {
+            FetchOutcome::Fresh { value, .. } => value,
+            FetchOutcome::NotModified { .. } => return Ok(Vec::new()),
+        };
+    if let Some(error) = response.error {
+        warn!(login, "recent-messages service reported: {}", error);
+    }
+    Ok(response.messages)
+}
+
+/// Loads the recent messages for `login` as [`ChannelMessage`]s flagged
+/// `is_backfill`, oldest first, paired with the time they were sent.
+pub async fn recent_messages(
+    client: &BackfillClient,
+    login: &str,
+    bm: &BadgeManager,
+    em: &EmoteManager,
+    highlights: &HighlightSet,
+    settings: &AppSettings,
+) -> Result<Vec<(i64, ChannelMessage)>, ProviderFetchError> {
+    let client = client
+        .get(settings)
+        .map_err(|source| ProviderFetchError::Request {
+            provider: BACKFILL_PROVIDER.to_owned(),
+            scope: login.to_owned(),
+            url: settings.backfill.base_url.clone(),
+            elapsed: Duration::ZERO,
+            source,
+        })?;
+    let lines = fetch_recent_lines(&client, &settings.backfill, login).await?;
+
+    let mut messages = Vec::with_capacity(lines.len());
+    for line in &lines {
+        let Some(privmsg) = Privmsg::parse(line) else {
+            continue;
+        };
+        let sent_at_ms = privmsg.sent_at_ms;
+        let message = ChannelMessage::from_privmsg(
+            privmsg,
+            bm.clone(),
+            em.clone(),
+            &settings.emotes,
+            highlights,
+        )
+        .await;
+        messages.push((sent_at_ms, message));
+    }
+    debug!(
+        login,
+        lines = lines.len(),
+        messages = messages.len(),
+        "loaded recent messages"
+    );
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use httpmock::prelude::*;
+
+    use super::*;
+
+    const LINE: &str = "@badge-info=subscriber/14;badges=subscriber/12,premium/1;color=#1E90FF;display-name=Viewer\\sOne;emotes=25:6-10,17-21/emotesv2_abc:12-15;historical=1;id=m-1;msg-id=highlighted-message;room-id=22484632;tmi-sent-ts=1700000000123;user-id=42 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #Forsen :hello Kappa PogU Kappa";
+
+    #[test]
+    fn parses_privmsg_tags() {
+        let privmsg = Privmsg::parse(LINE).unwrap();
+
+        assert_eq!(privmsg.message_id, "m-1");
+        assert_eq!(privmsg.sent_at_ms, 1_700_000_000_123);
+        assert_eq!(privmsg.broadcaster_user_id, "22484632");
+        assert_eq!(privmsg.broadcaster_user_login, "forsen");
+        assert_eq!(privmsg.chatter_user_id, "42");
+        assert_eq!(privmsg.chatter_user_login, "viewer");
+        assert_eq!(privmsg.chatter_user_name, "Viewer One");
+        assert_eq!(privmsg.text, "hello Kappa PogU Kappa");
+        assert_eq!(privmsg.color, "#1E90FF");
+        assert!(matches!(
+            privmsg.message_type,
+            ChannelMessageType::ChannelPointsHighlighted
+        ));
+        assert_eq!(
+            privmsg.badges,
+            vec![
+                ("subscriber".to_owned(), "12".to_owned(), "14".to_owned()),
+                ("premium".to_owned(), "1".to_owned(), String::new()),
+            ]
+        );
+        assert_eq!(
+            privmsg.emotes,
+            vec![
+                ("25".to_owned(), "Kappa".to_owned()),
+                ("emotesv2_abc".to_owned(), "PogU".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_actions_and_multibyte_emote_ranges() {
+        let line = "@emotes=25:3-7;id=m-2;room-id=1;tmi-sent-ts=1 :a!a@a PRIVMSG #b :\u{1}ACTION ой Kappa\u{1}";
+        let privmsg = Privmsg::parse(line).unwrap();
+
+        assert_eq!(privmsg.text, "ой Kappa");
+        assert_eq!(privmsg.chatter_user_name, "a");
+        assert_eq!(privmsg.emotes, vec![("25".to_owned(), "Kappa".to_owned())]);
+    }
+
+    #[test]
+    fn ignores_other_commands_and_untagged_lines() {
+        assert!(Privmsg::parse(
+            "@id=x;room-id=1;tmi-sent-ts=1 :tmi.twitch.tv CLEARCHAT #forsen :viewer"
+        )
+        .is_none());
+        assert!(Privmsg::parse(":viewer!viewer@viewer PRIVMSG #forsen :hi").is_none());
+        assert!(Privmsg::parse("").is_none());
+    }
+
+    #[test]
+    fn unescapes_tag_values() {
+        assert_eq!(unescape_tag_value(r"a\sb\:c\\d\ne\"), "a b;c\\d\ne");
+    }
+
+    #[test]
+    fn seen_ids_dedupe_per_channel_and_forget_oldest() {
+        let seen = SeenMessageIds::default();
+
+        assert!(seen.insert("forsen", "a"));
+        assert!(!seen.insert("Forsen", "a"));
+        assert!(seen.insert("xqc", "a"));
+
+        for i in 0..SEEN_IDS_PER_CHANNEL {
+            seen.insert("forsen", &i.to_string());
+        }
+        assert!(seen.insert("forsen", "a"));
+    }
+
+    #[tokio::test]
+    async fn fetches_lines_from_configured_service() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v2/recent-messages/forsen")
+                .query_param("limit", "50");
+            then.status(200).json_body(serde_json::json!({
+                "messages": [LINE],
+                "error": null,
+                "error_code": null
+            }));
+        });
+        let settings = BackfillSettings {
+            base_url: server.url("/api/v2/recent-messages/"),
+            message_limit: 50,
+            ..Default::default()
+        };
+
+        let lines = fetch_recent_lines(&reqwest::Client::new(), &settings, "forsen")
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(lines, vec![LINE.to_owned()]);
+    }
+}
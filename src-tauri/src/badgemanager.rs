@@ -5,14 +5,25 @@ use tracing::{debug, error, trace};
 
 use crate::{
     badgepersist::{SharedBadgeMetadataStore, TauriBadgeMetadataStore},
-    emote::providers::GLOBAL_SCOPE_KEY,
+    emote::providers::{
+        policy::{settled, PolicyError, ProviderHealth},
+        GLOBAL_SCOPE_KEY,
+    },
     token::TokenManager,
     types::ProviderSettings,
 };
-use twitch_api::helix::chat::{get_channel_chat_badges, get_global_chat_badges};
+use twitch_api::helix::{
+    chat::{get_channel_chat_badges, get_global_chat_badges},
+    ClientRequestError,
+};
 
 type SharedMap<V> = Arc<Mutex<HashMap<String, V>>>;
 type Scope<T> = HashMap<String, T>;
+type HelixClient = twitch_api::HelixClient<'static, reqwest::Client>;
+type BadgeFetchError = PolicyError<ClientRequestError<reqwest::Error>>;
+
+/// Name the badge fetch breaker logs under.
+const BADGE_PROVIDER: &str = "TwitchBadges";
 
 #[derive(Debug, Clone, Serialize, Deserialize, specta::Type, PartialEq)]
 pub struct BadgeSet {
@@ -77,6 +88,7 @@ pub struct Badge {
 pub struct BadgeManager {
     token_manager: Option<TokenManager>,
     persistence: SharedBadgeMetadataStore,
+    health: ProviderHealth,
     pub global_badges: SharedMap<BadgeSet>,
     pub scoped_badges: SharedMap<Scope<BadgeSet>>,
 }
@@ -86,6 +98,7 @@ impl BadgeManager {
         BadgeManager {
             token_manager: Some(token_manager),
             persistence: Arc::new(TauriBadgeMetadataStore::new(app_handle)),
+            health: Default::default(),
             global_badges: Arc::new(Mutex::new(HashMap::new())),
             scoped_badges: Default::default(),
         }
@@ -96,6 +109,7 @@ impl BadgeManager {
         BadgeManager {
             token_manager: None,
             persistence,
+            health: Default::default(),
             global_badges: Arc::new(Mutex::new(HashMap::new())),
             scoped_badges: Default::default(),
         }
@@ -153,25 +167,83 @@ impl BadgeManager {
 
     pub async fn load_global(
         &self,
-        client: twitch_api::HelixClient<'static, reqwest::Client>,
+        client: HelixClient,
         provider_settings: &ProviderSettings,
     ) -> Result<(), String> {
         self.hydrate_global(provider_settings).await;
 
-        let req = get_global_chat_badges::GetGlobalChatBadgesRequest::new();
+        let result = self.refresh_global(&client, provider_settings).await;
+        if !settled(&result) {
+            let manager = self.clone();
+            let settings = *provider_settings;
+            self.health.schedule_retry(
+                BADGE_PROVIDER,
+                GLOBAL_SCOPE_KEY,
+                provider_settings,
+                move || {
+                    let manager = manager.clone();
+                    let client = client.clone();
+                    async move { settled(&manager.refresh_global(&client, &settings).await) }
+                },
+            );
+        }
+        result.map_err(|err| err.to_string())
+    }
+
+    pub async fn load_channel(
+        self,
+        broadcaster_id: String,
+        client: HelixClient,
+        provider_settings: &ProviderSettings,
+    ) {
+        debug!(broadcaster_id, "loading channel");
+        self.hydrate_channel(&broadcaster_id, provider_settings)
+            .await;
 
+        debug!(broadcaster_id, "refreshing channel badges");
+        let result = self
+            .refresh_channel(&broadcaster_id, &client, provider_settings)
+            .await;
+        if let Err(err) = &result {
+            error!(broadcaster_id, "failed to load channel badges: err={}", err);
+        }
+        if !settled(&result) {
+            let manager = self.clone();
+            let settings = *provider_settings;
+            let scope = broadcaster_id.clone();
+            self.health
+                .schedule_retry(BADGE_PROVIDER, &scope, provider_settings, move || {
+                    let manager = manager.clone();
+                    let client = client.clone();
+                    let broadcaster_id = broadcaster_id.clone();
+                    async move {
+                        settled(
+                            &manager
+                                .refresh_channel(&broadcaster_id, &client, &settings)
+                                .await,
+                        )
+                    }
+                });
+        }
+    }
+
+    async fn refresh_global(
+        &self,
+        client: &HelixClient,
+        provider_settings: &ProviderSettings,
+    ) -> Result<(), BadgeFetchError> {
         debug!("getting global badges");
-        let Some(token_manager) = &self.token_manager else {
-            return Err("no active token".to_owned());
-        };
-        let Some(token) = token_manager.active_twitch_token().await else {
-            return Err("no active token".to_owned());
-        };
-        let response: Vec<twitch_api::helix::chat::BadgeSet> =
-            match client.req_get(req, &token).await {
-                Ok(resp) => resp.data,
-                Err(err) => return Err(err.to_string()),
-            };
+        let token = self.active_token().await?;
+        let response = self
+            .health
+            .fetch(BADGE_PROVIDER, provider_settings, || {
+                client.req_get(
+                    get_global_chat_badges::GetGlobalChatBadgesRequest::new(),
+                    &token,
+                )
+            })
+            .await?
+            .data;
 
         let badge_sets = response
             .into_iter()
@@ -186,50 +258,28 @@ impl BadgeManager {
         Ok(())
     }
 
-    pub async fn load_channel(
-        self,
-        broadcaster_id: String,
-        client: twitch_api::HelixClient<'static, reqwest::Client>,
+    async fn refresh_channel(
+        &self,
+        broadcaster_id: &str,
+        client: &HelixClient,
         provider_settings: &ProviderSettings,
-    ) {
-        debug!(broadcaster_id, "loading channel");
-        self.hydrate_channel(&broadcaster_id, provider_settings)
-            .await;
-
-        let mut badges: HashMap<String, BadgeSet> = Default::default();
-        debug!(broadcaster_id, "refreshing channel badges");
-        let req = get_channel_chat_badges::GetChannelChatBadgesRequest::broadcaster_id(
-            broadcaster_id.clone(),
-        );
-
+    ) -> Result<(), BadgeFetchError> {
         debug!(broadcaster_id, "getting badges");
-        let Some(token_manager) = &self.token_manager else {
-            error!(
-                broadcaster_id,
-                "failed to load channel badges: no active token"
-            );
-            return;
-        };
-        let Some(token) = token_manager.active_twitch_token().await else {
-            error!(
-                broadcaster_id,
-                "failed to load channel badges: no active token"
-            );
-            return;
-        };
-        let response: Vec<twitch_api::helix::chat::BadgeSet> =
-            match client.req_get(req, &token).await {
-                Ok(resp) => resp.data,
-                Err(err) => {
-                    error!(
+        let token = self.active_token().await?;
+        let response = self
+            .health
+            .fetch(BADGE_PROVIDER, provider_settings, || {
+                client.req_get(
+                    get_channel_chat_badges::GetChannelChatBadgesRequest::broadcaster_id(
                         broadcaster_id,
-                        "failed to load channel badges: err={}",
-                        err.to_string()
-                    );
-                    return;
-                }
-            };
+                    ),
+                    &token,
+                )
+            })
+            .await?
+            .data;
 
+        let mut badges: HashMap<String, BadgeSet> = Default::default();
         for b in response {
             let new_b = BadgeSet::from(b.clone());
             debug!(broadcaster_id, "adding badgeset: badgeset={:?}", b);
@@ -237,11 +287,24 @@ impl BadgeManager {
         }
 
         self.store_channel_badges(
-            broadcaster_id,
+            broadcaster_id.to_string(),
             scope_to_badge_sets(badges),
             provider_settings,
         )
         .await;
+        Ok(())
+    }
+
+    /// A missing token isn't something retrying fixes; the next login reloads
+    /// badges anyway.
+    async fn active_token(&self) -> Result<twitch_oauth2::UserToken, BadgeFetchError> {
+        let token = match &self.token_manager {
+            Some(token_manager) => token_manager.active_twitch_token().await,
+            None => None,
+        };
+        token.ok_or_else(|| {
+            PolicyError::Failed(ClientRequestError::Custom("no active token".into()))
+        })
     }
 
     pub async fn get(self, set_id: String, channel: String) -> Option<BadgeSet> {
@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// How long a channel's messages are held, and how many at most, before they
+/// go out together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchLimits {
+    pub window: Duration,
+    pub max_messages: usize,
+}
+
+struct Pending<T> {
+    messages: Vec<T>,
+    /// Bumped on every flush so a window timer only flushes the batch it was
+    /// started for.
+    generation: u64,
+    /// Held across a flush so the channel's batches go out in the order they
+    /// were taken.
+    order: Arc<Mutex<()>>,
+}
+
+impl<T> Default for Pending<T> {
+    fn default() -> Self {
+        Pending {
+            messages: Vec::new(),
+            generation: 0,
+            order: Default::default(),
+        }
+    }
+}
+
+type Channels<T> = HashMap<String, Pending<T>>;
+
+type Flush<T> = Arc<dyn Fn(&str, Vec<T>) + Send + Sync>;
+
+/// Coalesces each channel's messages into one `flush` per window, or sooner
+/// once `max_messages` are waiting.
+pub struct MessageBatcher<T> {
+    channels: Arc<Mutex<Channels<T>>>,
+    flush: Flush<T>,
+}
+
+impl<T> Clone for MessageBatcher<T> {
+    fn clone(&self) -> Self {
+        MessageBatcher {
+            channels: self.channels.clone(),
+            flush: self.flush.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> MessageBatcher<T> {
+    pub fn new(flush: impl Fn(&str, Vec<T>) + Send + Sync + 'static) -> Self {
+        MessageBatcher {
+            channels: Default::default(),
+            flush: Arc::new(flush),
+        }
+    }
+
+    pub fn push(&self, login: &str, message: T, limits: BatchLimits) {
+        let mut channels = self.channels.lock().unwrap();
+        let pending = channels.entry(login.to_owned()).or_default();
+        pending.messages.push(message);
+        if pending.messages.len() >= limits.max_messages.max(1) {
+            self.flush_locked(channels, login);
+            return;
+        }
+        let timer = (pending.messages.len() == 1).then_some(pending.generation);
+        drop(channels);
+
+        if let Some(generation) = timer {
+            let batcher = self.clone();
+            let login = login.to_owned();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(limits.window).await;
+                batcher.flush_window(&login, generation);
+            });
+        }
+    }
+
+    fn flush_window(&self, login: &str, generation: u64) {
+        let channels = self.channels.lock().unwrap();
+        let Some(pending) = channels.get(login) else {
+            return;
+        };
+        if pending.generation != generation || pending.messages.is_empty() {
+            return;
+        }
+        self.flush_locked(channels, login);
+    }
+
+    /// Takes the channel's waiting messages and flushes them. The ordering
+    /// guard is taken before `channels` is released, so a later batch can't
+    /// overtake this one while other channels keep batching.
+    fn flush_locked(&self, mut channels: MutexGuard<'_, Channels<T>>, login: &str) {
+        let Some(pending) = channels.get_mut(login) else {
+            return;
+        };
+        pending.generation += 1;
+        let messages = mem::take(&mut pending.messages);
+        let order = pending.order.clone();
+        let _order = order.lock().unwrap();
+        drop(channels);
+        (self.flush)(login, messages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Flushed = Arc<Mutex<Vec<(String, Vec<u32>)>>>;
+
+    fn batcher() -> (MessageBatcher<u32>, Flushed) {
+        let flushed = Flushed::default();
+        let sink = flushed.clone();
+        let batcher = MessageBatcher::new(move |login: &str, messages| {
+            sink.lock().unwrap().push((login.to_owned(), messages));
+        });
+        (batcher, flushed)
+    }
+
+    #[tokio::test]
+    async fn flushes_when_a_batch_fills_up() {
+        let (batcher, flushed) = batcher();
+        let limits = BatchLimits {
+            window: Duration::from_secs(60),
+            max_messages: 3,
+        };
+        for n in 0..7 {
+            batcher.push("maya", n, limits);
+        }
+        batcher.push("luna", 100, limits);
+
+        assert_eq!(
+            *flushed.lock().unwrap(),
+            vec![
+                ("maya".to_owned(), vec![0, 1, 2]),
+                ("maya".to_owned(), vec![3, 4, 5]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn flushes_each_channel_once_per_window() {
+        let (batcher, flushed) = batcher();
+        let limits = BatchLimits {
+            window: Duration::from_millis(20),
+            max_messages: 100,
+        };
+        batcher.push("maya", 1, limits);
+        batcher.push("luna", 2, limits);
+        batcher.push("maya", 3, limits);
+        assert!(flushed.lock().unwrap().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let mut flushed = flushed.lock().unwrap().clone();
+        flushed.sort();
+        assert_eq!(
+            flushed,
+            vec![
+                ("luna".to_owned(), vec![2]),
+                ("maya".to_owned(), vec![1, 3]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,521 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+
+use eyre::WrapErr;
+use tracing::{debug, warn};
+
+use crate::{
+    date::{self, rfc3339_from_unix_ms, DAY_MS},
+    history::{HistoryEntry, HistoryStore, ModerationAction},
+    logging, modlog,
+    types::{ChatLogFormat, ChatLogSettings},
+};
+
+/// Used under the app data directory when no log directory is configured.
+pub const DEFAULT_LOG_DIR: &str = "chat-logs";
+const EXPORT_DIR: &str = "exports";
+const WRITE_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type ChatLogSettingsReader = Arc<dyn Fn() -> ChatLogSettings + Send + Sync>;
+
+/// The configured log directory, or `default_root` when none is set.
+pub fn log_root(settings: &ChatLogSettings, default_root: &Path) -> PathBuf {
+    if settings.directory.is_empty() {
+        default_root.to_path_buf()
+    } else {
+        PathBuf::from(&settings.directory)
+    }
+}
+
+fn extension(format: ChatLogFormat) -> &'static str {
+    match format {
+        ChatLogFormat::Jsonl => "jsonl",
+        ChatLogFormat::Plain => "txt",
+        ChatLogFormat::Chatterino => "log",
+    }
+}
+
+/// Chatterino writes its logs in local time; the other formats stay in UTC.
+fn utc_offset_ms(format: ChatLogFormat, unix_ms: i64) -> i64 {
+    match format {
+        ChatLogFormat::Chatterino => date::local_offset_ms(unix_ms),
+        ChatLogFormat::Jsonl | ChatLogFormat::Plain => 0,
+    }
+}
+
+/// `(YYYY-MM-DD, HH:MM:SS)` on the clock `format` is written in.
+fn date_and_time(format: ChatLogFormat, unix_ms: i64) -> (String, String) {
+    let timestamp = rfc3339_from_unix_ms(unix_ms + utc_offset_ms(format, unix_ms));
+    (timestamp[..10].to_owned(), timestamp[11..19].to_owned())
+}
+
+fn daily_path(root: &Path, format: ChatLogFormat, channel_login: &str, date: &str) -> PathBuf {
+    let dir = match format {
+        ChatLogFormat::Chatterino => root.join("Twitch").join("Channels").join(channel_login),
+        ChatLogFormat::Jsonl | ChatLogFormat::Plain => root.join(channel_login),
+    };
+    dir.join(format!("{channel_login}-{date}.{}", extension(format)))
+}
+
+fn chatterino_header(unix_ms: i64) -> String {
+    let format = ChatLogFormat::Chatterino;
+    let (date, time) = date_and_time(format, unix_ms);
+    let zone = date::offset_label(utc_offset_ms(format, unix_ms));
+    format!("# Start logging at {date} {time} {zone}")
+}
+
+/// Renders `entry` as one line of `format`, or `None` if the format has no
+/// representation for it (JSON Lines only carries chat messages).
+pub fn format_entry(
+    format: ChatLogFormat,
+    entry: &HistoryEntry,
+    sent_at_ms: i64,
+) -> eyre::Result<Option<String>> {
+    let (date, time) = date_and_time(format, sent_at_ms);
+    Ok(match format {
+        ChatLogFormat::Jsonl => match entry {
+            HistoryEntry::Message(message) => Some(serde_json::to_string(message)?),
+            HistoryEntry::Notice(_) | HistoryEntry::Moderation(_) | HistoryEntry::ModAction(_) => {
+                None
+            }
+        },
+        ChatLogFormat::Plain => Some(format!("[{date} {time}] {}", describe(entry))),
+        ChatLogFormat::Chatterino => Some(format!("[{time}] {}", describe(entry))),
+    })
+}
+
+fn describe(entry: &HistoryEntry) -> String {
+    match entry {
+        HistoryEntry::Message(message) => {
+            format!("{}: {}", message.chatter_user_name, message.text)
+        }
+        HistoryEntry::Notice(notice) if notice.text.is_empty() => notice.system_message.clone(),
+        HistoryEntry::Notice(notice) => format!("{} {}", notice.system_message, notice.text),
+        HistoryEntry::Moderation(event) => match &event.action {
+            ModerationAction::ClearChat => "Chat has been cleared by a moderator.".to_owned(),
+            ModerationAction::ClearUserMessages {
+                target_user_login, ..
+            } => format!("{target_user_login}'s messages have been cleared."),
+            ModerationAction::DeleteMessage {
+                target_user_login, ..
+            } => format!("A message from {target_user_login} was deleted."),
+        },
+        HistoryEntry::ModAction(entry) => modlog::describe(entry),
+    }
+}
+
+/// Writes the stored history of `channel_login` between `from_ms` and `to_ms`
+/// to a new file under `<root>/exports`, returning its path.
+pub fn export(
+    store: &HistoryStore,
+    root: &Path,
+    channel_login: &str,
+    from_ms: i64,
+    to_ms: i64,
+    format: ChatLogFormat,
+) -> eyre::Result<PathBuf> {
+    let dir = root.join(EXPORT_DIR);
+    fs::create_dir_all(&dir).wrap_err_with(|| format!("failed to create {}", dir.display()))?;
+    let stamp = |unix_ms: i64| rfc3339_from_unix_ms(unix_ms).replace([':', '.'], "");
+    let path = dir.join(format!(
+        "{channel_login}_{}_{}.{}",
+        stamp(from_ms),
+        stamp(to_ms),
+        extension(format)
+    ));
+    let file =
+        File::create(&path).wrap_err_with(|| format!("failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+
+    if format == ChatLogFormat::Chatterino {
+        writeln!(out, "{}", chatterino_header(from_ms))?;
+    }
+    let written = store.for_each_between(channel_login, from_ms, to_ms, |record| {
+        if let Some(line) = format_entry(format, &record.entry, record.sent_at_ms)? {
+            writeln!(out, "{line}")?;
+        }
+        Ok(())
+    })?;
+    out.flush()?;
+
+    debug!(channel = channel_login, written, path = %path.display(), "exported chat log");
+    Ok(path)
+}
+
+enum LogCommand {
+    Append {
+        channel_login: String,
+        path: PathBuf,
+        /// Written whenever the file is (re)opened.
+        header: Option<String>,
+        line: String,
+        /// Files dated before this are removed when a new day's file opens.
+        retention_cutoff: Option<String>,
+    },
+    #[cfg(test)]
+    Flush(mpsc::Sender<()>),
+}
+
+/// Appends live chat to daily per-channel files on a background thread.
+#[derive(Clone)]
+pub struct ChatLogger {
+    writer: mpsc::Sender<LogCommand>,
+    default_root: PathBuf,
+    settings: ChatLogSettingsReader,
+}
+
+impl ChatLogger {
+    pub fn start(default_root: PathBuf, settings: ChatLogSettingsReader) -> eyre::Result<Self> {
+        let (writer, commands) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("chat-log-writer".to_string())
+            .spawn(move || run_writer(commands))
+            .wrap_err("failed to start chat log writer")?;
+        Ok(Self {
+            writer,
+            default_root,
+            settings,
+        })
+    }
+
+    pub fn log(&self, entry: &HistoryEntry, sent_at_ms: i64) {
+        let settings = (self.settings)();
+        if !settings.always_log {
+            return;
+        }
+        let line = match format_entry(settings.format, entry, sent_at_ms) {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(err) => {
+                warn!("failed to format chat log line: {err}");
+                return;
+            }
+        };
+
+        let channel_login = entry.channel_login().to_lowercase();
+        let (date, _) = date_and_time(settings.format, sent_at_ms);
+        let root = log_root(&settings, &self.default_root);
+        let command = LogCommand::Append {
+            path: daily_path(&root, settings.format, &channel_login, &date),
+            channel_login,
+            header: (settings.format == ChatLogFormat::Chatterino)
+                .then(|| chatterino_header(sent_at_ms)),
+            line,
+            retention_cutoff: settings.retention_days().map(|days| {
+                date_and_time(
+                    settings.format,
+                    sent_at_ms.saturating_sub(i64::from(days) * DAY_MS),
+                )
+                .0
+            }),
+        };
+        if self.writer.send(command).is_err() {
+            warn!("chat log writer stopped; dropping line");
+        }
+    }
+
+    #[cfg(test)]
+    fn flush(&self) {
+        let (ack, done) = mpsc::channel();
+        if self.writer.send(LogCommand::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+struct OpenLog {
+    path: PathBuf,
+    out: BufWriter<File>,
+}
+
+fn run_writer(commands: mpsc::Receiver<LogCommand>) {
+    let mut open: HashMap<String, OpenLog> = HashMap::new();
+    while let Ok(command) = commands.recv() {
+        handle(command, &mut open);
+        while let Ok(command) = commands.try_recv() {
+            handle(command, &mut open);
+        }
+        flush_all(&mut open);
+    }
+    flush_all(&mut open);
+    debug!("chat log writer stopped");
+}
+
+fn handle(command: LogCommand, open: &mut HashMap<String, OpenLog>) {
+    match command {
+        LogCommand::Append {
+            channel_login,
+            path,
+            header,
+            line,
+            retention_cutoff,
+        } => {
+            if let Err(err) = append(
+                open,
+                channel_login,
+                path.clone(),
+                header,
+                &line,
+                retention_cutoff,
+            ) {
+                logging::error_repeated(
+                    format!("chat_log:{}", path.display()),
+                    format!("failed to write chat log {}: {err:#}", path.display()),
+                    WRITE_ERROR_LOG_INTERVAL,
+                );
+            }
+        }
+        #[cfg(test)]
+        LogCommand::Flush(ack) => {
+            flush_all(open);
+            let _ = ack.send(());
+        }
+    }
+}
+
+fn append(
+    open: &mut HashMap<String, OpenLog>,
+    channel_login: String,
+    path: PathBuf,
+    header: Option<String>,
+    line: &str,
+    retention_cutoff: Option<String>,
+) -> eyre::Result<()> {
+    let current = open.get(&channel_login).map(|log| &log.path);
+    if current != Some(&path) {
+        if let Some(mut previous) = open.remove(&channel_login) {
+            previous.out.flush()?;
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+            if let Some(cutoff) = &retention_cutoff {
+                prune_before(dir, &channel_login, cutoff);
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut out = BufWriter::new(file);
+        if let Some(header) = header {
+            writeln!(out, "{header}")?;
+        }
+        open.insert(channel_login.clone(), OpenLog { path, out });
+    }
+
+    let log = open.get_mut(&channel_login).expect("log opened above");
+    writeln!(log.out, "{line}")?;
+    Ok(())
+}
+
+fn flush_all(open: &mut HashMap<String, OpenLog>) {
+    for log in open.values_mut() {
+        if let Err(err) = log.out.flush() {
+            warn!(path = %log.path.display(), "failed to flush chat log: {err}");
+        }
+    }
+}
+
+/// Removes `<channel_login>-<date>.*` files in `dir` dated before `cutoff`.
+fn prune_before(dir: &Path, channel_login: &str, cutoff: &str) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let prefix = format!("{channel_login}-");
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(date) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|rest| rest.get(..10))
+        else {
+            continue;
+        };
+        if date < cutoff {
+            match fs::remove_file(entry.path()) {
+                Ok(()) => debug!(path = %entry.path().display(), "removed expired chat log"),
+                Err(err) => {
+                    warn!(path = %entry.path().display(), "failed to remove chat log: {err}")
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{HistorySettingsReader, ModerationEvent, HISTORY_FILE};
+    use crate::types::{ChannelMessage, ChatLogRotation, HistorySettings};
+
+    // 2023-11-14T22:13:20Z
+    const SENT_AT_MS: i64 = 1_700_000_000_000;
+
+    fn message(id: &str, text: &str) -> HistoryEntry {
+        HistoryEntry::Message(ChannelMessage::test(id, "Forsen", "Viewer", text))
+    }
+
+    fn clear_chat() -> HistoryEntry {
+        HistoryEntry::Moderation(ModerationEvent {
+            ts: String::new(),
+            broadcaster_user_id: "1".to_string(),
+            broadcaster_user_login: "forsen".to_string(),
+            action: ModerationAction::ClearChat,
+        })
+    }
+
+    /// `HH:MM:SS` in this machine's time zone, as the Chatterino layout shows it.
+    fn local_time(unix_ms: i64) -> String {
+        date_and_time(ChatLogFormat::Chatterino, unix_ms).1
+    }
+
+    fn logger(root: &Path, settings: ChatLogSettings) -> ChatLogger {
+        let settings = settings.normalized();
+        ChatLogger::start(root.to_path_buf(), Arc::new(move || settings.clone())).unwrap()
+    }
+
+    #[test]
+    fn formats_lines_per_format() {
+        let entry = message("m1", "hello Kappa");
+
+        assert_eq!(
+            format_entry(ChatLogFormat::Plain, &entry, SENT_AT_MS)
+                .unwrap()
+                .unwrap(),
+            "[2023-11-14 22:13:20] Viewer: hello Kappa"
+        );
+        assert_eq!(
+            format_entry(ChatLogFormat::Chatterino, &entry, SENT_AT_MS)
+                .unwrap()
+                .unwrap(),
+            format!("[{}] Viewer: hello Kappa", local_time(SENT_AT_MS))
+        );
+        let json = format_entry(ChatLogFormat::Jsonl, &entry, SENT_AT_MS)
+            .unwrap()
+            .unwrap();
+        let decoded: ChannelMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.text, "hello Kappa");
+
+        assert_eq!(
+            format_entry(ChatLogFormat::Chatterino, &clear_chat(), SENT_AT_MS)
+                .unwrap()
+                .unwrap(),
+            format!(
+                "[{}] Chat has been cleared by a moderator.",
+                local_time(SENT_AT_MS)
+            )
+        );
+        assert!(
+            format_entry(ChatLogFormat::Jsonl, &clear_chat(), SENT_AT_MS)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn always_log_writes_chatterino_daily_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = logger(
+            dir.path(),
+            ChatLogSettings {
+                always_log: true,
+                ..Default::default()
+            },
+        );
+
+        logger.log(&message("m1", "first"), SENT_AT_MS);
+        logger.log(&message("m2", "second"), SENT_AT_MS + 1_000);
+        logger.log(&message("m3", "next day"), SENT_AT_MS + DAY_MS);
+        logger.flush();
+
+        let channel_dir = dir.path().join("Twitch/Channels/forsen");
+        let file = |unix_ms| {
+            let (date, _) = date_and_time(ChatLogFormat::Chatterino, unix_ms);
+            fs::read_to_string(channel_dir.join(format!("forsen-{date}.log"))).unwrap()
+        };
+        assert_eq!(
+            file(SENT_AT_MS),
+            format!(
+                "{}\n[{}] Viewer: first\n[{}] Viewer: second\n",
+                chatterino_header(SENT_AT_MS),
+                local_time(SENT_AT_MS),
+                local_time(SENT_AT_MS + 1_000)
+            )
+        );
+        assert!(file(SENT_AT_MS + DAY_MS)
+            .ends_with(&format!("[{}] Viewer: next day\n", local_time(SENT_AT_MS))));
+    }
+
+    #[test]
+    fn keep_days_removes_old_files_on_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let channel_dir = dir.path().join("forsen");
+        fs::create_dir_all(&channel_dir).unwrap();
+        fs::write(channel_dir.join("forsen-2023-11-01.txt"), "old\n").unwrap();
+        fs::write(channel_dir.join("forsen-2023-11-13.txt"), "recent\n").unwrap();
+        fs::write(channel_dir.join("notes.txt"), "keep\n").unwrap();
+        let logger = logger(
+            dir.path(),
+            ChatLogSettings {
+                always_log: true,
+                format: ChatLogFormat::Plain,
+                rotation: ChatLogRotation::KeepDays,
+                keep_days: 7,
+                ..Default::default()
+            },
+        );
+
+        logger.log(&message("m1", "hi"), SENT_AT_MS);
+        logger.flush();
+
+        assert!(!channel_dir.join("forsen-2023-11-01.txt").exists());
+        assert!(channel_dir.join("forsen-2023-11-13.txt").exists());
+        assert!(channel_dir.join("notes.txt").exists());
+        assert!(channel_dir.join("forsen-2023-11-14.txt").exists());
+    }
+
+    #[test]
+    fn disabled_logging_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = logger(dir.path(), ChatLogSettings::default());
+
+        logger.log(&message("m1", "hi"), SENT_AT_MS);
+        logger.flush();
+
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn exports_stored_history_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let history_settings = HistorySettings {
+            retention_days: 36_500,
+            ..Default::default()
+        };
+        let reader: HistorySettingsReader = Arc::new(move || history_settings.clone());
+        let store = HistoryStore::open(&dir.path().join(HISTORY_FILE), reader).unwrap();
+        store.record_at(message("m1", "before"), SENT_AT_MS - 1);
+        store.record_at(message("m2", "inside"), SENT_AT_MS);
+        store.record_at(message("m3", "after"), SENT_AT_MS + 1_000);
+        store.flush();
+
+        let path = export(
+            &store,
+            dir.path(),
+            "forsen",
+            SENT_AT_MS,
+            SENT_AT_MS + 1_000,
+            ChatLogFormat::Plain,
+        )
+        .unwrap();
+
+        assert_eq!(path.parent().unwrap(), dir.path().join(EXPORT_DIR));
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "[2023-11-14 22:13:20] Viewer: inside\n"
+        );
+    }
+}
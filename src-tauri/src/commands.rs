@@ -0,0 +1,739 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use twitch_api::{
+    helix::{
+        chat::{
+            AnnouncementColor, SendAShoutoutRequest, SendChatAnnouncementBody,
+            SendChatAnnouncementRequest,
+        },
+        EmptyBody,
+    },
+    types::NamedUserColor,
+};
+use twitch_oauth2::{Scope, UserToken};
+
+use crate::{
+    error::PepoError,
+    moderation::{self, ModerationError},
+    whisper::{self, WhisperInbox},
+};
+
+type HelixClient = twitch_api::HelixClient<'static, reqwest::Client>;
+
+/// `/timeout` without a duration, like on the website.
+const DEFAULT_TIMEOUT_SECS: u32 = 600;
+
+/// Who may run a command in a channel.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Everyone,
+    Moderator,
+    /// The broadcaster or one of their editors. Helix doesn't tell editors
+    /// they are one, so this is left for Twitch to enforce.
+    Editor,
+    Broadcaster,
+}
+
+struct CommandSpec {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    usage: &'static str,
+    description: &'static str,
+    permission: Permission,
+    scopes: &'static [Scope],
+}
+
+static COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "me",
+        aliases: &[],
+        usage: "/me <message>",
+        description: "Send a message in your name colour",
+        permission: Permission::Everyone,
+        scopes: &[Scope::UserWriteChat],
+    },
+    CommandSpec {
+        name: "ban",
+        aliases: &[],
+        usage: "/ban <user> [reason]",
+        description: "Permanently ban a user from chat",
+        permission: Permission::Moderator,
+        scopes: &[Scope::ModeratorManageBannedUsers],
+    },
+    CommandSpec {
+        name: "timeout",
+        aliases: &[],
+        usage: "/timeout <user> [duration] [reason]",
+        description: "Time a user out, for 10 minutes unless given e.g. 30s, 10m, 1h, 1d or 1w",
+        permission: Permission::Moderator,
+        scopes: &[Scope::ModeratorManageBannedUsers],
+    },
+    CommandSpec {
+        name: "unban",
+        aliases: &["untimeout"],
+        usage: "/unban <user>",
+        description: "Lift a ban or timeout",
+        permission: Permission::Moderator,
+        scopes: &[Scope::ModeratorManageBannedUsers],
+    },
+    CommandSpec {
+        name: "announce",
+        aliases: &[
+            "announceblue",
+            "announcegreen",
+            "announceorange",
+            "announcepurple",
+        ],
+        usage: "/announce <message>",
+        description: "Highlight a message for everyone in chat",
+        permission: Permission::Moderator,
+        scopes: &[Scope::ModeratorManageAnnouncements],
+    },
+    CommandSpec {
+        name: "raid",
+        aliases: &[],
+        usage: "/raid <channel>",
+        description: "Send your viewers to another channel",
+        permission: Permission::Broadcaster,
+        scopes: &[Scope::ChannelManageRaids],
+    },
+    CommandSpec {
+        name: "unraid",
+        aliases: &[],
+        usage: "/unraid",
+        description: "Cancel a pending raid",
+        permission: Permission::Broadcaster,
+        scopes: &[Scope::ChannelManageRaids],
+    },
+    CommandSpec {
+        name: "shoutout",
+        aliases: &["so"],
+        usage: "/shoutout <channel>",
+        description: "Recommend another channel to chat",
+        permission: Permission::Moderator,
+        scopes: &[Scope::ModeratorManageShoutouts],
+    },
+    CommandSpec {
+        name: "marker",
+        aliases: &[],
+        usage: "/marker [description]",
+        description: "Mark the current point of the stream for highlighting",
+        permission: Permission::Editor,
+        scopes: &[Scope::ChannelManageBroadcast],
+    },
+    CommandSpec {
+        name: "color",
+        aliases: &["colour"],
+        usage: "/color <name or #hex>",
+        description: "Change your name colour; hex colours need Turbo or Prime",
+        permission: Permission::Everyone,
+        scopes: &[Scope::UserManageChatColor],
+    },
+    CommandSpec {
+        name: "w",
+        aliases: &["whisper"],
+        usage: "/w <user> <message>",
+        description: "Whisper a user",
+        permission: Permission::Everyone,
+        scopes: &[Scope::UserManageWhispers],
+    },
+];
+
+fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name == name || spec.aliases.contains(&name))
+}
+
+/// Whether `name` is taken by a built-in command, so no alias may shadow it.
+pub fn is_builtin(name: &str) -> bool {
+    find(name).is_some()
+}
+
+/// A local shortcut: `/{name} args` runs `{expansion} args`.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct CommandAlias {
+    pub name: String,
+    /// A command line such as `/timeout` or `/announce Stream starts soon`,
+    /// or plain text to send.
+    pub expansion: String,
+}
+
+impl CommandAlias {
+    /// Strips the slash and case from `name`, or drops the alias when the
+    /// name is empty, contains spaces or belongs to a built-in command.
+    pub fn normalized(self) -> Option<Self> {
+        let name = self.name.trim().trim_start_matches('/').to_lowercase();
+        let expansion = self.expansion.trim().to_owned();
+        if name.is_empty()
+            || name.contains(char::is_whitespace)
+            || is_builtin(&name)
+            || expansion.is_empty()
+        {
+            return None;
+        }
+        Some(CommandAlias { name, expansion })
+    }
+}
+
+/// One entry in the chat input's command autocomplete.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct CommandInfo {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub usage: String,
+    pub description: String,
+    pub permission: Permission,
+    /// Set for local aliases.
+    pub expansion: Option<String>,
+}
+
+/// Built-in commands followed by the user's aliases.
+pub fn list(aliases: &[CommandAlias]) -> Vec<CommandInfo> {
+    let builtins = COMMANDS.iter().map(|spec| CommandInfo {
+        name: spec.name.to_owned(),
+        aliases: spec
+            .aliases
+            .iter()
+            .map(|alias| (*alias).to_owned())
+            .collect(),
+        usage: spec.usage.to_owned(),
+        description: spec.description.to_owned(),
+        permission: spec.permission,
+        expansion: None,
+    });
+    let local = aliases.iter().map(|alias| CommandInfo {
+        name: alias.name.clone(),
+        aliases: Vec::new(),
+        usage: format!("/{} …", alias.name),
+        description: format!("Alias for {}", alias.expansion),
+        permission: Permission::Everyone,
+        expansion: Some(alias.expansion.clone()),
+    });
+    builtins.chain(local).collect()
+}
+
+/// Why a line from the chat input wasn't sent.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommandError {
+    /// `suggestions` are commands and aliases starting the same way.
+    UnknownCommand {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    /// The arguments don't fit the command.
+    Usage {
+        usage: String,
+        message: String,
+    },
+    PermissionDenied {
+        command: String,
+        required: Permission,
+    },
+    UnknownUser {
+        login: String,
+    },
+    Failed {
+        error: PepoError,
+    },
+}
+
+impl From<ModerationError> for CommandError {
+    fn from(error: ModerationError) -> Self {
+        CommandError::Failed {
+            error: error.into(),
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand { name, suggestions } => {
+                write!(f, "unknown command /{name}")?;
+                if !suggestions.is_empty() {
+                    write!(f, "; did you mean /{}?", suggestions.join(", /"))?;
+                }
+                Ok(())
+            }
+            CommandError::Usage { usage, message } => write!(f, "{message} (usage: {usage})"),
+            CommandError::PermissionDenied { command, required } => {
+                write!(f, "/{command} requires {required:?} permission")
+            }
+            CommandError::UnknownUser { login } => write!(f, "no such user {login}"),
+            CommandError::Failed { error } => write!(f, "{error}"),
+        }
+    }
+}
+
+/// A line from the chat input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    /// Text for the outgoing queue, `/me` actions included.
+    Chat(String),
+    Command(Command),
+}
+
+/// A slash command that maps to a Helix call other than sending chat.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Ban {
+        login: String,
+        reason: String,
+    },
+    Timeout {
+        login: String,
+        duration_secs: u32,
+        reason: String,
+    },
+    Unban {
+        login: String,
+    },
+    Announce {
+        message: String,
+        color: AnnouncementColor,
+    },
+    Raid {
+        login: String,
+    },
+    Unraid,
+    Shoutout {
+        login: String,
+    },
+    Marker {
+        description: String,
+    },
+    /// A snake_case colour name or `#rrggbb`.
+    Color {
+        color: String,
+    },
+    Whisper {
+        login: String,
+        message: String,
+    },
+}
+
+impl Command {
+    fn spec(&self) -> &'static CommandSpec {
+        let name = match self {
+            Command::Ban { .. } => "ban",
+            Command::Timeout { .. } => "timeout",
+            Command::Unban { .. } => "unban",
+            Command::Announce { .. } => "announce",
+            Command::Raid { .. } => "raid",
+            Command::Unraid => "unraid",
+            Command::Shoutout { .. } => "shoutout",
+            Command::Marker { .. } => "marker",
+            Command::Color { .. } => "color",
+            Command::Whisper { .. } => "w",
+        };
+        find(name).expect("every command is registered")
+    }
+}
+
+/// Splits off the first word; the rest keeps its inner spacing.
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (s, ""),
+    }
+}
+
+/// Expands the first word when it names an alias. Aliases don't expand
+/// into other aliases.
+fn expand_alias(input: &str, aliases: &[CommandAlias]) -> String {
+    let Some(line) = input.strip_prefix('/') else {
+        return input.to_owned();
+    };
+    let (name, rest) = split_word(line);
+    let name = name.to_lowercase();
+    match aliases.iter().find(|alias| alias.name == name) {
+        Some(alias) if rest.is_empty() => alias.expansion.clone(),
+        Some(alias) => format!("{} {rest}", alias.expansion),
+        None => input.to_owned(),
+    }
+}
+
+/// Accepts `600`, `30s`, `10m`, `1h`, `1d` and `1w`.
+fn parse_duration(s: &str) -> Option<u32> {
+    let (digits, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(at) => s.split_at(at),
+        None => (s, "s"),
+    };
+    let multiplier = match unit.to_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    digits.parse::<u32>().ok()?.checked_mul(multiplier)
+}
+
+/// Accepts Twitch's colour names in any case, with or without separators,
+/// and `#rrggbb`.
+fn parse_color(s: &str) -> Option<String> {
+    if let Some(hex) = s.strip_prefix('#') {
+        return (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| format!("#{}", hex.to_lowercase()));
+    }
+    let compact = |name: &str| name.replace(['_', '-', ' '], "").to_lowercase();
+    let wanted = compact(s);
+    NamedUserColor::all()
+        .iter()
+        .map(ToString::to_string)
+        .find(|name| compact(name) == wanted)
+}
+
+fn login_arg(s: &str) -> String {
+    s.trim_start_matches('@').to_lowercase()
+}
+
+/// Turns a line from the chat input into a command, after expanding aliases.
+/// Lines not starting with `/` are sent as they are.
+pub fn parse(input: &str, aliases: &[CommandAlias]) -> Result<Line, CommandError> {
+    let input = expand_alias(input.trim(), aliases);
+    let Some(line) = input.strip_prefix('/') else {
+        return Ok(Line::Chat(input));
+    };
+    let (name, rest) = split_word(line);
+    let name = name.to_lowercase();
+    let Some(spec) = find(&name) else {
+        let mut suggestions: Vec<String> = COMMANDS
+            .iter()
+            .flat_map(|spec| std::iter::once(spec.name).chain(spec.aliases.iter().copied()))
+            .chain(aliases.iter().map(|alias| alias.name.as_str()))
+            .filter(|candidate| {
+                name.chars()
+                    .next()
+                    .is_some_and(|c| candidate.starts_with(c))
+            })
+            .map(str::to_owned)
+            .collect();
+        suggestions.sort();
+        return Err(CommandError::UnknownCommand { name, suggestions });
+    };
+    let usage = |message: &str| CommandError::Usage {
+        usage: spec.usage.to_owned(),
+        message: message.to_owned(),
+    };
+    let (first, remainder) = split_word(rest);
+
+    let command = match spec.name {
+        "me" if rest.is_empty() => return Err(usage("missing message")),
+        // Helix still honours `/me` itself.
+        "me" => return Ok(Line::Chat(format!("/me {rest}"))),
+        "ban" | "timeout" | "unban" | "raid" | "shoutout" | "w" if first.is_empty() => {
+            return Err(usage("missing user"))
+        }
+        "ban" => Command::Ban {
+            login: login_arg(first),
+            reason: remainder.to_owned(),
+        },
+        "timeout" => {
+            let (duration, reason) = split_word(remainder);
+            match parse_duration(duration) {
+                Some(duration_secs) => Command::Timeout {
+                    login: login_arg(first),
+                    duration_secs,
+                    reason: reason.to_owned(),
+                },
+                // A reason without a duration.
+                None if duration.chars().next().is_some_and(|c| !c.is_ascii_digit()) => {
+                    Command::Timeout {
+                        login: login_arg(first),
+                        duration_secs: DEFAULT_TIMEOUT_SECS,
+                        reason: remainder.to_owned(),
+                    }
+                }
+                None if duration.is_empty() => Command::Timeout {
+                    login: login_arg(first),
+                    duration_secs: DEFAULT_TIMEOUT_SECS,
+                    reason: String::new(),
+                },
+                None => return Err(usage(&format!("invalid duration {duration}"))),
+            }
+        }
+        "unban" => Command::Unban {
+            login: login_arg(first),
+        },
+        "announce" if rest.is_empty() => return Err(usage("missing message")),
+        "announce" => Command::Announce {
+            message: rest.to_owned(),
+            color: match name.strip_prefix("announce").unwrap_or_default() {
+                "" => AnnouncementColor::Primary,
+                color => AnnouncementColor::try_from(color)
+                    .map_err(|_| usage(&format!("invalid colour {color}")))?,
+            },
+        },
+        "raid" => Command::Raid {
+            login: login_arg(first),
+        },
+        "unraid" => Command::Unraid,
+        "shoutout" => Command::Shoutout {
+            login: login_arg(first),
+        },
+        "marker" => Command::Marker {
+            description: rest.to_owned(),
+        },
+        "color" if first.is_empty() => return Err(usage("missing colour")),
+        "color" => Command::Color {
+            color: parse_color(first).ok_or_else(|| usage(&format!("unknown colour {first}")))?,
+        },
+        "w" if remainder.is_empty() => return Err(usage("missing message")),
+        "w" => Command::Whisper {
+            login: login_arg(first),
+            message: remainder.to_owned(),
+        },
+        other => unreachable!("/{other} is registered but not parsed"),
+    };
+    Ok(Line::Command(command))
+}
+
+async fn check_permission(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    spec: &CommandSpec,
+) -> Result<(), CommandError> {
+    let is_broadcaster = token.user_id.as_str() == broadcaster_id;
+    let allowed = match spec.permission {
+        Permission::Everyone | Permission::Editor => true,
+        Permission::Broadcaster => is_broadcaster,
+        // Without `user:read:moderated_channels` Helix gets the last word.
+        Permission::Moderator => {
+            is_broadcaster
+                || moderation::is_moderated_channel(client, token, broadcaster_id)
+                    .await?
+                    .unwrap_or(true)
+        }
+    };
+    if !allowed {
+        return Err(CommandError::PermissionDenied {
+            command: spec.name.to_owned(),
+            required: spec.permission,
+        });
+    }
+    moderation::require_scopes(token, spec.scopes)?;
+    Ok(())
+}
+
+async fn user_id(
+    client: &HelixClient,
+    token: &UserToken,
+    login: &str,
+) -> Result<String, CommandError> {
+    client
+        .get_user_from_login(login, token)
+        .await
+        .map_err(ModerationError::from_client_error)?
+        .map(|user| user.id.to_string())
+        .ok_or_else(|| CommandError::UnknownUser {
+            login: login.to_owned(),
+        })
+}
+
+/// Runs `command` in `broadcaster_id`'s chat as the token's user.
+pub async fn run(
+    client: &HelixClient,
+    token: &UserToken,
+    whispers: &WhisperInbox,
+    broadcaster_id: &str,
+    command: Command,
+) -> Result<(), CommandError> {
+    check_permission(client, token, broadcaster_id, command.spec()).await?;
+    let me = token.user_id.as_str();
+    match command {
+        Command::Ban { login, reason } => {
+            let user_id = user_id(client, token, &login).await?;
+            return moderation::ban(client, token, broadcaster_id, &user_id, &reason, None)
+                .await
+                .map_err(Into::into);
+        }
+        Command::Timeout {
+            login,
+            duration_secs,
+            reason,
+        } => {
+            let user_id = user_id(client, token, &login).await?;
+            return moderation::ban(
+                client,
+                token,
+                broadcaster_id,
+                &user_id,
+                &reason,
+                Some(duration_secs),
+            )
+            .await
+            .map_err(Into::into);
+        }
+        Command::Unban { login } => {
+            let user_id = user_id(client, token, &login).await?;
+            return moderation::unban(client, token, broadcaster_id, &user_id)
+                .await
+                .map_err(Into::into);
+        }
+        Command::Announce { message, color } => {
+            let body = SendChatAnnouncementBody::new(message, color)
+                .unwrap_or_else(|never: std::convert::Infallible| match never {});
+            client
+                .req_post(
+                    SendChatAnnouncementRequest::new(broadcaster_id, me),
+                    body,
+                    token,
+                )
+                .await
+                .map(drop)
+        }
+        Command::Raid { login } => {
+            let to = user_id(client, token, &login).await?;
+            client
+                .start_a_raid(broadcaster_id, to.as_str(), token)
+                .await
+                .map(drop)
+        }
+        Command::Unraid => client.cancel_a_raid(broadcaster_id, token).await.map(drop),
+        Command::Shoutout { login } => {
+            let to = user_id(client, token, &login).await?;
+            client
+                .req_post(
+                    SendAShoutoutRequest::new(broadcaster_id, to.as_str(), me),
+                    EmptyBody,
+                    token,
+                )
+                .await
+                .map(drop)
+        }
+        Command::Marker { description } => client
+            .create_stream_marker(broadcaster_id, description, token)
+            .await
+            .map(drop),
+        Command::Color { color } => {
+            let color = NamedUserColor::try_from(color.as_str())
+                .map_err(|err| ModerationError::InvalidInput {
+                    message: err.to_string(),
+                })?
+                .to_owned();
+            client
+                .update_user_chat_color(me, color, token)
+                .await
+                .map(drop)
+        }
+        Command::Whisper { login, message } => {
+            return whisper::send(client, token, whispers, &login, &message)
+                .await
+                .map(drop)
+                .map_err(Into::into);
+        }
+    }
+    .map_err(|err| ModerationError::from_client_error(err).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alias(name: &str, expansion: &str) -> CommandAlias {
+        CommandAlias {
+            name: name.to_owned(),
+            expansion: expansion.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_builtin_commands() {
+        assert_eq!(
+            parse("hello /ban", &[]),
+            Ok(Line::Chat("hello /ban".to_owned()))
+        );
+        assert_eq!(
+            parse("/ME waves", &[]),
+            Ok(Line::Chat("/me waves".to_owned()))
+        );
+        assert_eq!(
+            parse("/timeout @Viewer 10m spamming links", &[]),
+            Ok(Line::Command(Command::Timeout {
+                login: "viewer".to_owned(),
+                duration_secs: 600,
+                reason: "spamming links".to_owned(),
+            }))
+        );
+        assert_eq!(
+            parse("/timeout viewer calm down", &[]),
+            Ok(Line::Command(Command::Timeout {
+                login: "viewer".to_owned(),
+                duration_secs: DEFAULT_TIMEOUT_SECS,
+                reason: "calm down".to_owned(),
+            }))
+        );
+        assert_eq!(
+            parse("/announceblue Stream starts soon", &[]),
+            Ok(Line::Command(Command::Announce {
+                message: "Stream starts soon".to_owned(),
+                color: AnnouncementColor::Blue,
+            }))
+        );
+        assert_eq!(
+            parse("/color BlueViolet", &[]),
+            Ok(Line::Command(Command::Color {
+                color: "blue_violet".to_owned()
+            }))
+        );
+        assert_eq!(
+            parse("/w forsen hi there", &[]),
+            Ok(Line::Command(Command::Whisper {
+                login: "forsen".to_owned(),
+                message: "hi there".to_owned(),
+            }))
+        );
+    }
+
+    #[test]
+    fn reports_usage_and_unknown_commands() {
+        assert!(matches!(
+            parse("/timeout viewer 10x", &[]),
+            Err(CommandError::Usage { .. })
+        ));
+        assert!(matches!(
+            parse("/w forsen", &[]),
+            Err(CommandError::Usage { .. })
+        ));
+        assert!(matches!(
+            parse("/color mauve", &[]),
+            Err(CommandError::Usage { .. })
+        ));
+        assert_eq!(
+            parse("/bna viewer", &[alias("bye", "/me waves")]),
+            Err(CommandError::UnknownCommand {
+                name: "bna".to_owned(),
+                suggestions: vec!["ban".to_owned(), "bye".to_owned()],
+            })
+        );
+    }
+
+    #[test]
+    fn expands_local_aliases() {
+        let aliases = [alias("perma", "/ban"), alias("hi", "hello chat")];
+        assert_eq!(
+            parse("/perma viewer being rude", &aliases),
+            Ok(Line::Command(Command::Ban {
+                login: "viewer".to_owned(),
+                reason: "being rude".to_owned(),
+            }))
+        );
+        assert_eq!(
+            parse("/HI", &aliases),
+            Ok(Line::Chat("hello chat".to_owned()))
+        );
+        assert_eq!(alias("/Ban", "/me no").normalized(), None);
+        assert_eq!(
+            alias(" /Perma ", "/ban ").normalized(),
+            Some(alias("perma", "/ban"))
+        );
+    }
+}
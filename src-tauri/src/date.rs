@@ -0,0 +1,72 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, SecondsFormat};
+
+pub const DAY_MS: i64 = 24 * 60 * 60 * 1_000;
+
+/// Parses `YYYY-MM-DD` as midnight UTC in unix milliseconds, rejecting dates
+/// that don't exist such as `2023-02-29`.
+pub fn parse_ymd(value: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_time(NaiveTime::MIN).and_utc().timestamp_millis())
+}
+
+/// Formats a unix timestamp in milliseconds as RFC 3339, matching the
+/// timestamps EventSub hands us for live messages.
+pub fn rfc3339_from_unix_ms(unix_ms: i64) -> String {
+    DateTime::from_timestamp_millis(unix_ms)
+        .map(|utc| utc.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+/// The local time zone's offset from UTC at `unix_ms`, in milliseconds.
+pub fn local_offset_ms(unix_ms: i64) -> i64 {
+    DateTime::from_timestamp_millis(unix_ms)
+        .map(|utc| i64::from(utc.with_timezone(&Local).offset().local_minus_utc()) * 1_000)
+        .unwrap_or(0)
+}
+
+/// `UTC` or e.g. `UTC+01:00`, naming an offset from [`local_offset_ms`].
+pub fn offset_label(offset_ms: i64) -> String {
+    if offset_ms == 0 {
+        return "UTC".to_owned();
+    }
+    let sign = if offset_ms < 0 { '-' } else { '+' };
+    let minutes = offset_ms.abs() / 60_000;
+    format!("UTC{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_only_real_dates() {
+        assert_eq!(parse_ymd("1970-01-02"), Some(DAY_MS));
+        assert_eq!(parse_ymd("2024-02-29"), Some(19_782 * DAY_MS));
+        assert_eq!(parse_ymd("2024-02-31"), None);
+        assert_eq!(parse_ymd("2023-02-29"), None);
+        assert_eq!(parse_ymd("2024-13-01"), None);
+        assert_eq!(parse_ymd("2024-00-10"), None);
+        assert_eq!(parse_ymd("2024-01"), None);
+        assert_eq!(parse_ymd("yesterday"), None);
+    }
+
+    #[test]
+    fn formats_rfc3339_timestamps() {
+        assert_eq!(rfc3339_from_unix_ms(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            rfc3339_from_unix_ms(1_700_000_000_123),
+            "2023-11-14T22:13:20.123Z"
+        );
+        assert_eq!(
+            rfc3339_from_unix_ms(951_782_400_000),
+            "2000-02-29T00:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn labels_utc_offsets() {
+        assert_eq!(offset_label(0), "UTC");
+        assert_eq!(offset_label(3_600_000), "UTC+01:00");
+        assert_eq!(offset_label(-(9 * 3_600_000 + 30 * 60_000)), "UTC-09:30");
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::types::AppearanceTheme;
+
 pub mod cache;
 pub mod persist;
 pub mod providers;
@@ -37,9 +39,86 @@ pub struct Emote {
     pub provider: String,
     /// The scope of the emote (e.g. "Global", "Channel").
     pub scope: String,
+    /// Whether the emote image is animated.
+    #[serde(default)]
+    pub animated: bool,
+    /// Intrinsic width at 1x, in CSS pixels, when the provider reports it.
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// Intrinsic height at 1x, in CSS pixels, when the provider reports it.
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Per-density image URLs, ordered from smallest to largest scale.
+    #[serde(default)]
+    pub images: Vec<EmoteImage>,
+}
+
+/// One rendition of an emote at a given display density.
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type, PartialEq, Eq)]
+pub struct EmoteImage {
+    /// Display density multiplier: 1, 2 or 4.
+    pub scale: u8,
+    /// URL for dark backgrounds, or the only URL when the provider has no
+    /// themed variants.
+    pub url: String,
+    /// URL for light backgrounds when the provider serves a separate image.
+    pub light_url: Option<String>,
+}
+
+/// Twitch emotes are always 28x28 at 1x.
+const TWITCH_EMOTE_SIZE: u32 = 28;
+/// Twitch CDN path segment for each display density we expose.
+const TWITCH_SCALES: [(u8, &str); 3] = [(1, "1.0"), (2, "2.0"), (4, "3.0")];
+
+fn twitch_emote_images(id: &str, format: &[String], theme_mode: &[String]) -> Vec<EmoteImage> {
+    let fmt = if format.iter().any(|f| f == "animated") {
+        "animated"
+    } else if format.is_empty() {
+        // Unknown format: the CDN serves the animated image when there is one.
+        "default"
+    } else {
+        "static"
+    };
+    let has_light = theme_mode.is_empty() || theme_mode.iter().any(|t| t == "light");
+    TWITCH_SCALES
+        .iter()
+        .map(|(scale, segment)| EmoteImage {
+            scale: *scale,
+            url: format!("https://static-cdn.jtvnw.net/emoticons/v2/{id}/{fmt}/dark/{segment}"),
+            light_url: has_light.then(|| {
+                format!("https://static-cdn.jtvnw.net/emoticons/v2/{id}/{fmt}/light/{segment}")
+            }),
+        })
+        .collect()
 }
 
 impl Emote {
+    /// Returns the best URL for `scale` under `theme`: the largest image not
+    /// above the requested density, falling back to the smallest available.
+    /// `System` resolves to the dark variant since the backend can't see the
+    /// OS preference.
+    pub fn image_url(&self, scale: u8, theme: &AppearanceTheme) -> Option<String> {
+        let image = self
+            .images
+            .iter()
+            .filter(|image| image.scale <= scale)
+            .max_by_key(|image| image.scale)
+            .or_else(|| self.images.iter().min_by_key(|image| image.scale))?;
+        let url = match theme {
+            AppearanceTheme::Light => image.light_url.as_ref().unwrap_or(&image.url),
+            AppearanceTheme::System | AppearanceTheme::Dark => &image.url,
+        };
+        Some(url.clone())
+    }
+
+    /// Points `url` at the largest rendition for `theme`.
+    pub fn themed(mut self, theme: &AppearanceTheme) -> Self {
+        if let Some(url) = self.image_url(u8::MAX, theme) {
+            self.url = url;
+        }
+        self
+    }
+
     pub fn from_emote_fragment(
         name: String,
         value: &twitch_api::eventsub::channel::chat::Emote,
@@ -47,22 +126,46 @@ impl Emote {
     ) -> Self {
         let id = value.id.to_string();
         let format: Vec<String> = value.format.iter().map(|v| v.to_string()).collect();
-        let fmt = format
-            .last()
-            .cloned()
-            .unwrap_or_else(|| "static".to_string());
-        let url = format!(
-            "https://static-cdn.jtvnw.net/emoticons/v2/{}/{}/dark/3.0",
-            id, fmt
-        );
+        let theme_mode = vec!["light".to_string(), "dark".to_string()];
+        let images = twitch_emote_images(&id, &format, &theme_mode);
         Emote {
+            url: images
+                .last()
+                .map(|image| image.url.clone())
+                .unwrap_or_default(),
+            animated: format.iter().any(|f| f == "animated"),
+            width: Some(TWITCH_EMOTE_SIZE),
+            height: Some(TWITCH_EMOTE_SIZE),
+            images,
             id,
             name: name.clone(),
             emote_set_id: value.emote_set_id.to_string(),
             format,
-            theme_mode: vec!["light".to_string(), "dark".to_string()],
+            theme_mode,
+            scale: vec!["1.0".to_string(), "2.0".to_string(), "3.0".to_string()],
+            provider: "Twitch".to_string(),
+            scope,
+            ..Default::default()
+        }
+    }
+
+    /// Builds an emote from an IRC `emotes` tag entry, which carries only the
+    /// id, so the format is left for the CDN to pick.
+    pub fn from_irc_emote(id: String, name: String, scope: String) -> Self {
+        let theme_mode = vec!["light".to_string(), "dark".to_string()];
+        let images = twitch_emote_images(&id, &[], &theme_mode);
+        Emote {
+            url: images
+                .last()
+                .map(|image| image.url.clone())
+                .unwrap_or_default(),
+            width: Some(TWITCH_EMOTE_SIZE),
+            height: Some(TWITCH_EMOTE_SIZE),
+            images,
+            id,
+            name,
+            theme_mode,
             scale: vec!["1.0".to_string(), "2.0".to_string(), "3.0".to_string()],
-            url,
             provider: "Twitch".to_string(),
             scope,
             ..Default::default()
@@ -76,15 +179,16 @@ impl From<&twitch_api::helix::chat::UserEmote> for Emote {
         let format: Vec<String> = value.format.iter().map(|v| v.to_string()).collect();
         let scale: Vec<String> = value.scale.iter().map(|v| v.to_string()).collect();
         let theme_mode: Vec<String> = value.theme_mode.iter().map(|v| v.to_string()).collect();
-        let fmt = format
-            .last()
-            .cloned()
-            .unwrap_or_else(|| "static".to_string());
-        let url = format!(
-            "https://static-cdn.jtvnw.net/emoticons/v2/{}/{}/dark/3.0",
-            id, fmt
-        );
+        let images = twitch_emote_images(&id, &format, &theme_mode);
         Emote {
+            url: images
+                .last()
+                .map(|image| image.url.clone())
+                .unwrap_or_default(),
+            animated: format.iter().any(|f| f == "animated"),
+            width: Some(TWITCH_EMOTE_SIZE),
+            height: Some(TWITCH_EMOTE_SIZE),
+            images,
             id,
             name: value.name.clone(),
             emote_type: value.emote_type.clone(),
@@ -92,7 +196,6 @@ impl From<&twitch_api::helix::chat::UserEmote> for Emote {
             format,
             scale,
             theme_mode,
-            url,
             provider: "Twitch".to_string(),
             scope: "Subscription".to_string(),
             ..Default::default()
@@ -106,30 +209,119 @@ impl From<&twitch_api::helix::chat::GlobalEmote> for Emote {
         let format: Vec<String> = value.format.iter().map(|v| v.to_string()).collect();
         let scale: Vec<String> = value.scale.iter().map(|v| v.to_string()).collect();
         let theme_mode: Vec<String> = value.theme_mode.iter().map(|v| v.to_string()).collect();
-        let fmt = format
-            .last()
-            .cloned()
-            .unwrap_or_else(|| "static".to_string());
-        let tm = theme_mode
-            .last()
-            .cloned()
-            .unwrap_or_else(|| "dark".to_string());
-        let sc = scale.last().cloned().unwrap_or_else(|| "1.0".to_string());
-        let url = format!(
-            "https://static-cdn.jtvnw.net/emoticons/v2/{}/{}/{}/{}",
-            id, fmt, tm, sc
-        );
+        let images = twitch_emote_images(&id, &format, &theme_mode);
         Emote {
+            url: images
+                .last()
+                .map(|image| image.url.clone())
+                .unwrap_or_default(),
+            animated: format.iter().any(|f| f == "animated"),
+            width: Some(TWITCH_EMOTE_SIZE),
+            height: Some(TWITCH_EMOTE_SIZE),
+            images,
             id,
             name: value.name.clone(),
             emote_set_id: providers::GLOBAL_SCOPE_KEY.to_owned(),
             format,
             scale,
             theme_mode,
-            url,
             provider: "Twitch".to_string(),
             scope: "Global".to_string(),
             ..Default::default()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emote_with_images(images: Vec<EmoteImage>) -> Emote {
+        Emote {
+            id: "1".to_string(),
+            name: "Kappa".to_string(),
+            url: "fallback".to_string(),
+            images,
+            ..Default::default()
+        }
+    }
+
+    fn image(scale: u8, light: bool) -> EmoteImage {
+        EmoteImage {
+            scale,
+            url: format!("dark-{scale}"),
+            light_url: light.then(|| format!("light-{scale}")),
+        }
+    }
+
+    #[test]
+    fn image_url_picks_largest_scale_not_above_request() {
+        let emote = emote_with_images(vec![image(1, true), image(2, true), image(4, true)]);
+
+        assert_eq!(
+            emote.image_url(2, &AppearanceTheme::Dark).as_deref(),
+            Some("dark-2")
+        );
+        assert_eq!(
+            emote.image_url(3, &AppearanceTheme::Light).as_deref(),
+            Some("light-2")
+        );
+        assert_eq!(
+            emote
+                .image_url(u8::MAX, &AppearanceTheme::System)
+                .as_deref(),
+            Some("dark-4")
+        );
+    }
+
+    #[test]
+    fn image_url_falls_back_to_dark_and_smallest_available() {
+        let emote = emote_with_images(vec![image(2, false), image(4, false)]);
+
+        assert_eq!(
+            emote.image_url(1, &AppearanceTheme::Light).as_deref(),
+            Some("dark-2")
+        );
+    }
+
+    #[test]
+    fn themed_keeps_url_when_no_images_are_known() {
+        let emote = emote_with_images(Vec::new()).themed(&AppearanceTheme::Light);
+
+        assert_eq!(emote.url, "fallback");
+    }
+
+    #[test]
+    fn twitch_images_cover_each_scale_and_theme() {
+        let images = twitch_emote_images(
+            "25",
+            &["static".to_string(), "animated".to_string()],
+            &["light".to_string(), "dark".to_string()],
+        );
+
+        assert_eq!(
+            images.iter().map(|image| image.scale).collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+        assert_eq!(
+            images[2].url,
+            "https://static-cdn.jtvnw.net/emoticons/v2/25/animated/dark/3.0"
+        );
+        assert_eq!(
+            images[0].light_url.as_deref(),
+            Some("https://static-cdn.jtvnw.net/emoticons/v2/25/animated/light/1.0")
+        );
+    }
+
+    #[test]
+    fn irc_emotes_let_the_cdn_pick_the_format() {
+        let emote =
+            Emote::from_irc_emote("25".to_string(), "Kappa".to_string(), "Channel".to_string());
+
+        assert_eq!(
+            emote.url,
+            "https://static-cdn.jtvnw.net/emoticons/v2/25/default/dark/3.0"
+        );
+        assert!(!emote.animated);
+    }
+}
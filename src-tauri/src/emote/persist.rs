@@ -11,7 +11,11 @@ use tauri_plugin_store::StoreExt;
 use tracing::{debug, error, warn};
 
 use crate::{
-    emote::{cache::EmoteCache, providers::GLOBAL_SCOPE_KEY, Emote},
+    emote::{
+        cache::EmoteCache,
+        providers::{http::CacheValidators, GLOBAL_SCOPE_KEY},
+        Emote,
+    },
     types::{EmoteProviderId, ProviderSettings},
 };
 
@@ -34,12 +38,14 @@ pub(crate) trait EmoteMetadataStore: Send + Sync {
         provider_id: EmoteProviderId,
         scope_key: &str,
         cache: &EmoteCache,
+        validators: &CacheValidators,
         provider_settings: &ProviderSettings,
     );
 }
 
 pub(crate) struct HydratedEmoteCache {
     pub cache: EmoteCache,
+    pub validators: CacheValidators,
     pub saved_at_unix_secs: u64,
     pub loaded_at_unix_secs: u64,
 }
@@ -115,6 +121,7 @@ impl EmoteMetadataStore for TauriEmoteMetadataStore {
         provider_id: EmoteProviderId,
         scope_key: &str,
         cache: &EmoteCache,
+        validators: &CacheValidators,
         provider_settings: &ProviderSettings,
     ) {
         let key = cache_key(provider_id, scope_key);
@@ -131,7 +138,8 @@ impl EmoteMetadataStore for TauriEmoteMetadataStore {
             scope_key.to_string(),
             cache.emotes(),
             unix_now(),
-        );
+        )
+        .with_validators(validators.clone());
         store.set(&key, serde_json::json!(payload));
         prune_expired_channel_entries(&store, provider_id, unix_now(), provider_settings);
 
@@ -148,6 +156,9 @@ struct StoredEmoteCache {
     scope_key: String,
     saved_at_unix_secs: u64,
     emotes: Vec<Emote>,
+    /// HTTP validators for the response these emotes came from.
+    #[serde(default)]
+    validators: CacheValidators,
 }
 
 impl StoredEmoteCache {
@@ -163,9 +174,15 @@ impl StoredEmoteCache {
             scope_key,
             saved_at_unix_secs,
             emotes,
+            validators: CacheValidators::default(),
         }
     }
 
+    fn with_validators(mut self, validators: CacheValidators) -> Self {
+        self.validators = validators;
+        self
+    }
+
     fn validate_for(
         &self,
         provider: EmoteProviderId,
@@ -196,6 +213,7 @@ impl StoredEmoteCache {
     fn into_hydrated(self, provider_name: String, loaded_at_unix_secs: u64) -> HydratedEmoteCache {
         HydratedEmoteCache {
             cache: EmoteCache::from_emotes(self.scope_key, provider_name, self.emotes),
+            validators: self.validators,
             saved_at_unix_secs: self.saved_at_unix_secs,
             loaded_at_unix_secs,
         }
@@ -223,6 +241,7 @@ fn provider_key(provider_id: EmoteProviderId) -> &'static str {
         EmoteProviderId::Bttv => "bttv",
         EmoteProviderId::Ffz => "ffz",
         EmoteProviderId::Seventv => "seventv",
+        EmoteProviderId::Local => "local",
     }
 }
 
@@ -299,6 +318,23 @@ impl MemoryEmoteMetadataStore {
         scope_key: &str,
         emotes: Vec<Emote>,
         saved_at_unix_secs: u64,
+    ) {
+        self.insert_with_validators(
+            provider_id,
+            scope_key,
+            emotes,
+            CacheValidators::default(),
+            saved_at_unix_secs,
+        );
+    }
+
+    pub(crate) fn insert_with_validators(
+        &self,
+        provider_id: EmoteProviderId,
+        scope_key: &str,
+        emotes: Vec<Emote>,
+        validators: CacheValidators,
+        saved_at_unix_secs: u64,
     ) {
         self.entries.lock().unwrap().insert(
             cache_key(provider_id, scope_key),
@@ -307,9 +343,18 @@ impl MemoryEmoteMetadataStore {
                 scope_key.to_string(),
                 emotes,
                 saved_at_unix_secs,
-            ),
+            )
+            .with_validators(validators),
         );
     }
+
+    pub(crate) fn saved_at(&self, provider_id: EmoteProviderId, scope_key: &str) -> Option<u64> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&cache_key(provider_id, scope_key))
+            .map(|payload| payload.saved_at_unix_secs)
+    }
 }
 
 #[cfg(test)]
@@ -339,10 +384,17 @@ impl EmoteMetadataStore for MemoryEmoteMetadataStore {
         provider_id: EmoteProviderId,
         scope_key: &str,
         cache: &EmoteCache,
+        validators: &CacheValidators,
         _provider_settings: &ProviderSettings,
     ) {
         let now = *self.now_unix_secs.lock().unwrap();
-        self.insert(provider_id, scope_key, cache.emotes(), now);
+        self.insert_with_validators(
+            provider_id,
+            scope_key,
+            cache.emotes(),
+            validators.clone(),
+            now,
+        );
     }
 }
 
@@ -385,6 +437,22 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn stored_cache_without_validators_still_decodes() {
+        let mut value = serde_json::to_value(StoredEmoteCache::new(
+            EmoteProviderId::Bttv,
+            GLOBAL_SCOPE_KEY.to_string(),
+            vec![emote("LUL")],
+            NOW,
+        ))
+        .unwrap();
+        value.as_object_mut().unwrap().remove("validators");
+
+        let decoded: StoredEmoteCache = serde_json::from_value(value).unwrap();
+
+        assert!(decoded.validators.is_empty());
+    }
+
     #[test]
     fn channel_metadata_expires_after_retention() {
         let payload = StoredEmoteCache::new(
@@ -9,8 +9,14 @@ use tracing::{debug, error};
 use crate::emote::{
     cache::{EmoteCache, EmoteCacheTrait, MultiCache},
     persist::SharedEmoteMetadataStore,
-    providers::{http::fetch_json, EmoteProvider, GLOBAL_SCOPE_KEY},
-    Emote,
+    providers::{
+        http::{
+            self, fetch_json, CacheValidators, FetchOutcome, ProviderFetchError, ScopeValidators,
+        },
+        policy::{PolicyError, ProviderHealth},
+        EmoteProvider, GLOBAL_SCOPE_KEY,
+    },
+    Emote, EmoteImage,
 };
 use crate::types::{EmoteProviderId, ProviderSettings};
 
@@ -19,6 +25,10 @@ const BTTV_CDN_BASE: &str = "https://cdn.betterttv.net/emote";
 
 type SharedMap<V> = Arc<Mutex<HashMap<String, V>>>;
 
+const BTTV_DEFAULT_SIZE: u32 = 28;
+/// BTTV serves 1x/2x/3x; 3x is the closest match for a 4x display.
+const BTTV_SCALES: [(u8, &str); 3] = [(1, "1x"), (2, "2x"), (4, "3x")];
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
@@ -27,6 +37,11 @@ struct BttvEmote {
     code: String,
     image_type: String,
     animated: bool,
+    /// Only present for emotes that aren't the default 28x28.
+    #[serde(default)]
+    width: Option<u32>,
+    #[serde(default)]
+    height: Option<u32>,
 }
 
 #[derive(Deserialize)]
@@ -42,6 +57,14 @@ fn bttv_to_emote(bttv: &BttvEmote, scope: &str) -> Emote {
     } else {
         "static".to_string()
     };
+    let images: Vec<EmoteImage> = BTTV_SCALES
+        .iter()
+        .map(|(scale, segment)| EmoteImage {
+            scale: *scale,
+            url: format!("{}/{}/{}", BTTV_CDN_BASE, bttv.id, segment),
+            light_url: None,
+        })
+        .collect();
     Emote {
         id: bttv.id.clone(),
         name: bttv.code.clone(),
@@ -50,6 +73,10 @@ fn bttv_to_emote(bttv: &BttvEmote, scope: &str) -> Emote {
         scale: vec!["1x".to_string(), "2x".to_string(), "3x".to_string()],
         provider: "BTTV".to_string(),
         scope: scope.to_string(),
+        animated: bttv.animated,
+        width: Some(bttv.width.unwrap_or(BTTV_DEFAULT_SIZE)),
+        height: Some(bttv.height.unwrap_or(BTTV_DEFAULT_SIZE)),
+        images,
         ..Default::default()
     }
 }
@@ -57,7 +84,9 @@ fn bttv_to_emote(bttv: &BttvEmote, scope: &str) -> Emote {
 #[derive(Clone)]
 pub struct BttvProvider {
     cache: SharedMap<EmoteCache>,
+    validators: ScopeValidators,
     persistence: SharedEmoteMetadataStore,
+    health: ProviderHealth,
     api_base: String,
 }
 
@@ -69,7 +98,9 @@ impl BttvProvider {
     fn with_api_base(persistence: SharedEmoteMetadataStore, api_base: String) -> Self {
         BttvProvider {
             cache: Default::default(),
+            validators: Default::default(),
             persistence,
+            health: Default::default(),
             api_base,
         }
     }
@@ -98,6 +129,8 @@ impl BttvProvider {
         );
 
         let cache = hydrated.cache;
+        self.validators
+            .insert(scope_key.to_string(), hydrated.validators);
         self.cache
             .lock()
             .unwrap()
@@ -109,10 +142,17 @@ impl BttvProvider {
         &self,
         scope_key: String,
         cache: EmoteCache,
+        validators: CacheValidators,
         provider_settings: &ProviderSettings,
     ) {
-        self.persistence
-            .save_cache(self.get_id(), &scope_key, &cache, provider_settings);
+        self.persistence.save_cache(
+            self.get_id(),
+            &scope_key,
+            &cache,
+            &validators,
+            provider_settings,
+        );
+        self.validators.insert(scope_key.clone(), validators);
         self.cache.lock().unwrap().insert(scope_key, cache);
     }
 
@@ -126,80 +166,191 @@ impl BttvProvider {
             );
         }
     }
-}
 
-impl EmoteProvider<MultiCache> for BttvProvider {
-    fn get_id(&self) -> EmoteProviderId {
-        EmoteProviderId::Bttv
-    }
-
-    fn hydrate_cache(&self, scope_key: &str, provider_settings: &ProviderSettings) -> bool {
-        self.hydrate_persisted_cache(scope_key, provider_settings)
-            .is_some()
-    }
-
-    fn load_global_emotes(&self, client: &reqwest::Client, provider_settings: &ProviderSettings) {
+    async fn refresh_global(
+        &self,
+        client: &reqwest::Client,
+        provider_settings: &ProviderSettings,
+    ) -> Result<(), PolicyError<ProviderFetchError>> {
         let fallback = self.hydrate_persisted_cache(GLOBAL_SCOPE_KEY, provider_settings);
+        let validators = self.validators.for_request(GLOBAL_SCOPE_KEY, &fallback);
         let url = format!("{}/emotes/global", self.api_base);
 
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                fetch_json::<Vec<BttvEmote>>(client, "BTTV", "global", &url).await
+        let outcome = self
+            .health
+            .fetch(&self.get_name(), provider_settings, || {
+                fetch_json::<Vec<BttvEmote>>(client, "BTTV", "global", &url, validators.as_ref())
             })
-        }) {
-            Ok(emotes) => {
+            .await;
+        match outcome {
+            Ok(FetchOutcome::Fresh {
+                value: emotes,
+                validators: fresh_validators,
+            }) => {
                 let cache = EmoteCache::new(GLOBAL_SCOPE_KEY.to_owned(), self.get_name());
                 debug!(count = emotes.len(), "loaded bttv global emotes");
                 for bttv in &emotes {
                     cache.set_emote(bttv.code.clone(), bttv_to_emote(bttv, "Global"));
                 }
-                self.store_fresh_cache(GLOBAL_SCOPE_KEY.to_owned(), cache, provider_settings);
+                self.store_fresh_cache(
+                    GLOBAL_SCOPE_KEY.to_owned(),
+                    cache,
+                    fresh_validators,
+                    provider_settings,
+                );
+                Ok(())
+            }
+            Ok(FetchOutcome::NotModified {
+                validators: refreshed,
+            }) => {
+                if let Some((cache, validators)) = http::not_modified(
+                    &self.get_name(),
+                    GLOBAL_SCOPE_KEY,
+                    fallback,
+                    validators,
+                    refreshed,
+                ) {
+                    self.store_fresh_cache(
+                        GLOBAL_SCOPE_KEY.to_owned(),
+                        cache,
+                        validators,
+                        provider_settings,
+                    );
+                }
+                Ok(())
             }
             Err(err) => {
-                error!("failed to load bttv global emotes: err={}", err);
+                match &err {
+                    PolicyError::CircuitOpen { .. } => {
+                        debug!("skipped bttv global emotes: {}", err)
+                    }
+                    PolicyError::Failed(_) => {
+                        error!("failed to load bttv global emotes: err={}", err)
+                    }
+                }
                 self.log_fallback(GLOBAL_SCOPE_KEY, fallback);
+                Err(err)
             }
         }
     }
 
-    fn load_channel_emotes(
+    async fn refresh_channel(
         &self,
         broadcaster_id: String,
         client: &reqwest::Client,
         provider_settings: &ProviderSettings,
-    ) {
+    ) -> Result<(), PolicyError<ProviderFetchError>> {
         let fallback = self.hydrate_persisted_cache(&broadcaster_id, provider_settings);
+        let validators = self.validators.for_request(&broadcaster_id, &fallback);
         let url = format!("{}/users/twitch/{}", self.api_base, broadcaster_id);
 
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
+        let outcome = self
+            .health
+            .fetch(&self.get_name(), provider_settings, || {
                 fetch_json::<BttvChannelResponse>(
                     client,
                     "BTTV",
                     format!("channel:{broadcaster_id}"),
                     &url,
+                    validators.as_ref(),
                 )
-                .await
             })
-        }) {
-            Ok(resp) => {
+            .await;
+        match outcome {
+            Ok(FetchOutcome::Fresh {
+                value: resp,
+                validators: fresh_validators,
+            }) => {
                 let cache = EmoteCache::new(broadcaster_id.clone(), self.get_name());
                 let total = resp.channel_emotes.len() + resp.shared_emotes.len();
                 debug!(broadcaster_id, count = total, "loaded bttv channel emotes");
                 for bttv in resp.channel_emotes.iter().chain(resp.shared_emotes.iter()) {
                     cache.set_emote(bttv.code.clone(), bttv_to_emote(bttv, "Channel"));
                 }
-                self.store_fresh_cache(broadcaster_id, cache, provider_settings);
+                self.store_fresh_cache(broadcaster_id, cache, fresh_validators, provider_settings);
+                Ok(())
+            }
+            Ok(FetchOutcome::NotModified {
+                validators: refreshed,
+            }) => {
+                if let Some((cache, validators)) = http::not_modified(
+                    &self.get_name(),
+                    &broadcaster_id,
+                    fallback,
+                    validators,
+                    refreshed,
+                ) {
+                    self.store_fresh_cache(broadcaster_id, cache, validators, provider_settings);
+                }
+                Ok(())
             }
             Err(err) => {
-                error!(
-                    broadcaster_id,
-                    "failed to load bttv channel emotes: err={}", err
-                );
+                match &err {
+                    PolicyError::CircuitOpen { .. } => {
+                        debug!(broadcaster_id, "skipped bttv channel emotes: {}", err)
+                    }
+                    PolicyError::Failed(_) => error!(
+                        broadcaster_id,
+                        "failed to load bttv channel emotes: err={}", err
+                    ),
+                }
                 self.log_fallback(&broadcaster_id, fallback);
+                Err(err)
             }
         }
     }
+}
+
+impl EmoteProvider<MultiCache> for BttvProvider {
+    fn get_id(&self) -> EmoteProviderId {
+        EmoteProviderId::Bttv
+    }
+
+    fn hydrate_cache(&self, scope_key: &str, provider_settings: &ProviderSettings) -> bool {
+        self.hydrate_persisted_cache(scope_key, provider_settings)
+            .is_some()
+    }
+
+    fn load_global_emotes(&self, client: &reqwest::Client, provider_settings: &ProviderSettings) {
+        let provider = self.clone();
+        let client = client.clone();
+        self.health.load_with_retry(
+            &self.get_name(),
+            GLOBAL_SCOPE_KEY,
+            provider_settings,
+            move |settings| {
+                let provider = provider.clone();
+                let client = client.clone();
+                async move { provider.refresh_global(&client, &settings).await }
+            },
+        );
+    }
+
+    fn load_channel_emotes(
+        &self,
+        broadcaster_id: String,
+        client: &reqwest::Client,
+        provider_settings: &ProviderSettings,
+    ) {
+        let provider = self.clone();
+        let client = client.clone();
+        let scope_key = broadcaster_id.clone();
+        self.health.load_with_retry(
+            &self.get_name(),
+            &scope_key,
+            provider_settings,
+            move |settings| {
+                let provider = provider.clone();
+                let client = client.clone();
+                let broadcaster_id = broadcaster_id.clone();
+                async move {
+                    provider
+                        .refresh_channel(broadcaster_id, &client, &settings)
+                        .await
+                }
+            },
+        );
+    }
 
     fn get_emote_cache(&self, scope: String) -> MultiCache {
         let store = self.cache.lock().unwrap();
@@ -223,11 +374,20 @@ mod tests {
     use crate::emote::{
         cache::EmoteCacheTrait,
         persist::MemoryEmoteMetadataStore,
-        providers::{EmoteProvider, GLOBAL_SCOPE_KEY},
+        providers::{http::CacheValidators, EmoteProvider, GLOBAL_SCOPE_KEY},
     };
 
     const NOW: u64 = 1_800_000_000;
 
+    fn outage_settings() -> ProviderSettings {
+        ProviderSettings {
+            fetch_retry_base_ms: 1,
+            fetch_retry_max_ms: 1,
+            background_retry_enabled: false,
+            ..Default::default()
+        }
+    }
+
     fn emote(name: &str) -> Emote {
         Emote {
             id: format!("id-{name}"),
@@ -254,10 +414,109 @@ mod tests {
         );
         let provider = BttvProvider::with_api_base(persistence, server.base_url());
 
-        provider.load_global_emotes(&reqwest::Client::new(), &ProviderSettings::default());
+        let result = provider
+            .refresh_global(&reqwest::Client::new(), &outage_settings())
+            .await;
+
+        assert!(result.is_err());
 
         let cache = provider.get_emote_cache(GLOBAL_SCOPE_KEY.to_string());
         assert!(cache.has_emote("CachedBTTV".to_string()));
+        mock.assert_hits(3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn not_modified_keeps_persisted_cache_and_bumps_saved_at() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/emotes/global")
+                .header("If-None-Match", "\"bttv-v1\"");
+            then.status(304);
+        });
+        let persistence = MemoryEmoteMetadataStore::new(NOW);
+        persistence.insert_with_validators(
+            EmoteProviderId::Bttv,
+            GLOBAL_SCOPE_KEY,
+            vec![emote("CachedBTTV")],
+            CacheValidators {
+                etag: Some("\"bttv-v1\"".to_string()),
+                last_modified: None,
+                body_bytes: 2048,
+            },
+            NOW - 60,
+        );
+        let provider = BttvProvider::with_api_base(persistence.clone(), server.base_url());
+
+        let result = provider
+            .refresh_global(&reqwest::Client::new(), &ProviderSettings::default())
+            .await;
+
+        assert!(result.is_ok());
+
+        let cache = provider.get_emote_cache(GLOBAL_SCOPE_KEY.to_string());
+        assert!(cache.has_emote("CachedBTTV".to_string()));
+        assert_eq!(
+            persistence.saved_at(EmoteProviderId::Bttv, GLOBAL_SCOPE_KEY),
+            Some(NOW)
+        );
         mock.assert();
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn background_retry_fills_cache_after_outage() {
+        let server = MockServer::start();
+        let mut outage = server.mock(|when, then| {
+            when.method(GET).path("/emotes/global");
+            then.status(503).body("down");
+        });
+        let provider =
+            BttvProvider::with_api_base(MemoryEmoteMetadataStore::new(NOW), server.base_url());
+        let settings = ProviderSettings {
+            fetch_retry_attempts: 1,
+            circuit_breaker_threshold: 1,
+            circuit_breaker_cooldown_secs: 1,
+            background_retry_enabled: true,
+            ..outage_settings()
+        };
+
+        provider.load_global_emotes(&reqwest::Client::new(), &settings);
+        for _ in 0..50 {
+            if outage.hits() > 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        outage.assert_hits(1);
+        assert!(!provider
+            .get_emote_cache(GLOBAL_SCOPE_KEY.to_string())
+            .has_emote("Recovered".to_string()));
+
+        outage.delete();
+        let recovered = server.mock(|when, then| {
+            when.method(GET).path("/emotes/global");
+            then.status(200).json_body_obj(&serde_json::json!([
+                {
+                    "id": "recovered-id",
+                    "code": "Recovered",
+                    "imageType": "png",
+                    "animated": false
+                }
+            ]));
+        });
+
+        for _ in 0..50 {
+            if provider
+                .get_emote_cache(GLOBAL_SCOPE_KEY.to_string())
+                .has_emote("Recovered".to_string())
+            {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert!(provider
+            .get_emote_cache(GLOBAL_SCOPE_KEY.to_string())
+            .has_emote("Recovered".to_string()));
+        recovered.assert_hits(1);
+    }
 }
@@ -9,8 +9,14 @@ use tracing::{debug, error};
 use crate::emote::{
     cache::{EmoteCache, EmoteCacheTrait, MultiCache},
     persist::SharedEmoteMetadataStore,
-    providers::{http::fetch_json, EmoteProvider, GLOBAL_SCOPE_KEY},
-    Emote,
+    providers::{
+        http::{
+            self, fetch_json, CacheValidators, FetchOutcome, ProviderFetchError, ScopeValidators,
+        },
+        policy::{PolicyError, ProviderHealth},
+        EmoteProvider, GLOBAL_SCOPE_KEY,
+    },
+    Emote, EmoteImage,
 };
 use crate::types::{EmoteProviderId, ProviderSettings};
 
@@ -19,14 +25,20 @@ const FFZ_CDN_BASE: &str = "https://cdn.frankerfacez.com/emote";
 
 type SharedMap<V> = Arc<Mutex<HashMap<String, V>>>;
 
-#[derive(Deserialize)]
-struct FfzAnimated {}
+/// Scale-keyed image URLs ("1", "2", "4"). Not every emote has every scale.
+type FfzUrls = HashMap<String, String>;
 
 #[derive(Deserialize)]
 struct FfzEmote {
     id: u64,
     name: String,
-    animated: Option<FfzAnimated>,
+    #[serde(default)]
+    width: Option<u32>,
+    #[serde(default)]
+    height: Option<u32>,
+    #[serde(default)]
+    urls: FfzUrls,
+    animated: Option<FfzUrls>,
 }
 
 #[derive(Deserialize)]
@@ -47,6 +59,38 @@ struct FfzRoomResponse {
     sets: HashMap<String, FfzEmoteSet>,
 }
 
+fn ffz_images(ffz: &FfzEmote) -> Vec<EmoteImage> {
+    let is_animated = ffz.animated.is_some();
+    let urls = ffz.animated.as_ref().unwrap_or(&ffz.urls);
+    if urls.is_empty() {
+        return [1, 2, 4]
+            .into_iter()
+            .map(|scale| EmoteImage {
+                scale,
+                url: if is_animated {
+                    format!("{}/{}/animated/{}", FFZ_CDN_BASE, ffz.id, scale)
+                } else {
+                    format!("{}/{}/{}", FFZ_CDN_BASE, ffz.id, scale)
+                },
+                light_url: None,
+            })
+            .collect();
+    }
+
+    let mut images: Vec<EmoteImage> = urls
+        .iter()
+        .filter_map(|(scale, url)| {
+            Some(EmoteImage {
+                scale: scale.parse().ok()?,
+                url: url.clone(),
+                light_url: None,
+            })
+        })
+        .collect();
+    images.sort_by_key(|image| image.scale);
+    images
+}
+
 fn ffz_to_emote(ffz: &FfzEmote, scope: &str) -> Emote {
     let is_animated = ffz.animated.is_some();
     let format = if is_animated {
@@ -54,19 +98,23 @@ fn ffz_to_emote(ffz: &FfzEmote, scope: &str) -> Emote {
     } else {
         "static".to_string()
     };
-    let url = if is_animated {
-        format!("{}/{}/animated/4", FFZ_CDN_BASE, ffz.id)
-    } else {
-        format!("{}/{}/4", FFZ_CDN_BASE, ffz.id)
-    };
+    let images = ffz_images(ffz);
+    let url = images
+        .last()
+        .map(|image| image.url.clone())
+        .unwrap_or_default();
     Emote {
         id: ffz.id.to_string(),
         name: ffz.name.clone(),
         url,
         format: vec![format],
-        scale: vec!["1".to_string(), "2".to_string(), "4".to_string()],
+        scale: images.iter().map(|image| image.scale.to_string()).collect(),
         provider: "FFZ".to_string(),
         scope: scope.to_string(),
+        animated: is_animated,
+        width: ffz.width,
+        height: ffz.height,
+        images,
         ..Default::default()
     }
 }
@@ -74,7 +122,9 @@ fn ffz_to_emote(ffz: &FfzEmote, scope: &str) -> Emote {
 #[derive(Clone)]
 pub struct FfzProvider {
     cache: SharedMap<EmoteCache>,
+    validators: ScopeValidators,
     persistence: SharedEmoteMetadataStore,
+    health: ProviderHealth,
     api_base: String,
 }
 
@@ -86,7 +136,9 @@ impl FfzProvider {
     fn with_api_base(persistence: SharedEmoteMetadataStore, api_base: String) -> Self {
         FfzProvider {
             cache: Default::default(),
+            validators: Default::default(),
             persistence,
+            health: Default::default(),
             api_base,
         }
     }
@@ -115,6 +167,8 @@ impl FfzProvider {
         );
 
         let cache = hydrated.cache;
+        self.validators
+            .insert(scope_key.to_string(), hydrated.validators);
         self.cache
             .lock()
             .unwrap()
@@ -126,10 +180,17 @@ impl FfzProvider {
         &self,
         scope_key: String,
         cache: EmoteCache,
+        validators: CacheValidators,
         provider_settings: &ProviderSettings,
     ) {
-        self.persistence
-            .save_cache(self.get_id(), &scope_key, &cache, provider_settings);
+        self.persistence.save_cache(
+            self.get_id(),
+            &scope_key,
+            &cache,
+            &validators,
+            provider_settings,
+        );
+        self.validators.insert(scope_key.clone(), validators);
         self.cache.lock().unwrap().insert(scope_key, cache);
     }
 
@@ -143,28 +204,27 @@ impl FfzProvider {
             );
         }
     }
-}
-
-impl EmoteProvider<MultiCache> for FfzProvider {
-    fn get_id(&self) -> EmoteProviderId {
-        EmoteProviderId::Ffz
-    }
-
-    fn hydrate_cache(&self, scope_key: &str, provider_settings: &ProviderSettings) -> bool {
-        self.hydrate_persisted_cache(scope_key, provider_settings)
-            .is_some()
-    }
 
-    fn load_global_emotes(&self, client: &reqwest::Client, provider_settings: &ProviderSettings) {
+    async fn refresh_global(
+        &self,
+        client: &reqwest::Client,
+        provider_settings: &ProviderSettings,
+    ) -> Result<(), PolicyError<ProviderFetchError>> {
         let fallback = self.hydrate_persisted_cache(GLOBAL_SCOPE_KEY, provider_settings);
+        let validators = self.validators.for_request(GLOBAL_SCOPE_KEY, &fallback);
         let url = format!("{}/set/global", self.api_base);
 
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                fetch_json::<FfzGlobalResponse>(client, "FFZ", "global", &url).await
+        let outcome = self
+            .health
+            .fetch(&self.get_name(), provider_settings, || {
+                fetch_json::<FfzGlobalResponse>(client, "FFZ", "global", &url, validators.as_ref())
             })
-        }) {
-            Ok(resp) => {
+            .await;
+        match outcome {
+            Ok(FetchOutcome::Fresh {
+                value: resp,
+                validators: fresh_validators,
+            }) => {
                 let cache = EmoteCache::new(GLOBAL_SCOPE_KEY.to_owned(), self.get_name());
                 let mut count = 0;
                 for set in resp.sets.values() {
@@ -174,36 +234,73 @@ impl EmoteProvider<MultiCache> for FfzProvider {
                     }
                 }
                 debug!(count, "loaded ffz global emotes");
-                self.store_fresh_cache(GLOBAL_SCOPE_KEY.to_owned(), cache, provider_settings);
+                self.store_fresh_cache(
+                    GLOBAL_SCOPE_KEY.to_owned(),
+                    cache,
+                    fresh_validators,
+                    provider_settings,
+                );
+                Ok(())
+            }
+            Ok(FetchOutcome::NotModified {
+                validators: refreshed,
+            }) => {
+                if let Some((cache, validators)) = http::not_modified(
+                    &self.get_name(),
+                    GLOBAL_SCOPE_KEY,
+                    fallback,
+                    validators,
+                    refreshed,
+                ) {
+                    self.store_fresh_cache(
+                        GLOBAL_SCOPE_KEY.to_owned(),
+                        cache,
+                        validators,
+                        provider_settings,
+                    );
+                }
+                Ok(())
             }
             Err(err) => {
-                error!("failed to load ffz global emotes: err={}", err);
+                match &err {
+                    PolicyError::CircuitOpen { .. } => debug!("skipped ffz global emotes: {}", err),
+                    PolicyError::Failed(_) => {
+                        error!("failed to load ffz global emotes: err={}", err);
+                    }
+                }
                 self.log_fallback(GLOBAL_SCOPE_KEY, fallback);
+                Err(err)
             }
         }
     }
 
-    fn load_channel_emotes(
+    async fn refresh_channel(
         &self,
         broadcaster_id: String,
         client: &reqwest::Client,
         provider_settings: &ProviderSettings,
-    ) {
+    ) -> Result<(), PolicyError<ProviderFetchError>> {
         let fallback = self.hydrate_persisted_cache(&broadcaster_id, provider_settings);
+        let validators = self.validators.for_request(&broadcaster_id, &fallback);
         let url = format!("{}/room/id/{}", self.api_base, broadcaster_id);
 
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
+        let outcome = self
+            .health
+            .fetch(&self.get_name(), provider_settings, || {
                 fetch_json::<FfzRoomResponse>(
                     client,
                     "FFZ",
                     format!("channel:{broadcaster_id}"),
                     &url,
+                    validators.as_ref(),
                 )
-                .await
             })
-        }) {
-            Ok(resp) => {
+            .await;
+        match outcome {
+            Ok(FetchOutcome::Fresh {
+                value: resp,
+                validators: fresh_validators,
+            }) => {
                 let cache = EmoteCache::new(broadcaster_id.clone(), self.get_name());
                 let mut count = 0;
                 for set in resp.sets.values() {
@@ -213,17 +310,92 @@ impl EmoteProvider<MultiCache> for FfzProvider {
                     }
                 }
                 debug!(broadcaster_id, count, "loaded ffz channel emotes");
-                self.store_fresh_cache(broadcaster_id, cache, provider_settings);
+                self.store_fresh_cache(broadcaster_id, cache, fresh_validators, provider_settings);
+                Ok(())
+            }
+            Ok(FetchOutcome::NotModified {
+                validators: refreshed,
+            }) => {
+                if let Some((cache, validators)) = http::not_modified(
+                    &self.get_name(),
+                    &broadcaster_id,
+                    fallback,
+                    validators,
+                    refreshed,
+                ) {
+                    self.store_fresh_cache(broadcaster_id, cache, validators, provider_settings);
+                }
+                Ok(())
             }
             Err(err) => {
-                error!(
-                    broadcaster_id,
-                    "failed to load ffz channel emotes: err={}", err
-                );
+                match &err {
+                    PolicyError::CircuitOpen { .. } => {
+                        debug!(broadcaster_id, "skipped ffz channel emotes: {}", err)
+                    }
+                    PolicyError::Failed(_) => {
+                        error!(
+                            broadcaster_id,
+                            "failed to load ffz channel emotes: err={}", err
+                        );
+                    }
+                }
                 self.log_fallback(&broadcaster_id, fallback);
+                Err(err)
             }
         }
     }
+}
+
+impl EmoteProvider<MultiCache> for FfzProvider {
+    fn get_id(&self) -> EmoteProviderId {
+        EmoteProviderId::Ffz
+    }
+
+    fn hydrate_cache(&self, scope_key: &str, provider_settings: &ProviderSettings) -> bool {
+        self.hydrate_persisted_cache(scope_key, provider_settings)
+            .is_some()
+    }
+
+    fn load_global_emotes(&self, client: &reqwest::Client, provider_settings: &ProviderSettings) {
+        let provider = self.clone();
+        let client = client.clone();
+        self.health.load_with_retry(
+            &self.get_name(),
+            GLOBAL_SCOPE_KEY,
+            provider_settings,
+            move |settings| {
+                let provider = provider.clone();
+                let client = client.clone();
+                async move { provider.refresh_global(&client, &settings).await }
+            },
+        );
+    }
+
+    fn load_channel_emotes(
+        &self,
+        broadcaster_id: String,
+        client: &reqwest::Client,
+        provider_settings: &ProviderSettings,
+    ) {
+        let provider = self.clone();
+        let client = client.clone();
+        let scope_key = broadcaster_id.clone();
+        self.health.load_with_retry(
+            &self.get_name(),
+            &scope_key,
+            provider_settings,
+            move |settings| {
+                let provider = provider.clone();
+                let client = client.clone();
+                let broadcaster_id = broadcaster_id.clone();
+                async move {
+                    provider
+                        .refresh_channel(broadcaster_id, &client, &settings)
+                        .await
+                }
+            },
+        );
+    }
 
     fn get_emote_cache(&self, scope: String) -> MultiCache {
         let store = self.cache.lock().unwrap();
@@ -252,6 +424,15 @@ mod tests {
 
     const NOW: u64 = 1_800_000_000;
 
+    fn outage_settings() -> ProviderSettings {
+        ProviderSettings {
+            fetch_retry_base_ms: 1,
+            fetch_retry_max_ms: 1,
+            background_retry_enabled: false,
+            ..Default::default()
+        }
+    }
+
     fn emote(name: &str) -> Emote {
         Emote {
             id: format!("id-{name}"),
@@ -278,10 +459,43 @@ mod tests {
         );
         let provider = FfzProvider::with_api_base(persistence, server.base_url());
 
-        provider.load_global_emotes(&reqwest::Client::new(), &ProviderSettings::default());
+        let result = provider
+            .refresh_global(&reqwest::Client::new(), &outage_settings())
+            .await;
+
+        assert!(result.is_err());
 
         let cache = provider.get_emote_cache(GLOBAL_SCOPE_KEY.to_string());
         assert!(cache.has_emote("CachedFFZ".to_string()));
-        mock.assert();
+        mock.assert_hits(3);
+    }
+
+    #[test]
+    fn ffz_emote_uses_reported_sizes_and_available_scales() {
+        let ffz: FfzEmote = serde_json::from_value(serde_json::json!({
+            "id": 720507,
+            "name": "WideFFZ",
+            "width": 64,
+            "height": 20,
+            "urls": {
+                "1": "https://cdn.frankerfacez.com/emote/720507/1",
+                "2": "https://cdn.frankerfacez.com/emote/720507/2"
+            }
+        }))
+        .unwrap();
+
+        let emote = ffz_to_emote(&ffz, "Channel");
+
+        assert_eq!((emote.width, emote.height), (Some(64), Some(20)));
+        assert!(!emote.animated);
+        assert_eq!(
+            emote
+                .images
+                .iter()
+                .map(|image| image.scale)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(emote.url, "https://cdn.frankerfacez.com/emote/720507/2");
     }
 }
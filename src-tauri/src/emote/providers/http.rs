@@ -1,16 +1,104 @@
 use std::{
+    collections::HashMap,
     error::Error,
     fmt,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
-use reqwest::StatusCode;
-use serde::de::DeserializeOwned;
+use reqwest::{header, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::types::ProviderSettings;
 
 const BODY_PREVIEW_BYTES: usize = 512;
 
+/// Validators from the last full response for a scope, replayed as
+/// `If-None-Match` / `If-Modified-Since` so unchanged scopes come back as a
+/// bodyless 304.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Size of the body these validators describe, i.e. what a 304 saves.
+    pub body_bytes: u64,
+}
+
+impl CacheValidators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    fn from_headers(headers: &header::HeaderMap, body_bytes: usize) -> Self {
+        let value = |name: header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        Self {
+            etag: value(header::ETAG),
+            last_modified: value(header::LAST_MODIFIED),
+            body_bytes: body_bytes as u64,
+        }
+    }
+}
+
+/// The validators of every scope a provider loaded, shared by its clones.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScopeValidators(Arc<Mutex<HashMap<String, CacheValidators>>>);
+
+impl ScopeValidators {
+    pub fn insert(&self, scope_key: String, validators: CacheValidators) {
+        self.0.lock().unwrap().insert(scope_key, validators);
+    }
+
+    /// Validators to send for `scope_key`; only when there's a cached copy a
+    /// 304 could stand in for.
+    pub fn for_request<C>(&self, scope_key: &str, fallback: &Option<C>) -> Option<CacheValidators> {
+        fallback.as_ref()?;
+        self.0
+            .lock()
+            .unwrap()
+            .get(scope_key)
+            .filter(|validators| !validators.is_empty())
+            .cloned()
+    }
+}
+
+/// A 304 means the cached copy is current: hands it back with its validators
+/// to re-save, so `saved_at` reflects the confirmation and retention doesn't
+/// expire it. Validators the 304 sent replace the saved ones.
+pub(crate) fn not_modified<C>(
+    provider: &str,
+    scope_key: &str,
+    fallback: Option<C>,
+    validators: Option<CacheValidators>,
+    refreshed: CacheValidators,
+) -> Option<(C, CacheValidators)> {
+    let (Some(cache), Some(saved)) = (fallback, validators) else {
+        return None;
+    };
+    let validators = CacheValidators {
+        etag: refreshed.etag.or(saved.etag),
+        last_modified: refreshed.last_modified.or(saved.last_modified),
+        body_bytes: saved.body_bytes,
+    };
+    crate::logging::record_conditional_fetch_saved(provider, scope_key, validators.body_bytes);
+    Some((cache, validators))
+}
+
+#[derive(Debug)]
+pub(crate) enum FetchOutcome<T> {
+    Fresh {
+        value: T,
+        validators: CacheValidators,
+    },
+    /// The server confirmed the cached copy is current (HTTP 304). Carries
+    /// the validators the 304 sent, if any, without a body size.
+    NotModified { validators: CacheValidators },
+}
+
 #[derive(Debug)]
 pub(crate) enum ProviderFetchError {
     Request {
@@ -100,19 +188,31 @@ pub(crate) fn provider_client(settings: &ProviderSettings) -> reqwest::Client {
         .expect("valid emote provider HTTP client")
 }
 
+/// Fetches and decodes `url`. When `validators` are given the request is made
+/// conditional and a 304 yields [`FetchOutcome::NotModified`]; a 304 without
+/// validators is treated like any other non-success status.
 pub(crate) async fn fetch_json<T>(
     client: &reqwest::Client,
     provider: &str,
     scope: impl Into<String>,
     url: &str,
-) -> Result<T, ProviderFetchError>
+    validators: Option<&CacheValidators>,
+) -> Result<FetchOutcome<T>, ProviderFetchError>
 where
     T: DeserializeOwned,
 {
     let scope = scope.into();
     let start = Instant::now();
-    let response = client
-        .get(url)
+    let mut request = client.get(url);
+    if let Some(validators) = validators {
+        if let Some(etag) = &validators.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request
         .send()
         .await
         .map_err(|source| ProviderFetchError::Request {
@@ -123,6 +223,12 @@ where
             source,
         })?;
     let status = response.status();
+    if status == StatusCode::NOT_MODIFIED && validators.is_some() {
+        return Ok(FetchOutcome::NotModified {
+            validators: CacheValidators::from_headers(response.headers(), 0),
+        });
+    }
+    let headers = response.headers().clone();
     let body = response
         .bytes()
         .await
@@ -147,14 +253,19 @@ where
         });
     }
 
-    serde_json::from_slice::<T>(&body).map_err(|source| ProviderFetchError::Decode {
-        provider: provider.to_owned(),
-        scope,
-        url: url.to_owned(),
-        status,
-        elapsed,
-        body_preview,
-        source,
+    let value =
+        serde_json::from_slice::<T>(&body).map_err(|source| ProviderFetchError::Decode {
+            provider: provider.to_owned(),
+            scope,
+            url: url.to_owned(),
+            status,
+            elapsed,
+            body_preview,
+            source,
+        })?;
+    Ok(FetchOutcome::Fresh {
+        value,
+        validators: CacheValidators::from_headers(&headers, body.len()),
     })
 }
 
@@ -192,17 +303,19 @@ mod tests {
         });
 
         let client = reqwest::Client::new();
-        let response: TestResponse =
-            fetch_json(&client, "TestProvider", "global", &server.url("/ok"))
-                .await
-                .unwrap();
+        let response = fetch_json(&client, "TestProvider", "global", &server.url("/ok"), None)
+            .await
+            .unwrap();
 
-        assert_eq!(
-            response,
-            TestResponse {
-                value: "ok".to_owned()
-            }
-        );
+        match response {
+            FetchOutcome::Fresh { value, .. } => assert_eq!(
+                value,
+                TestResponse {
+                    value: "ok".to_owned()
+                }
+            ),
+            other => panic!("expected fresh response, got {other:?}"),
+        }
         mock.assert();
     }
 
@@ -215,10 +328,15 @@ mod tests {
         });
 
         let client = reqwest::Client::new();
-        let err =
-            fetch_json::<TestResponse>(&client, "TestProvider", "global", &server.url("/bad"))
-                .await
-                .unwrap_err();
+        let err = fetch_json::<TestResponse>(
+            &client,
+            "TestProvider",
+            "global",
+            &server.url("/bad"),
+            None,
+        )
+        .await
+        .unwrap_err();
 
         match err {
             ProviderFetchError::Status {
@@ -248,6 +366,7 @@ mod tests {
             "TestProvider",
             "global",
             &server.url("/invalid-json"),
+            None,
         )
         .await
         .unwrap_err();
@@ -265,4 +384,118 @@ mod tests {
         }
         mock.assert();
     }
+
+    #[tokio::test]
+    async fn fetch_json_captures_validators_from_fresh_response() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/etag");
+            then.status(200)
+                .header("ETag", "\"v1\"")
+                .header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+                .body(r#"{"value":"ok"}"#);
+        });
+
+        let client = reqwest::Client::new();
+        let response = fetch_json::<TestResponse>(
+            &client,
+            "TestProvider",
+            "global",
+            &server.url("/etag"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        match response {
+            FetchOutcome::Fresh { validators, .. } => assert_eq!(
+                validators,
+                CacheValidators {
+                    etag: Some("\"v1\"".to_owned()),
+                    last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+                    body_bytes: 14,
+                }
+            ),
+            other => panic!("expected fresh response, got {other:?}"),
+        }
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn fetch_json_sends_validators_and_reports_not_modified() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/etag")
+                .header("If-None-Match", "\"v1\"")
+                .header("If-Modified-Since", "Wed, 21 Oct 2015 07:28:00 GMT");
+            then.status(304).header("ETag", "\"v2\"");
+        });
+        let validators = CacheValidators {
+            etag: Some("\"v1\"".to_owned()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+            body_bytes: 14,
+        };
+
+        let client = reqwest::Client::new();
+        let response = fetch_json::<TestResponse>(
+            &client,
+            "TestProvider",
+            "global",
+            &server.url("/etag"),
+            Some(&validators),
+        )
+        .await
+        .unwrap();
+
+        let FetchOutcome::NotModified {
+            validators: refreshed,
+        } = response
+        else {
+            panic!("expected not modified, got {response:?}");
+        };
+        mock.assert();
+
+        let (_, merged) = not_modified(
+            "TestProvider",
+            "global",
+            Some(()),
+            Some(validators),
+            refreshed,
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            CacheValidators {
+                etag: Some("\"v2\"".to_owned()),
+                last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+                body_bytes: 14,
+            }
+        );
+    }
+
+    #[test]
+    fn validators_are_only_sent_with_a_cached_copy() {
+        let validators = ScopeValidators::default();
+        validators.insert("global".to_string(), CacheValidators::default());
+        validators.insert(
+            "1234".to_string(),
+            CacheValidators {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+                body_bytes: 10,
+            },
+        );
+
+        assert!(validators.for_request("global", &Some(())).is_none());
+        assert!(validators.for_request("1234", &None::<()>).is_none());
+        assert_eq!(
+            validators
+                .for_request("1234", &Some(()))
+                .unwrap()
+                .etag
+                .as_deref(),
+            Some("\"v1\"")
+        );
+    }
 }
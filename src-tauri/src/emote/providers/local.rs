@@ -0,0 +1,629 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, SystemTime},
+};
+
+use serde::Deserialize;
+use tracing::{debug, error, info, warn};
+
+use crate::emote::{
+    cache::{EmoteCache, EmoteCacheTrait, MultiCache},
+    providers::{EmoteProvider, GLOBAL_SCOPE_KEY},
+    Emote, EmoteImage,
+};
+use crate::types::{EmoteProviderId, EmoteSettings, ProviderSettings};
+
+/// Custom URI scheme the webview uses to load local emote images.
+pub const LOCAL_EMOTE_SCHEME: &str = "pepo-emote";
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "gif", "webp", "jpg", "jpeg", "avif"];
+
+/// Served-id → file path for every emote the local provider has loaded.
+/// Shared with the URI scheme handler so only known files are ever served.
+#[derive(Clone, Default)]
+pub struct LocalEmoteFiles(Arc<RwLock<HashMap<String, PathBuf>>>);
+
+impl LocalEmoteFiles {
+    fn replace(&self, files: HashMap<String, PathBuf>) {
+        *self.0.write().unwrap() = files;
+    }
+
+    fn get(&self, id: &str) -> Option<PathBuf> {
+        self.0.read().unwrap().get(id).cloned()
+    }
+}
+
+/// Manifest format:
+///
+/// ```json
+/// { "emotes": { "teamLOL": "lol.png", "teamHi": { "file": "hi.gif", "channels": ["1234"] } } }
+/// ```
+///
+/// Paths are relative to the manifest. `channels` are broadcaster IDs; an
+/// entry without channels is global.
+#[derive(Deserialize)]
+struct LocalManifest {
+    emotes: BTreeMap<String, LocalManifestEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LocalManifestEntry {
+    File(String),
+    Scoped {
+        file: String,
+        #[serde(default)]
+        channels: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LocalSource {
+    /// Top-level images are global; images in a subdirectory named after a
+    /// broadcaster ID are scoped to that channel.
+    Directory(PathBuf),
+    Manifest(PathBuf),
+}
+
+impl LocalSource {
+    fn from_setting(path: &str) -> Option<Self> {
+        let path = path.trim();
+        if path.is_empty() {
+            return None;
+        }
+        let path = PathBuf::from(path);
+        let is_manifest = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        Some(if is_manifest {
+            LocalSource::Manifest(path)
+        } else {
+            LocalSource::Directory(path)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LocalEmoteEntry {
+    name: String,
+    scope: String,
+    path: PathBuf,
+}
+
+fn is_image(path: &Path) -> bool {
+    path.is_file()
+        && path.extension().is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+fn image_entries(dir: &Path, scope: &str) -> Result<Vec<LocalEmoteEntry>, String> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|err| format!("{}: {err}", dir.display()))? {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if !is_image(&path) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        entries.push(LocalEmoteEntry {
+            name: name.to_owned(),
+            scope: scope.to_owned(),
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+fn scan_source(source: &LocalSource) -> Result<Vec<LocalEmoteEntry>, String> {
+    let mut entries = match source {
+        LocalSource::Directory(dir) => {
+            let mut entries = image_entries(dir, GLOBAL_SCOPE_KEY)?;
+            for entry in fs::read_dir(dir).map_err(|err| format!("{}: {err}", dir.display()))? {
+                let path = entry.map_err(|err| err.to_string())?.path();
+                if !path.is_dir() {
+                    continue;
+                }
+                if let Some(scope) = path.file_name().and_then(|name| name.to_str()) {
+                    entries.extend(image_entries(&path, scope)?);
+                }
+            }
+            entries
+        }
+        LocalSource::Manifest(manifest_path) => {
+            let raw = fs::read_to_string(manifest_path)
+                .map_err(|err| format!("{}: {err}", manifest_path.display()))?;
+            let manifest: LocalManifest = serde_json::from_str(&raw)
+                .map_err(|err| format!("{}: {err}", manifest_path.display()))?;
+            let base = manifest_path.parent().unwrap_or(Path::new("."));
+
+            let mut entries = Vec::new();
+            for (name, entry) in manifest.emotes {
+                let (file, channels) = match entry {
+                    LocalManifestEntry::File(file) => (file, Vec::new()),
+                    LocalManifestEntry::Scoped { file, channels } => (file, channels),
+                };
+                let path = base.join(file);
+                if !is_image(&path) {
+                    warn!(name, path = %path.display(), "skipping local emote with missing image");
+                    continue;
+                }
+                if channels.is_empty() {
+                    entries.push(LocalEmoteEntry {
+                        name: name.clone(),
+                        scope: GLOBAL_SCOPE_KEY.to_owned(),
+                        path: path.clone(),
+                    });
+                }
+                for channel in channels {
+                    entries.push(LocalEmoteEntry {
+                        name: name.clone(),
+                        scope: channel,
+                        path: path.clone(),
+                    });
+                }
+            }
+            entries
+        }
+    };
+    entries.sort_by(|a, b| (&a.scope, &a.name).cmp(&(&b.scope, &b.name)));
+    Ok(entries)
+}
+
+/// Cheap change detector: the source's own mtime plus (path, mtime, size) of
+/// every file it resolved to. Directory mtimes catch added/removed files.
+fn fingerprint(
+    source: &LocalSource,
+    entries: &[LocalEmoteEntry],
+) -> Vec<(PathBuf, Option<SystemTime>, u64)> {
+    let stat = |path: &Path| {
+        let meta = fs::metadata(path).ok();
+        (
+            path.to_path_buf(),
+            meta.as_ref().and_then(|meta| meta.modified().ok()),
+            meta.map(|meta| meta.len()).unwrap_or_default(),
+        )
+    };
+    let mut paths: Vec<PathBuf> = match source {
+        LocalSource::Directory(dir) => {
+            let mut dirs = vec![dir.clone()];
+            if let Ok(read_dir) = fs::read_dir(dir) {
+                dirs.extend(
+                    read_dir
+                        .flatten()
+                        .map(|entry| entry.path())
+                        .filter(|path| path.is_dir()),
+                );
+            }
+            dirs
+        }
+        LocalSource::Manifest(path) => vec![path.clone()],
+    };
+    paths.extend(entries.iter().map(|entry| entry.path.clone()));
+    paths.sort();
+    paths.dedup();
+    paths.iter().map(|path| stat(path)).collect()
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn local_emote_id(entry: &LocalEmoteEntry) -> String {
+    format!(
+        "{}/{}",
+        percent_encode(&entry.scope),
+        percent_encode(&entry.name)
+    )
+}
+
+/// URL the webview loads for a served id. Windows and Android webviews only
+/// accept custom schemes through the `http://<scheme>.localhost` form.
+pub fn local_emote_url(id: &str) -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{LOCAL_EMOTE_SCHEME}.localhost/{id}")
+    } else {
+        format!("{LOCAL_EMOTE_SCHEME}://localhost/{id}")
+    }
+}
+
+/// Reads intrinsic dimensions from PNG and GIF headers; other formats are
+/// left for the webview to size.
+fn image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let mut header = Vec::with_capacity(24);
+    fs::File::open(path)
+        .ok()?
+        .take(24)
+        .read_to_end(&mut header)
+        .ok()?;
+    if header.len() >= 24 && header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let width = u32::from_be_bytes(header[16..20].try_into().ok()?);
+        let height = u32::from_be_bytes(header[20..24].try_into().ok()?);
+        return Some((width, height));
+    }
+    if header.len() >= 10 && header.starts_with(b"GIF8") {
+        let width = u16::from_le_bytes([header[6], header[7]]) as u32;
+        let height = u16::from_le_bytes([header[8], header[9]]) as u32;
+        return Some((width, height));
+    }
+    None
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("avif") => "image/avif",
+        _ => "application/octet-stream",
+    }
+}
+
+fn local_to_emote(entry: &LocalEmoteEntry, id: String) -> Emote {
+    let url = local_emote_url(&id);
+    let animated = entry
+        .path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    let (width, height) = image_dimensions(&entry.path).unzip();
+    let scope = if entry.scope == GLOBAL_SCOPE_KEY {
+        "Global"
+    } else {
+        "Channel"
+    };
+    Emote {
+        id,
+        name: entry.name.clone(),
+        format: vec![if animated { "animated" } else { "static" }.to_string()],
+        images: vec![EmoteImage {
+            scale: 1,
+            url: url.clone(),
+            light_url: None,
+        }],
+        url,
+        provider: "Local".to_string(),
+        scope: scope.to_string(),
+        animated,
+        width,
+        height,
+        ..Default::default()
+    }
+}
+
+/// Answers `pepo-emote://` requests from the webview. Only ids registered by
+/// the provider resolve, so arbitrary paths can't be read through the scheme.
+pub fn serve(
+    files: &LocalEmoteFiles,
+    request: &tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    let id = request.uri().path().trim_start_matches('/');
+    let response = files
+        .get(id)
+        .ok_or_else(|| "unknown local emote".to_owned())
+        .and_then(|path| {
+            fs::read(&path)
+                .map(|bytes| (path, bytes))
+                .map_err(|err| err.to_string())
+        });
+
+    match response {
+        Ok((path, bytes)) => tauri::http::Response::builder()
+            .status(200)
+            .header("Content-Type", content_type(&path))
+            .header("Cache-Control", "no-cache")
+            .body(bytes)
+            .unwrap(),
+        Err(err) => {
+            debug!(id, "local emote request failed: {}", err);
+            tauri::http::Response::builder()
+                .status(404)
+                .body(Vec::new())
+                .unwrap()
+        }
+    }
+}
+
+struct LocalState {
+    cache: Mutex<HashMap<String, EmoteCache>>,
+    files: LocalEmoteFiles,
+    source_path: Mutex<String>,
+    poll_interval: Mutex<Duration>,
+    loaded: Mutex<Option<(String, Vec<(PathBuf, Option<SystemTime>, u64)>)>>,
+    watching: AtomicBool,
+}
+
+impl LocalState {
+    /// Rescans the configured source when it (or its settings path) changed
+    /// since the last load. Returns whether caches were replaced.
+    fn refresh_if_changed(&self) -> bool {
+        let source_path = self.source_path.lock().unwrap().clone();
+        let Some(source) = LocalSource::from_setting(&source_path) else {
+            let had_emotes = self.loaded.lock().unwrap().take().is_some();
+            if had_emotes {
+                self.cache.lock().unwrap().clear();
+                self.files.replace(HashMap::new());
+            }
+            return had_emotes;
+        };
+
+        let entries = match scan_source(&source) {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("failed to load local emotes: err={}", err);
+                return false;
+            }
+        };
+        let fingerprint = fingerprint(&source, &entries);
+        {
+            let loaded = self.loaded.lock().unwrap();
+            if loaded.as_ref() == Some(&(source_path.clone(), fingerprint.clone())) {
+                return false;
+            }
+        }
+
+        let mut caches: HashMap<String, EmoteCache> = HashMap::new();
+        let mut files = HashMap::new();
+        for entry in &entries {
+            let id = local_emote_id(entry);
+            caches
+                .entry(entry.scope.clone())
+                .or_insert_with(|| {
+                    EmoteCache::new(
+                        entry.scope.clone(),
+                        EmoteProviderId::Local.provider_name().to_string(),
+                    )
+                })
+                .set_emote(entry.name.clone(), local_to_emote(entry, id.clone()));
+            files.insert(id, entry.path.clone());
+        }
+        info!(
+            source = %source_path,
+            count = entries.len(),
+            scopes = caches.len(),
+            "loaded local emotes"
+        );
+
+        *self.cache.lock().unwrap() = caches;
+        self.files.replace(files);
+        *self.loaded.lock().unwrap() = Some((source_path, fingerprint));
+        true
+    }
+}
+
+#[derive(Clone)]
+pub struct LocalProvider {
+    state: Arc<LocalState>,
+}
+
+impl LocalProvider {
+    pub(crate) fn new(files: LocalEmoteFiles) -> Self {
+        LocalProvider {
+            state: Arc::new(LocalState {
+                cache: Default::default(),
+                files,
+                source_path: Default::default(),
+                poll_interval: Mutex::new(Duration::from_millis(
+                    EmoteSettings::default().local_emotes_poll_ms,
+                )),
+                loaded: Default::default(),
+                watching: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Scans the source on a blocking thread, then polls it for changes until
+    /// the provider is dropped. Polling keeps this dependency-free and works
+    /// the same for folders, manifests and network drives.
+    fn ensure_watcher(&self) {
+        if self.state.watching.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let state: Weak<LocalState> = Arc::downgrade(&self.state);
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let tick_state = state.clone();
+                let refreshed = tokio::task::spawn_blocking(move || {
+                    tick_state.upgrade().map(|state| state.refresh_if_changed())
+                })
+                .await;
+                match refreshed {
+                    Ok(Some(true)) => debug!("local emote source changed; reloaded"),
+                    Ok(Some(false)) => {}
+                    Ok(None) => break,
+                    Err(err) => error!("local emote watcher failed: err={}", err),
+                }
+
+                let Some(interval) = state
+                    .upgrade()
+                    .map(|state| *state.poll_interval.lock().unwrap())
+                else {
+                    break;
+                };
+                tokio::time::sleep(interval).await;
+            }
+            debug!("local emote watcher stopped");
+        });
+    }
+}
+
+impl EmoteProvider<MultiCache> for LocalProvider {
+    fn get_id(&self) -> EmoteProviderId {
+        EmoteProviderId::Local
+    }
+
+    fn configure(&self, emote_settings: &EmoteSettings) {
+        *self.state.source_path.lock().unwrap() = emote_settings.local_emotes_path.clone();
+        *self.state.poll_interval.lock().unwrap() = emote_settings.local_emotes_poll_interval();
+    }
+
+    fn hydrate_cache(&self, _scope_key: &str, _provider_settings: &ProviderSettings) -> bool {
+        !self.state.cache.lock().unwrap().is_empty()
+    }
+
+    fn load_global_emotes(&self, _client: &reqwest::Client, _provider_settings: &ProviderSettings) {
+        self.ensure_watcher();
+    }
+
+    fn load_channel_emotes(
+        &self,
+        broadcaster_id: String,
+        _client: &reqwest::Client,
+        _provider_settings: &ProviderSettings,
+    ) {
+        self.ensure_watcher();
+        debug!(broadcaster_id, "local channel emotes requested");
+    }
+
+    fn get_emote_cache(&self, scope: String) -> MultiCache {
+        let store = self.state.cache.lock().unwrap();
+        let mut caches = Vec::new();
+        if scope != GLOBAL_SCOPE_KEY {
+            if let Some(channel_cache) = store.get(&scope) {
+                caches.push(channel_cache.clone());
+            }
+        }
+        if let Some(global_cache) = store.get(GLOBAL_SCOPE_KEY) {
+            caches.push(global_cache.clone());
+        }
+        MultiCache::new(caches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_1X1: &[u8] = &[
+        0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0, 0, 13, b'I', b'H', b'D', b'R', 0,
+        0, 0, 56, 0, 0, 0, 28,
+    ];
+
+    fn provider_for(path: &Path) -> (LocalProvider, LocalEmoteFiles) {
+        let files = LocalEmoteFiles::default();
+        let provider = LocalProvider::new(files.clone());
+        provider.configure(&EmoteSettings {
+            local_emotes_path: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        });
+        (provider, files)
+    }
+
+    #[test]
+    fn directory_source_scopes_subfolders_to_channels() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("teamLOL.png"), PNG_1X1).unwrap();
+        fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+        fs::create_dir(dir.path().join("1234")).unwrap();
+        fs::write(
+            dir.path().join("1234").join("teamHi.gif"),
+            b"GIF89a\x10\x00\x08\x00",
+        )
+        .unwrap();
+        let (provider, files) = provider_for(dir.path());
+
+        provider.state.refresh_if_changed();
+
+        let channel = provider.get_emote_cache("1234".to_string());
+        let other = provider.get_emote_cache("5678".to_string());
+        let lol = channel.get_emote("teamLOL".to_string()).unwrap();
+        let hi = channel.get_emote("teamHi".to_string()).unwrap();
+        assert_eq!((lol.width, lol.height), (Some(56), Some(28)));
+        assert!(hi.animated);
+        assert_eq!((hi.width, hi.height), (Some(16), Some(8)));
+        assert!(!other.has_emote("teamHi".to_string()));
+        assert!(!other.has_emote("notes".to_string()));
+        assert_eq!(files.get(&lol.id), Some(dir.path().join("teamLOL.png")));
+    }
+
+    #[test]
+    fn manifest_source_resolves_relative_files_and_channels() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("lol.png"), PNG_1X1).unwrap();
+        let manifest = dir.path().join("emotes.json");
+        fs::write(
+            &manifest,
+            r#"{ "emotes": {
+                "teamLOL": "lol.png",
+                "teamOnly": { "file": "lol.png", "channels": ["1234"] },
+                "teamMissing": "missing.png"
+            } }"#,
+        )
+        .unwrap();
+        let (provider, _) = provider_for(&manifest);
+
+        provider.state.refresh_if_changed();
+
+        let global = provider.get_emote_cache(GLOBAL_SCOPE_KEY.to_string());
+        assert!(global.has_emote("teamLOL".to_string()));
+        assert!(!global.has_emote("teamOnly".to_string()));
+        assert!(!global.has_emote("teamMissing".to_string()));
+        assert!(provider
+            .get_emote_cache("1234".to_string())
+            .has_emote("teamOnly".to_string()));
+    }
+
+    #[test]
+    fn refresh_picks_up_added_files_and_skips_unchanged_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("first.png"), PNG_1X1).unwrap();
+        let (provider, _) = provider_for(dir.path());
+
+        assert!(provider.state.refresh_if_changed());
+        assert!(!provider.state.refresh_if_changed());
+
+        fs::write(dir.path().join("second.png"), PNG_1X1).unwrap();
+        // Directory mtime granularity can be coarse; the new file is still
+        // listed in the fingerprint even when the mtime doesn't move.
+        assert!(provider.state.refresh_if_changed());
+        assert!(provider
+            .get_emote_cache(GLOBAL_SCOPE_KEY.to_string())
+            .has_emote("second".to_string()));
+    }
+
+    #[test]
+    fn serve_only_returns_registered_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("team LOL.png"), PNG_1X1).unwrap();
+        let (provider, files) = provider_for(dir.path());
+        provider.state.refresh_if_changed();
+
+        let request = |path: &str| {
+            tauri::http::Request::builder()
+                .uri(format!("pepo-emote://localhost/{path}"))
+                .body(Vec::new())
+                .unwrap()
+        };
+
+        let ok = serve(&files, &request("_global/team%20LOL"));
+        assert_eq!(ok.status(), 200);
+        assert_eq!(ok.headers()["Content-Type"], "image/png");
+        assert_eq!(ok.body().as_slice(), PNG_1X1);
+
+        let missing = serve(&files, &request("..%2F..%2Fetc%2Fpasswd"));
+        assert_eq!(missing.status(), 404);
+    }
+}
@@ -1,9 +1,11 @@
 use crate::emote::{cache::EmoteCacheTrait, Emote};
-use crate::types::{EmoteProviderId, ProviderSettings};
+use crate::types::{EmoteProviderId, EmoteSettings, ProviderSettings};
 
 pub mod bttv;
 pub mod ffz;
 pub mod http;
+pub mod local;
+pub mod policy;
 pub mod seventv;
 pub mod twitch;
 
@@ -14,6 +16,8 @@ pub trait EmoteProvider<T: EmoteCacheTrait>: Send + Sync {
     fn get_name(&self) -> String {
         self.get_id().provider_name().to_string()
     }
+    /// Applies user settings that affect where the provider reads from.
+    fn configure(&self, _emote_settings: &EmoteSettings) {}
     fn hydrate_cache(&self, _scope_key: &str, _provider_settings: &ProviderSettings) -> bool {
         false
     }
@@ -25,6 +29,10 @@ pub trait EmoteProvider<T: EmoteCacheTrait>: Send + Sync {
         provider_settings: &ProviderSettings,
     );
     fn load_user_emotes(&self) {}
+    fn refresh_user_emotes(&self, _broadcaster_id: &str) {}
     fn insert_emote(&self, _scope: String, _name: String, _emote: Emote) {}
     fn get_emote_cache(&self, scope: String) -> T;
+    fn get_usable_emote_cache(&self, scope: String) -> T {
+        self.get_emote_cache(scope)
+    }
 }
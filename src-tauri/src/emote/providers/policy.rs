@@ -0,0 +1,572 @@
+use std::{
+    collections::{hash_map::RandomState, HashSet},
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::StatusCode;
+use tracing::{debug, info, warn};
+use twitch_api::helix::{ClientRequestError, HelixRequestGetError};
+
+use crate::emote::providers::http::ProviderFetchError;
+use crate::types::ProviderSettings;
+
+/// Stop retrying a scope in the background after this many cooldowns; a
+/// later join or refresh will schedule it again.
+const MAX_BACKGROUND_RETRIES: u32 = 30;
+
+/// Errors worth retrying: the provider may answer differently a moment later.
+pub(crate) trait TransientError {
+    fn is_transient(&self) -> bool;
+}
+
+pub(crate) fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+impl TransientError for ProviderFetchError {
+    fn is_transient(&self) -> bool {
+        match self {
+            ProviderFetchError::Request { .. } => true,
+            ProviderFetchError::Status { status, .. } => is_transient_status(*status),
+            ProviderFetchError::Decode { .. } => false,
+        }
+    }
+}
+
+impl TransientError for ClientRequestError<reqwest::Error> {
+    fn is_transient(&self) -> bool {
+        match self {
+            ClientRequestError::RequestError(_) | ClientRequestError::HyperError(_) => true,
+            ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                status,
+                ..
+            }) => is_transient_status(*status),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum PolicyError<E> {
+    /// The provider's breaker is open, so no request was made.
+    CircuitOpen {
+        provider: String,
+        retry_in: Duration,
+    },
+    Failed(E),
+}
+
+impl<E: TransientError> PolicyError<E> {
+    pub fn is_transient(&self) -> bool {
+        match self {
+            PolicyError::CircuitOpen { .. } => true,
+            PolicyError::Failed(err) => err.is_transient(),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PolicyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::CircuitOpen { provider, retry_in } => write!(
+                f,
+                "provider={provider} circuit open; skipped request, retry in {:.1}s",
+                retry_in.as_secs_f64()
+            ),
+            PolicyError::Failed(err) => err.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    attempts: u32,
+    base: Duration,
+    max: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_settings(settings: &ProviderSettings) -> Self {
+        Self {
+            attempts: settings.fetch_retry_attempts.max(1),
+            base: settings.fetch_retry_base(),
+            max: settings.fetch_retry_max(),
+        }
+    }
+
+    /// Delay before retry `attempt` (1-based): `base * 2^(attempt - 1)` capped
+    /// at `max`, of which the upper half is jittered so concurrent channel
+    /// joins don't retry in lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        let ceiling = self.base.saturating_mul(factor).min(self.max);
+        let half = ceiling / 2;
+        half + jitter(ceiling - half)
+    }
+}
+
+/// Uniform-ish delay in `[0, max)`. `RandomState` is reseeded per instance,
+/// which is plenty for spreading retries without pulling in an RNG.
+fn jitter(max: Duration) -> Duration {
+    let max_nanos = max.as_nanos().min(u64::MAX as u128) as u64;
+    if max_nanos == 0 {
+        return Duration::ZERO;
+    }
+    let hasher = RandomState::new().build_hasher();
+    Duration::from_nanos(hasher.finish() % max_nanos)
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+/// Opens after `circuit_breaker_threshold` consecutive transient failures so
+/// an outage costs one timeout instead of one per channel join. After the
+/// cooldown it half-opens and lets a single probe through; the probe's result
+/// closes or re-opens it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CircuitBreaker {
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl CircuitBreaker {
+    /// `Ok` when a request may go out, otherwise how long until it might.
+    pub fn allow(&self, settings: &ProviderSettings, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let Some(opened_at) = state.opened_at else {
+            return Ok(());
+        };
+        let cooldown = settings.circuit_breaker_cooldown();
+        let elapsed = now.saturating_duration_since(opened_at);
+        if elapsed < cooldown {
+            return Err(cooldown - elapsed);
+        }
+        if state.probing {
+            return Err(settings.fetch_retry_max());
+        }
+        state.probing = true;
+        Ok(())
+    }
+
+    /// How long until [`allow`](Self::allow) would let a request through.
+    pub fn retry_in(&self, settings: &ProviderSettings, now: Instant) -> Duration {
+        let state = self.state.lock().unwrap();
+        state
+            .opened_at
+            .map(|opened_at| {
+                settings
+                    .circuit_breaker_cooldown()
+                    .saturating_sub(now.saturating_duration_since(opened_at))
+            })
+            .unwrap_or_default()
+    }
+
+    #[cfg(test)]
+    fn is_open(&self) -> bool {
+        self.state.lock().unwrap().opened_at.is_some()
+    }
+
+    pub fn record_success(&self, provider: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.opened_at.is_some() {
+            info!(provider, "provider recovered; circuit closed");
+        }
+        *state = BreakerState::default();
+    }
+
+    pub fn record_failure(&self, provider: &str, settings: &ProviderSettings, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.probing {
+            state.probing = false;
+            state.opened_at = Some(now);
+            debug!(provider, "provider probe failed; circuit re-opened");
+        } else if state.opened_at.is_none()
+            && state.consecutive_failures >= settings.circuit_breaker_threshold
+        {
+            state.opened_at = Some(now);
+            warn!(
+                provider,
+                failures = state.consecutive_failures,
+                cooldown_secs = settings.circuit_breaker_cooldown_secs,
+                "provider failing; circuit opened"
+            );
+        }
+    }
+}
+
+/// Per-provider fetch state: the circuit breaker plus the scopes that already
+/// have a background retry waiting on it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProviderHealth {
+    breaker: CircuitBreaker,
+    pending_retries: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ProviderHealth {
+    /// Runs `op` under the provider's breaker, retrying transient failures
+    /// with jittered exponential backoff. Non-transient failures (404s, bad
+    /// payloads) mean the provider is reachable and don't count against it.
+    pub async fn fetch<T, E, F, Fut>(
+        &self,
+        provider: &str,
+        settings: &ProviderSettings,
+        mut op: F,
+    ) -> Result<T, PolicyError<E>>
+    where
+        E: TransientError + fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let policy = RetryPolicy::from_settings(settings);
+        let mut last_err = None;
+        for attempt in 1..=policy.attempts {
+            if let Err(retry_in) = self.breaker.allow(settings, Instant::now()) {
+                return Err(match last_err {
+                    Some(err) => PolicyError::Failed(err),
+                    None => PolicyError::CircuitOpen {
+                        provider: provider.to_owned(),
+                        retry_in,
+                    },
+                });
+            }
+            match op().await {
+                Ok(value) => {
+                    self.breaker.record_success(provider);
+                    return Ok(value);
+                }
+                Err(err) if !err.is_transient() => {
+                    self.breaker.record_success(provider);
+                    return Err(PolicyError::Failed(err));
+                }
+                Err(err) => {
+                    self.breaker
+                        .record_failure(provider, settings, Instant::now());
+                    if attempt < policy.attempts {
+                        let delay = policy.backoff(attempt);
+                        debug!(
+                            provider,
+                            attempt,
+                            delay_ms = delay.as_millis() as u64,
+                            "retrying transient provider fetch: err={}",
+                            err
+                        );
+                        tokio::time::sleep(delay).await;
+                    }
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(PolicyError::Failed(
+            last_err.expect("retry loop runs at least once"),
+        ))
+    }
+
+    /// Runs a provider load in the background and, if it failed
+    /// transiently, keeps re-running it via [`schedule_retry`].
+    ///
+    /// [`schedule_retry`]: Self::schedule_retry
+    pub fn load_with_retry<E, F, Fut>(
+        &self,
+        provider: &str,
+        scope: &str,
+        settings: &ProviderSettings,
+        load: F,
+    ) where
+        E: TransientError,
+        F: Fn(ProviderSettings) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), PolicyError<E>>> + Send + 'static,
+    {
+        let health = self.clone();
+        let provider = provider.to_owned();
+        let scope = scope.to_owned();
+        let settings = *settings;
+        tauri::async_runtime::spawn(async move {
+            if settled(&load(settings).await) {
+                return;
+            }
+            let load = Arc::new(load);
+            health.schedule_retry(&provider, &scope, &settings, move || {
+                let load = load.clone();
+                async move { settled(&load(settings).await) }
+            });
+        });
+    }
+
+    /// Keeps retrying `scope` in the background until `load` reports it
+    /// settled, so the cache fills once the provider recovers. At most one
+    /// retry runs per scope, paced by the breaker's cooldown.
+    pub fn schedule_retry<F, Fut>(
+        &self,
+        provider: &str,
+        scope: &str,
+        settings: &ProviderSettings,
+        load: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send,
+    {
+        if !settings.background_retry_enabled
+            || !self
+                .pending_retries
+                .lock()
+                .unwrap()
+                .insert(scope.to_owned())
+        {
+            return;
+        }
+        debug!(provider, scope, "scheduled background provider retry");
+
+        let health = self.clone();
+        let provider = provider.to_owned();
+        let scope = scope.to_owned();
+        let settings = *settings;
+        tauri::async_runtime::spawn(async move {
+            let policy = RetryPolicy::from_settings(&settings);
+            let mut recovered = false;
+            for attempt in 1..=MAX_BACKGROUND_RETRIES {
+                let delay = health
+                    .breaker
+                    .retry_in(&settings, Instant::now())
+                    .max(policy.backoff(attempt));
+                tokio::time::sleep(delay).await;
+                if load().await {
+                    recovered = true;
+                    break;
+                }
+            }
+            if recovered {
+                debug!(provider, scope, "background provider retry settled");
+            } else {
+                warn!(provider, scope, "gave up background provider retry");
+            }
+            health.pending_retries.lock().unwrap().remove(&scope);
+        });
+    }
+
+    #[cfg(test)]
+    fn has_pending_retry(&self, scope: &str) -> bool {
+        self.pending_retries.lock().unwrap().contains(scope)
+    }
+}
+
+/// Whether a load needs no further retries: it succeeded, or failed in a way
+/// retrying won't fix.
+pub(crate) fn settled<T, E: TransientError>(result: &Result<T, PolicyError<E>>) -> bool {
+    match result {
+        Ok(_) => true,
+        Err(err) => !err.is_transient(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestError {
+        transient: bool,
+    }
+
+    impl TransientError for TestError {
+        fn is_transient(&self) -> bool {
+            self.transient
+        }
+    }
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "transient={}", self.transient)
+        }
+    }
+
+    fn settings() -> ProviderSettings {
+        ProviderSettings {
+            fetch_retry_attempts: 3,
+            fetch_retry_base_ms: 1,
+            fetch_retry_max_ms: 2,
+            circuit_breaker_threshold: 2,
+            circuit_breaker_cooldown_secs: 60,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn helix_errors_are_transient_only_for_server_side_statuses() {
+        let helix_error = |status: StatusCode| {
+            ClientRequestError::<reqwest::Error>::HelixRequestGetError(
+                HelixRequestGetError::Error {
+                    error: "error".to_owned(),
+                    status,
+                    message: String::new(),
+                    uri: "https://api.twitch.tv/helix/chat/badges".parse().unwrap(),
+                },
+            )
+        };
+
+        assert!(helix_error(StatusCode::SERVICE_UNAVAILABLE).is_transient());
+        assert!(helix_error(StatusCode::TOO_MANY_REQUESTS).is_transient());
+        assert!(!helix_error(StatusCode::NOT_FOUND).is_transient());
+        assert!(
+            !ClientRequestError::<reqwest::Error>::Custom("no active token".into()).is_transient()
+        );
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_within_cap() {
+        let policy = RetryPolicy::from_settings(&ProviderSettings {
+            fetch_retry_base_ms: 100,
+            fetch_retry_max_ms: 1_000,
+            ..Default::default()
+        });
+
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first < Duration::from_millis(100));
+            let third = policy.backoff(3);
+            assert!(third >= Duration::from_millis(200) && third < Duration::from_millis(400));
+            let capped = policy.backoff(40);
+            assert!(capped >= Duration::from_millis(500) && capped < Duration::from_millis(1_000));
+        }
+    }
+
+    #[test]
+    fn breaker_opens_after_threshold_and_half_opens_for_one_probe() {
+        let settings = settings();
+        let breaker = CircuitBreaker::default();
+        let start = Instant::now();
+
+        breaker.record_failure("Test", &settings, start);
+        assert!(breaker.allow(&settings, start).is_ok());
+        breaker.record_failure("Test", &settings, start);
+        assert!(breaker.is_open());
+        assert_eq!(
+            breaker.allow(&settings, start + Duration::from_secs(15)),
+            Err(Duration::from_secs(45))
+        );
+
+        let after_cooldown = start + Duration::from_secs(60);
+        assert!(breaker.allow(&settings, after_cooldown).is_ok());
+        assert!(breaker.allow(&settings, after_cooldown).is_err());
+
+        breaker.record_failure("Test", &settings, after_cooldown);
+        assert_eq!(
+            breaker.retry_in(&settings, after_cooldown),
+            Duration::from_secs(60)
+        );
+
+        let second_probe = after_cooldown + Duration::from_secs(60);
+        assert!(breaker.allow(&settings, second_probe).is_ok());
+        breaker.record_success("Test");
+        assert!(!breaker.is_open());
+        assert!(breaker.allow(&settings, second_probe).is_ok());
+    }
+
+    #[tokio::test]
+    async fn fetch_retries_transient_errors_until_success() {
+        let health = ProviderHealth::default();
+        let mut calls = 0;
+
+        let result = health
+            .fetch("Test", &settings(), || {
+                calls += 1;
+                let outcome = if calls < 3 {
+                    Err(TestError { transient: true })
+                } else {
+                    Ok(calls)
+                };
+                async move { outcome }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 3);
+        assert!(!health.breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn fetch_does_not_retry_permanent_errors() {
+        let health = ProviderHealth::default();
+        let mut calls = 0;
+
+        let result: Result<(), _> = health
+            .fetch("Test", &settings(), || {
+                calls += 1;
+                async { Err(TestError { transient: false }) }
+            })
+            .await;
+
+        assert!(matches!(result, Err(PolicyError::Failed(_))));
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn open_breaker_fails_fast_without_calling_provider() {
+        let health = ProviderHealth::default();
+        let settings = settings();
+        let mut calls = 0;
+
+        let first: Result<(), _> = health
+            .fetch("Test", &settings, || {
+                calls += 1;
+                async { Err(TestError { transient: true }) }
+            })
+            .await;
+        assert!(matches!(first, Err(PolicyError::Failed(_))));
+        assert_eq!(calls, 2);
+
+        let second: Result<(), _> = health
+            .fetch("Test", &settings, || {
+                calls += 1;
+                async { Err(TestError { transient: true }) }
+            })
+            .await;
+        assert!(matches!(second, Err(PolicyError::CircuitOpen { .. })));
+        assert_eq!(calls, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn background_retry_runs_until_load_settles() {
+        let health = ProviderHealth::default();
+        let calls = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let load_calls = calls.clone();
+
+        health.schedule_retry("Test", "_global", &settings(), move || {
+            let calls = load_calls.clone();
+            async move { calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) >= 2 }
+        });
+        assert!(health.has_pending_retry("_global"));
+
+        for _ in 0..200 {
+            if !health.has_pending_retry("_global") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!health.has_pending_retry("_global"));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn background_retry_respects_setting() {
+        let health = ProviderHealth::default();
+        let settings = ProviderSettings {
+            background_retry_enabled: false,
+            ..settings()
+        };
+
+        health.schedule_retry("Test", "_global", &settings, || async { true });
+
+        assert!(!health.has_pending_retry("_global"));
+    }
+}
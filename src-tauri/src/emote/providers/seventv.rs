@@ -9,8 +9,14 @@ use tracing::{debug, error};
 use crate::emote::{
     cache::{EmoteCache, EmoteCacheTrait, MultiCache},
     persist::SharedEmoteMetadataStore,
-    providers::{http::fetch_json, EmoteProvider, GLOBAL_SCOPE_KEY},
-    Emote,
+    providers::{
+        http::{
+            self, fetch_json, CacheValidators, FetchOutcome, ProviderFetchError, ScopeValidators,
+        },
+        policy::{PolicyError, ProviderHealth},
+        EmoteProvider, GLOBAL_SCOPE_KEY,
+    },
+    Emote, EmoteImage,
 };
 use crate::types::{EmoteProviderId, ProviderSettings};
 
@@ -18,9 +24,19 @@ const SEVENTV_API_BASE: &str = "https://7tv.io/v3";
 
 type SharedMap<V> = Arc<Mutex<HashMap<String, V>>>;
 
+#[derive(Deserialize)]
+struct SeventvFile {
+    name: String,
+    width: u32,
+    height: u32,
+    format: String,
+}
+
 #[derive(Deserialize)]
 struct SeventvHost {
     url: String,
+    #[serde(default)]
+    files: Vec<SeventvFile>,
 }
 
 #[derive(Deserialize)]
@@ -51,16 +67,36 @@ struct SeventvChannelResponse {
     emote_set: SeventvChannelEmoteSet,
 }
 
+/// 7TV serves 1x-4x; 3x is skipped since we only expose 1x/2x/4x densities.
+const SEVENTV_SCALES: [u8; 3] = [1, 2, 4];
+
 fn seventv_to_emote(emote: &SeventvEmote, scope: &str) -> Emote {
     let format = if emote.data.animated {
         "animated".to_string()
     } else {
         "static".to_string()
     };
+    let host = &emote.data.host;
+    let webp_file = |scale: u8| {
+        let name = format!("{scale}x.webp");
+        host.files
+            .iter()
+            .find(|file| file.format.eq_ignore_ascii_case("webp") && file.name == name)
+    };
+    let images: Vec<EmoteImage> = SEVENTV_SCALES
+        .iter()
+        .filter(|scale| host.files.is_empty() || webp_file(**scale).is_some())
+        .map(|scale| EmoteImage {
+            scale: *scale,
+            url: format!("https:{}/{}x.webp", host.url, scale),
+            light_url: None,
+        })
+        .collect();
+    let base_file = webp_file(1);
     Emote {
         id: emote.id.clone(),
         name: emote.name.clone(),
-        url: format!("https:{}/4x.webp", emote.data.host.url),
+        url: format!("https:{}/4x.webp", host.url),
         format: vec![format],
         scale: vec![
             "1x".to_string(),
@@ -70,6 +106,10 @@ fn seventv_to_emote(emote: &SeventvEmote, scope: &str) -> Emote {
         ],
         provider: "7TV".to_string(),
         scope: scope.to_string(),
+        animated: emote.data.animated,
+        width: base_file.map(|file| file.width),
+        height: base_file.map(|file| file.height),
+        images,
         ..Default::default()
     }
 }
@@ -77,7 +117,9 @@ fn seventv_to_emote(emote: &SeventvEmote, scope: &str) -> Emote {
 #[derive(Clone)]
 pub struct SeventvProvider {
     cache: SharedMap<EmoteCache>,
+    validators: ScopeValidators,
     persistence: SharedEmoteMetadataStore,
+    health: ProviderHealth,
     api_base: String,
 }
 
@@ -89,7 +131,9 @@ impl SeventvProvider {
     fn with_api_base(persistence: SharedEmoteMetadataStore, api_base: String) -> Self {
         SeventvProvider {
             cache: Default::default(),
+            validators: Default::default(),
             persistence,
+            health: Default::default(),
             api_base,
         }
     }
@@ -118,6 +162,8 @@ impl SeventvProvider {
         );
 
         let cache = hydrated.cache;
+        self.validators
+            .insert(scope_key.to_string(), hydrated.validators);
         self.cache
             .lock()
             .unwrap()
@@ -129,10 +175,17 @@ impl SeventvProvider {
         &self,
         scope_key: String,
         cache: EmoteCache,
+        validators: CacheValidators,
         provider_settings: &ProviderSettings,
     ) {
-        self.persistence
-            .save_cache(self.get_id(), &scope_key, &cache, provider_settings);
+        self.persistence.save_cache(
+            self.get_id(),
+            &scope_key,
+            &cache,
+            &validators,
+            provider_settings,
+        );
+        self.validators.insert(scope_key.clone(), validators);
         self.cache.lock().unwrap().insert(scope_key, cache);
     }
 
@@ -146,63 +199,107 @@ impl SeventvProvider {
             );
         }
     }
-}
-
-impl EmoteProvider<MultiCache> for SeventvProvider {
-    fn get_id(&self) -> EmoteProviderId {
-        EmoteProviderId::Seventv
-    }
 
-    fn hydrate_cache(&self, scope_key: &str, provider_settings: &ProviderSettings) -> bool {
-        self.hydrate_persisted_cache(scope_key, provider_settings)
-            .is_some()
-    }
-
-    fn load_global_emotes(&self, client: &reqwest::Client, provider_settings: &ProviderSettings) {
+    async fn refresh_global(
+        &self,
+        client: &reqwest::Client,
+        provider_settings: &ProviderSettings,
+    ) -> Result<(), PolicyError<ProviderFetchError>> {
         let fallback = self.hydrate_persisted_cache(GLOBAL_SCOPE_KEY, provider_settings);
+        let validators = self.validators.for_request(GLOBAL_SCOPE_KEY, &fallback);
         let url = format!("{}/emote-sets/global", self.api_base);
 
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                fetch_json::<SeventvGlobalResponse>(client, "7TV", "global", &url).await
+        let outcome = self
+            .health
+            .fetch(&self.get_name(), provider_settings, || {
+                fetch_json::<SeventvGlobalResponse>(
+                    client,
+                    "7TV",
+                    "global",
+                    &url,
+                    validators.as_ref(),
+                )
             })
-        }) {
-            Ok(resp) => {
+            .await;
+        match outcome {
+            Ok(FetchOutcome::Fresh {
+                value: resp,
+                validators: fresh_validators,
+            }) => {
                 let cache = EmoteCache::new(GLOBAL_SCOPE_KEY.to_owned(), self.get_name());
                 debug!(count = resp.emotes.len(), "loaded seventv global emotes");
                 for emote in &resp.emotes {
                     cache.set_emote(emote.name.clone(), seventv_to_emote(emote, "Global"));
                 }
-                self.store_fresh_cache(GLOBAL_SCOPE_KEY.to_owned(), cache, provider_settings);
+                self.store_fresh_cache(
+                    GLOBAL_SCOPE_KEY.to_owned(),
+                    cache,
+                    fresh_validators,
+                    provider_settings,
+                );
+                Ok(())
+            }
+            Ok(FetchOutcome::NotModified {
+                validators: refreshed,
+            }) => {
+                if let Some((cache, validators)) = http::not_modified(
+                    &self.get_name(),
+                    GLOBAL_SCOPE_KEY,
+                    fallback,
+                    validators,
+                    refreshed,
+                ) {
+                    self.store_fresh_cache(
+                        GLOBAL_SCOPE_KEY.to_owned(),
+                        cache,
+                        validators,
+                        provider_settings,
+                    );
+                }
+                Ok(())
             }
             Err(err) => {
-                error!("failed to load seventv global emotes: err={}", err);
+                match &err {
+                    PolicyError::CircuitOpen { .. } => {
+                        debug!("skipped seventv global emotes: {}", err)
+                    }
+                    PolicyError::Failed(_) => {
+                        error!("failed to load seventv global emotes: err={}", err);
+                    }
+                }
                 self.log_fallback(GLOBAL_SCOPE_KEY, fallback);
+                Err(err)
             }
         }
     }
 
-    fn load_channel_emotes(
+    async fn refresh_channel(
         &self,
         broadcaster_id: String,
         client: &reqwest::Client,
         provider_settings: &ProviderSettings,
-    ) {
+    ) -> Result<(), PolicyError<ProviderFetchError>> {
         let fallback = self.hydrate_persisted_cache(&broadcaster_id, provider_settings);
+        let validators = self.validators.for_request(&broadcaster_id, &fallback);
         let url = format!("{}/users/twitch/{}", self.api_base, broadcaster_id);
 
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
+        let outcome = self
+            .health
+            .fetch(&self.get_name(), provider_settings, || {
                 fetch_json::<SeventvChannelResponse>(
                     client,
                     "7TV",
                     format!("channel:{broadcaster_id}"),
                     &url,
+                    validators.as_ref(),
                 )
-                .await
             })
-        }) {
-            Ok(resp) => {
+            .await;
+        match outcome {
+            Ok(FetchOutcome::Fresh {
+                value: resp,
+                validators: fresh_validators,
+            }) => {
                 let cache = EmoteCache::new(broadcaster_id.clone(), self.get_name());
                 let emotes = resp.emote_set.emotes.unwrap_or_default();
                 debug!(
@@ -213,17 +310,92 @@ impl EmoteProvider<MultiCache> for SeventvProvider {
                 for emote in &emotes {
                     cache.set_emote(emote.name.clone(), seventv_to_emote(emote, "Channel"));
                 }
-                self.store_fresh_cache(broadcaster_id, cache, provider_settings);
+                self.store_fresh_cache(broadcaster_id, cache, fresh_validators, provider_settings);
+                Ok(())
+            }
+            Ok(FetchOutcome::NotModified {
+                validators: refreshed,
+            }) => {
+                if let Some((cache, validators)) = http::not_modified(
+                    &self.get_name(),
+                    &broadcaster_id,
+                    fallback,
+                    validators,
+                    refreshed,
+                ) {
+                    self.store_fresh_cache(broadcaster_id, cache, validators, provider_settings);
+                }
+                Ok(())
             }
             Err(err) => {
-                error!(
-                    broadcaster_id,
-                    "failed to load seventv channel emotes: err={}", err
-                );
+                match &err {
+                    PolicyError::CircuitOpen { .. } => {
+                        debug!(broadcaster_id, "skipped seventv channel emotes: {}", err)
+                    }
+                    PolicyError::Failed(_) => {
+                        error!(
+                            broadcaster_id,
+                            "failed to load seventv channel emotes: err={}", err
+                        );
+                    }
+                }
                 self.log_fallback(&broadcaster_id, fallback);
+                Err(err)
             }
         }
     }
+}
+
+impl EmoteProvider<MultiCache> for SeventvProvider {
+    fn get_id(&self) -> EmoteProviderId {
+        EmoteProviderId::Seventv
+    }
+
+    fn hydrate_cache(&self, scope_key: &str, provider_settings: &ProviderSettings) -> bool {
+        self.hydrate_persisted_cache(scope_key, provider_settings)
+            .is_some()
+    }
+
+    fn load_global_emotes(&self, client: &reqwest::Client, provider_settings: &ProviderSettings) {
+        let provider = self.clone();
+        let client = client.clone();
+        self.health.load_with_retry(
+            &self.get_name(),
+            GLOBAL_SCOPE_KEY,
+            provider_settings,
+            move |settings| {
+                let provider = provider.clone();
+                let client = client.clone();
+                async move { provider.refresh_global(&client, &settings).await }
+            },
+        );
+    }
+
+    fn load_channel_emotes(
+        &self,
+        broadcaster_id: String,
+        client: &reqwest::Client,
+        provider_settings: &ProviderSettings,
+    ) {
+        let provider = self.clone();
+        let client = client.clone();
+        let scope_key = broadcaster_id.clone();
+        self.health.load_with_retry(
+            &self.get_name(),
+            &scope_key,
+            provider_settings,
+            move |settings| {
+                let provider = provider.clone();
+                let client = client.clone();
+                let broadcaster_id = broadcaster_id.clone();
+                async move {
+                    provider
+                        .refresh_channel(broadcaster_id, &client, &settings)
+                        .await
+                }
+            },
+        );
+    }
 
     fn get_emote_cache(&self, scope: String) -> MultiCache {
         let store = self.cache.lock().unwrap();
@@ -252,6 +424,15 @@ mod tests {
 
     const NOW: u64 = 1_800_000_000;
 
+    fn outage_settings() -> ProviderSettings {
+        ProviderSettings {
+            fetch_retry_base_ms: 1,
+            fetch_retry_max_ms: 1,
+            background_retry_enabled: false,
+            ..Default::default()
+        }
+    }
+
     fn emote(name: &str, scope: &str) -> Emote {
         Emote {
             id: format!("id-{name}"),
@@ -278,11 +459,15 @@ mod tests {
         );
         let provider = SeventvProvider::with_api_base(persistence, server.base_url());
 
-        provider.load_global_emotes(&reqwest::Client::new(), &ProviderSettings::default());
+        let result = provider
+            .refresh_global(&reqwest::Client::new(), &outage_settings())
+            .await;
+
+        assert!(result.is_err());
 
         let cache = provider.get_emote_cache(GLOBAL_SCOPE_KEY.to_string());
         assert!(cache.has_emote("Cached7TV".to_string()));
-        mock.assert();
+        mock.assert_hits(3);
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -301,15 +486,19 @@ mod tests {
         );
         let provider = SeventvProvider::with_api_base(persistence, server.base_url());
 
-        provider.load_channel_emotes(
-            "1234".to_string(),
-            &reqwest::Client::new(),
-            &ProviderSettings::default(),
-        );
+        let result = provider
+            .refresh_channel(
+                "1234".to_string(),
+                &reqwest::Client::new(),
+                &outage_settings(),
+            )
+            .await;
+
+        assert!(result.is_err());
 
         let cache = provider.get_emote_cache("1234".to_string());
         assert!(cache.has_emote("CachedChannel7TV".to_string()));
-        mock.assert();
+        mock.assert_hits(3);
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -341,7 +530,11 @@ mod tests {
         );
         let provider = SeventvProvider::with_api_base(persistence.clone(), server.base_url());
 
-        provider.load_global_emotes(&reqwest::Client::new(), &ProviderSettings::default());
+        let result = provider
+            .refresh_global(&reqwest::Client::new(), &ProviderSettings::default())
+            .await;
+
+        assert!(result.is_ok());
 
         let cache = provider.get_emote_cache(GLOBAL_SCOPE_KEY.to_string());
         assert!(cache.has_emote("Fresh7TV".to_string()));
@@ -359,4 +552,80 @@ mod tests {
         assert!(!persisted.cache.has_emote("Stale7TV".to_string()));
         mock.assert();
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn open_circuit_skips_requests_and_uses_persisted_cache() {
+        let server = MockServer::start();
+        let global_mock = server.mock(|when, then| {
+            when.method(GET).path("/emote-sets/global");
+            then.status(503).body("down");
+        });
+        let channel_mock = server.mock(|when, then| {
+            when.method(GET).path("/users/twitch/1234");
+            then.status(503).body("down");
+        });
+        let persistence = MemoryEmoteMetadataStore::new(NOW);
+        persistence.insert(
+            EmoteProviderId::Seventv,
+            "1234",
+            vec![emote("CachedChannel7TV", "Channel")],
+            NOW,
+        );
+        let provider = SeventvProvider::with_api_base(persistence, server.base_url());
+        let settings = ProviderSettings {
+            circuit_breaker_threshold: 3,
+            ..outage_settings()
+        };
+
+        let client = reqwest::Client::new();
+        assert!(provider.refresh_global(&client, &settings).await.is_err());
+        let result = provider
+            .refresh_channel("1234".to_string(), &client, &settings)
+            .await;
+
+        assert!(matches!(result, Err(PolicyError::CircuitOpen { .. })));
+
+        let cache = provider.get_emote_cache("1234".to_string());
+        assert!(cache.has_emote("CachedChannel7TV".to_string()));
+        global_mock.assert_hits(3);
+        channel_mock.assert_hits(0);
+    }
+
+    #[test]
+    fn seventv_emote_reads_size_and_webp_scales_from_host_files() {
+        let emote: SeventvEmote = serde_json::from_value(serde_json::json!({
+            "id": "wide-id",
+            "name": "Wide7TV",
+            "data": {
+                "animated": true,
+                "host": {
+                    "url": "//cdn.7tv.app/emote/wide-id",
+                    "files": [
+                        { "name": "1x.avif", "width": 96, "height": 32, "format": "AVIF" },
+                        { "name": "1x.webp", "width": 96, "height": 32, "format": "WEBP" },
+                        { "name": "2x.webp", "width": 192, "height": 64, "format": "WEBP" },
+                        { "name": "4x.webp", "width": 384, "height": 128, "format": "WEBP" }
+                    ]
+                }
+            }
+        }))
+        .unwrap();
+
+        let emote = seventv_to_emote(&emote, "Channel");
+
+        assert!(emote.animated);
+        assert_eq!((emote.width, emote.height), (Some(96), Some(32)));
+        assert_eq!(
+            emote
+                .images
+                .iter()
+                .map(|image| image.scale)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+        assert_eq!(
+            emote.images[0].url,
+            "https://cdn.7tv.app/emote/wide-id/1x.webp"
+        );
+    }
 }
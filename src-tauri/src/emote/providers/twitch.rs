@@ -1,10 +1,15 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
-use futures::TryStreamExt;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+use twitch_api::eventsub::channel::chat::{
+    notification::{Chatter, Notification},
+    ChannelChatNotificationV1Payload,
+};
+use twitch_api::helix::chat::{GetUserEmotesRequest, UserEmote};
 
 use crate::emote::{
     cache::{EmoteCache, EmoteCacheTrait, MultiCache},
@@ -15,9 +20,60 @@ use crate::token::TokenManager;
 use crate::types::{EmoteProviderId, ProviderSettings};
 
 const USER_EMOTES_SCOPE_KEY: &str = "_user_emotes";
+const FOLLOWER_EMOTE_TYPE: &str = "follower";
+/// Upper bound on Get User Emotes pages; guards against a cursor that never ends.
+const MAX_USER_EMOTE_PAGES: usize = 100;
+const USER_EMOTE_PAGE_ATTEMPTS: usize = 3;
 
 type SharedMap<V> = Arc<Mutex<HashMap<String, V>>>;
 
+fn user_channel_scope_key(broadcaster_id: &str) -> String {
+    format!("{USER_EMOTES_SCOPE_KEY}:{broadcaster_id}")
+}
+
+fn is_user_scope_key(scope: &str) -> bool {
+    scope == USER_EMOTES_SCOPE_KEY || scope.starts_with(&format!("{USER_EMOTES_SCOPE_KEY}:"))
+}
+
+/// Follower emotes are only usable in the channel that owns them, so they are
+/// dropped unless they belong to `broadcaster_id`.
+fn usable_in_channel(emote: &UserEmote, broadcaster_id: Option<&str>) -> bool {
+    if emote.emote_type != FOLLOWER_EMOTE_TYPE {
+        return true;
+    }
+    broadcaster_id.is_some_and(|broadcaster_id| emote.owner_id.as_str() == broadcaster_id)
+}
+
+/// Whether a chat notification changed which emotes `user_id` can use in the
+/// channel (a new sub, resub, gifted sub or upgrade for that user).
+pub(crate) fn notification_grants_user_emotes(
+    payload: &ChannelChatNotificationV1Payload,
+    user_id: &str,
+) -> bool {
+    let chatter_is_user = matches!(
+        &payload.chatter,
+        Chatter::Chatter {
+            chatter_user_id,
+            ..
+        } if chatter_user_id.as_str() == user_id
+    );
+
+    match &payload.notification {
+        Notification::Subscription(_)
+        | Notification::Resubscription(_)
+        | Notification::GiftPaidUpgrade(_)
+        | Notification::PrimePaidUpgrade(_)
+        | Notification::SharedChatSub(_)
+        | Notification::SharedChatResub(_)
+        | Notification::SharedChatGiftPaidUpgrade(_)
+        | Notification::SharedChatPrimePaidUpgrade(_) => chatter_is_user,
+        Notification::SubGift(gift) | Notification::SharedChatSubGift(gift) => {
+            gift.recipient_user_id.as_str() == user_id
+        }
+        _ => false,
+    }
+}
+
 #[derive(Clone)]
 pub struct TwitchProvider {
     cache: SharedMap<EmoteCache>,
@@ -36,6 +92,110 @@ impl TwitchProvider {
             token_manager,
         }
     }
+
+    /// Walks every page of Get User Emotes, retrying individual pages so a
+    /// single failed request doesn't drop the whole inventory.
+    async fn fetch_user_emotes(
+        &self,
+        broadcaster_id: Option<&str>,
+    ) -> Result<Vec<UserEmote>, String> {
+        let Some(token) = self.token_manager.active_twitch_token().await else {
+            return Err("no active token".to_owned());
+        };
+
+        let mut req = GetUserEmotesRequest::user_id(token.user_id.clone());
+        req.broadcaster_id =
+            broadcaster_id.map(|id| Cow::Owned(twitch_api::types::UserId::from(id.to_owned())));
+
+        let mut emotes = Vec::new();
+        let mut seen_ids = HashSet::new();
+        let mut seen_cursors = HashSet::new();
+        for page in 0..MAX_USER_EMOTE_PAGES {
+            let mut attempt = 0;
+            let response = loop {
+                attempt += 1;
+                match self.client.req_get(req.clone(), &token).await {
+                    Ok(response) => break response,
+                    Err(err) if attempt < USER_EMOTE_PAGE_ATTEMPTS => {
+                        warn!(page, attempt, "retrying user emote page: err={}", err);
+                    }
+                    Err(err) => return Err(err.to_string()),
+                }
+            };
+
+            for emote in response.data {
+                if seen_ids.insert(emote.id.to_string()) {
+                    emotes.push(emote);
+                }
+            }
+
+            let Some(cursor) = response.pagination else {
+                return Ok(emotes);
+            };
+            if !seen_cursors.insert(cursor.to_string()) {
+                warn!(page, "user emote pagination repeated a cursor; stopping");
+                return Ok(emotes);
+            }
+            req.after = Some(Cow::Owned(cursor));
+        }
+
+        warn!(
+            pages = MAX_USER_EMOTE_PAGES,
+            "user emote pagination hit the page limit"
+        );
+        Ok(emotes)
+    }
+
+    /// Fetches the user's emotes in the background; the scope's cache is
+    /// replaced once they arrive.
+    fn load_user_scope(&self, broadcaster_id: Option<&str>) {
+        let provider = self.clone();
+        let broadcaster_id = broadcaster_id.map(ToOwned::to_owned);
+        tauri::async_runtime::spawn(async move {
+            provider.fetch_user_scope(broadcaster_id.as_deref()).await;
+        });
+    }
+
+    async fn fetch_user_scope(&self, broadcaster_id: Option<&str>) {
+        let scope_key = broadcaster_id
+            .map(user_channel_scope_key)
+            .unwrap_or_else(|| USER_EMOTES_SCOPE_KEY.to_owned());
+
+        let emotes = match self.fetch_user_emotes(broadcaster_id).await {
+            Ok(emotes) => emotes,
+            Err(err) => {
+                error!(scope_key, "failed to load user emotes: err={}", err);
+                return;
+            }
+        };
+
+        let cache = EmoteCache::new(scope_key.clone(), self.get_name());
+        for emote in emotes
+            .iter()
+            .filter(|emote| usable_in_channel(emote, broadcaster_id))
+        {
+            cache.set_emote(emote.name.clone(), emote.into());
+        }
+        info!(
+            scope_key,
+            total = emotes.len(),
+            usable = cache.len(),
+            "loaded user emotes"
+        );
+
+        let mut store = self.cache.lock().unwrap();
+        store.insert(scope_key, cache);
+    }
+
+    fn loaded_user_channel_scopes(&self) -> Vec<String> {
+        let prefix = format!("{USER_EMOTES_SCOPE_KEY}:");
+        self.cache
+            .lock()
+            .unwrap()
+            .keys()
+            .filter_map(|key| key.strip_prefix(&prefix).map(ToOwned::to_owned))
+            .collect()
+    }
 }
 
 impl EmoteProvider<MultiCache> for TwitchProvider {
@@ -44,28 +204,31 @@ impl EmoteProvider<MultiCache> for TwitchProvider {
     }
 
     fn load_global_emotes(&self, _client: &reqwest::Client, _provider_settings: &ProviderSettings) {
-        let cache = EmoteCache::new(GLOBAL_SCOPE_KEY.to_owned(), self.get_name());
+        let provider = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let cache = EmoteCache::new(GLOBAL_SCOPE_KEY.to_owned(), provider.get_name());
 
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let Some(token) = self.token_manager.active_twitch_token().await else {
+            let global_emotes = async {
+                let Some(token) = provider.token_manager.active_twitch_token().await else {
                     return Err("no active token".to_owned());
                 };
-                self.client
+                provider
+                    .client
                     .get_global_emotes(&token)
                     .await
                     .map_err(|err| err.to_string())
-            })
-        }) {
-            Ok(resp) => resp
-                .iter()
-                .map(|v| cache.clone().set_emote(v.name.clone(), v.into()))
-                .collect(),
-            Err(err) => error!("failed to load global emotes: err={}", err.to_string()),
-        }
+            };
+            match global_emotes.await {
+                Ok(resp) => resp
+                    .iter()
+                    .map(|v| cache.clone().set_emote(v.name.clone(), v.into()))
+                    .collect(),
+                Err(err) => error!("failed to load global emotes: err={}", err.to_string()),
+            }
 
-        let mut store = self.cache.lock().unwrap();
-        store.insert(GLOBAL_SCOPE_KEY.to_owned(), cache);
+            let mut store = provider.cache.lock().unwrap();
+            store.insert(GLOBAL_SCOPE_KEY.to_owned(), cache);
+        });
     }
 
     fn load_channel_emotes(
@@ -74,42 +237,38 @@ impl EmoteProvider<MultiCache> for TwitchProvider {
         _client: &reqwest::Client,
         _provider_settings: &ProviderSettings,
     ) {
-        let mut store = self.cache.lock().unwrap();
-        store
-            .entry(broadcaster_id.clone())
-            .or_insert_with(|| EmoteCache::new(broadcaster_id.clone(), self.get_name()));
+        {
+            let mut store = self.cache.lock().unwrap();
+            store
+                .entry(broadcaster_id.clone())
+                .or_insert_with(|| EmoteCache::new(broadcaster_id.clone(), self.get_name()));
+        }
         debug!(
             broadcaster_id,
             "prepared twitch channel emote cache for EventSub fragments"
         );
+
+        self.load_user_scope(Some(&broadcaster_id));
     }
 
     fn load_user_emotes(&self) {
-        let cache = EmoteCache::new(USER_EMOTES_SCOPE_KEY.to_owned(), self.get_name());
-
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let Some(token) = self.token_manager.active_twitch_token().await else {
-                    return Err("no active token".to_owned());
-                };
-                self.client
-                    .get_user_emotes(&token.user_id, &token)
-                    .try_collect::<Vec<_>>()
-                    .await
-                    .map_err(|err| err.to_string())
-            })
-        }) {
-            Ok(emotes) => {
-                info!("loaded {} user emotes", emotes.len());
-                for emote in &emotes {
-                    cache.set_emote(emote.name.clone(), emote.into());
-                }
-            }
-            Err(err) => error!("failed to load user emotes: err={}", err),
+        self.load_user_scope(None);
+        for broadcaster_id in self.loaded_user_channel_scopes() {
+            self.load_user_scope(Some(&broadcaster_id));
         }
+    }
 
-        let mut store = self.cache.lock().unwrap();
-        store.insert(USER_EMOTES_SCOPE_KEY.to_owned(), cache);
+    fn refresh_user_emotes(&self, broadcaster_id: &str) {
+        let loaded = self
+            .cache
+            .lock()
+            .unwrap()
+            .contains_key(&user_channel_scope_key(broadcaster_id));
+        if !loaded {
+            return;
+        }
+        debug!(broadcaster_id, "refreshing user emotes for channel");
+        self.load_user_scope(Some(broadcaster_id));
     }
 
     fn insert_emote(&self, scope: String, name: String, emote: Emote) {
@@ -130,13 +289,16 @@ impl EmoteProvider<MultiCache> for TwitchProvider {
         let mut store = self.cache.lock().unwrap();
         tracing::trace!(scope, "twitch get_emote_cache");
         let mut caches = Vec::new();
-        if scope != GLOBAL_SCOPE_KEY && scope != USER_EMOTES_SCOPE_KEY {
+        if scope != GLOBAL_SCOPE_KEY && !is_user_scope_key(&scope) {
             let cache_name = self.get_name();
             let channel_cache = store
                 .entry(scope.clone())
                 .or_insert_with(|| EmoteCache::new(scope.clone(), cache_name))
                 .clone();
             caches.push(channel_cache);
+            if let Some(user_channel_cache) = store.get(&user_channel_scope_key(&scope)) {
+                caches.push(user_channel_cache.clone());
+            }
         } else if let Some(channel_cache) = store.get(&scope) {
             caches.push(channel_cache.clone());
         }
@@ -148,4 +310,124 @@ impl EmoteProvider<MultiCache> for TwitchProvider {
         }
         MultiCache::new(caches)
     }
+
+    /// Only emotes the active user can send: their per-channel inventory (or
+    /// the channel-agnostic one until that loads) plus Twitch globals. Emotes
+    /// learned from other chatters' fragments are render-only.
+    fn get_usable_emote_cache(&self, scope: String) -> MultiCache {
+        let store = self.cache.lock().unwrap();
+        tracing::trace!(scope, "twitch get_usable_emote_cache");
+        let mut caches = Vec::new();
+        let user_cache = (scope != GLOBAL_SCOPE_KEY)
+            .then(|| store.get(&user_channel_scope_key(&scope)))
+            .flatten()
+            .or_else(|| store.get(USER_EMOTES_SCOPE_KEY));
+        if let Some(user_cache) = user_cache {
+            caches.push(user_cache.clone());
+        }
+        if let Some(global_cache) = store.get(GLOBAL_SCOPE_KEY) {
+            caches.push(global_cache.clone());
+        }
+        MultiCache::new(caches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_emote(name: &str, emote_type: &str, owner_id: &str) -> UserEmote {
+        serde_json::from_value(serde_json::json!({
+            "id": format!("id-{name}"),
+            "name": name,
+            "emote_type": emote_type,
+            "emote_set_id": "set",
+            "owner_id": owner_id,
+            "format": ["static"],
+            "scale": ["1.0"],
+            "theme_mode": ["dark"],
+        }))
+        .unwrap()
+    }
+
+    fn notification(
+        chatter_id: &str,
+        notice: serde_json::Value,
+    ) -> ChannelChatNotificationV1Payload {
+        let mut payload = serde_json::json!({
+            "broadcaster_user_id": "1234",
+            "broadcaster_user_name": "Maya",
+            "broadcaster_user_login": "maya",
+            "chatter_user_id": chatter_id,
+            "chatter_user_name": "Viewer",
+            "chatter_user_login": "viewer",
+            "chatter_is_anonymous": false,
+            "color": "",
+            "badges": [],
+            "system_message": "",
+            "message_id": "msg-1",
+            "message": { "text": "", "fragments": [] },
+            "sub": null,
+            "resub": null,
+            "sub_gift": null,
+            "community_sub_gift": null,
+            "gift_paid_upgrade": null,
+            "prime_paid_upgrade": null,
+            "pay_it_forward": null,
+            "raid": null,
+            "unraid": null,
+            "announcement": null,
+            "bits_badge_tier": null,
+            "charity_donation": null,
+        });
+        let object = payload.as_object_mut().unwrap();
+        for (key, value) in notice.as_object().unwrap() {
+            object.insert(key.clone(), value.clone());
+        }
+        serde_json::from_value(payload).unwrap()
+    }
+
+    #[test]
+    fn follower_emotes_are_only_usable_in_their_own_channel() {
+        let follower = user_emote("mayaHi", "follower", "1234");
+
+        assert!(usable_in_channel(&follower, Some("1234")));
+        assert!(!usable_in_channel(&follower, Some("5678")));
+        assert!(!usable_in_channel(&follower, None));
+        assert!(usable_in_channel(
+            &user_emote("mayaSub", "subscriptions", "1234"),
+            Some("5678")
+        ));
+    }
+
+    #[test]
+    fn own_sub_and_received_gift_grant_user_emotes() {
+        let sub = notification(
+            "42",
+            serde_json::json!({
+                "notice_type": "sub",
+                "sub": { "sub_tier": "1000", "is_prime": false, "duration_months": 1 },
+            }),
+        );
+        assert!(notification_grants_user_emotes(&sub, "42"));
+        assert!(!notification_grants_user_emotes(&sub, "7"));
+
+        let gift = notification(
+            "7",
+            serde_json::json!({
+                "notice_type": "sub_gift",
+                "sub_gift": {
+                    "duration_months": 1,
+                    "cumulative_total": null,
+                    "recipient_user_id": "42",
+                    "recipient_user_name": "Viewer",
+                    "recipient_user_login": "viewer",
+                    "sub_tier": "1000",
+                    "community_gift_id": null,
+                },
+            }),
+        );
+        assert!(notification_grants_user_emotes(&gift, "42"));
+        assert!(!notification_grants_user_emotes(&gift, "7"));
+    }
 }
@@ -1,16 +1,22 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
+use tauri::Manager;
 use tracing::{debug, error};
 
 use crate::emote::{
     cache::{EmoteCacheTrait, MultiCache},
     persist::{SharedEmoteMetadataStore, TauriEmoteMetadataStore},
     providers::{
-        bttv::BttvProvider, ffz::FfzProvider, http::provider_client, seventv::SeventvProvider,
-        twitch::TwitchProvider, EmoteProvider, GLOBAL_SCOPE_KEY,
+        bttv::BttvProvider,
+        ffz::FfzProvider,
+        http::provider_client,
+        local::{LocalEmoteFiles, LocalProvider},
+        seventv::SeventvProvider,
+        twitch::TwitchProvider,
+        EmoteProvider, GLOBAL_SCOPE_KEY,
     },
     Emote,
 };
@@ -29,6 +35,8 @@ pub struct EmoteManager {
     token_manager: Option<TokenManager>,
     persistence: SharedEmoteMetadataStore,
     name_cache: SharedMap<String>,
+    followed_channels: Arc<Mutex<Option<HashSet<String>>>>,
+    local_files: LocalEmoteFiles,
 }
 
 impl EmoteManager {
@@ -41,8 +49,13 @@ impl EmoteManager {
             providers: Arc::new(Mutex::new(Vec::new())),
             client,
             token_manager: Some(token_manager),
+            local_files: app_handle
+                .try_state::<LocalEmoteFiles>()
+                .map(|files| files.inner().clone())
+                .unwrap_or_default(),
             persistence: Arc::new(TauriEmoteMetadataStore::new(app_handle)),
             name_cache: Default::default(),
+            followed_channels: Default::default(),
         }
     }
 
@@ -54,6 +67,8 @@ impl EmoteManager {
             token_manager: None,
             persistence,
             name_cache: Default::default(),
+            followed_channels: Default::default(),
+            local_files: Default::default(),
         }
     }
 
@@ -130,7 +145,60 @@ impl EmoteManager {
         }
     }
 
+    /// Re-fetches the user's emote inventory for a channel after something
+    /// changed what they can send there (sub, gift, follow).
+    pub fn refresh_user_emotes(&self, broadcaster_id: &str) {
+        let providers = self.providers.lock().unwrap().clone();
+        for p in providers.iter() {
+            p.refresh_user_emotes(broadcaster_id);
+        }
+    }
+
+    /// Records the current followed channel set and refreshes user emotes for
+    /// any channel whose follow state changed since the last call, so follower
+    /// emotes appear (or disappear) without rejoining.
+    pub fn update_followed_channels(&self, followed: HashSet<String>) {
+        let changed: Vec<String> = {
+            let mut previous = self.followed_channels.lock().unwrap();
+            let changed = match previous.as_ref() {
+                Some(previous) => previous.symmetric_difference(&followed).cloned().collect(),
+                None => Vec::new(),
+            };
+            *previous = Some(followed);
+            changed
+        };
+
+        for broadcaster_id in changed {
+            debug!(
+                broadcaster_id,
+                "follow state changed; refreshing user emotes"
+            );
+            self.refresh_user_emotes(&broadcaster_id);
+        }
+    }
+
     pub fn get_emote_cache(&self, scope: String, emote_settings: &EmoteSettings) -> MultiCache {
+        self.collect_caches(scope, emote_settings, |p, scope| p.get_emote_cache(scope))
+    }
+
+    /// Like [`EmoteManager::get_emote_cache`], but limited to emotes the active
+    /// user can actually send in `scope`. Used by the picker and autocomplete.
+    pub fn get_usable_emote_cache(
+        &self,
+        scope: String,
+        emote_settings: &EmoteSettings,
+    ) -> MultiCache {
+        self.collect_caches(scope, emote_settings, |p, scope| {
+            p.get_usable_emote_cache(scope)
+        })
+    }
+
+    fn collect_caches(
+        &self,
+        scope: String,
+        emote_settings: &EmoteSettings,
+        cache_for: impl Fn(&ProviderRef, String) -> MultiCache,
+    ) -> MultiCache {
         let providers = self.providers.lock().unwrap();
         let provider_ids = emote_settings
             .clone()
@@ -139,7 +207,7 @@ impl EmoteManager {
         let caches: Vec<_> = provider_ids
             .iter()
             .flat_map(|id| providers.iter().find(|p| p.get_id() == *id))
-            .flat_map(|p| p.get_emote_cache(scope.clone()).into_caches())
+            .flat_map(|p| cache_for(p, scope.clone()).into_caches())
             .collect();
 
         let mc = MultiCache::new(caches);
@@ -181,14 +249,13 @@ impl EmoteManager {
             EmoteProviderId::Bttv => Arc::new(BttvProvider::new(self.persistence.clone())),
             EmoteProviderId::Ffz => Arc::new(FfzProvider::new(self.persistence.clone())),
             EmoteProviderId::Seventv => Arc::new(SeventvProvider::new(self.persistence.clone())),
+            EmoteProviderId::Local => Arc::new(LocalProvider::new(self.local_files.clone())),
         }
     }
 
     fn ensure_providers(&self, emote_settings: &EmoteSettings) -> Vec<ProviderRef> {
-        let provider_ids = emote_settings
-            .clone()
-            .normalized()
-            .enabled_provider_ids_ordered();
+        let emote_settings = emote_settings.clone().normalized();
+        let provider_ids = emote_settings.enabled_provider_ids_ordered();
         let mut store = self.providers.lock().unwrap();
         let providers = provider_ids
             .into_iter()
@@ -200,21 +267,26 @@ impl EmoteManager {
                     .unwrap_or_else(|| self.provider(id))
             })
             .collect::<Vec<_>>();
+        for provider in &providers {
+            provider.configure(&emote_settings);
+        }
         *store = providers.clone();
         providers
     }
 
-    pub fn resolve_user_name(&self, user_id: &str) -> Option<String> {
-        // Check cache first
-        {
-            let cache = self.name_cache.lock().unwrap();
-            if let Some(name) = cache.get(user_id) {
-                return Some(name.clone());
-            }
+    /// The display name of a user if [`Self::resolve_user_name`] already
+    /// looked it up.
+    pub fn cached_user_name(&self, user_id: &str) -> Option<String> {
+        self.name_cache.lock().unwrap().get(user_id).cloned()
+    }
+
+    pub async fn resolve_user_name(&self, user_id: &str) -> Option<String> {
+        if let Some(name) = self.cached_user_name(user_id) {
+            return Some(name);
         }
 
         // Resolve via Helix API
-        let result = tauri::async_runtime::block_on(async {
+        let result = async {
             let Some(token_manager) = &self.token_manager else {
                 return Err("no active token".to_owned());
             };
@@ -225,7 +297,8 @@ impl EmoteManager {
                 .get_user_from_id(user_id, &token)
                 .await
                 .map_err(|err| err.to_string())
-        });
+        }
+        .await;
 
         match result {
             Ok(Some(user)) => {
@@ -285,6 +358,10 @@ mod tests {
                     id: EmoteProviderId::Seventv,
                     enabled: false,
                 },
+                crate::types::EmoteProviderPreference {
+                    id: EmoteProviderId::Local,
+                    enabled: false,
+                },
             ],
             ..Default::default()
         }
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use twitch_api::helix::ClientRequestError;
+
+use crate::{commands::CommandError, moderation::ModerationError};
+
+/// Why a command failed, for the frontend to react to by `kind`.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PepoError {
+    /// Nobody is logged in.
+    AuthMissing,
+    /// Twitch no longer accepts the token; logging in again fixes it.
+    AuthExpired {
+        message: String,
+    },
+    NotFound {
+        message: String,
+    },
+    /// `missing_scopes` lists scopes the token lacks when re-authorizing would
+    /// help; it is empty when the user simply isn't allowed.
+    PermissionDenied {
+        message: String,
+        missing_scopes: Vec<String>,
+    },
+    /// `retry_after_secs` is set when Twitch said when to try again.
+    RateLimited {
+        message: String,
+        retry_after_secs: Option<u64>,
+    },
+    /// Rejected before anything was sent, or refused by Twitch as malformed.
+    InvalidInput {
+        message: String,
+    },
+    /// Twitch couldn't be reached.
+    Network {
+        message: String,
+    },
+    /// Twitch or another provider answered with an error.
+    Provider {
+        message: String,
+    },
+    /// Something went wrong on our side.
+    Internal {
+        message: String,
+    },
+}
+
+impl PepoError {
+    pub fn from_client_error<RE>(err: ClientRequestError<RE>) -> Self
+    where
+        RE: std::error::Error + Send + Sync + 'static,
+    {
+        ModerationError::from_client_error(err).into()
+    }
+
+    /// Wraps a failure of ours with what we were doing, e.g.
+    /// `PepoError::internal("failed to read chat history", e)`.
+    pub fn internal(context: &str, err: impl fmt::Display) -> Self {
+        PepoError::Internal {
+            message: format!("{context}: {err:#}"),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        PepoError::NotFound {
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        PepoError::InvalidInput {
+            message: message.into(),
+        }
+    }
+
+    /// Whether the stored login has to be thrown away.
+    pub fn is_auth_expired(&self) -> bool {
+        matches!(self, PepoError::AuthExpired { .. })
+    }
+}
+
+impl From<ModerationError> for PepoError {
+    fn from(error: ModerationError) -> Self {
+        match error {
+            ModerationError::NotLoggedIn => PepoError::AuthMissing,
+            ModerationError::MissingScopes { ref scopes } => PepoError::PermissionDenied {
+                message: error.to_string(),
+                missing_scopes: scopes.clone(),
+            },
+            ModerationError::InvalidInput { message } | ModerationError::Rejected { message } => {
+                PepoError::InvalidInput { message }
+            }
+            ModerationError::Unauthorized { message } => PepoError::AuthExpired { message },
+            ModerationError::NotModerator { message } => PepoError::PermissionDenied {
+                message,
+                missing_scopes: Vec::new(),
+            },
+            ModerationError::NotFound { message } => PepoError::NotFound { message },
+            ModerationError::RateLimited {
+                message,
+                retry_after_secs,
+            } => PepoError::RateLimited {
+                message,
+                retry_after_secs,
+            },
+            ModerationError::Network { message } => PepoError::Network { message },
+            ModerationError::Conflict { message } | ModerationError::Failed { message } => {
+                PepoError::Provider { message }
+            }
+        }
+    }
+}
+
+impl From<CommandError> for PepoError {
+    fn from(error: CommandError) -> Self {
+        match error {
+            CommandError::UnknownCommand { .. } | CommandError::Usage { .. } => {
+                PepoError::InvalidInput {
+                    message: error.to_string(),
+                }
+            }
+            CommandError::PermissionDenied { .. } => PepoError::PermissionDenied {
+                message: error.to_string(),
+                missing_scopes: Vec::new(),
+            },
+            CommandError::UnknownUser { .. } => PepoError::NotFound {
+                message: error.to_string(),
+            },
+            CommandError::Failed { error } => error,
+        }
+    }
+}
+
+impl fmt::Display for PepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PepoError::AuthMissing => write!(f, "no active token"),
+            PepoError::AuthExpired { message }
+            | PepoError::NotFound { message }
+            | PepoError::PermissionDenied { message, .. }
+            | PepoError::RateLimited { message, .. }
+            | PepoError::InvalidInput { message }
+            | PepoError::Network { message }
+            | PepoError::Provider { message }
+            | PepoError::Internal { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for PepoError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Permission;
+
+    #[test]
+    fn moderation_errors_keep_their_meaning() {
+        assert_eq!(
+            PepoError::from(ModerationError::NotLoggedIn),
+            PepoError::AuthMissing
+        );
+        assert_eq!(
+            PepoError::from(ModerationError::MissingScopes {
+                scopes: vec!["moderator:manage:banned_users".to_owned()],
+            }),
+            PepoError::PermissionDenied {
+                message: "missing scopes: moderator:manage:banned_users".to_owned(),
+                missing_scopes: vec!["moderator:manage:banned_users".to_owned()],
+            }
+        );
+        assert!(
+            PepoError::from(ModerationError::from_status(401, String::new())).is_auth_expired()
+        );
+        assert_eq!(
+            PepoError::from(
+                ModerationError::from_status(429, "slow down".to_owned()).with_retry_after(Some(7))
+            ),
+            PepoError::RateLimited {
+                message: "slow down".to_owned(),
+                retry_after_secs: Some(7),
+            }
+        );
+        assert!(matches!(
+            PepoError::from(ModerationError::from_status(503, String::new())),
+            PepoError::Provider { .. }
+        ));
+    }
+
+    #[test]
+    fn command_errors_flatten_into_kinds() {
+        assert!(matches!(
+            PepoError::from(CommandError::UnknownCommand {
+                name: "bna".to_owned(),
+                suggestions: vec!["ban".to_owned()],
+            }),
+            PepoError::InvalidInput { .. }
+        ));
+        assert!(matches!(
+            PepoError::from(CommandError::PermissionDenied {
+                command: "raid".to_owned(),
+                required: Permission::Broadcaster,
+            }),
+            PepoError::PermissionDenied { .. }
+        ));
+        assert_eq!(
+            PepoError::from(CommandError::from(ModerationError::NotLoggedIn)),
+            PepoError::AuthMissing
+        );
+    }
+
+    #[test]
+    fn serializes_with_a_kind_tag() {
+        let value = serde_json::to_value(PepoError::RateLimited {
+            message: "slow down".to_owned(),
+            retry_after_secs: None,
+        })
+        .unwrap();
+        assert_eq!(value["kind"], "rate_limited");
+    }
+}
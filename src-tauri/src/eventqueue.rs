@@ -0,0 +1,451 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::warn;
+use twitch_api::eventsub::{Event, Message as M, Payload as P};
+
+use crate::{
+    eventsub::{EventSubMessage, EventSubSettingsReader},
+    types::OverloadPolicy,
+};
+
+/// Queue depth for one channel.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct ChannelQueueMetrics {
+    pub login: String,
+    pub depth: u32,
+    /// Chat messages shed from this channel since login.
+    pub dropped: u64,
+    pub focused: bool,
+}
+
+/// A snapshot of the EventSub queue, busiest channel first.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct EventQueueMetrics {
+    pub capacity: u32,
+    /// Events waiting across every channel.
+    pub depth: u32,
+    pub channels: Vec<ChannelQueueMetrics>,
+}
+
+/// Emitted on `chat_overloaded:{login}` where messages were shed.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct OverloadMarker {
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct ChannelQueue {
+    events: VecDeque<EventSubMessage>,
+    dropped: u64,
+    /// Shed since the consumer last saw a marker for this channel.
+    unreported: u64,
+}
+
+impl ChannelQueue {
+    fn is_idle(&self) -> bool {
+        self.events.is_empty() && self.unreported == 0
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Events that belong to no channel, like whispers or auth failures.
+    control: VecDeque<EventSubMessage>,
+    channels: HashMap<String, ChannelQueue>,
+    /// Channels with something to hand out, served round robin.
+    rotation: VecDeque<String>,
+    depth: usize,
+    focused: HashSet<String>,
+    closed: bool,
+}
+
+impl State {
+    fn push(&mut self, message: EventSubMessage, capacity: usize, policy: OverloadPolicy) {
+        let Some(login) = channel_login(&message).map(str::to_owned) else {
+            self.control.push_back(message);
+            return;
+        };
+
+        if self.depth >= capacity && is_chat_message(&message) {
+            let victim = match policy {
+                OverloadPolicy::ShedBusiestUnfocused => self.busiest_unfocused(),
+                OverloadPolicy::ShedIncoming => None,
+            };
+            match victim {
+                Some(victim) if self.shed_oldest_chat_message(&victim) => {}
+                _ => {
+                    self.record_shed(&login);
+                    return;
+                }
+            }
+        }
+
+        let queue = self.channels.entry(login.clone()).or_default();
+        if queue.is_idle() {
+            self.rotation.push_back(login);
+        }
+        queue.events.push_back(message);
+        self.depth += 1;
+    }
+
+    /// The unfocused channel with the most queued events, or the busiest of
+    /// all when every queued channel is focused.
+    fn busiest_unfocused(&self) -> Option<String> {
+        let busiest = |focused: bool| {
+            self.channels
+                .iter()
+                .filter(|(login, _)| focused || !self.focused.contains(*login))
+                .filter(|(_, queue)| !queue.events.is_empty())
+                .max_by_key(|(_, queue)| queue.events.len())
+                .map(|(login, _)| login.clone())
+        };
+        busiest(false).or_else(|| busiest(true))
+    }
+
+    /// Only chat messages are shed; moderation events keep their order.
+    fn shed_oldest_chat_message(&mut self, login: &str) -> bool {
+        let Some(queue) = self.channels.get(login) else {
+            return false;
+        };
+        let Some(index) = queue.events.iter().position(is_chat_message) else {
+            return false;
+        };
+        // Counted first, while the channel still has its place in the rotation.
+        self.record_shed(login);
+        if let Some(queue) = self.channels.get_mut(login) {
+            queue.events.remove(index);
+            self.depth -= 1;
+        }
+        true
+    }
+
+    fn record_shed(&mut self, login: &str) {
+        let queue = self.channels.entry(login.to_owned()).or_default();
+        if queue.is_idle() {
+            self.rotation.push_back(login.to_owned());
+        }
+        if queue.unreported == 0 {
+            warn!(
+                channel = login,
+                depth = self.depth,
+                "event queue overloaded"
+            );
+        }
+        queue.dropped += 1;
+        queue.unreported += 1;
+    }
+
+    fn pop(&mut self) -> Option<EventSubMessage> {
+        if let Some(message) = self.control.pop_front() {
+            return Some(message);
+        }
+        while let Some(login) = self.rotation.pop_front() {
+            let Some(queue) = self.channels.get_mut(&login) else {
+                continue;
+            };
+            // The marker goes out before whatever survived the shedding.
+            let message = if queue.unreported > 0 {
+                let dropped = std::mem::take(&mut queue.unreported);
+                Some(EventSubMessage::Overloaded {
+                    login: login.clone(),
+                    dropped,
+                })
+            } else {
+                queue.events.pop_front().inspect(|_| self.depth -= 1)
+            };
+            if !queue.is_idle() {
+                self.rotation.push_back(login);
+            }
+            if message.is_some() {
+                return message;
+            }
+        }
+        None
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    notify: Notify,
+    settings: EventSubSettingsReader,
+}
+
+/// Creates the queue between the EventSub socket and message processing.
+/// Pushing never waits: past capacity, chat messages are shed according to
+/// the configured policy instead.
+pub fn channel(settings: EventSubSettingsReader) -> (EventSender, EventReceiver, EventQueue) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State::default()),
+        notify: Notify::new(),
+        settings,
+    });
+    let sender = EventSender {
+        shared: shared.clone(),
+        _open: Arc::new(OpenGuard {
+            shared: shared.clone(),
+        }),
+    };
+    let receiver = EventReceiver {
+        shared: shared.clone(),
+    };
+    (sender, receiver, EventQueue { shared })
+}
+
+/// Closes the queue once the last sender is gone.
+struct OpenGuard {
+    shared: Arc<Shared>,
+}
+
+impl Drop for OpenGuard {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.notify.notify_one();
+    }
+}
+
+#[derive(Clone)]
+pub struct EventSender {
+    shared: Arc<Shared>,
+    _open: Arc<OpenGuard>,
+}
+
+impl EventSender {
+    pub fn send(&self, message: EventSubMessage) {
+        let settings = (self.shared.settings)();
+        self.shared.state.lock().unwrap().push(
+            message,
+            settings.queue_capacity,
+            settings.overload_policy,
+        );
+        self.shared.notify.notify_one();
+    }
+}
+
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+impl EventReceiver {
+    /// The next event, or `None` once every sender is gone and the queue has
+    /// drained.
+    pub async fn recv(&mut self) -> Option<EventSubMessage> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(message) = state.pop() {
+                    return Some(message);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+/// Inspects and steers a running queue.
+#[derive(Clone)]
+pub struct EventQueue {
+    shared: Arc<Shared>,
+}
+
+impl EventQueue {
+    /// Channels the user is looking at, shed from last.
+    pub fn set_focused(&self, logins: Vec<String>) {
+        self.shared.state.lock().unwrap().focused = logins
+            .into_iter()
+            .map(|login| login.trim().to_lowercase())
+            .collect();
+    }
+
+    pub fn metrics(&self) -> EventQueueMetrics {
+        let capacity = (self.shared.settings)().queue_capacity;
+        let state = self.shared.state.lock().unwrap();
+        let mut channels: Vec<_> = state
+            .channels
+            .iter()
+            .map(|(login, queue)| ChannelQueueMetrics {
+                login: login.clone(),
+                depth: queue.events.len() as u32,
+                dropped: queue.dropped,
+                focused: state.focused.contains(login),
+            })
+            .collect();
+        channels.sort_by(|a, b| b.depth.cmp(&a.depth).then_with(|| a.login.cmp(&b.login)));
+        EventQueueMetrics {
+            capacity: capacity as u32,
+            depth: (state.depth + state.control.len()) as u32,
+            channels,
+        }
+    }
+}
+
+fn is_chat_message(message: &EventSubMessage) -> bool {
+    matches!(
+        message,
+        EventSubMessage::Notification(notification)
+            if matches!(notification.event, Event::ChannelChatMessageV1(_))
+    )
+}
+
+fn channel_login(message: &EventSubMessage) -> Option<&str> {
+    let EventSubMessage::Notification(notification) = message else {
+        return None;
+    };
+    macro_rules! broadcaster_login {
+        ($($variant:ident),* $(,)?) => {
+            match &notification.event {
+                $(Event::$variant(P {
+                    message: M::Notification(payload),
+                    ..
+                }) => Some(payload.broadcaster_user_login.as_str()),)*
+                _ => None,
+            }
+        };
+    }
+    broadcaster_login!(
+        ChannelChatMessageV1,
+        ChannelChatNotificationV1,
+        ChannelChatClearV1,
+        ChannelChatClearUserMessagesV1,
+        ChannelChatMessageDeleteV1,
+        ChannelModerateV2,
+        ChannelSuspiciousUserMessageV1,
+        ChannelSuspiciousUserUpdateV1,
+        AutomodMessageHoldV1,
+        AutomodMessageUpdateV1,
+        ChannelUnbanRequestCreateV1,
+        ChannelUnbanRequestResolveV1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twitch_api::eventsub::EventsubWebsocketData;
+
+    use crate::eventsub::EventNotification;
+
+    fn chat(login: &str, id: &str) -> EventSubMessage {
+        let frame = serde_json::json!({
+            "metadata": {
+                "message_id": id,
+                "message_type": "notification",
+                "message_timestamp": "2024-01-01T00:00:00Z",
+                "subscription_type": "channel.chat.message",
+                "subscription_version": "1"
+            },
+            "payload": {
+                "subscription": {
+                    "id": "sub",
+                    "status": "enabled",
+                    "type": "channel.chat.message",
+                    "version": "1",
+                    "condition": {"broadcaster_user_id": "1", "user_id": "2"},
+                    "transport": {"method": "websocket", "session_id": "s"},
+                    "created_at": "2024-01-01T00:00:00Z",
+                    "cost": 0
+                },
+                "event": {
+                    "broadcaster_user_id": "1",
+                    "broadcaster_user_login": login,
+                    "broadcaster_user_name": login,
+                    "chatter_user_id": "2",
+                    "chatter_user_login": "viewer",
+                    "chatter_user_name": "viewer",
+                    "message_id": id,
+                    "message": {"text": id, "fragments": []},
+                    "color": "",
+                    "badges": [],
+                    "message_type": "text",
+                    "cheer": null,
+                    "reply": null,
+                    "channel_points_custom_reward_id": null,
+                    "channel_points_animation_id": null
+                }
+            }
+        })
+        .to_string();
+        let EventsubWebsocketData::Notification { metadata, payload } =
+            Event::parse_websocket(&frame).unwrap()
+        else {
+            panic!("not a notification");
+        };
+        EventSubMessage::Notification(EventNotification {
+            ts: metadata.message_timestamp.into_owned(),
+            event: payload,
+        })
+    }
+
+    fn message_id(message: &EventSubMessage) -> String {
+        match message {
+            EventSubMessage::Notification(notification) => match &notification.event {
+                Event::ChannelChatMessageV1(P {
+                    message: M::Notification(payload),
+                    ..
+                }) => payload.message_id.to_string(),
+                _ => panic!("not a chat message"),
+            },
+            EventSubMessage::Overloaded { login, dropped } => {
+                format!("overloaded:{login}:{dropped}")
+            }
+            EventSubMessage::AuthFailed(_) => "auth_failed".to_owned(),
+        }
+    }
+
+    fn drain(state: &mut State) -> Vec<String> {
+        std::iter::from_fn(|| state.pop())
+            .map(|message| message_id(&message))
+            .collect()
+    }
+
+    #[test]
+    fn serves_channels_round_robin() {
+        let mut state = State::default();
+        for id in ["a1", "a2", "a3"] {
+            state.push(chat("a", id), 10, OverloadPolicy::ShedBusiestUnfocused);
+        }
+        state.push(chat("b", "b1"), 10, OverloadPolicy::ShedBusiestUnfocused);
+        state.push(
+            EventSubMessage::AuthFailed("revoked".to_owned()),
+            10,
+            OverloadPolicy::ShedBusiestUnfocused,
+        );
+
+        assert_eq!(drain(&mut state), ["auth_failed", "a1", "b1", "a2", "a3"]);
+    }
+
+    #[test]
+    fn sheds_oldest_from_busiest_unfocused_channel() {
+        let mut state = State::default();
+        state.focused.insert("a".to_owned());
+        for id in ["a1", "a2", "a3"] {
+            state.push(chat("a", id), 4, OverloadPolicy::ShedBusiestUnfocused);
+        }
+        state.push(chat("b", "b1"), 4, OverloadPolicy::ShedBusiestUnfocused);
+        state.push(chat("a", "a4"), 4, OverloadPolicy::ShedBusiestUnfocused);
+        state.push(chat("a", "a5"), 4, OverloadPolicy::ShedBusiestUnfocused);
+
+        assert_eq!(state.channels["b"].dropped, 1);
+        assert_eq!(state.channels["a"].dropped, 1);
+        assert_eq!(
+            drain(&mut state),
+            ["overloaded:a:1", "overloaded:b:1", "a2", "a3", "a4", "a5"]
+        );
+    }
+
+    #[test]
+    fn shed_incoming_drops_the_new_message() {
+        let mut state = State::default();
+        state.push(chat("a", "a1"), 1, OverloadPolicy::ShedIncoming);
+        state.push(chat("a", "a2"), 1, OverloadPolicy::ShedIncoming);
+
+        assert_eq!(drain(&mut state), ["overloaded:a:1", "a1"]);
+    }
+}
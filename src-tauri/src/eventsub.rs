@@ -7,7 +7,6 @@ use futures::{stream, TryStreamExt};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
-use std::sync::mpsc::{Receiver, SyncSender};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -19,9 +18,14 @@ use twitch_api::{
     eventsub::{self, Event, EventsubWebsocketData, ReconnectPayload, SessionData, WelcomePayload},
     HelixClient,
 };
-use twitch_oauth2::UserToken;
+use twitch_oauth2::{TwitchToken, UserToken};
 
-use crate::{logging, token::TokenManager, types::EventSubSettings};
+use crate::{
+    eventqueue::{self, EventQueue, EventReceiver, EventSender},
+    logging, moderation,
+    token::TokenManager,
+    types::EventSubSettings,
+};
 
 type SharedMap<V> = Arc<Mutex<HashMap<String, Mutex<HashSet<V>>>>>;
 type DesiredChannels = Arc<Mutex<HashMap<String, UserId>>>;
@@ -39,10 +43,16 @@ pub struct EventNotification {
 pub enum EventSubMessage {
     Notification(EventNotification),
     AuthFailed(String),
+    /// `dropped` chat messages for `login` were shed to keep up.
+    Overloaded {
+        login: String,
+        dropped: u64,
+    },
 }
 
 pub struct EventSubRuntime {
-    pub events: Receiver<EventSubMessage>,
+    pub events: EventReceiver,
+    pub queue: EventQueue,
     pub handles: Vec<tauri::async_runtime::JoinHandle<()>>,
 }
 
@@ -68,6 +78,7 @@ pub struct EventSubManager {
     subscriptions: SharedMap<EventSubSubscription>,
     desired_channels: DesiredChannels,
     user_update_subscription_id: Arc<Mutex<Option<twitch_api::types::EventSubId>>>,
+    whisper_subscription_id: Arc<Mutex<Option<twitch_api::types::EventSubId>>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -204,6 +215,7 @@ impl EventSubManager {
             subscriptions: Arc::new(Mutex::new(HashMap::new())),
             desired_channels: Arc::new(Mutex::new(HashMap::new())),
             user_update_subscription_id: Arc::new(Mutex::new(None)),
+            whisper_subscription_id: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -223,6 +235,7 @@ impl EventSubManager {
     fn clear_active_subscriptions(&self) {
         self.subscriptions.lock().unwrap().clear();
         *self.user_update_subscription_id.lock().unwrap() = None;
+        *self.whisper_subscription_id.lock().unwrap() = None;
     }
 
     fn add_desired_channel(&self, channel_name: String, chat_id: UserId) {
@@ -266,6 +279,19 @@ impl EventSubManager {
             .unwrap_or(false)
     }
 
+    fn has_subscription_type(&self, channel_name: &str, sub_type: &eventsub::EventType) -> bool {
+        let guard = self.subscriptions.lock().unwrap();
+        guard
+            .get(channel_name)
+            .map(|subs| {
+                subs.lock()
+                    .unwrap()
+                    .iter()
+                    .any(|sub| sub.sub_type == *sub_type)
+            })
+            .unwrap_or(false)
+    }
+
     fn get_subscriptions(&self, channel_name: &str) -> Vec<EventSubSubscription> {
         let guard = self.subscriptions.lock().unwrap();
         guard
@@ -330,55 +356,205 @@ impl EventSubManager {
     ) -> Result<(), Report> {
         let transport = eventsub::Transport::websocket(session_id);
         debug!(
-            "EventSubManager - creating ChannelChatMessageV1: user_id={}, session_id={}",
+            "EventSubManager - creating channel subscriptions: user_id={}, session_id={}",
             chat_id, session_id
         );
-        let user_id = token.clone().user_id;
-        let message =
+        let user_id = token.user_id.clone();
+        let chat =
             eventsub::channel::chat::ChannelChatMessageV1::new(chat_id.clone(), user_id.clone());
-        match client
-            .create_eventsub_subscription(message.clone(), transport.clone(), token)
-            .await
-        {
-            Ok(resp) => {
-                self.add_subscription(
-                    chat_name.clone(),
-                    EventSubSubscription {
-                        channel_name: chat_name.clone(),
-                        id: resp.id.clone(),
-                        sub_type: resp.type_.clone(),
-                    },
-                );
+        self.create_channel_subscription(&chat_name, chat, &transport, client, token)
+            .await?;
+
+        let notification = eventsub::channel::chat::ChannelChatNotificationV1::new(
+            chat_id.clone(),
+            user_id.clone(),
+        );
+        self.create_channel_subscription(&chat_name, notification, &transport, client, token)
+            .await?;
+
+        let clear =
+            eventsub::channel::chat::ChannelChatClearV1::new(chat_id.clone(), user_id.clone());
+        self.create_channel_subscription(&chat_name, clear, &transport, client, token)
+            .await?;
+
+        let clear_user_messages = eventsub::channel::chat::ChannelChatClearUserMessagesV1::new(
+            chat_id.clone(),
+            user_id.clone(),
+        );
+        self.create_channel_subscription(
+            &chat_name,
+            clear_user_messages,
+            &transport,
+            client,
+            token,
+        )
+        .await?;
+
+        let message_delete = eventsub::channel::chat::ChannelChatMessageDeleteV1::new(
+            chat_id.clone(),
+            user_id.clone(),
+        );
+        self.create_channel_subscription(&chat_name, message_delete, &transport, client, token)
+            .await?;
+
+        if self.moderates(&chat_id, client, token).await {
+            self.create_moderator_subscriptions(chat_id, &chat_name, &transport, client, token)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the token's user may receive moderator-only events for `chat_id`.
+    async fn moderates(
+        &self,
+        chat_id: &UserId,
+        client: &HelixClient<'static, reqwest::Client>,
+        token: &UserToken,
+    ) -> bool {
+        if token.user_id == *chat_id {
+            return true;
+        }
+        match moderation::is_moderated_channel(client, token, chat_id.as_str()).await {
+            Ok(moderated) => moderated.unwrap_or(false),
+            Err(err) => {
+                warn!("EventSubManager - couldn't check moderator status for {chat_id}: {err}");
+                false
             }
-            Err(err) => self.handle_create_subscription_error(err)?,
         }
+    }
 
-        debug!(
-            "EventSubManager - creating ChannelChatNotificationV1: user_id={}, session_id={}",
-            chat_id, session_id
+    async fn create_moderator_subscriptions(
+        &self,
+        chat_id: UserId,
+        chat_name: &str,
+        transport: &eventsub::Transport,
+        client: &HelixClient<'static, reqwest::Client>,
+        token: &UserToken,
+    ) -> Result<(), Report> {
+        let user_id = token.user_id.clone();
+        let automod_hold =
+            eventsub::automod::message::AutomodMessageHoldV1::new(chat_id.clone(), user_id.clone());
+        self.create_optional_subscription(chat_name, automod_hold, transport, client, token)
+            .await?;
+
+        let automod_update = eventsub::automod::message::AutomodMessageUpdateV1::new(
+            chat_id.clone(),
+            user_id.clone(),
         );
-        let condition = eventsub::channel::chat::ChannelChatNotificationV1::new(
+        self.create_optional_subscription(chat_name, automod_update, transport, client, token)
+            .await?;
+
+        let unban_request =
+            eventsub::channel::ChannelUnbanRequestCreateV1::new(chat_id.clone(), user_id.clone());
+        self.create_optional_subscription(chat_name, unban_request, transport, client, token)
+            .await?;
+
+        let unban_resolve =
+            eventsub::channel::ChannelUnbanRequestResolveV1::new(chat_id.clone(), user_id.clone());
+        self.create_optional_subscription(chat_name, unban_resolve, transport, client, token)
+            .await?;
+
+        let moderate = eventsub::channel::ChannelModerateV2::new(chat_id.clone(), user_id.clone());
+        self.create_optional_subscription(chat_name, moderate, transport, client, token)
+            .await?;
+
+        let suspicious_message = eventsub::channel::ChannelSuspiciousUserMessageV1::new(
             chat_id.clone(),
             user_id.clone(),
         );
+        self.create_optional_subscription(chat_name, suspicious_message, transport, client, token)
+            .await?;
+
+        let suspicious_update =
+            eventsub::channel::ChannelSuspiciousUserUpdateV1::new(chat_id.clone(), user_id.clone());
+        self.create_optional_subscription(chat_name, suspicious_update, transport, client, token)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Like `create_channel_subscription`, but skipped when the token wasn't
+    /// granted the subscription's scope and only logged when Twitch refuses it,
+    /// so moderator extras never stop the chat itself from joining.
+    async fn create_optional_subscription<E>(
+        &self,
+        chat_name: &str,
+        subscription: E,
+        transport: &eventsub::Transport,
+        client: &HelixClient<'static, reqwest::Client>,
+        token: &UserToken,
+    ) -> Result<(), Report>
+    where
+        E: eventsub::EventSubscription + Send,
+    {
+        if !E::SCOPE.matches(token.scopes()) {
+            trace!(
+                "EventSubManager - skipping {:?} for {}: scope not granted",
+                E::EVENT_TYPE,
+                chat_name
+            );
+            return Ok(());
+        }
+        if self.has_subscription_type(chat_name, &E::EVENT_TYPE) {
+            return Ok(());
+        }
+        match self
+            .create_channel_subscription(chat_name, subscription, transport, client, token)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let err_msg = format!("{:?}", err);
+                match classify_error_text(&err_msg) {
+                    EventSubFailure::AuthFailed | EventSubFailure::StaleSession => Err(err),
+                    _ => {
+                        warn!(
+                            "EventSubManager - couldn't create {:?} for {}: {}",
+                            E::EVENT_TYPE,
+                            chat_name,
+                            err_msg
+                        );
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    async fn create_channel_subscription<E>(
+        &self,
+        chat_name: &str,
+        subscription: E,
+        transport: &eventsub::Transport,
+        client: &HelixClient<'static, reqwest::Client>,
+        token: &UserToken,
+    ) -> Result<(), Report>
+    where
+        E: eventsub::EventSubscription + Send,
+    {
+        trace!(
+            "EventSubManager - creating {:?} for {}",
+            E::EVENT_TYPE,
+            chat_name
+        );
         match client
-            .create_eventsub_subscription(condition.clone(), transport.clone(), &token.clone())
+            .create_eventsub_subscription(subscription, transport.clone(), token)
             .await
         {
             Ok(resp) => {
                 self.add_subscription(
-                    chat_name.clone(),
+                    chat_name.to_string(),
                     EventSubSubscription {
-                        channel_name: chat_name.clone(),
+                        channel_name: chat_name.to_string(),
                         id: resp.id.clone(),
                         sub_type: resp.type_.clone(),
                     },
                 );
+                Ok(())
             }
-            Err(err) => self.handle_create_subscription_error(err)?,
+            Err(err) => self.handle_create_subscription_error(err),
         }
-
-        Ok(())
     }
 
     fn handle_create_subscription_error<E>(&self, err: E) -> Result<(), Report>
@@ -454,7 +630,7 @@ impl EventSubManager {
         token_manager: TokenManager,
         settings_reader: EventSubSettingsReader,
     ) -> Result<EventSubRuntime, Report> {
-        let (std_tx, std_rx) = std::sync::mpsc::sync_channel::<EventSubMessage>(32);
+        let (events_tx, events_rx, queue) = eventqueue::channel(settings_reader.clone());
         let mut handles = Vec::new();
 
         #[cfg(debug_assertions)]
@@ -538,7 +714,7 @@ impl EventSubManager {
                         .clone()
                         .process_socket_read(
                             next_socket_read(&mut s, settings).await,
-                            std_tx.clone(),
+                            events_tx.clone(),
                             &client,
                             token_manager.clone(),
                             ConnectionMode::Fresh,
@@ -553,7 +729,7 @@ impl EventSubManager {
                                 .complete_reconnect_handoff(
                                     reconnect_url,
                                     &mut s,
-                                    std_tx.clone(),
+                                    events_tx.clone(),
                                     &client,
                                     token_manager.clone(),
                                     settings_reader.clone(),
@@ -569,7 +745,7 @@ impl EventSubManager {
                                     error!("eventsub reconnect handoff failed - {}", err_msg);
                                     if classify_error_text(&err_msg) == EventSubFailure::AuthFailed
                                     {
-                                        let _ = std_tx.send(EventSubMessage::AuthFailed(
+                                        events_tx.send(EventSubMessage::AuthFailed(
                                             "token expired or revoked".into(),
                                         ));
                                         return;
@@ -585,7 +761,7 @@ impl EventSubManager {
                         }
                         SocketAction::AuthFailed(reason) => {
                             warn!("auth failure detected, stopping EventSub reconnect loop");
-                            let _ = std_tx.send(EventSubMessage::AuthFailed(reason));
+                            events_tx.send(EventSubMessage::AuthFailed(reason));
                             return;
                         }
                     }
@@ -600,7 +776,8 @@ impl EventSubManager {
         handles.push(websocket_handle);
 
         Ok(EventSubRuntime {
-            events: std_rx,
+            events: events_rx,
+            queue,
             handles,
         })
     }
@@ -608,7 +785,7 @@ impl EventSubManager {
     async fn process_socket_read(
         self,
         read: SocketRead,
-        ts: SyncSender<EventSubMessage>,
+        ts: EventSender,
         client: &HelixClient<'static, reqwest::Client>,
         token_manager: TokenManager,
         mode: ConnectionMode,
@@ -680,7 +857,7 @@ impl EventSubManager {
         self,
         reconnect_url: String,
         old_socket: &mut EventSubSocket,
-        ts: SyncSender<EventSubMessage>,
+        ts: EventSender,
         client: &HelixClient<'static, reqwest::Client>,
         token_manager: TokenManager,
         settings_reader: EventSubSettingsReader,
@@ -733,7 +910,7 @@ impl EventSubManager {
     async fn process_old_socket_during_handoff(
         self,
         read: SocketRead,
-        ts: SyncSender<EventSubMessage>,
+        ts: EventSender,
         client: &HelixClient<'static, reqwest::Client>,
         token_manager: TokenManager,
         eventsub_settings: EventSubSettings,
@@ -793,7 +970,7 @@ impl EventSubManager {
     async fn process_message(
         self,
         msg: tungstenite::Message,
-        ts: SyncSender<EventSubMessage>,
+        ts: EventSender,
         client: &HelixClient<'static, reqwest::Client>,
         token_manager: TokenManager,
         mode: ConnectionMode,
@@ -847,7 +1024,7 @@ impl EventSubManager {
                         ts.send(EventSubMessage::Notification(EventNotification {
                             ts: metadata.message_timestamp.into_owned(),
                             event: payload,
-                        }))?;
+                        }));
                         Ok(SocketAction::Continue)
                     }
                     re @ EventsubWebsocketData::Revocation { .. } => {
@@ -935,6 +1112,75 @@ impl EventSubManager {
 
         *self.user_update_subscription_id.lock().unwrap() = Some(resp.id.clone());
 
+        self.create_whisper_subscription(&transport, client, token)
+            .await
+    }
+
+    async fn create_whisper_subscription(
+        &self,
+        transport: &eventsub::Transport,
+        client: &HelixClient<'static, reqwest::Client>,
+        token: &UserToken,
+    ) -> Result<(), Report> {
+        // Whispers are opt-in, so most tokens can't subscribe.
+        if !eventsub::user::UserWhisperMessageV1::SCOPE.matches(token.scopes()) {
+            trace!("EventSubManager - skipping whispers: scope not granted");
+            return Ok(());
+        }
+        if self.whisper_subscription_id.lock().unwrap().is_some() {
+            return Ok(());
+        }
+        match client
+            .create_eventsub_subscription(
+                eventsub::user::UserWhisperMessageV1::new(token.user_id.clone()),
+                transport.clone(),
+                token,
+            )
+            .await
+        {
+            Ok(resp) => *self.whisper_subscription_id.lock().unwrap() = Some(resp.id),
+            Err(err) => {
+                let err_msg = format!("{:?}", err);
+                match classify_error_text(&err_msg) {
+                    EventSubFailure::AuthFailed | EventSubFailure::StaleSession => {
+                        return self.handle_create_subscription_error(err)
+                    }
+                    _ => warn!(
+                        "EventSubManager - couldn't subscribe to whispers: {}",
+                        err_msg
+                    ),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates the subscriptions a scope upgrade unlocks on the live session:
+    /// whispers, and the moderator events of channels already joined. Without a
+    /// session the next welcome message creates them instead.
+    pub async fn subscribe_upgraded_scopes(
+        self,
+        client: &HelixClient<'static, reqwest::Client>,
+        token: UserToken,
+    ) -> Result<(), Report> {
+        let Some(session_id) = self.session_id() else {
+            return Ok(());
+        };
+        let transport = eventsub::Transport::websocket(&session_id);
+
+        self.create_whisper_subscription(&transport, client, &token)
+            .await?;
+
+        for (chat_name, chat_id) in self.desired_channels_snapshot() {
+            if !self.has_subscription(&chat_name) || !self.moderates(&chat_id, client, &token).await
+            {
+                continue;
+            }
+            self.create_moderator_subscriptions(chat_id, &chat_name, &transport, client, &token)
+                .await?;
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,406 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, RwLock},
+};
+
+use futures::TryStreamExt;
+use regex::{NoExpand, Regex};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use twitch_api::helix::users::GetUserBlockListRequest;
+use twitch_oauth2::UserToken;
+
+use crate::{
+    error::PepoError,
+    message::Fragment,
+    rule::{self, text_regex, word_regex, CompileCache, Rule},
+    types::{ChannelMessage, FilterSettings},
+};
+
+pub use crate::rule::validate;
+
+type HelixClient = twitch_api::HelixClient<'static, reqwest::Client>;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    /// Messages from the user whose login is `pattern`.
+    #[default]
+    IgnoreUser,
+    /// Messages whose text matches the regular expression `pattern`.
+    IgnorePattern,
+    /// Rewrites the word `pattern` to `replacement`, e.g. to blur spoilers.
+    ReplaceWord,
+    /// Messages from chatters with a badge from the `pattern` set.
+    HideBadge,
+}
+
+/// What happens to a message an ignore or badge rule catches.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Never reaches the chat view or the history.
+    #[default]
+    Drop,
+    /// Kept with `filtered` set, for the UI to show as a placeholder.
+    Collapse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(default)]
+pub struct FilterRule {
+    pub id: String,
+    pub enabled: bool,
+    pub kind: FilterKind,
+    pub pattern: String,
+    pub case_sensitive: bool,
+    /// Only used by `replace_word`.
+    pub replacement: String,
+    /// Ignored by `replace_word`.
+    pub action: FilterAction,
+    /// Channel logins the rule applies to; empty means every channel.
+    pub channels: Vec<String>,
+}
+
+impl Default for FilterRule {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            enabled: true,
+            kind: FilterKind::default(),
+            pattern: String::new(),
+            case_sensitive: false,
+            replacement: String::new(),
+            action: FilterAction::default(),
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl Rule for FilterRule {
+    const NOUN: &'static str = "filter";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Also lowercases logins.
+    fn normalized(mut self) -> Option<Self> {
+        self.id = self.id.trim().to_owned();
+        self.pattern = match self.kind {
+            FilterKind::IgnoreUser => self.pattern.trim().trim_start_matches('@').to_lowercase(),
+            FilterKind::HideBadge => self.pattern.trim().to_lowercase(),
+            FilterKind::IgnorePattern | FilterKind::ReplaceWord => self.pattern.trim().to_owned(),
+        };
+        self.channels = rule::normalize_channels(self.channels);
+        if self.id.is_empty() || self.pattern.is_empty() {
+            return None;
+        }
+        Some(self)
+    }
+
+    fn regex(&self) -> Option<(&str, bool)> {
+        (self.kind == FilterKind::IgnorePattern).then_some((&self.pattern, self.case_sensitive))
+    }
+}
+
+/// Why a message was collapsed. `rule_id` is unset for the Twitch block list.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct FilterReason {
+    pub rule_id: Option<String>,
+    pub reason: String,
+}
+
+enum Matcher {
+    User(String),
+    Pattern(Regex),
+    Replace(Regex, String),
+    Badge(String),
+}
+
+struct CompiledRule {
+    rule: FilterRule,
+    matcher: Matcher,
+}
+
+/// Enabled rules compiled in their saved order.
+pub struct FilterSet {
+    rules: Vec<CompiledRule>,
+}
+
+impl FilterSet {
+    /// Rules that no longer compile are skipped; saving through
+    /// [`validate`] keeps them out in the first place.
+    pub fn compile(rules: &[FilterRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| rule.clone().normalized())
+            .filter_map(|rule| {
+                let matcher = match rule.kind {
+                    FilterKind::IgnoreUser => Ok(Matcher::User(rule.pattern.clone())),
+                    FilterKind::HideBadge => Ok(Matcher::Badge(rule.pattern.clone())),
+                    FilterKind::IgnorePattern => {
+                        text_regex(&rule.pattern, rule.case_sensitive).map(Matcher::Pattern)
+                    }
+                    FilterKind::ReplaceWord => word_regex(&rule.pattern, rule.case_sensitive)
+                        .map(|regex| Matcher::Replace(regex, rule.replacement.clone())),
+                };
+                match matcher {
+                    Ok(matcher) => Some(CompiledRule { rule, matcher }),
+                    Err(err) => {
+                        warn!(rule = rule.id, "skipping filter rule: {}", err);
+                        None
+                    }
+                }
+            })
+            .collect();
+        FilterSet { rules }
+    }
+
+    /// Runs the rules over `message`; `false` means it should be dropped.
+    /// A rule that drops wins over one that only collapses.
+    fn apply(&self, message: &mut ChannelMessage, blocked: Option<FilterAction>) -> bool {
+        let channel_login = message.broadcaster_user_login.to_lowercase();
+        let chatter_login = message.chatter_user_login.to_lowercase();
+        let mut collapsed = match blocked {
+            Some(FilterAction::Drop) => return false,
+            Some(FilterAction::Collapse) => Some(FilterReason {
+                rule_id: None,
+                reason: "blocked user".to_owned(),
+            }),
+            None => None,
+        };
+
+        for compiled in &self.rules {
+            if !rule::applies_to(&compiled.rule.channels, &channel_login) {
+                continue;
+            }
+            let reason = match &compiled.matcher {
+                Matcher::User(login) if *login == chatter_login => {
+                    format!("ignored user {login}")
+                }
+                Matcher::Pattern(regex) if regex.is_match(&message.text) => {
+                    "ignored pattern".to_owned()
+                }
+                Matcher::Badge(set_id)
+                    if message
+                        .badges
+                        .iter()
+                        .any(|badge| badge.set_id.eq_ignore_ascii_case(set_id)) =>
+                {
+                    format!("hidden badge {set_id}")
+                }
+                Matcher::Replace(regex, replacement) => {
+                    replace_text(message, regex, replacement);
+                    continue;
+                }
+                _ => continue,
+            };
+            match compiled.rule.action {
+                FilterAction::Drop => return false,
+                FilterAction::Collapse => {
+                    collapsed.get_or_insert(FilterReason {
+                        rule_id: Some(compiled.rule.id.clone()),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        message.filtered = collapsed;
+        true
+    }
+}
+
+/// Emotes are left alone; only text fragments are rewritten.
+fn replace_text(message: &mut ChannelMessage, regex: &Regex, replacement: &str) {
+    if !regex.is_match(&message.text) {
+        return;
+    }
+    message.text = regex
+        .replace_all(&message.text, NoExpand(replacement))
+        .into_owned();
+    for fragment in &mut message.fragments {
+        if let Fragment::Text(text) = fragment {
+            text.text = regex
+                .replace_all(&text.text, NoExpand(replacement))
+                .into_owned();
+        }
+    }
+}
+
+/// The saved filters, compiled once per change, plus the account's Twitch
+/// block list when the user chose to honour it.
+#[derive(Clone, Default)]
+pub struct MessageFilters {
+    compiled: CompileCache<FilterRule, FilterSet>,
+    blocked_user_ids: Arc<RwLock<HashSet<String>>>,
+}
+
+impl MessageFilters {
+    /// Filters `message` in place; `false` means it should not be shown.
+    pub fn apply(&self, settings: &FilterSettings, message: &mut ChannelMessage) -> bool {
+        let blocked = (settings.honor_block_list
+            && self
+                .blocked_user_ids
+                .read()
+                .unwrap()
+                .contains(&message.chatter_user_id))
+        .then_some(settings.block_list_action);
+        self.compiled(&settings.rules).apply(message, blocked)
+    }
+
+    fn compiled(&self, rules: &[FilterRule]) -> Arc<FilterSet> {
+        self.compiled.get(rules, FilterSet::compile)
+    }
+
+    pub fn set_blocked_user_ids(&self, user_ids: HashSet<String>) {
+        *self.blocked_user_ids.write().unwrap() = user_ids;
+    }
+}
+
+/// The user IDs the account has blocked on Twitch.
+pub async fn fetch_blocked_user_ids(
+    client: &HelixClient,
+    token: &UserToken,
+) -> Result<HashSet<String>, PepoError> {
+    let mut request = GetUserBlockListRequest::broadcaster_id(token.user_id.as_str());
+    request.first = Some(100);
+    let blocks: Vec<_> = twitch_api::helix::make_stream(request, token, client, VecDeque::from)
+        .try_collect()
+        .await
+        .map_err(PepoError::from_client_error)?;
+    Ok(blocks
+        .into_iter()
+        .map(|block| block.user_id.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::TextFragment;
+
+    fn rule(id: &str, kind: FilterKind, pattern: &str, action: FilterAction) -> FilterRule {
+        FilterRule {
+            id: id.to_owned(),
+            kind,
+            pattern: pattern.to_owned(),
+            action,
+            ..Default::default()
+        }
+    }
+
+    fn message(channel: &str, chatter: &str, text: &str) -> ChannelMessage {
+        ChannelMessage {
+            chatter_user_id: "2".to_string(),
+            ..ChannelMessage::test("m1", channel, chatter, text)
+        }
+    }
+
+    fn settings(rules: Vec<FilterRule>) -> FilterSettings {
+        FilterSettings {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn drops_or_collapses_ignored_messages() {
+        let mut scoped = rule(
+            "bots",
+            FilterKind::IgnoreUser,
+            "@NightBot",
+            FilterAction::Drop,
+        );
+        scoped.channels = vec!["maya".to_owned()];
+        let settings = settings(vec![
+            scoped,
+            rule(
+                "links",
+                FilterKind::IgnorePattern,
+                r"https?://",
+                FilterAction::Collapse,
+            ),
+        ]);
+        let filters = MessageFilters::default();
+
+        assert!(!filters.apply(&settings, &mut message("maya", "nightbot", "hi")));
+        let mut elsewhere = message("luna", "nightbot", "hi");
+        assert!(filters.apply(&settings, &mut elsewhere));
+        assert_eq!(elsewhere.filtered, None);
+
+        let mut link = message("luna", "viewer", "see HTTPS://example.com");
+        assert!(filters.apply(&settings, &mut link));
+        assert_eq!(
+            link.filtered,
+            Some(FilterReason {
+                rule_id: Some("links".to_owned()),
+                reason: "ignored pattern".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn replaces_whole_words_in_text_fragments() {
+        let mut spoiler = rule(
+            "spoiler",
+            FilterKind::ReplaceWord,
+            "Snape",
+            FilterAction::Drop,
+        );
+        spoiler.replacement = "[spoiler]".to_owned();
+        let settings = settings(vec![spoiler]);
+        let mut message = message("maya", "viewer", "snape killed $0 Snapes, snape!");
+
+        assert!(MessageFilters::default().apply(&settings, &mut message));
+        assert_eq!(message.text, "[spoiler] killed $0 Snapes, [spoiler]!");
+        assert_eq!(
+            message.fragments,
+            vec![Fragment::Text(TextFragment {
+                index: 0,
+                text: "[spoiler] killed $0 Snapes, [spoiler]!".to_owned(),
+            })]
+        );
+        assert_eq!(message.filtered, None);
+    }
+
+    #[test]
+    fn honours_the_block_list_only_when_enabled() {
+        let filters = MessageFilters::default();
+        filters.set_blocked_user_ids(HashSet::from(["2".to_owned()]));
+        let mut settings = settings(Vec::new());
+
+        assert!(filters.apply(&settings, &mut message("maya", "viewer", "hi")));
+        settings.honor_block_list = true;
+        assert!(!filters.apply(&settings, &mut message("maya", "viewer", "hi")));
+        settings.block_list_action = FilterAction::Collapse;
+        let mut collapsed = message("maya", "viewer", "hi");
+        assert!(filters.apply(&settings, &mut collapsed));
+        assert_eq!(collapsed.filtered.unwrap().rule_id, None);
+    }
+
+    #[test]
+    fn validate_reports_the_broken_rule() {
+        let err = validate(vec![rule(
+            "broken",
+            FilterKind::IgnorePattern,
+            "[a-",
+            FilterAction::Drop,
+        )])
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            PepoError::InvalidInput { message }
+                if message.starts_with("filter rule \"broken\" has an invalid regex")
+        ));
+        assert!(validate(vec![rule(
+            "empty",
+            FilterKind::IgnoreUser,
+            "@",
+            FilterAction::Drop
+        )])
+        .is_err());
+    }
+}
@@ -0,0 +1,416 @@
+use std::sync::Arc;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    rule::{self, text_regex, word_regex, CompileCache, Rule},
+    types::ChannelMessage,
+};
+
+pub use crate::rule::validate;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightKind {
+    /// `pattern` appears anywhere in the text.
+    #[default]
+    Keyword,
+    /// `pattern` is a regular expression matched against the text.
+    Regex,
+    /// `pattern` appears as a word of its own.
+    WholeWord,
+    /// The chatter's login is `pattern`.
+    Username,
+    /// The chatter has a badge from the `pattern` set, e.g. `moderator` or `vip`.
+    Badge,
+    /// Twitch marked the message as the chatter's first in the channel.
+    FirstTimeChatter,
+    /// The text mentions the logged-in account.
+    SelfMention,
+}
+
+impl HighlightKind {
+    fn needs_pattern(self) -> bool {
+        !matches!(
+            self,
+            HighlightKind::FirstTimeChatter | HighlightKind::SelfMention
+        )
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(default)]
+pub struct HighlightRule {
+    pub id: String,
+    pub enabled: bool,
+    pub kind: HighlightKind,
+    pub pattern: String,
+    pub case_sensitive: bool,
+    /// CSS colour for matching messages.
+    pub color: String,
+    /// When several rules match, the highest priority picks the colour.
+    pub priority: i32,
+    /// Ask for a desktop notification when a live message matches.
+    pub notify: bool,
+    /// Channel logins the rule applies to; empty means every channel.
+    pub channels: Vec<String>,
+}
+
+impl Default for HighlightRule {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            enabled: true,
+            kind: HighlightKind::default(),
+            pattern: String::new(),
+            case_sensitive: false,
+            color: String::new(),
+            priority: 0,
+            notify: false,
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl Rule for HighlightRule {
+    const NOUN: &'static str = "highlight";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Also lowercases logins.
+    fn normalized(mut self) -> Option<Self> {
+        self.id = self.id.trim().to_owned();
+        self.color = self.color.trim().to_owned();
+        self.pattern = match self.kind {
+            HighlightKind::Username => self.pattern.trim().trim_start_matches('@').to_lowercase(),
+            HighlightKind::Badge => self.pattern.trim().to_lowercase(),
+            HighlightKind::FirstTimeChatter | HighlightKind::SelfMention => String::new(),
+            _ => self.pattern.trim().to_owned(),
+        };
+        self.channels = rule::normalize_channels(self.channels);
+        if self.id.is_empty() || (self.kind.needs_pattern() && self.pattern.is_empty()) {
+            return None;
+        }
+        Some(self)
+    }
+
+    fn regex(&self) -> Option<(&str, bool)> {
+        (self.kind == HighlightKind::Regex).then_some((&self.pattern, self.case_sensitive))
+    }
+}
+
+/// The matching rules of a message, attached as `ChannelMessage::highlight`.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct MessageHighlight {
+    /// Highest priority first.
+    pub rule_ids: Vec<String>,
+    pub color: String,
+    pub notify: bool,
+}
+
+/// Emitted on `highlight_notification` when a live message matches a rule
+/// with `notify` set.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct HighlightNotification {
+    pub channel_login: String,
+    pub chatter_user_name: String,
+    pub message_id: String,
+    pub text: String,
+    pub rule_ids: Vec<String>,
+}
+
+impl HighlightNotification {
+    /// Messages a filter collapsed stay quiet.
+    pub fn for_message(message: &ChannelMessage) -> Option<Self> {
+        if message.filtered.is_some() {
+            return None;
+        }
+        let highlight = message
+            .highlight
+            .as_ref()
+            .filter(|highlight| highlight.notify)?;
+        Some(HighlightNotification {
+            channel_login: message.broadcaster_user_login.clone(),
+            chatter_user_name: message.chatter_user_name.clone(),
+            message_id: message.message_id.clone(),
+            text: message.text.clone(),
+            rule_ids: highlight.rule_ids.clone(),
+        })
+    }
+}
+
+/// What the rules look at in a message.
+pub struct HighlightInput<'a> {
+    pub channel_login: &'a str,
+    pub chatter_login: &'a str,
+    pub text: &'a str,
+    pub badge_set_ids: &'a [String],
+    pub first_message: bool,
+}
+
+enum Matcher {
+    Text(Regex),
+    Username(String),
+    Badge(String),
+    FirstTimeChatter,
+    SelfMention,
+}
+
+struct CompiledRule {
+    rule: HighlightRule,
+    matcher: Matcher,
+}
+
+/// Enabled rules compiled for one account, highest priority first.
+pub struct HighlightSet {
+    self_login: String,
+    mention: Option<Regex>,
+    rules: Vec<CompiledRule>,
+}
+
+impl HighlightSet {
+    /// Rules that no longer compile are skipped; saving through
+    /// [`validate`] keeps them out in the first place.
+    pub fn compile(rules: &[HighlightRule], self_login: &str) -> Self {
+        let self_login = self_login.trim().to_lowercase();
+        let mut compiled: Vec<_> = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| rule.clone().normalized())
+            .filter_map(|rule| {
+                let matcher = match rule.kind {
+                    HighlightKind::Keyword => {
+                        text_regex(&regex::escape(&rule.pattern), rule.case_sensitive)
+                            .map(Matcher::Text)
+                    }
+                    HighlightKind::Regex => {
+                        text_regex(&rule.pattern, rule.case_sensitive).map(Matcher::Text)
+                    }
+                    HighlightKind::WholeWord => {
+                        word_regex(&rule.pattern, rule.case_sensitive).map(Matcher::Text)
+                    }
+                    HighlightKind::Username => Ok(Matcher::Username(rule.pattern.clone())),
+                    HighlightKind::Badge => Ok(Matcher::Badge(rule.pattern.clone())),
+                    HighlightKind::FirstTimeChatter => Ok(Matcher::FirstTimeChatter),
+                    HighlightKind::SelfMention => Ok(Matcher::SelfMention),
+                };
+                match matcher {
+                    Ok(matcher) => Some(CompiledRule { rule, matcher }),
+                    Err(err) => {
+                        warn!(rule = rule.id, "skipping highlight rule: {}", err);
+                        None
+                    }
+                }
+            })
+            .collect();
+        compiled.sort_by(|a, b| b.rule.priority.cmp(&a.rule.priority));
+
+        let mention = (!self_login.is_empty())
+            .then(|| {
+                text_regex(
+                    &format!(r"(?:^|\W)@?{}(?:\W|$)", regex::escape(&self_login)),
+                    false,
+                )
+            })
+            .and_then(Result::ok);
+        HighlightSet {
+            self_login,
+            mention,
+            rules: compiled,
+        }
+    }
+
+    pub fn evaluate(&self, input: &HighlightInput) -> Option<MessageHighlight> {
+        let channel_login = input.channel_login.to_lowercase();
+        let chatter_login = input.chatter_login.to_lowercase();
+        let matched: Vec<_> = self
+            .rules
+            .iter()
+            .filter(|compiled| rule::applies_to(&compiled.rule.channels, &channel_login))
+            .filter(|compiled| match &compiled.matcher {
+                Matcher::Text(regex) => regex.is_match(input.text),
+                Matcher::Username(login) => *login == chatter_login,
+                Matcher::Badge(set_id) => input
+                    .badge_set_ids
+                    .iter()
+                    .any(|badge| badge.eq_ignore_ascii_case(set_id)),
+                Matcher::FirstTimeChatter => input.first_message,
+                // Quoting yourself isn't a mention.
+                Matcher::SelfMention => {
+                    chatter_login != self.self_login
+                        && self
+                            .mention
+                            .as_ref()
+                            .is_some_and(|mention| mention.is_match(input.text))
+                }
+            })
+            .map(|compiled| &compiled.rule)
+            .collect();
+        let top = matched.first()?;
+        Some(MessageHighlight {
+            rule_ids: matched.iter().map(|rule| rule.id.clone()).collect(),
+            color: top.color.clone(),
+            notify: matched.iter().any(|rule| rule.notify),
+        })
+    }
+}
+
+/// Compiles the saved rules once and again only after they change.
+#[derive(Clone)]
+pub struct Highlighter {
+    self_login: String,
+    cache: CompileCache<HighlightRule, HighlightSet>,
+}
+
+impl Highlighter {
+    pub fn new(self_login: impl Into<String>) -> Self {
+        Highlighter {
+            self_login: self_login.into(),
+            cache: Default::default(),
+        }
+    }
+
+    pub fn compiled(&self, rules: &[HighlightRule]) -> Arc<HighlightSet> {
+        self.cache.get(rules, |rules| {
+            HighlightSet::compile(rules, &self.self_login)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PepoError;
+
+    fn rule(id: &str, kind: HighlightKind, pattern: &str, priority: i32) -> HighlightRule {
+        HighlightRule {
+            id: id.to_owned(),
+            kind,
+            pattern: pattern.to_owned(),
+            color: format!("#{id}"),
+            priority,
+            ..Default::default()
+        }
+    }
+
+    fn input<'a>(channel: &'a str, chatter: &'a str, text: &'a str) -> HighlightInput<'a> {
+        HighlightInput {
+            channel_login: channel,
+            chatter_login: chatter,
+            text,
+            badge_set_ids: &[],
+            first_message: false,
+        }
+    }
+
+    #[test]
+    fn highest_priority_match_picks_the_colour() {
+        let mut scoped = rule("scoped", HighlightKind::Keyword, "pog", 10);
+        scoped.channels = vec!["#Luna".to_owned()];
+        scoped.notify = true;
+        let set = HighlightSet::compile(
+            &[
+                rule("word", HighlightKind::WholeWord, "gg", 1),
+                rule("regex", HighlightKind::Regex, r"^\d+$", 2),
+                rule("user", HighlightKind::Username, "@Maya", 5),
+                scoped,
+            ],
+            "pepo",
+        );
+
+        let matched = set.evaluate(&input("luna", "maya", "POGGERS gg")).unwrap();
+        assert_eq!(matched.rule_ids, ["scoped", "user", "word"]);
+        assert_eq!(matched.color, "#scoped");
+        assert!(matched.notify);
+
+        assert!(set.evaluate(&input("maya", "viewer", "ggwp 42")).is_none());
+        assert_eq!(
+            set.evaluate(&input("maya", "viewer", "42"))
+                .unwrap()
+                .rule_ids,
+            ["regex"]
+        );
+    }
+
+    #[test]
+    fn matches_badges_first_messages_and_mentions() {
+        let set = HighlightSet::compile(
+            &[
+                rule("vip", HighlightKind::Badge, "VIP", 0),
+                rule("first", HighlightKind::FirstTimeChatter, "", 0),
+                rule("me", HighlightKind::SelfMention, "", 0),
+            ],
+            "Pepo",
+        );
+        let badges = ["vip".to_owned()];
+
+        let mut message = input("maya", "viewer", "hi");
+        message.badge_set_ids = &badges;
+        message.first_message = true;
+        assert_eq!(set.evaluate(&message).unwrap().rule_ids, ["vip", "first"]);
+
+        assert!(set
+            .evaluate(&input("maya", "viewer", "hey @pepo!"))
+            .is_some());
+        assert!(set.evaluate(&input("maya", "viewer", "PEPO hi")).is_some());
+        assert!(set.evaluate(&input("maya", "viewer", "pepojam")).is_none());
+        assert!(set.evaluate(&input("maya", "pepo", "@pepo")).is_none());
+    }
+
+    #[test]
+    fn notifies_only_for_shown_messages_with_notifying_rules() {
+        let mut loud = rule("loud", HighlightKind::Keyword, "pog", 0);
+        loud.notify = true;
+        let set = HighlightSet::compile(
+            &[loud, rule("quiet", HighlightKind::Keyword, "gg", 0)],
+            "pepo",
+        );
+        let mut message = ChannelMessage::test("m1", "luna", "maya", "pog gg");
+        message.highlight = set.evaluate(&input("luna", "maya", &message.text));
+
+        let notification = HighlightNotification::for_message(&message).unwrap();
+        assert_eq!(notification.rule_ids, ["loud", "quiet"]);
+        assert_eq!(notification.channel_login, "luna");
+
+        message.filtered = Some(crate::filter::FilterReason {
+            rule_id: None,
+            reason: "collapsed".to_owned(),
+        });
+        assert!(HighlightNotification::for_message(&message).is_none());
+
+        message.filtered = None;
+        message.highlight = set.evaluate(&input("luna", "maya", "gg"));
+        assert!(HighlightNotification::for_message(&message).is_none());
+    }
+
+    #[test]
+    fn validate_reports_the_broken_rule() {
+        let err = validate(vec![
+            rule("ok", HighlightKind::Keyword, "hi", 0),
+            rule("broken", HighlightKind::Regex, "(unclosed", 0),
+        ])
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            PepoError::InvalidInput { message }
+                if message.starts_with("highlight rule \"broken\" has an invalid regex")
+        ));
+
+        assert!(validate(vec![
+            rule("dup", HighlightKind::Keyword, "a", 0),
+            rule(" dup ", HighlightKind::Keyword, "b", 0),
+        ])
+        .is_err());
+        assert!(validate(vec![rule("empty", HighlightKind::Username, " @ ", 0)]).is_err());
+        assert_eq!(
+            validate(vec![rule("me", HighlightKind::SelfMention, "", 0)])
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,854 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::WrapErr;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
+
+use crate::{
+    modlog::{self, AuditColumns, ModLogEntry},
+    search::SearchColumns,
+    types::{AppearanceTheme, ChannelMessage, HistorySettings},
+};
+
+pub const HISTORY_FILE: &str = "chat-history.sqlite3";
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1_000;
+const SCHEMA_VERSION: i64 = 1;
+/// Age and global-cap pruning scan the whole table, so they run on this
+/// cadence instead of after every batch.
+const GLOBAL_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Messages keep search, chatter and audit columns next to the payload so
+/// filters don't need to decode it. The full-text index is kept in sync by
+/// triggers so rows are searchable as soon as they're committed.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY,
+    channel_login TEXT NOT NULL,
+    broadcaster_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    message_id TEXT,
+    sent_at_ms INTEGER NOT NULL,
+    payload TEXT NOT NULL,
+    text TEXT,
+    chatter_login TEXT,
+    chatter_id TEXT,
+    has_emote INTEGER NOT NULL DEFAULT 0,
+    has_link INTEGER NOT NULL DEFAULT 0,
+    badges TEXT NOT NULL DEFAULT '',
+    action TEXT,
+    moderator_id TEXT,
+    moderator_login TEXT,
+    target_id TEXT,
+    target_login TEXT
+);
+CREATE INDEX IF NOT EXISTS history_channel_time ON history (channel_login, sent_at_ms, id);
+CREATE INDEX IF NOT EXISTS history_time ON history (sent_at_ms, id);
+CREATE UNIQUE INDEX IF NOT EXISTS history_message ON history (channel_login, kind, message_id);
+CREATE INDEX IF NOT EXISTS history_chatter ON history (chatter_login, sent_at_ms, id);
+CREATE INDEX IF NOT EXISTS history_channel_chatter ON history (broadcaster_id, chatter_id, sent_at_ms, id);
+CREATE INDEX IF NOT EXISTS history_mod_actions ON history (broadcaster_id, kind, sent_at_ms, id);
+CREATE VIRTUAL TABLE IF NOT EXISTS history_fts USING fts5(text, content='history', content_rowid='id');
+CREATE TRIGGER IF NOT EXISTS history_fts_insert AFTER INSERT ON history WHEN new.text IS NOT NULL BEGIN
+    INSERT INTO history_fts (rowid, text) VALUES (new.id, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS history_fts_delete AFTER DELETE ON history WHEN old.text IS NOT NULL BEGIN
+    INSERT INTO history_fts (history_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+";
+
+pub type HistorySettingsReader = Arc<dyn Fn() -> HistorySettings + Send + Sync>;
+
+/// Everything the history database records for a channel.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HistoryEntry {
+    Message(ChannelMessage),
+    Notice(ChannelNotice),
+    Moderation(ModerationEvent),
+    /// A `channel.moderate` event, only delivered in channels we moderate.
+    ModAction(ModLogEntry),
+}
+
+impl HistoryEntry {
+    fn kind(&self) -> &'static str {
+        match self {
+            HistoryEntry::Message(_) => "message",
+            HistoryEntry::Notice(_) => "notice",
+            HistoryEntry::Moderation(_) => "moderation",
+            HistoryEntry::ModAction(_) => "mod_action",
+        }
+    }
+
+    pub fn channel_login(&self) -> &str {
+        match self {
+            HistoryEntry::Message(message) => &message.broadcaster_user_login,
+            HistoryEntry::Notice(notice) => &notice.broadcaster_user_login,
+            HistoryEntry::Moderation(event) => &event.broadcaster_user_login,
+            HistoryEntry::ModAction(entry) => &entry.broadcaster_user_login,
+        }
+    }
+
+    fn broadcaster_id(&self) -> &str {
+        match self {
+            HistoryEntry::Message(message) => &message.broadcaster_user_id,
+            HistoryEntry::Notice(notice) => &notice.broadcaster_user_id,
+            HistoryEntry::Moderation(event) => &event.broadcaster_user_id,
+            HistoryEntry::ModAction(entry) => &entry.broadcaster_user_id,
+        }
+    }
+
+    /// Twitch's id for the event, used to drop duplicate deliveries.
+    /// Moderation events have none.
+    fn message_id(&self) -> Option<&str> {
+        match self {
+            HistoryEntry::Message(message) => Some(&message.message_id),
+            HistoryEntry::Notice(notice) => Some(&notice.message_id),
+            HistoryEntry::Moderation(_) | HistoryEntry::ModAction(_) => None,
+        }
+    }
+
+    /// Stored messages carry the index they had when received; hand out a new
+    /// one so they can't collide with live messages in the UI.
+    fn with_fresh_index(self) -> Self {
+        match self {
+            HistoryEntry::Message(message) => HistoryEntry::Message(message.with_fresh_index()),
+            other => other,
+        }
+    }
+}
+
+/// A `channel.chat.notification` event: subs, raids, announcements and the like.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct ChannelNotice {
+    pub ts: String,
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    /// Empty when the notice was triggered anonymously.
+    pub chatter_user_id: String,
+    pub chatter_user_name: String,
+    pub message_id: String,
+    /// Twitch's `notice_type`, e.g. `sub`, `raid` or `announcement`.
+    pub notice_type: String,
+    pub system_message: String,
+    /// The chatter's own message, if they attached one.
+    pub text: String,
+}
+
+impl ChannelNotice {
+    pub fn new(
+        value: &twitch_api::eventsub::channel::ChannelChatNotificationV1Payload,
+        ts: String,
+    ) -> Self {
+        use twitch_api::eventsub::channel::chat::notification::Chatter;
+
+        let (chatter_user_id, chatter_user_name) = match &value.chatter {
+            Chatter::Chatter {
+                chatter_user_id,
+                chatter_user_name,
+                ..
+            } => (chatter_user_id.to_string(), chatter_user_name.to_string()),
+            _ => (String::new(), String::new()),
+        };
+        let notice_type = serde_json::to_value(&value.notification)
+            .ok()
+            .and_then(|notification| {
+                notification
+                    .get("notice_type")
+                    .and_then(|notice_type| notice_type.as_str())
+                    .map(ToOwned::to_owned)
+            })
+            .unwrap_or_default();
+
+        ChannelNotice {
+            ts,
+            broadcaster_user_id: value.broadcaster_user_id.to_string(),
+            broadcaster_user_login: value.broadcaster_user_login.to_string(),
+            chatter_user_id,
+            chatter_user_name,
+            message_id: value.message_id.to_string(),
+            notice_type,
+            system_message: value.system_message.clone(),
+            text: value.message.text.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct ModerationEvent {
+    pub ts: String,
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    pub action: ModerationAction,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// A moderator cleared the whole chat.
+    ClearChat,
+    /// A user's messages were purged, e.g. by a ban or timeout.
+    ClearUserMessages {
+        target_user_id: String,
+        target_user_login: String,
+    },
+    DeleteMessage {
+        target_user_id: String,
+        target_user_login: String,
+        message_id: String,
+    },
+}
+
+impl ModerationEvent {
+    pub fn clear_chat(
+        value: &twitch_api::eventsub::channel::ChannelChatClearV1Payload,
+        ts: String,
+    ) -> Self {
+        ModerationEvent {
+            ts,
+            broadcaster_user_id: value.broadcaster_user_id.to_string(),
+            broadcaster_user_login: value.broadcaster_user_login.to_string(),
+            action: ModerationAction::ClearChat,
+        }
+    }
+
+    pub fn clear_user_messages(
+        value: &twitch_api::eventsub::channel::ChannelChatClearUserMessagesV1Payload,
+        ts: String,
+    ) -> Self {
+        ModerationEvent {
+            ts,
+            broadcaster_user_id: value.broadcaster_user_id.to_string(),
+            broadcaster_user_login: value.broadcaster_user_login.to_string(),
+            action: ModerationAction::ClearUserMessages {
+                target_user_id: value.target_user_id.to_string(),
+                target_user_login: value.target_user_login.to_string(),
+            },
+        }
+    }
+
+    pub fn delete_message(
+        value: &twitch_api::eventsub::channel::ChannelChatMessageDeleteV1Payload,
+        ts: String,
+    ) -> Self {
+        ModerationEvent {
+            ts,
+            broadcaster_user_id: value.broadcaster_user_id.to_string(),
+            broadcaster_user_login: value.broadcaster_user_login.to_string(),
+            action: ModerationAction::DeleteMessage {
+                target_user_id: value.target_user_id.to_string(),
+                target_user_login: value.target_user_login.to_string(),
+                message_id: value.message_id.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct HistoryRecord {
+    /// Row id; pass it back as `before` to page further into the past.
+    pub id: i64,
+    pub sent_at_ms: i64,
+    pub entry: HistoryEntry,
+}
+
+impl HistoryRecord {
+    pub fn with_emote_theme(mut self, theme: &AppearanceTheme) -> Self {
+        if let HistoryEntry::Message(message) = self.entry {
+            self.entry = HistoryEntry::Message(message.with_emote_theme(theme));
+        }
+        self
+    }
+}
+
+struct PendingEntry {
+    sent_at_ms: i64,
+    entry: HistoryEntry,
+}
+
+enum WriterCommand {
+    Record(PendingEntry),
+    Flush(mpsc::Sender<()>),
+}
+
+/// On-disk chat history. Writes are queued to a dedicated thread that commits
+/// them in batches, so recording never blocks the EventSub loop on disk I/O.
+#[derive(Clone)]
+pub struct HistoryStore {
+    writer: mpsc::Sender<WriterCommand>,
+    reader: Arc<Mutex<Connection>>,
+    settings: HistorySettingsReader,
+}
+
+impl HistoryStore {
+    pub fn open(path: &Path, settings: HistorySettingsReader) -> eyre::Result<Self> {
+        let conn = open_connection(path)?;
+        migrate(&conn)?;
+        let reader = open_connection(path)?;
+
+        let (writer, commands) = mpsc::channel();
+        let writer_settings = settings.clone();
+        std::thread::Builder::new()
+            .name("chat-history-writer".to_string())
+            .spawn(move || run_writer(conn, commands, writer_settings))
+            .wrap_err("failed to start chat history writer")?;
+
+        Ok(Self {
+            writer,
+            reader: Arc::new(Mutex::new(reader)),
+            settings,
+        })
+    }
+
+    /// Queues `entry` with the current time as its send time.
+    pub fn record(&self, entry: HistoryEntry) {
+        self.record_at(entry, unix_now_ms());
+    }
+
+    pub fn record_at(&self, entry: HistoryEntry, sent_at_ms: i64) {
+        if !(self.settings)().enabled {
+            return;
+        }
+        let command = WriterCommand::Record(PendingEntry { sent_at_ms, entry });
+        if self.writer.send(command).is_err() {
+            warn!("chat history writer stopped; dropping entry");
+        }
+    }
+
+    /// Blocks until everything queued so far is committed.
+    pub fn flush(&self) {
+        let (ack, done) = mpsc::channel();
+        if self.writer.send(WriterCommand::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+
+    /// Up to `limit` entries for `channel_login` older than the record `before`
+    /// (or the newest ones), oldest first.
+    pub fn channel_history(
+        &self,
+        channel_login: &str,
+        before: Option<i64>,
+        limit: usize,
+    ) -> eyre::Result<Vec<HistoryRecord>> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let conn = self.reader.lock().unwrap();
+        let mut statement = conn.prepare_cached(
+            "SELECT id, sent_at_ms, payload FROM history
+             WHERE channel_login = ?1
+               AND (?2 IS NULL OR (sent_at_ms, id) < (SELECT sent_at_ms, id FROM history WHERE id = ?2))
+             ORDER BY sent_at_ms DESC, id DESC
+             LIMIT ?3",
+        )?;
+        let rows = statement.query_map(params![channel_login, before, limit as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            let (id, sent_at_ms, payload) = row?;
+            match serde_json::from_str::<HistoryEntry>(&payload) {
+                Ok(entry) => records.push(HistoryRecord {
+                    id,
+                    sent_at_ms,
+                    entry: entry.with_fresh_index(),
+                }),
+                Err(err) => warn!(id, "skipping undecodable chat history entry: {err}"),
+            }
+        }
+        records.reverse();
+        Ok(records)
+    }
+
+    /// The newest `limit` messages `chatter_id` sent in `broadcaster_id`'s
+    /// channel, oldest first.
+    pub fn chatter_messages(
+        &self,
+        broadcaster_id: &str,
+        chatter_id: &str,
+        limit: usize,
+    ) -> eyre::Result<Vec<ChannelMessage>> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let conn = self.reader.lock().unwrap();
+        let mut statement = conn.prepare_cached(
+            "SELECT id, payload FROM history
+             WHERE broadcaster_id = ?1 AND chatter_id = ?2 AND kind = 'message'
+             ORDER BY sent_at_ms DESC, id DESC
+             LIMIT ?3",
+        )?;
+        let rows = statement
+            .query_map(params![broadcaster_id, chatter_id, limit as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            let (id, payload) = row?;
+            match serde_json::from_str::<HistoryEntry>(&payload) {
+                Ok(HistoryEntry::Message(message)) => {
+                    messages.push(message.with_fresh_index());
+                }
+                Ok(_) => {}
+                Err(err) => warn!(id, "skipping undecodable chat history entry: {err}"),
+            }
+        }
+        messages.reverse();
+        Ok(messages)
+    }
+
+    pub(crate) fn with_reader<T>(&self, read: impl FnOnce(&Connection) -> T) -> T {
+        read(&self.reader.lock().unwrap())
+    }
+
+    /// Streams every entry for `channel_login` sent in `[from_ms, to_ms)` to
+    /// `visit`, oldest first, returning how many were visited.
+    pub fn for_each_between(
+        &self,
+        channel_login: &str,
+        from_ms: i64,
+        to_ms: i64,
+        mut visit: impl FnMut(HistoryRecord) -> eyre::Result<()>,
+    ) -> eyre::Result<usize> {
+        let conn = self.reader.lock().unwrap();
+        let mut statement = conn.prepare_cached(
+            "SELECT id, sent_at_ms, payload FROM history
+             WHERE channel_login = ?1 AND sent_at_ms >= ?2 AND sent_at_ms < ?3
+             ORDER BY sent_at_ms, id",
+        )?;
+        let mut rows = statement.query(params![channel_login, from_ms, to_ms])?;
+        let mut visited = 0;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let payload: String = row.get(2)?;
+            match serde_json::from_str::<HistoryEntry>(&payload) {
+                Ok(entry) => {
+                    visit(HistoryRecord {
+                        id,
+                        sent_at_ms: row.get(1)?,
+                        entry,
+                    })?;
+                    visited += 1;
+                }
+                Err(err) => warn!(id, "skipping undecodable chat history entry: {err}"),
+            }
+        }
+        Ok(visited)
+    }
+}
+
+fn open_connection(path: &Path) -> eyre::Result<Connection> {
+    let conn = Connection::open(path)
+        .wrap_err_with(|| format!("failed to open chat history at {}", path.display()))?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.busy_timeout(Duration::from_secs(5))?;
+    Ok(conn)
+}
+
+fn migrate(conn: &Connection) -> eyre::Result<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        warn!(
+            version,
+            expected = SCHEMA_VERSION,
+            "chat history was written by a newer version"
+        );
+    }
+    conn.execute_batch(SCHEMA)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    Ok(())
+}
+
+fn run_writer(
+    mut conn: Connection,
+    commands: mpsc::Receiver<WriterCommand>,
+    settings: HistorySettingsReader,
+) {
+    let mut last_global_prune: Option<Instant> = None;
+    while let Ok(first) = commands.recv() {
+        let settings = settings();
+        let mut batch = Vec::new();
+        let mut acks = Vec::new();
+        collect(first, &mut batch, &mut acks);
+
+        let deadline = Instant::now() + settings.write_flush_interval();
+        while acks.is_empty() && batch.len() < settings.write_batch_size {
+            match commands.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(command) => collect(command, &mut batch, &mut acks),
+                Err(_) => break,
+            }
+        }
+
+        if !batch.is_empty() {
+            let prune_global = last_global_prune
+                .map(|at| at.elapsed() >= GLOBAL_PRUNE_INTERVAL)
+                .unwrap_or(true);
+            match write_batch(&mut conn, &batch, &settings, prune_global, unix_now_ms()) {
+                Ok(written) => {
+                    debug!(
+                        queued = batch.len(),
+                        written, "committed chat history batch"
+                    );
+                    if prune_global {
+                        last_global_prune = Some(Instant::now());
+                    }
+                }
+                Err(err) => error!(count = batch.len(), "failed to write chat history: {err}"),
+            }
+        }
+        for ack in acks {
+            let _ = ack.send(());
+        }
+    }
+    debug!("chat history writer stopped");
+}
+
+fn collect(
+    command: WriterCommand,
+    batch: &mut Vec<PendingEntry>,
+    acks: &mut Vec<mpsc::Sender<()>>,
+) {
+    match command {
+        WriterCommand::Record(entry) => batch.push(entry),
+        WriterCommand::Flush(ack) => acks.push(ack),
+    }
+}
+
+fn write_batch(
+    conn: &mut Connection,
+    batch: &[PendingEntry],
+    settings: &HistorySettings,
+    prune_global: bool,
+    now_ms: i64,
+) -> eyre::Result<usize> {
+    let tx = conn.transaction()?;
+    let mut written = 0;
+    let mut channels = HashSet::new();
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT OR IGNORE INTO history
+                 (channel_login, broadcaster_id, kind, message_id, sent_at_ms, payload,
+                  text, chatter_login, has_emote, has_link, badges, chatter_id,
+                  action, moderator_id, moderator_login, target_id, target_login)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12,
+                     ?13, ?14, ?15, ?16, ?17)",
+        )?;
+        for pending in batch {
+            let entry = &pending.entry;
+            let channel_login = entry.channel_login().to_lowercase();
+            let payload = serde_json::to_string(entry)?;
+            let columns = match entry {
+                HistoryEntry::Message(message) => Some(SearchColumns::new(message)),
+                _ => None,
+            };
+            let audit = match entry {
+                HistoryEntry::ModAction(action) => Some(AuditColumns::new(action)),
+                _ => None,
+            };
+            written += insert.execute(params![
+                channel_login,
+                entry.broadcaster_id(),
+                entry.kind(),
+                entry.message_id(),
+                pending.sent_at_ms,
+                payload,
+                columns.as_ref().map(|columns| &columns.text),
+                columns.as_ref().map(|columns| &columns.chatter_login),
+                columns.as_ref().is_some_and(|columns| columns.has_emote),
+                columns.as_ref().is_some_and(|columns| columns.has_link),
+                columns
+                    .as_ref()
+                    .map(|columns| columns.badges.as_str())
+                    .unwrap_or_default(),
+                columns.as_ref().map(|columns| &columns.chatter_id),
+                audit.as_ref().map(|audit| audit.action),
+                audit.as_ref().map(|audit| &audit.moderator_id),
+                audit.as_ref().map(|audit| &audit.moderator_login),
+                audit.as_ref().and_then(|audit| audit.target_id.as_ref()),
+                audit.as_ref().and_then(|audit| audit.target_login.as_ref()),
+            ])?;
+            if let HistoryEntry::ModAction(action) = entry {
+                if let Some(annotation) = action.annotation() {
+                    modlog::annotate_history(
+                        &tx,
+                        &action.broadcaster_user_id,
+                        &annotation,
+                        pending.sent_at_ms,
+                    )?;
+                }
+            }
+            channels.insert(channel_login);
+        }
+    }
+
+    for channel_login in &channels {
+        prune_channel(&tx, channel_login, settings.max_entries_for(channel_login))?;
+    }
+    if prune_global {
+        prune_global_limits(&tx, settings, now_ms)?;
+    }
+    tx.commit()?;
+    Ok(written)
+}
+
+fn prune_channel(conn: &Connection, channel_login: &str, keep: usize) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM history WHERE id IN (
+             SELECT id FROM history WHERE channel_login = ?1
+             ORDER BY sent_at_ms DESC, id DESC
+             LIMIT -1 OFFSET ?2
+         )",
+        params![channel_login, keep as i64],
+    )
+}
+
+fn prune_global_limits(
+    conn: &Connection,
+    settings: &HistorySettings,
+    now_ms: i64,
+) -> rusqlite::Result<usize> {
+    let cutoff_ms = now_ms.saturating_sub(settings.retention().as_millis() as i64);
+    let expired = conn.execute(
+        "DELETE FROM history WHERE sent_at_ms < ?1",
+        params![cutoff_ms],
+    )?;
+    let over_cap = conn.execute(
+        "DELETE FROM history WHERE id IN (
+             SELECT id FROM history
+             ORDER BY sent_at_ms DESC, id DESC
+             LIMIT -1 OFFSET ?1
+         )",
+        params![settings.max_total_entries as i64],
+    )?;
+    if expired + over_cap > 0 {
+        debug!(expired, over_cap, "pruned chat history");
+    }
+    Ok(expired + over_cap)
+}
+
+pub(crate) fn unix_now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn settings_reader(settings: HistorySettings) -> HistorySettingsReader {
+        let settings = settings.normalized();
+        Arc::new(move || settings.clone())
+    }
+
+    fn open_store(dir: &tempfile::TempDir, settings: HistorySettings) -> HistoryStore {
+        HistoryStore::open(&dir.path().join(HISTORY_FILE), settings_reader(settings)).unwrap()
+    }
+
+    fn message(channel: &str, id: &str, text: &str) -> HistoryEntry {
+        HistoryEntry::Message(ChannelMessage {
+            chatter_user_id: "42".to_string(),
+            ..ChannelMessage::test(id, channel, "viewer", text)
+        })
+    }
+
+    fn texts(records: &[HistoryRecord]) -> Vec<String> {
+        records
+            .iter()
+            .map(|record| match &record.entry {
+                HistoryEntry::Message(message) => message.text.clone(),
+                other => panic!("expected message, got {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pages_channel_history_oldest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(&dir, HistorySettings::default());
+        let now = unix_now_ms();
+        for i in 0..5 {
+            store.record_at(
+                message("forsen", &format!("m{i}"), &format!("msg {i}")),
+                now + i,
+            );
+        }
+        store.record_at(message("xqc", "other", "elsewhere"), now);
+        store.flush();
+
+        let newest = store.channel_history("forsen", None, 2).unwrap();
+        assert_eq!(texts(&newest), vec!["msg 3", "msg 4"]);
+
+        let older = store
+            .channel_history("forsen", Some(newest[0].id), 10)
+            .unwrap();
+        assert_eq!(texts(&older), vec!["msg 0", "msg 1", "msg 2"]);
+    }
+
+    #[test]
+    fn visits_entries_in_time_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(&dir, HistorySettings::default());
+        let now = unix_now_ms();
+        for i in 0..4 {
+            store.record_at(
+                message("forsen", &format!("m{i}"), &format!("msg {i}")),
+                now + i * 1_000,
+            );
+        }
+        store.flush();
+
+        let mut visited = Vec::new();
+        let count = store
+            .for_each_between("forsen", now + 1_000, now + 3_000, |record| {
+                visited.push(record);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(texts(&visited), vec!["msg 1", "msg 2"]);
+    }
+
+    #[test]
+    fn duplicate_deliveries_are_recorded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(&dir, HistorySettings::default());
+        let now = unix_now_ms();
+
+        store.record_at(message("forsen", "m1", "first"), now);
+        store.record_at(message("forsen", "m1", "first"), now + 1);
+        store.flush();
+
+        assert_eq!(store.channel_history("forsen", None, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn finds_a_chatters_latest_messages_in_a_channel() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(&dir, HistorySettings::default());
+        let now = unix_now_ms();
+        for i in 0..4 {
+            store.record_at(
+                message("forsen", &format!("m{i}"), &format!("msg {i}")),
+                now + i,
+            );
+        }
+        let HistoryEntry::Message(mut other) = message("forsen", "o1", "someone else") else {
+            unreachable!()
+        };
+        other.chatter_user_id = "7".to_string();
+        store.record_at(HistoryEntry::Message(other), now + 10);
+        store.record_at(message("xqc", "x1", "elsewhere"), now + 20);
+        store.flush();
+
+        let messages = store.chatter_messages("forsen-id", "42", 3).unwrap();
+        let texts: Vec<_> = messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["msg 1", "msg 2", "msg 3"]);
+        assert!(store
+            .chatter_messages("forsen-id", "1", 3)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn prunes_per_channel_and_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(
+            &dir,
+            HistorySettings {
+                max_entries_per_channel: 3,
+                channel_max_entries: HashMap::from([("xqc".to_string(), 1)]),
+                retention_days: 1,
+                ..Default::default()
+            },
+        );
+        let now = unix_now_ms();
+        let two_days_ago = now - 2 * 24 * 60 * 60 * 1_000;
+
+        store.record_at(message("forsen", "old", "expired"), two_days_ago);
+        for i in 0..5 {
+            store.record_at(
+                message("forsen", &format!("m{i}"), &format!("msg {i}")),
+                now + i,
+            );
+        }
+        store.record_at(message("xqc", "x1", "first"), now);
+        store.record_at(message("xqc", "x2", "second"), now + 1);
+        store.flush();
+
+        assert_eq!(
+            texts(&store.channel_history("forsen", None, 10).unwrap()),
+            vec!["msg 2", "msg 3", "msg 4"]
+        );
+        assert_eq!(
+            texts(&store.channel_history("xqc", None, 10).unwrap()),
+            vec!["second"]
+        );
+    }
+
+    #[test]
+    fn records_notices_and_moderation_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(&dir, HistorySettings::default());
+
+        store.record(HistoryEntry::Moderation(ModerationEvent {
+            ts: String::new(),
+            broadcaster_user_id: "forsen-id".to_string(),
+            broadcaster_user_login: "forsen".to_string(),
+            action: ModerationAction::ClearChat,
+        }));
+        store.record(HistoryEntry::Moderation(ModerationEvent {
+            ts: String::new(),
+            broadcaster_user_id: "forsen-id".to_string(),
+            broadcaster_user_login: "forsen".to_string(),
+            action: ModerationAction::ClearChat,
+        }));
+        store.flush();
+
+        let records = store.channel_history("forsen", None, 10).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(matches!(
+            &records[0].entry,
+            HistoryEntry::Moderation(ModerationEvent {
+                action: ModerationAction::ClearChat,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn disabled_history_records_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(
+            &dir,
+            HistorySettings {
+                enabled: false,
+                ..Default::default()
+            },
+        );
+
+        store.record(message("forsen", "m1", "hello"));
+        store.flush();
+
+        assert!(store
+            .channel_history("forsen", None, 10)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -21,21 +21,43 @@ use twitch_api::{client::ClientDefault, HelixClient};
 
 use crate::badgemanager::BadgeManager;
 use crate::emote::cache::EmoteCacheTrait;
+use crate::emote::providers::local::{LocalEmoteFiles, LOCAL_EMOTE_SCHEME};
 use crate::emotemanager::EmoteManager;
+use crate::error::PepoError;
 use crate::types::{AppSettings, AuthState, ChannelCache};
 
+mod automod;
+mod backfill;
 mod badgemanager;
 mod badgepersist;
+mod batch;
+mod chatlog;
+mod commands;
+mod date;
 mod emote;
 mod emotemanager;
+mod error;
+mod eventqueue;
 mod eventsub;
+mod filter;
+mod highlight;
+mod history;
 mod internal;
 mod logging;
 mod message;
+mod moderation;
+mod modlog;
+mod outgoing;
 #[cfg(target_os = "linux")]
 mod platform;
+mod rule;
+mod search;
 mod token;
+mod trust;
 mod types;
+mod unban;
+mod usercard;
+mod whisper;
 
 #[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
 struct InternalState {
@@ -59,6 +81,7 @@ type SharedBadgeManager = Mutex<BadgeManager>;
 type SharedEmoteManager = Mutex<EmoteManager>;
 type SharedPollHandle = Mutex<Option<tauri::async_runtime::JoinHandle<()>>>;
 type SharedEventSubHandles = Mutex<Vec<tauri::async_runtime::JoinHandle<()>>>;
+type SharedEventQueue = Mutex<Option<eventqueue::EventQueue>>;
 /// Guards against overlapping token refreshes (supervisor tick vs. focus event).
 type RefreshLock = Mutex<()>;
 
@@ -77,6 +100,32 @@ fn app_settings(state_syncer: &StateSyncer) -> AppSettings {
         .normalized()
 }
 
+fn open_history_store(
+    app_handle: &AppHandle,
+    state_syncer: StateSyncer,
+) -> eyre::Result<history::HistoryStore> {
+    let dir = app_handle.path().app_data_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    history::HistoryStore::open(
+        &dir.join(history::HISTORY_FILE),
+        Arc::new(move || app_settings(&state_syncer).history),
+    )
+}
+
+fn start_chat_logger(
+    app_handle: &AppHandle,
+    state_syncer: StateSyncer,
+) -> eyre::Result<chatlog::ChatLogger> {
+    let default_root = app_handle
+        .path()
+        .app_data_dir()?
+        .join(chatlog::DEFAULT_LOG_DIR);
+    chatlog::ChatLogger::start(
+        default_root,
+        Arc::new(move || app_settings(&state_syncer).chat_log),
+    )
+}
+
 fn make_eventsub_settings_reader(state_syncer: StateSyncer) -> eventsub::EventSubSettingsReader {
     Arc::new(move || app_settings(&state_syncer).eventsub)
 }
@@ -193,98 +242,1017 @@ fn repo_root() -> std::path::PathBuf {
         .to_path_buf()
 }
 
-#[cfg(debug_assertions)]
-fn typescript_exporter() -> Typescript {
-    Typescript::default()
-        .formatter(specta_typescript::formatter::prettier)
-        .bigint(specta_typescript::BigIntExportBehavior::Number)
-        .header("/* eslint-disable */")
+#[cfg(debug_assertions)]
+fn typescript_exporter() -> Typescript {
+    Typescript::default()
+        .formatter(specta_typescript::formatter::prettier)
+        .bigint(specta_typescript::BigIntExportBehavior::Number)
+        .header("/* eslint-disable */")
+}
+
+#[cfg(debug_assertions)]
+fn export_bindings(builder: &tauri_specta::Builder<tauri::Wry>, path: std::path::PathBuf) {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("Failed to create bindings output directory");
+    }
+
+    builder
+        .export(typescript_exporter(), path)
+        .expect("Failed to export typescript bindings");
+}
+
+pub fn specta_builder() -> tauri_specta::Builder<tauri::Wry> {
+    tauri_specta::Builder::<tauri::Wry>::new()
+        .typ::<types::UserToken>()
+        .typ::<types::ChannelMessage>()
+        .typ::<types::ChannelMessageTranslation>()
+        .typ::<types::ChannelMessageTranslationUpdate>()
+        .typ::<types::AuthState>()
+        .typ::<types::AuthPhase>()
+        .typ::<types::ChannelCache>()
+        .typ::<types::ChannelStatus>()
+        .typ::<types::AppSettings>()
+        .typ::<InternalState>()
+        .typ::<outgoing::DeliveryUpdate>()
+        .typ::<whisper::WhisperEvent>()
+        .typ::<trust::TrustStatusUpdate>()
+        .typ::<modlog::MessageAnnotation>()
+        .typ::<eventqueue::OverloadMarker>()
+        .typ::<highlight::HighlightNotification>()
+        .commands(collect_commands![
+            get_followed_streams,
+            get_followed_channels,
+            get_channel_info,
+            join_chat,
+            leave_chat,
+            login,
+            logout,
+            send_chat_message,
+            emit_state,
+            update_state,
+            search_emotes,
+            get_channel_history,
+            export_chat_log,
+            search_chat_history,
+            get_user_card,
+            request_moderator_scopes,
+            request_broadcaster_scopes,
+            request_color_scopes,
+            get_channel_moderation,
+            ban_user,
+            timeout_user,
+            unban_user,
+            delete_chat_message,
+            clear_chat,
+            get_automod_queue,
+            resolve_automod_message,
+            list_unban_requests,
+            approve_unban_request,
+            deny_unban_request,
+            get_moderation_log,
+            set_user_trust_status,
+            list_blocked_terms,
+            add_blocked_term,
+            remove_blocked_term,
+            get_chat_settings,
+            update_chat_settings,
+            list_commands,
+            request_whisper_scopes,
+            send_whisper,
+            list_whisper_threads,
+            get_whisper_thread,
+            set_focused_channels,
+            get_event_queue_metrics,
+            get_highlight_rules,
+            set_highlight_rules,
+            get_filter_rules,
+            set_filter_rules,
+            set_honor_block_list,
+            refresh_block_list,
+        ])
+}
+
+/// Sends a line from the chat input. Chat text goes through the outgoing
+/// queue and reports back on `message_delivery`; slash commands run directly.
+#[tauri::command]
+#[specta::specta]
+async fn send_chat_message(
+    broadcaster_id: String,
+    message: String,
+    app_handle: AppHandle,
+    state_syncer: State<'_, StateSyncer>,
+    token_manager: State<'_, TokenManager>,
+    client: State<'_, HelixClient<'static, reqwest::Client>>,
+    outgoing: State<'_, outgoing::OutgoingQueue>,
+    whispers: State<'_, whisper::WhisperInbox>,
+) -> Result<Option<outgoing::QueuedMessage>, PepoError> {
+    debug!(
+        "sending chat message: broadcaster_id={}, message={}",
+        broadcaster_id, message
+    );
+
+    let settings = app_settings(&state_syncer).chat;
+    let line = commands::parse(&message, &settings.command_aliases)?;
+    let token = active_token(&token_manager).await?;
+    match line {
+        commands::Line::Chat(text) => {
+            let class = rate_class(
+                &app_handle,
+                client.inner(),
+                &outgoing,
+                &token,
+                &broadcaster_id,
+                &settings,
+            )
+            .await;
+            Ok(Some(outgoing.enqueue(token, &broadcaster_id, text, class)))
+        }
+        commands::Line::Command(command) => {
+            commands::run(client.inner(), &token, &whispers, &broadcaster_id, command).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Which chat limit the outgoing queue paces the user's messages to in a
+/// channel. Lookups are cached by the queue for a few minutes.
+async fn rate_class(
+    app_handle: &AppHandle,
+    client: &HelixClient<'static, reqwest::Client>,
+    outgoing: &outgoing::OutgoingQueue,
+    token: &twitch_oauth2::UserToken,
+    broadcaster_id: &str,
+    settings: &types::ChatSettings,
+) -> outgoing::RateClass {
+    let user_id = token.user_id.as_str();
+    if let Some(class) = outgoing.cached_class(user_id, broadcaster_id) {
+        return class;
+    }
+    // A failed lookup only means slower pacing, so fall back to the badges.
+    let moderated = moderation::is_moderated_channel(client, token, broadcaster_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("could not check moderated channels: {}", e);
+            None
+        });
+    let badges = own_badges(app_handle, broadcaster_id, user_id).await;
+    let role = moderation::ChannelRole::detect(user_id, broadcaster_id, moderated, &badges);
+    let verified_bot = settings
+        .verified_bot_user_ids
+        .iter()
+        .any(|id| id == user_id);
+    let class = outgoing::RateClass::detect(role, &badges, verified_bot);
+    outgoing.remember_class(user_id, broadcaster_id, class);
+    class
+}
+
+/// Badges on the user's own latest message in the channel, from history.
+async fn own_badges(
+    app_handle: &AppHandle,
+    broadcaster_id: &str,
+    user_id: &str,
+) -> Vec<types::BadgeRef> {
+    let Some(store) = app_handle.try_state::<history::HistoryStore>() else {
+        return Vec::new();
+    };
+    let store = store.inner().clone();
+    let broadcaster_id = broadcaster_id.to_owned();
+    let user_id = user_id.to_owned();
+    tokio::task::spawn_blocking(move || store.chatter_messages(&broadcaster_id, &user_id, 1))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .and_then(|mut messages| messages.pop())
+        .map(|message| message.badges)
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn send_whisper(
+    to_login: String,
+    message: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+    whispers: State<'_, whisper::WhisperInbox>,
+) -> Result<whisper::WhisperThreadSummary, PepoError> {
+    let token = active_token(&token_manager).await?;
+    let event = whisper::send(client_ref.inner(), &token, &whispers, &to_login, &message).await?;
+    Ok(event.thread)
+}
+
+/// The active account's whisper conversations, most recently active first.
+#[tauri::command]
+#[specta::specta]
+async fn list_whisper_threads(
+    token_manager: State<'_, TokenManager>,
+    whispers: State<'_, whisper::WhisperInbox>,
+) -> Result<Vec<whisper::WhisperThreadSummary>, PepoError> {
+    let token = active_token(&token_manager).await?;
+    Ok(whispers.list(token.user_id.as_str()))
+}
+
+/// A conversation's whispers, oldest first. Opening it marks it read.
+#[tauri::command]
+#[specta::specta]
+async fn get_whisper_thread(
+    peer_user_id: String,
+    token_manager: State<'_, TokenManager>,
+    whispers: State<'_, whisper::WhisperInbox>,
+) -> Result<Vec<whisper::Whisper>, PepoError> {
+    let token = active_token(&token_manager).await?;
+    Ok(whispers.open_thread(token.user_id.as_str(), &peer_user_id))
+}
+
+/// Channels the user has open; they are the last to lose messages when the
+/// EventSub queue overflows.
+#[tauri::command]
+#[specta::specta]
+async fn set_focused_channels(
+    logins: Vec<String>,
+    event_queue: State<'_, SharedEventQueue>,
+) -> Result<(), PepoError> {
+    if let Some(queue) = event_queue.lock().await.as_ref() {
+        queue.set_focused(logins);
+    }
+    Ok(())
+}
+
+/// `None` until EventSub is running.
+#[tauri::command]
+#[specta::specta]
+async fn get_event_queue_metrics(
+    event_queue: State<'_, SharedEventQueue>,
+) -> Result<Option<eventqueue::EventQueueMetrics>, PepoError> {
+    Ok(event_queue
+        .lock()
+        .await
+        .as_ref()
+        .map(eventqueue::EventQueue::metrics))
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_highlight_rules(state_syncer: State<'_, StateSyncer>) -> Vec<highlight::HighlightRule> {
+    app_settings(&state_syncer).highlights.rules
+}
+
+/// Saves the highlight rules, refusing the whole list if any rule is broken.
+#[tauri::command]
+#[specta::specta]
+fn set_highlight_rules(
+    rules: Vec<highlight::HighlightRule>,
+    state_syncer: State<'_, StateSyncer>,
+) -> Result<Vec<highlight::HighlightRule>, PepoError> {
+    let rules = highlight::validate(rules)?;
+    let mut settings = app_settings(&state_syncer);
+    settings.highlights.rules = rules.clone();
+    state_syncer.update::<AppSettings>(APP_SETTINGS_KEY, settings, true);
+    Ok(rules)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_filter_rules(state_syncer: State<'_, StateSyncer>) -> Vec<filter::FilterRule> {
+    app_settings(&state_syncer).filters.rules
+}
+
+/// Saves the filter rules, refusing the whole list if any rule is broken.
+#[tauri::command]
+#[specta::specta]
+fn set_filter_rules(
+    rules: Vec<filter::FilterRule>,
+    state_syncer: State<'_, StateSyncer>,
+) -> Result<Vec<filter::FilterRule>, PepoError> {
+    let rules = filter::validate(rules)?;
+    let mut settings = app_settings(&state_syncer);
+    settings.filters.rules = rules.clone();
+    state_syncer.update::<AppSettings>(APP_SETTINGS_KEY, settings, true);
+    Ok(rules)
+}
+
+/// Saves whether chat hides the account's Twitch block list, loading the list
+/// when turned on. Returns how many users are on it.
+#[tauri::command]
+#[specta::specta]
+async fn set_honor_block_list(
+    honor: bool,
+    state_syncer: State<'_, StateSyncer>,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+    message_filters: State<'_, filter::MessageFilters>,
+) -> Result<u32, PepoError> {
+    let blocked = if honor {
+        let token = active_token(&token_manager).await?;
+        filter::fetch_blocked_user_ids(client_ref.inner(), &token).await?
+    } else {
+        Default::default()
+    };
+    let count = blocked.len() as u32;
+    message_filters.set_blocked_user_ids(blocked);
+
+    let mut settings = app_settings(&state_syncer);
+    settings.filters.honor_block_list = honor;
+    state_syncer.update::<AppSettings>(APP_SETTINGS_KEY, settings, true);
+    Ok(count)
+}
+
+/// Reloads the account's Twitch block list, returning how many users are on it.
+#[tauri::command]
+#[specta::specta]
+async fn refresh_block_list(
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+    message_filters: State<'_, filter::MessageFilters>,
+) -> Result<u32, PepoError> {
+    let token = active_token(&token_manager).await?;
+    let blocked = filter::fetch_blocked_user_ids(client_ref.inner(), &token).await?;
+    let count = blocked.len() as u32;
+    message_filters.set_blocked_user_ids(blocked);
+    Ok(count)
+}
+
+/// Built-in slash commands and local aliases, for autocomplete.
+#[tauri::command]
+#[specta::specta]
+fn list_commands(state_syncer: State<'_, StateSyncer>) -> Vec<commands::CommandInfo> {
+    commands::list(&app_settings(&state_syncer).chat.command_aliases)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn request_moderator_scopes(
+    app_handle: AppHandle,
+    state_syncer: State<'_, StateSyncer>,
+    token_manager: State<'_, TokenManager>,
+    eventsub_manager_ref: State<'_, SharedEventSubManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<types::UserToken, PepoError> {
+    let user_token = request_scopes(
+        &app_handle,
+        state_syncer.inner(),
+        token_manager.inner(),
+        &token::MODERATOR_SCOPES,
+    )
+    .await?;
+    subscribe_upgraded_scopes(&eventsub_manager_ref, client_ref.inner(), &token_manager).await;
+    Ok(user_token)
+}
+
+/// Re-authorizes with the scopes behind /raid, /unraid and /marker.
+#[tauri::command]
+#[specta::specta]
+async fn request_broadcaster_scopes(
+    app_handle: AppHandle,
+    state_syncer: State<'_, StateSyncer>,
+    token_manager: State<'_, TokenManager>,
+) -> Result<types::UserToken, PepoError> {
+    request_scopes(
+        &app_handle,
+        state_syncer.inner(),
+        token_manager.inner(),
+        &token::BROADCASTER_SCOPES,
+    )
+    .await
+}
+
+/// Re-authorizes with the scope /color needs to change the name colour.
+#[tauri::command]
+#[specta::specta]
+async fn request_color_scopes(
+    app_handle: AppHandle,
+    state_syncer: State<'_, StateSyncer>,
+    token_manager: State<'_, TokenManager>,
+) -> Result<types::UserToken, PepoError> {
+    request_scopes(
+        &app_handle,
+        state_syncer.inner(),
+        token_manager.inner(),
+        &token::COLOR_SCOPES,
+    )
+    .await
+}
+
+/// Opts in to whispers, re-authorizing with the scopes to read and send them.
+#[tauri::command]
+#[specta::specta]
+async fn request_whisper_scopes(
+    app_handle: AppHandle,
+    state_syncer: State<'_, StateSyncer>,
+    token_manager: State<'_, TokenManager>,
+    eventsub_manager_ref: State<'_, SharedEventSubManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<types::UserToken, PepoError> {
+    let user_token = request_scopes(
+        &app_handle,
+        state_syncer.inner(),
+        token_manager.inner(),
+        &token::WHISPER_SCOPES,
+    )
+    .await?;
+    subscribe_upgraded_scopes(&eventsub_manager_ref, client_ref.inner(), &token_manager).await;
+    Ok(user_token)
+}
+
+async fn request_scopes(
+    app_handle: &AppHandle,
+    state_syncer: &StateSyncer,
+    token_manager: &TokenManager,
+    scopes: &[twitch_oauth2::Scope],
+) -> Result<types::UserToken, PepoError> {
+    let device_code = token_manager.start_scope_upgrade_flow(scopes).await?;
+    authorize_device_code(
+        app_handle,
+        state_syncer,
+        token_manager,
+        device_code,
+        DeviceCodeFlow::ScopeUpgrade,
+    )
+    .await?;
+    let user_token = token_manager
+        .active_public_token()
+        .await
+        .ok_or(PepoError::AuthMissing)?;
+    persist_authorized_token(state_syncer, user_token.clone());
+    Ok(user_token)
+}
+
+/// Subscribes to the events newly granted scopes allow without waiting for
+/// the next EventSub reconnect.
+async fn subscribe_upgraded_scopes(
+    eventsub_manager_ref: &SharedEventSubManager,
+    client: &HelixClient<'static, reqwest::Client>,
+    token_manager: &TokenManager,
+) {
+    let Some(token) = token_manager.active_twitch_token().await else {
+        return;
+    };
+    let eventsub_manager = eventsub_manager_ref.lock().await.clone();
+    if let Err(err) = eventsub_manager
+        .subscribe_upgraded_scopes(client, token)
+        .await
+    {
+        error!("subscribe_upgraded_scopes - {:?}", err);
+    }
+}
+
+async fn active_token(token_manager: &TokenManager) -> Result<twitch_oauth2::UserToken, PepoError> {
+    token_manager
+        .active_twitch_token()
+        .await
+        .ok_or(PepoError::AuthMissing)
+}
+
+fn history_unavailable() -> PepoError {
+    PepoError::Internal {
+        message: "chat history is unavailable".to_owned(),
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn get_channel_moderation(
+    broadcaster_id: String,
+    app_handle: AppHandle,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<moderation::ModerationCapabilities, PepoError> {
+    use twitch_oauth2::TwitchToken;
+
+    let token = active_token(&token_manager).await?;
+    let user_id = token.user_id.to_string();
+    let moderated =
+        moderation::is_moderated_channel(client_ref.inner(), &token, &broadcaster_id).await?;
+
+    // The user's own latest message carries the only lead moderator signal.
+    let badges = own_badges(&app_handle, &broadcaster_id, &user_id).await;
+
+    let role = moderation::ChannelRole::detect(&user_id, &broadcaster_id, moderated, &badges);
+    Ok(moderation::ModerationCapabilities::new(
+        role,
+        token.scopes(),
+    ))
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn ban_user(
+    broadcaster_id: String,
+    user_id: String,
+    reason: Option<String>,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::ban(
+        client_ref.inner(),
+        &token,
+        &broadcaster_id,
+        &user_id,
+        reason.as_deref().unwrap_or_default(),
+        None,
+    )
+    .await
+    .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn timeout_user(
+    broadcaster_id: String,
+    user_id: String,
+    duration_secs: u32,
+    reason: Option<String>,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::ban(
+        client_ref.inner(),
+        &token,
+        &broadcaster_id,
+        &user_id,
+        reason.as_deref().unwrap_or_default(),
+        Some(duration_secs),
+    )
+    .await
+    .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn unban_user(
+    broadcaster_id: String,
+    user_id: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::unban(client_ref.inner(), &token, &broadcaster_id, &user_id)
+        .await
+        .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn delete_chat_message(
+    broadcaster_id: String,
+    message_id: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::delete_messages(
+        client_ref.inner(),
+        &token,
+        &broadcaster_id,
+        Some(&message_id),
+    )
+    .await
+    .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn clear_chat(
+    broadcaster_id: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::delete_messages(client_ref.inner(), &token, &broadcaster_id, None)
+        .await
+        .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn get_automod_queue(
+    broadcaster_id: String,
+    automod_queue: State<'_, automod::AutomodQueue>,
+) -> Vec<automod::HeldMessage> {
+    automod_queue.pending(&broadcaster_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn resolve_automod_message(
+    message_id: String,
+    allow: bool,
+    app_handle: AppHandle,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+    automod_queue: State<'_, automod::AutomodQueue>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::resolve_held_message(client_ref.inner(), &token, &message_id, allow).await?;
+    // The update event would drop it too; don't leave it clickable until then.
+    if let Some((resolved, pending)) = automod_queue.resolve(&message_id) {
+        emit_automod_queue(&app_handle, &resolved.broadcaster_user_login, pending);
+    }
+    Ok(())
+}
+
+fn emit_automod_queue(app_handle: &AppHandle, login: &str, pending: Vec<automod::HeldMessage>) {
+    if let Err(e) = app_handle.emit(&format!("automod_queue:{}", login), pending) {
+        error!("failed to emit automod queue for {}: {}", login, e);
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+fn list_unban_requests(
+    broadcaster_id: String,
+    include_resolved: Option<bool>,
+    unban_inbox: State<'_, unban::UnbanInbox>,
+) -> Vec<unban::UnbanRequest> {
+    unban_inbox.list(&broadcaster_id, include_resolved.unwrap_or(false))
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn approve_unban_request(
+    request_id: String,
+    resolution_text: Option<String>,
+    app_handle: AppHandle,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+    unban_inbox: State<'_, unban::UnbanInbox>,
+) -> Result<unban::UnbanRequest, PepoError> {
+    resolve_unban_request(
+        &app_handle,
+        &token_manager,
+        client_ref.inner(),
+        &unban_inbox,
+        &request_id,
+        true,
+        resolution_text.unwrap_or_default(),
+    )
+    .await
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn deny_unban_request(
+    request_id: String,
+    resolution_text: Option<String>,
+    app_handle: AppHandle,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+    unban_inbox: State<'_, unban::UnbanInbox>,
+) -> Result<unban::UnbanRequest, PepoError> {
+    resolve_unban_request(
+        &app_handle,
+        &token_manager,
+        client_ref.inner(),
+        &unban_inbox,
+        &request_id,
+        false,
+        resolution_text.unwrap_or_default(),
+    )
+    .await
+}
+
+async fn resolve_unban_request(
+    app_handle: &AppHandle,
+    token_manager: &TokenManager,
+    client: &HelixClient<'static, reqwest::Client>,
+    unban_inbox: &unban::UnbanInbox,
+    request_id: &str,
+    approve: bool,
+    resolution_text: String,
+) -> Result<unban::UnbanRequest, PepoError> {
+    let token = active_token(token_manager).await?;
+    let request = unban_inbox
+        .get(request_id)
+        .ok_or_else(|| PepoError::not_found(format!("unknown unban request: {}", request_id)))?;
+    moderation::resolve_unban_request(
+        client,
+        &token,
+        &request.broadcaster_user_id,
+        request_id,
+        approve,
+        &resolution_text,
+    )
+    .await?;
+
+    let status = if approve {
+        unban::UnbanRequestStatus::Approved
+    } else {
+        unban::UnbanRequestStatus::Denied
+    };
+    let resolved = unban_inbox
+        .resolve(
+            request_id,
+            status,
+            Some(token.login.to_string()),
+            Some(resolution_text).filter(|text| !text.is_empty()),
+        )
+        .unwrap_or(request);
+    emit_unban_request(app_handle, &resolved);
+    Ok(resolved)
+}
+
+fn emit_unban_request(app_handle: &AppHandle, request: &unban::UnbanRequest) {
+    let key = format!("unban_request:{}", request.broadcaster_user_login);
+    if let Err(e) = app_handle.emit(&key, request) {
+        error!("failed to emit unban request {}: {}", request.id, e);
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn search_emotes(
+    query: String,
+    broadcaster_id: String,
+    limit: Option<usize>,
+    emote_manager_ref: State<'_, SharedEmoteManager>,
+    state_syncer: State<'_, StateSyncer>,
+) -> Result<Vec<emote::Emote>, PepoError> {
+    let emote_manager = emote_manager_ref.lock().await.clone();
+    let settings = app_settings(state_syncer.inner());
+    let cache = emote_manager.get_usable_emote_cache(broadcaster_id, &settings.emotes);
+    Ok(cache
+        .search_emotes(
+            &query,
+            limit.unwrap_or(settings.emotes.autocomplete_result_limit),
+        )
+        .into_iter()
+        .map(|emote| emote.themed(&settings.appearance.theme))
+        .collect())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn get_channel_history(
+    channel: String,
+    before: Option<i64>,
+    limit: Option<usize>,
+    app_handle: AppHandle,
+    state_syncer: State<'_, StateSyncer>,
+) -> Result<Vec<history::HistoryRecord>, PepoError> {
+    let store = app_handle
+        .try_state::<history::HistoryStore>()
+        .ok_or_else(history_unavailable)?
+        .inner()
+        .clone();
+    let channel_login = channel.trim().to_lowercase();
+    let limit = limit.unwrap_or(history::DEFAULT_PAGE_SIZE);
+    let records =
+        tokio::task::spawn_blocking(move || store.channel_history(&channel_login, before, limit))
+            .await
+            .map_err(|e| PepoError::internal("history lookup failed", e))?
+            .map_err(|e| PepoError::internal("failed to read chat history", e))?;
+
+    let theme = app_settings(state_syncer.inner()).appearance.theme;
+    Ok(records
+        .into_iter()
+        .map(|record| record.with_emote_theme(&theme))
+        .collect())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn search_chat_history(
+    query: String,
+    cursor: Option<i64>,
+    limit: Option<usize>,
+    app_handle: AppHandle,
+    state_syncer: State<'_, StateSyncer>,
+) -> Result<search::SearchPage, PepoError> {
+    let store = app_handle
+        .try_state::<history::HistoryStore>()
+        .ok_or_else(history_unavailable)?
+        .inner()
+        .clone();
+    let query = search::SearchQuery::parse(&query).map_err(PepoError::invalid_input)?;
+    let limit = limit.unwrap_or(search::DEFAULT_PAGE_SIZE);
+    let page = tokio::task::spawn_blocking(move || search::search(&store, &query, cursor, limit))
+        .await
+        .map_err(|e| PepoError::internal("history search failed", e))?
+        .map_err(|e| PepoError::internal("failed to search chat history", e))?;
+
+    let theme = app_settings(state_syncer.inner()).appearance.theme;
+    Ok(search::SearchPage {
+        hits: page
+            .hits
+            .into_iter()
+            .map(|hit| hit.with_emote_theme(&theme))
+            .collect(),
+        ..page
+    })
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn list_blocked_terms(
+    broadcaster_id: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<Vec<moderation::BlockedTerm>, PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::list_blocked_terms(client_ref.inner(), &token, &broadcaster_id)
+        .await
+        .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn add_blocked_term(
+    broadcaster_id: String,
+    text: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<moderation::BlockedTerm, PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::add_blocked_term(client_ref.inner(), &token, &broadcaster_id, &text)
+        .await
+        .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn remove_blocked_term(
+    broadcaster_id: String,
+    term_id: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::remove_blocked_term(client_ref.inner(), &token, &broadcaster_id, &term_id)
+        .await
+        .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn get_chat_settings(
+    broadcaster_id: String,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<moderation::ChannelChatSettings, PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::get_chat_settings(client_ref.inner(), &token, &broadcaster_id)
+        .await
+        .map_err(PepoError::from)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn update_chat_settings(
+    broadcaster_id: String,
+    update: moderation::ChatSettingsUpdate,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<moderation::ChannelChatSettings, PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::update_chat_settings(client_ref.inner(), &token, &broadcaster_id, update)
+        .await
+        .map_err(PepoError::from)
+}
+
+/// Marks a chatter monitored or restricted, or clears that when `treatment` is
+/// `None`. The change comes back as a `channel.suspicious_user.update` event.
+#[tauri::command]
+#[specta::specta]
+async fn set_user_trust_status(
+    broadcaster_id: String,
+    user_id: String,
+    treatment: Option<trust::Treatment>,
+    token_manager: State<'_, TokenManager>,
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+) -> Result<(), PepoError> {
+    let token = active_token(&token_manager).await?;
+    moderation::set_suspicious_status(
+        client_ref.inner(),
+        &token,
+        &broadcaster_id,
+        &user_id,
+        treatment,
+    )
+    .await
+    .map_err(PepoError::from)
 }
 
-#[cfg(debug_assertions)]
-fn export_bindings(builder: &tauri_specta::Builder<tauri::Wry>, path: std::path::PathBuf) {
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).expect("Failed to create bindings output directory");
+fn emit_trust_status(app_handle: &AppHandle, login: &str, update: &trust::TrustStatusUpdate) {
+    if let Err(e) = app_handle.emit(&format!("trust_status:{}", login), update) {
+        error!(
+            "failed to emit trust status for {}: {}",
+            update.user_login, e
+        );
     }
-
-    builder
-        .export(typescript_exporter(), path)
-        .expect("Failed to export typescript bindings");
 }
 
-pub fn specta_builder() -> tauri_specta::Builder<tauri::Wry> {
-    tauri_specta::Builder::<tauri::Wry>::new()
-        .typ::<types::UserToken>()
-        .typ::<types::ChannelMessage>()
-        .typ::<types::ChannelMessageTranslation>()
-        .typ::<types::ChannelMessageTranslationUpdate>()
-        .typ::<types::AuthState>()
-        .typ::<types::AuthPhase>()
-        .typ::<types::ChannelCache>()
-        .typ::<types::ChannelStatus>()
-        .typ::<types::AppSettings>()
-        .typ::<InternalState>()
-        .commands(collect_commands![
-            get_followed_streams,
-            get_followed_channels,
-            get_channel_info,
-            join_chat,
-            leave_chat,
-            login,
-            logout,
-            send_chat_message,
-            emit_state,
-            update_state,
-            search_emotes,
-        ])
+#[tauri::command]
+#[specta::specta]
+async fn get_moderation_log(
+    broadcaster_id: String,
+    filter: Option<modlog::ModLogFilter>,
+    cursor: Option<i64>,
+    limit: Option<usize>,
+    app_handle: AppHandle,
+) -> Result<modlog::ModLogPage, PepoError> {
+    let store = app_handle
+        .try_state::<history::HistoryStore>()
+        .ok_or_else(history_unavailable)?
+        .inner()
+        .clone();
+    let filter = filter.unwrap_or_default();
+    let limit = limit.unwrap_or(modlog::DEFAULT_PAGE_SIZE);
+    tokio::task::spawn_blocking(move || {
+        modlog::query(&store, &broadcaster_id, &filter, cursor, limit)
+    })
+    .await
+    .map_err(|e| PepoError::internal("moderation log lookup failed", e))?
+    .map_err(|e| PepoError::internal("failed to read moderation log", e))
 }
 
 #[tauri::command]
 #[specta::specta]
-fn send_chat_message(
+async fn get_user_card(
     broadcaster_id: String,
-    message: String,
-    _app_handle: AppHandle,
+    user_id: String,
+    app_handle: AppHandle,
     token_manager: State<'_, TokenManager>,
-    client: State<'_, HelixClient<'static, reqwest::Client>>,
-) -> Result<(), String> {
-    debug!(
-        "sending chat message: broadcaster_id={}, message={}",
-        broadcaster_id, message
-    );
+    client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
+    user_card_cache: State<'_, usercard::UserCardCache>,
+    state_syncer: State<'_, StateSyncer>,
+) -> Result<usercard::UserCard, PepoError> {
+    let profile = match user_card_cache.get(&broadcaster_id, &user_id, Instant::now()) {
+        Some(profile) => profile,
+        None => {
+            let token = active_token(&token_manager).await?;
+            let profile =
+                usercard::fetch_profile(client_ref.inner(), &token, &broadcaster_id, &user_id)
+                    .await?
+                    .ok_or_else(|| PepoError::not_found(format!("user not found: {}", user_id)))?;
+            user_card_cache.insert(&broadcaster_id, profile.clone(), Instant::now());
+            profile
+        }
+    };
 
-    let client = client.inner();
+    let recent_messages = match app_handle.try_state::<history::HistoryStore>() {
+        Some(store) => {
+            let store = store.inner().clone();
+            tokio::task::spawn_blocking(move || {
+                // Include messages still queued for the history writer.
+                store.flush();
+                store.chatter_messages(&broadcaster_id, &user_id, usercard::RECENT_MESSAGE_LIMIT)
+            })
+            .await
+            .map_err(|e| PepoError::internal("history lookup failed", e))?
+            .map_err(|e| PepoError::internal("failed to read chat history", e))?
+        }
+        None => Vec::new(),
+    };
 
-    let token_guard = tauri::async_runtime::block_on(token_manager.active_twitch_token())
-        .ok_or_else(|| "no active token".to_owned())?;
-    let user_id = token_guard.user_id.clone();
-    match tauri::async_runtime::block_on(client.send_chat_message(
-        broadcaster_id,
-        user_id,
-        message.as_str(),
-        &token_guard,
-    )) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(format!("request failed: {:?}", e)),
-    }
+    let theme = app_settings(state_syncer.inner()).appearance.theme;
+    Ok(usercard::UserCard::new(
+        profile,
+        recent_messages
+            .into_iter()
+            .map(|message| message.with_emote_theme(&theme))
+            .collect(),
+    ))
 }
 
 #[tauri::command]
 #[specta::specta]
-async fn search_emotes(
-    query: String,
-    broadcaster_id: String,
-    limit: Option<usize>,
-    emote_manager_ref: State<'_, SharedEmoteManager>,
+async fn export_chat_log(
+    channel: String,
+    from: i64,
+    to: i64,
+    format: types::ChatLogFormat,
+    app_handle: AppHandle,
     state_syncer: State<'_, StateSyncer>,
-) -> Result<Vec<emote::Emote>, String> {
-    let emote_manager = emote_manager_ref.lock().await.clone();
-    let settings = app_settings(state_syncer.inner());
-    let cache = emote_manager.get_emote_cache(broadcaster_id, &settings.emotes);
-    Ok(cache.search_emotes(
-        &query,
-        limit.unwrap_or(settings.emotes.autocomplete_result_limit),
-    ))
+) -> Result<String, PepoError> {
+    if from >= to {
+        return Err(PepoError::invalid_input(
+            "export range must end after it starts",
+        ));
+    }
+    let store = app_handle
+        .try_state::<history::HistoryStore>()
+        .ok_or_else(history_unavailable)?
+        .inner()
+        .clone();
+    let default_root = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| PepoError::internal("failed to resolve app data directory", e))?
+        .join(chatlog::DEFAULT_LOG_DIR);
+    let root = chatlog::log_root(&app_settings(state_syncer.inner()).chat_log, &default_root);
+    let channel_login = channel.trim().to_lowercase();
+
+    let path = tokio::task::spawn_blocking(move || {
+        // Include whatever is still queued for the history writer.
+        store.flush();
+        chatlog::export(&store, &root, &channel_login, from, to, format)
+    })
+    .await
+    .map_err(|e| PepoError::internal("chat log export failed", e))?
+    .map_err(|e| PepoError::internal("failed to export chat log", e))?;
+    Ok(path.display().to_string())
 }
 
 #[tauri::command]
@@ -293,20 +1261,17 @@ async fn get_channel_info(
     channel_name: String,
     token_manager: State<'_, TokenManager>,
     client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
-) -> Result<types::ChannelInfo, String> {
+) -> Result<types::ChannelInfo, PepoError> {
     debug!("get_channel_info: channel={}", channel_name);
 
-    let token = token_manager
-        .active_twitch_token()
-        .await
-        .ok_or_else(|| "no active token".to_owned())?;
+    let token = active_token(&token_manager).await?;
     let client = client_ref.inner();
 
     let channel = client
         .get_channel_from_login(&channel_name, &token)
         .await
-        .map_err(|e| format!("failed to get channel: {:?}", e))?
-        .ok_or_else(|| format!("channel not found: {}", channel_name))?;
+        .map_err(PepoError::from_client_error)?
+        .ok_or_else(|| PepoError::not_found(format!("channel not found: {}", channel_name)))?;
 
     let mut channel_info = types::ChannelInfo::from(channel);
 
@@ -331,13 +1296,10 @@ async fn join_chat(
     token_manager: State<'_, TokenManager>,
     client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
     state_syncer: State<'_, StateSyncer>,
-) -> Result<types::ChannelInfo, String> {
+) -> Result<types::ChannelInfo, PepoError> {
     debug!("join: channel={}", channel_name);
 
-    let token = token_manager
-        .active_twitch_token()
-        .await
-        .ok_or_else(|| "no active token".to_owned())?;
+    let token = active_token(&token_manager).await?;
     let client = client_ref.inner().clone();
     let eventsub_manager = eventsub_manager_ref.lock().await.clone();
     let badge_manager = badge_manager_ref.lock().await.clone();
@@ -347,17 +1309,17 @@ async fn join_chat(
     let channel = client
         .get_channel_from_login(&channel_name, &token)
         .await
-        .map_err(|e| format!("failed to get channel: {:?}", e))?
-        .ok_or_else(|| format!("channel not found: {}", channel_name))?;
+        .map_err(PepoError::from_client_error)?
+        .ok_or_else(|| PepoError::not_found(format!("channel not found: {}", channel_name)))?;
 
     debug!("join: got channel info - {:?}", channel.clone());
 
     let channel_info = types::ChannelInfo::from(channel.clone());
     let broadcaster_id = channel.broadcaster_id.to_string();
-    let emote_settings = settings.emotes;
+    let emote_settings = settings.emotes.clone();
     let provider_settings = settings.providers;
 
-    spawn_joined_channel_cache_refresh(app_handle, channel_info.clone(), token.clone());
+    spawn_joined_channel_cache_refresh(app_handle.clone(), channel_info.clone(), token.clone());
 
     badge_manager.hydrate_global(&provider_settings).await;
     badge_manager
@@ -365,6 +1327,20 @@ async fn join_chat(
         .await;
     emote_manager.preload(&broadcaster_id, &emote_settings, &provider_settings);
 
+    if settings.backfill.enabled {
+        let highlights =
+            highlight::HighlightSet::compile(&settings.highlights.rules, token.login.as_str());
+        backfill_channel(
+            &app_handle,
+            channel.broadcaster_login.as_str(),
+            &badge_manager,
+            &emote_manager,
+            &highlights,
+            &settings,
+        )
+        .await;
+    }
+
     if let Err(e) = eventsub_manager
         .join_chat(
             channel.broadcaster_id.clone(),
@@ -375,7 +1351,7 @@ async fn join_chat(
         .await
     {
         error!("join_chat - {:?}", e);
-        return Err(format!("failed to join channel chat: {:?}", e));
+        return Err(PepoError::internal("failed to join channel chat", e));
     }
 
     debug!("joined channel");
@@ -398,24 +1374,91 @@ async fn join_chat(
     Ok(channel_info)
 }
 
+/// Emits recent messages for a channel being joined ahead of its EventSub
+/// subscription, so they land before any live message.
+async fn backfill_channel(
+    app_handle: &AppHandle,
+    login: &str,
+    badge_manager: &BadgeManager,
+    emote_manager: &EmoteManager,
+    highlights: &highlight::HighlightSet,
+    settings: &AppSettings,
+) {
+    let client = app_handle.state::<backfill::BackfillClient>();
+    let messages = match backfill::recent_messages(
+        &client,
+        login,
+        badge_manager,
+        emote_manager,
+        highlights,
+        settings,
+    )
+    .await
+    {
+        Ok(messages) => messages,
+        Err(e) => {
+            warn!("failed to backfill {}: {}", login, e);
+            return;
+        }
+    };
+
+    let seen = app_handle.state::<backfill::SeenMessageIds>();
+    let message_filters = app_handle.state::<filter::MessageFilters>();
+    let history_store = app_handle.try_state::<history::HistoryStore>();
+    let key = format!("chat_message:{}", login);
+    let mut batch = Vec::new();
+    let mut emitted = 0;
+    for (sent_at_ms, message) in messages {
+        if !seen.insert(login, &message.message_id) {
+            continue;
+        }
+        let mut message = message.with_emote_theme(&settings.appearance.theme);
+        if !message_filters.apply(&settings.filters, &mut message) {
+            continue;
+        }
+        if let Some(store) = &history_store {
+            store.record_at(history::HistoryEntry::Message(message.clone()), sent_at_ms);
+        }
+        if settings.chat.batch_messages {
+            batch.push(message);
+        } else if let Err(e) = app_handle.emit(&key, message) {
+            error!("failed to emit backfilled message: {}", e);
+            return;
+        } else {
+            emitted += 1;
+        }
+    }
+    // Backfill arrives all at once, so it goes out as a single batch.
+    if !batch.is_empty() {
+        let count = batch.len();
+        if let Err(e) = app_handle.emit(&format!("chat_messages:{}", login), batch) {
+            error!("failed to emit backfilled messages: {}", e);
+            return;
+        }
+        emitted += count;
+    }
+    debug!(channel = login, emitted, "backfilled recent messages");
+}
+
 #[tauri::command]
 #[specta::specta]
 async fn leave_chat(
     channel_name: String,
-    _app_handle: AppHandle,
+    app_handle: AppHandle,
     eventsub_manager_ref: State<'_, SharedEventSubManager>,
     token_manager: State<'_, TokenManager>,
     client_ref: State<'_, HelixClient<'static, reqwest::Client>>,
-) -> Result<(), String> {
+) -> Result<(), PepoError> {
     debug!("leave: channel={}", channel_name);
 
-    let token = token_manager
-        .active_twitch_token()
-        .await
-        .ok_or_else(|| "no active token".to_owned())?;
+    let token = active_token(&token_manager).await?;
     let client = client_ref.inner();
     let eventsub_manager = eventsub_manager_ref.lock().await.clone();
 
+    if let Some(automod_queue) = app_handle.try_state::<automod::AutomodQueue>() {
+        automod_queue.clear_channel(&channel_name);
+    }
+
     match eventsub_manager
         .leave_chat(channel_name, client, token.clone())
         .await
@@ -429,47 +1472,44 @@ async fn leave_chat(
 
 #[tauri::command]
 #[specta::specta]
-fn get_followed_streams(
+async fn get_followed_streams(
     _app_handle: AppHandle,
     token_manager: State<'_, TokenManager>,
     client: State<'_, HelixClient<'static, reqwest::Client>>,
-) -> Result<Vec<types::Stream>, String> {
+) -> Result<Vec<types::Stream>, PepoError> {
     let client = client.inner();
 
-    let token_guard = tauri::async_runtime::block_on(token_manager.active_twitch_token())
-        .ok_or_else(|| "no active token".to_owned())?;
-    let streams = tauri::async_runtime::block_on(
-        client
-            .get_followed_streams(&token_guard)
-            .try_collect::<Vec<_>>(),
-    )
-    .unwrap();
+    let token_guard = active_token(&token_manager).await?;
+    let streams: Vec<_> = client
+        .get_followed_streams(&token_guard)
+        .try_collect()
+        .await
+        .map_err(PepoError::from_client_error)?;
 
     Ok(streams.into_iter().map(types::Stream::from).collect())
 }
 
 #[tauri::command]
 #[specta::specta]
-fn get_followed_channels(
+async fn get_followed_channels(
     _app_handle: AppHandle,
     token_manager: State<'_, TokenManager>,
     client: State<'_, HelixClient<'static, reqwest::Client>>,
     state_syncer: State<'_, StateSyncer>,
-) -> Result<Vec<types::Broadcaster>, String> {
+) -> Result<Vec<types::Broadcaster>, PepoError> {
     let client = client.inner();
     let channel_cache_settings = app_settings(state_syncer.inner()).channel_cache;
 
-    let token_guard = tauri::async_runtime::block_on(token_manager.active_twitch_token())
-        .ok_or_else(|| "no active token".to_owned())?;
-    let channels = match tauri::async_runtime::block_on(
-        client
-            .get_followed_channels(token_guard.user_id.to_string(), &token_guard)
-            .try_collect::<Vec<_>>(),
-    ) {
+    let token_guard = active_token(&token_manager).await?;
+    let channels: Vec<_> = match client
+        .get_followed_channels(token_guard.user_id.to_string(), &token_guard)
+        .try_collect()
+        .await
+    {
         Ok(channels) => channels,
         Err(err) => {
             error!("failed to get followed channels: err={}", err.to_string());
-            return Err(err.to_string());
+            return Err(PepoError::from_client_error(err));
         }
     };
 
@@ -479,12 +1519,11 @@ fn get_followed_channels(
             chunk.iter().map(|b| b.broadcaster_id.clone()).collect();
 
         debug!("ids={:?}", ids);
-        let mut u: Vec<twitch_api::helix::users::User> = tauri::async_runtime::block_on(
-            client
-                .get_users_from_ids(&twitch_api::types::Collection::from(ids), &token_guard)
-                .try_collect::<Vec<_>>(),
-        )
-        .unwrap();
+        let mut u: Vec<twitch_api::helix::users::User> = client
+            .get_users_from_ids(&twitch_api::types::Collection::from(ids), &token_guard)
+            .try_collect()
+            .await
+            .map_err(PepoError::from_client_error)?;
         users.append(&mut u);
     }
 
@@ -511,7 +1550,7 @@ async fn refresh_joined_channel_cache(
     app_handle: &AppHandle,
     channel_info: types::ChannelInfo,
     token: &twitch_oauth2::UserToken,
-) -> Result<(), String> {
+) -> Result<(), PepoError> {
     let client_ref = app_handle.state::<HelixClient<'static, reqwest::Client>>();
     let client = client_ref.inner();
     let state_syncer = app_handle.state::<StateSyncer>();
@@ -535,9 +1574,8 @@ async fn refresh_joined_channel_cache(
         }
     };
 
-    let live_streams = fetch_live_streams_for_logins(client, &[candidate.login.clone()], token)
-        .await
-        .map_err(|err| format!("failed to get joined channel stream: {err}"))?;
+    let live_streams =
+        fetch_live_streams_for_logins(client, &[candidate.login.clone()], token).await?;
     let mut cache = state_syncer.snapshot::<types::ChannelCache>("channel_cache");
     let previous_status = cache.channels.get(&candidate.login).cloned();
 
@@ -557,7 +1595,7 @@ async fn fetch_live_streams_for_logins(
     client: &HelixClient<'static, reqwest::Client>,
     logins: &[String],
     token: &twitch_oauth2::UserToken,
-) -> Result<HashMap<String, types::Stream>, String> {
+) -> Result<HashMap<String, types::Stream>, PepoError> {
     if logins.is_empty() {
         return Ok(HashMap::new());
     }
@@ -567,7 +1605,7 @@ async fn fetch_live_streams_for_logins(
         .get_streams_from_logins(&login_collection, token)
         .try_collect()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(PepoError::from_client_error)?;
 
     Ok(streams
         .into_iter()
@@ -578,12 +1616,8 @@ async fn fetch_live_streams_for_logins(
         .collect())
 }
 
-async fn poll_channel_cache(app_handle: &AppHandle) -> Result<(), String> {
-    let token = app_handle
-        .state::<TokenManager>()
-        .active_twitch_token()
-        .await
-        .ok_or_else(|| "no active token".to_owned())?;
+async fn poll_channel_cache(app_handle: &AppHandle) -> Result<(), PepoError> {
+    let token = active_token(app_handle.state::<TokenManager>().inner()).await?;
     let client = app_handle.state::<HelixClient<'static, reqwest::Client>>();
     let client = client.inner();
     let state_syncer = app_handle.state::<StateSyncer>();
@@ -604,7 +1638,22 @@ async fn poll_channel_cache(app_handle: &AppHandle) -> Result<(), String> {
         .get_followed_channels(token.user_id.to_string(), &token)
         .try_collect()
         .await
-        .map_err(|e| format!("failed to get followed channels: {}", e))?;
+        .map_err(PepoError::from_client_error)?;
+
+    {
+        let emote_manager = app_handle
+            .state::<SharedEmoteManager>()
+            .lock()
+            .await
+            .clone();
+        let followed_ids = channels
+            .iter()
+            .map(|channel| channel.broadcaster_id.to_string())
+            .collect();
+        tauri::async_runtime::spawn(async move {
+            emote_manager.update_followed_channels(followed_ids);
+        });
+    }
 
     let followed_candidates: Vec<ChannelCacheCandidate> = channels
         .iter()
@@ -641,7 +1690,7 @@ async fn poll_channel_cache(app_handle: &AppHandle) -> Result<(), String> {
             .get_users_from_ids(&id_collection, &token)
             .try_collect()
             .await
-            .map_err(|e| format!("failed to get users: {}", e))?;
+            .map_err(PepoError::from_client_error)?;
         for user in chunk_users {
             users.insert(user.login.to_string(), user);
         }
@@ -651,9 +1700,7 @@ async fn poll_channel_cache(app_handle: &AppHandle) -> Result<(), String> {
         .iter()
         .map(|candidate| candidate.login.clone())
         .collect();
-    let live_streams = fetch_live_streams_for_logins(client, &logins, &token)
-        .await
-        .map_err(|e| format!("failed to get streams: {}", e))?;
+    let live_streams = fetch_live_streams_for_logins(client, &logins, &token).await?;
     let cache = channel_cache_from_candidates(&candidates, &users, &live_streams, &previous_cache);
 
     info!(
@@ -666,17 +1713,12 @@ async fn poll_channel_cache(app_handle: &AppHandle) -> Result<(), String> {
     Ok(())
 }
 
-fn is_auth_failure_error(error: &str) -> bool {
-    let lower = error.to_lowercase();
-    lower.contains("401") || lower.contains("unauthorized") || lower.contains("invalid oauth token")
-}
-
 async fn handle_channel_cache_poll_error(
     app_handle: &AppHandle,
     context: &str,
-    error: String,
+    error: PepoError,
 ) -> bool {
-    if is_auth_failure_error(&error) {
+    if error.is_auth_expired() {
         error!(
             "channel cache poll failed due to auth; clearing auth: {}",
             error
@@ -700,6 +1742,59 @@ async fn handle_channel_cache_poll_error(
     false
 }
 
+/// Shows the device code, opens the verification page and waits for the user
+/// to approve it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DeviceCodeFlow {
+    Login,
+    /// The user stays logged in with their current token until Twitch hands
+    /// out the upgraded one.
+    ScopeUpgrade,
+}
+
+async fn authorize_device_code(
+    app_handle: &AppHandle,
+    state_syncer: &StateSyncer,
+    token_manager: &TokenManager,
+    device_code: twitch_oauth2::id::DeviceCodeResponse,
+    flow: DeviceCodeFlow,
+) -> Result<twitch_oauth2::UserToken, PepoError> {
+    {
+        let auth_state_ref = state_syncer.get::<AuthState>("auth_state");
+        let mut auth_state = auth_state_ref.lock().unwrap();
+
+        auth_state.device_code = device_code.user_code;
+        if flow == DeviceCodeFlow::Login {
+            auth_state.phase = types::AuthPhase::WaitingForAuth;
+            auth_state.token = None;
+        }
+    }
+
+    debug!("pausing to show verification code");
+    tokio::time::sleep(app_settings(state_syncer).auth.login_activation_delay()).await;
+
+    info!("login {}", device_code.verification_uri);
+    // The code is on screen either way, so the page can be opened by hand.
+    if let Err(e) = app_handle
+        .opener()
+        .open_url(device_code.verification_uri.clone(), None::<&str>)
+    {
+        warn!("failed to open verification page: {}", e);
+    }
+
+    let result = token_manager.finish_device_code_flow().await;
+    {
+        let auth_state_ref = state_syncer.get::<AuthState>("auth_state");
+        let mut auth_state = auth_state_ref.lock().unwrap();
+
+        auth_state.device_code = String::new();
+        if result.is_err() && flow == DeviceCodeFlow::Login {
+            auth_state.phase = types::AuthPhase::FailedAuth;
+        }
+    }
+    result
+}
+
 #[tauri::command]
 #[specta::specta]
 async fn login(
@@ -708,7 +1803,7 @@ async fn login(
     client: State<'_, HelixClient<'static, reqwest::Client>>,
     token_manager: State<'_, TokenManager>,
     quick: bool,
-) -> Result<types::UserToken, String> {
+) -> Result<types::UserToken, PepoError> {
     let client = client.inner();
     let token_manager = token_manager.inner().clone();
     info!(quick, "login");
@@ -716,7 +1811,8 @@ async fn login(
     let auth_state_snapshot = state_syncer.snapshot::<AuthState>("auth_state");
     token_manager
         .ensure_loaded(auth_state_snapshot.token.clone())
-        .await?;
+        .await
+        .map_err(|e| PepoError::internal("failed to load stored tokens", e))?;
 
     let twitch_token: twitch_oauth2::UserToken;
     let user_token: types::UserToken;
@@ -724,7 +1820,7 @@ async fn login(
         .await
     {
         if quick {
-            return Err("no valid token".to_string());
+            return Err(PepoError::AuthMissing);
         }
     }
 
@@ -733,44 +1829,27 @@ async fn login(
         twitch_token = token_manager
             .active_twitch_token()
             .await
-            .ok_or_else(|| "no active token".to_owned())?;
+            .ok_or(PepoError::AuthMissing)?;
         user_token = token_manager
             .active_public_token()
             .await
-            .ok_or_else(|| "no active token".to_owned())?;
+            .ok_or(PepoError::AuthMissing)?;
     } else if quick {
-        return Err("no valid token".to_string());
+        return Err(PepoError::AuthMissing);
     } else {
-        let device_code = token_manager.start_device_code_flow().await;
-
-        {
-            let auth_state_ref = state_syncer.get::<AuthState>("auth_state");
-            let mut auth_state = auth_state_ref.lock().unwrap();
-
-            auth_state.phase = types::AuthPhase::WaitingForAuth;
-            auth_state.device_code = device_code.user_code;
-            auth_state.token = None;
-        }
-
-        debug!("pausing to show verification code");
-        tokio::time::sleep(
-            app_settings(state_syncer.inner())
-                .auth
-                .login_activation_delay(),
+        let device_code = token_manager.start_device_code_flow().await?;
+        twitch_token = authorize_device_code(
+            &app_handle,
+            state_syncer.inner(),
+            &token_manager,
+            device_code,
+            DeviceCodeFlow::Login,
         )
-        .await;
-
-        info!("login {}", device_code.verification_uri);
-        app_handle
-            .opener()
-            .open_url(device_code.verification_uri.clone(), None::<&str>)
-            .unwrap();
-
-        twitch_token = token_manager.finish_device_code_flow().await;
+        .await?;
         user_token = token_manager
             .active_public_token()
             .await
-            .ok_or_else(|| "no active token".to_owned())?;
+            .ok_or(PepoError::AuthMissing)?;
     }
 
     {
@@ -830,6 +1909,19 @@ async fn login(
         });
     }
 
+    // Twitch block list, when filters honour it
+    if app_settings(&state_syncer).filters.honor_block_list {
+        let client = client.clone();
+        let twitch_token = twitch_token.clone();
+        let message_filters = app_handle.state::<filter::MessageFilters>().inner().clone();
+        tauri::async_runtime::spawn(async move {
+            match filter::fetch_blocked_user_ids(&client, &twitch_token).await {
+                Ok(blocked) => message_filters.set_blocked_user_ids(blocked),
+                Err(err) => warn!("failed to load block list: {}", err),
+            }
+        });
+    }
+
     // Global badges
     {
         let badge_manager = badge_manager.clone();
@@ -854,7 +1946,7 @@ async fn login(
         });
     }
 
-    // EventSub connection + message processing thread
+    // EventSub connection + message processing task
     {
         let eventsub_manager = eventsub_manager.clone();
         let client = client.clone();
@@ -863,7 +1955,32 @@ async fn login(
         let badge_manager_ref = badge_manager.clone();
         let emote_manager_ref = emote_manager.clone();
         let state_syncer_ref = state_syncer.inner().clone();
+        let history_store = app_handle
+            .try_state::<history::HistoryStore>()
+            .map(|store| store.inner().clone());
+        let chat_logger = app_handle
+            .try_state::<chatlog::ChatLogger>()
+            .map(|logger| logger.inner().clone());
+        let seen_message_ids = app_handle
+            .state::<backfill::SeenMessageIds>()
+            .inner()
+            .clone();
+        let automod_queue = app_handle.state::<automod::AutomodQueue>().inner().clone();
+        let unban_inbox = app_handle.state::<unban::UnbanInbox>().inner().clone();
+        let trust_statuses = app_handle.state::<trust::TrustStatuses>().inner().clone();
+        let whispers = app_handle.state::<whisper::WhisperInbox>().inner().clone();
+        let message_filters = app_handle.state::<filter::MessageFilters>().inner().clone();
+        let user_id = twitch_token.user_id.to_string();
+        let highlighter = highlight::Highlighter::new(twitch_token.login.as_str());
         let eventsub_settings_reader = make_eventsub_settings_reader(state_syncer.inner().clone());
+        let chat_batcher = {
+            let app_ref = app_handle.clone();
+            batch::MessageBatcher::new(move |login: &str, messages: Vec<types::ChannelMessage>| {
+                if let Err(e) = app_ref.emit(&format!("chat_messages:{}", login), messages) {
+                    error!("failed to emit chat messages for {}: {}", login, e);
+                }
+            })
+        };
 
         {
             let eventsub_handle_state = app_handle.state::<SharedEventSubHandles>();
@@ -878,47 +1995,284 @@ async fn login(
                 Ok(runtime) => runtime,
                 Err(e) => {
                     error!("failed to start eventsub: {}", e);
-                    return Err("failed to start eventsub".to_string());
+                    return Err(PepoError::internal("failed to start eventsub", e));
                 }
             };
-        let eventsub::EventSubRuntime { events, handles } = eventsub_runtime;
+        let eventsub::EventSubRuntime {
+            mut events,
+            queue,
+            handles,
+        } = eventsub_runtime;
 
         {
             let eventsub_handle_state = app_handle.state::<SharedEventSubHandles>();
             *eventsub_handle_state.lock().await = handles;
         }
+        *app_handle.state::<SharedEventQueue>().lock().await = Some(queue);
 
-        std::thread::spawn(move || {
+        // Parsing, badge and emote lookups all happen here, off the socket
+        // task and the IPC thread; it ends when the EventSub runtime is dropped.
+        tauri::async_runtime::spawn(async move {
             use twitch_api::eventsub::{Message as M, Payload as P};
 
-            for msg in events {
+            let archive = |entry: history::HistoryEntry| {
+                if let Some(logger) = &chat_logger {
+                    logger.log(&entry, history::unix_now_ms());
+                }
+                if let Some(store) = &history_store {
+                    store.record(entry);
+                }
+            };
+
+            while let Some(msg) = events.recv().await {
                 match msg {
                     eventsub::EventSubMessage::AuthFailed(reason) => {
                         error!("EventSub auth failed: {}", reason);
-                        clear_auth(&app_ref);
+                        clear_auth_async(&app_ref, true).await;
                         break;
                     }
+                    eventsub::EventSubMessage::Overloaded { login, dropped } => {
+                        let marker = eventqueue::OverloadMarker { dropped };
+                        if let Err(e) = app_ref.emit(&format!("chat_overloaded:{}", login), marker)
+                        {
+                            error!("failed to emit overload marker for {}: {}", login, e);
+                        }
+                    }
                     eventsub::EventSubMessage::Notification(notification) => {
                         match notification.event {
                             twitch_api::eventsub::Event::ChannelChatMessageV1(P {
                                 message: M::Notification(chat_message),
                                 ..
                             }) => {
+                                if !seen_message_ids.insert(
+                                    chat_message.broadcaster_user_login.as_str(),
+                                    chat_message.message_id.as_str(),
+                                ) {
+                                    debug!(
+                                        "skipping duplicate chat message {}",
+                                        chat_message.message_id
+                                    );
+                                    continue;
+                                }
                                 let settings = app_settings(&state_syncer_ref);
-                                let channel_msg = types::ChannelMessage::new(
+                                let highlights = highlighter.compiled(&settings.highlights.rules);
+                                let mut channel_msg = types::ChannelMessage::new(
                                     chat_message.clone(),
                                     notification.ts.to_string(),
                                     badge_manager_ref.clone(),
                                     emote_manager_ref.clone(),
                                     settings.emotes,
+                                    &highlights,
                                     app_ref.clone(),
+                                )
+                                .await
+                                .with_emote_theme(&settings.appearance.theme);
+                                channel_msg.trust_status = trust_statuses.get(
+                                    chat_message.broadcaster_user_id.as_str(),
+                                    chat_message.chatter_user_id.as_str(),
+                                );
+                                if !message_filters.apply(&settings.filters, &mut channel_msg) {
+                                    trace!("filtered chat message {}", chat_message.message_id);
+                                    continue;
+                                }
+                                let login = chat_message.broadcaster_user_login.as_str();
+                                trace!("chat message: channel={} msg={:?}", login, channel_msg);
+                                archive(history::HistoryEntry::Message(channel_msg.clone()));
+                                if let Some(notification) =
+                                    highlight::HighlightNotification::for_message(&channel_msg)
+                                {
+                                    if let Err(e) =
+                                        app_ref.emit("highlight_notification", notification)
+                                    {
+                                        error!("failed to emit highlight notification: {}", e);
+                                    }
+                                }
+                                if settings.chat.batch_messages {
+                                    chat_batcher.push(
+                                        login,
+                                        channel_msg,
+                                        settings.chat.batch_limits(),
+                                    );
+                                } else if let Err(e) =
+                                    app_ref.emit(&format!("chat_message:{}", login), channel_msg)
+                                {
+                                    error!("failed to emit chat message for {}: {}", login, e);
+                                }
+                            }
+                            twitch_api::eventsub::Event::ChannelChatNotificationV1(P {
+                                message: M::Notification(chat_notification),
+                                ..
+                            }) => {
+                                if emote::providers::twitch::notification_grants_user_emotes(
+                                    &chat_notification,
+                                    &user_id,
+                                ) {
+                                    let emote_manager = emote_manager_ref.clone();
+                                    let broadcaster_id =
+                                        chat_notification.broadcaster_user_id.to_string();
+                                    tauri::async_runtime::spawn(async move {
+                                        emote_manager.refresh_user_emotes(&broadcaster_id);
+                                    });
+                                }
+                                archive(history::HistoryEntry::Notice(
+                                    history::ChannelNotice::new(
+                                        &chat_notification,
+                                        notification.ts.to_string(),
+                                    ),
+                                ));
+                            }
+                            twitch_api::eventsub::Event::ChannelChatClearV1(P {
+                                message: M::Notification(clear),
+                                ..
+                            }) => {
+                                archive(history::HistoryEntry::Moderation(
+                                    history::ModerationEvent::clear_chat(
+                                        &clear,
+                                        notification.ts.to_string(),
+                                    ),
+                                ));
+                            }
+                            twitch_api::eventsub::Event::ChannelChatClearUserMessagesV1(P {
+                                message: M::Notification(clear),
+                                ..
+                            }) => {
+                                archive(history::HistoryEntry::Moderation(
+                                    history::ModerationEvent::clear_user_messages(
+                                        &clear,
+                                        notification.ts.to_string(),
+                                    ),
+                                ));
+                            }
+                            twitch_api::eventsub::Event::ChannelChatMessageDeleteV1(P {
+                                message: M::Notification(deleted),
+                                ..
+                            }) => {
+                                archive(history::HistoryEntry::Moderation(
+                                    history::ModerationEvent::delete_message(
+                                        &deleted,
+                                        notification.ts.to_string(),
+                                    ),
+                                ));
+                            }
+                            twitch_api::eventsub::Event::ChannelModerateV2(P {
+                                message: M::Notification(moderated),
+                                ..
+                            }) => {
+                                let Some(entry) = modlog::ModLogEntry::new(
+                                    &moderated,
+                                    notification.ts.to_string(),
+                                ) else {
+                                    continue;
+                                };
+                                let login = entry.broadcaster_user_login.clone();
+                                if let Some(annotation) = entry.annotation() {
+                                    if let Err(e) = app_ref
+                                        .emit(&format!("message_moderated:{}", login), annotation)
+                                    {
+                                        error!("failed to emit message annotation: {}", e);
+                                    }
+                                }
+                                if let Err(e) =
+                                    app_ref.emit(&format!("mod_action:{}", login), &entry)
+                                {
+                                    error!("failed to emit moderator action: {}", e);
+                                }
+                                archive(history::HistoryEntry::ModAction(entry));
+                            }
+                            twitch_api::eventsub::Event::ChannelSuspiciousUserMessageV1(P {
+                                message: M::Notification(suspicious),
+                                ..
+                            }) => {
+                                let update = trust_statuses.record_message(&suspicious);
+                                emit_trust_status(
+                                    &app_ref,
+                                    suspicious.broadcaster_user_login.as_str(),
+                                    &update,
+                                );
+                            }
+                            twitch_api::eventsub::Event::ChannelSuspiciousUserUpdateV1(P {
+                                message: M::Notification(updated),
+                                ..
+                            }) => {
+                                let update = trust_statuses.record_update(&updated);
+                                emit_trust_status(
+                                    &app_ref,
+                                    updated.broadcaster_user_login.as_str(),
+                                    &update,
+                                );
+                            }
+                            twitch_api::eventsub::Event::AutomodMessageHoldV1(P {
+                                message: M::Notification(held),
+                                ..
+                            }) => {
+                                let pending = automod_queue.hold(automod::HeldMessage::new(&held));
+                                emit_automod_queue(
+                                    &app_ref,
+                                    held.broadcaster_user_login.as_str(),
+                                    pending,
+                                );
+                            }
+                            twitch_api::eventsub::Event::AutomodMessageUpdateV1(P {
+                                message: M::Notification(update),
+                                ..
+                            }) => {
+                                debug!(
+                                    "automod message {} resolved by {}: {:?}",
+                                    update.message_id, update.moderator_user_login, update.status
                                 );
-                                let key =
-                                    format!("chat_message:{}", chat_message.broadcaster_user_login);
-                                trace!("chat message: id={} msg={:?}", key, channel_msg);
-                                app_ref
-                                    .emit(&key, channel_msg)
-                                    .expect("unable to emit state")
+                                if let Some((_, pending)) =
+                                    automod_queue.resolve(update.message_id.as_str())
+                                {
+                                    emit_automod_queue(
+                                        &app_ref,
+                                        update.broadcaster_user_login.as_str(),
+                                        pending,
+                                    );
+                                }
+                            }
+                            twitch_api::eventsub::Event::ChannelUnbanRequestCreateV1(P {
+                                message: M::Notification(created),
+                                ..
+                            }) => {
+                                let recent_messages = match history_store.clone() {
+                                    Some(store) => {
+                                        let broadcaster_id =
+                                            created.broadcaster_user_id.to_string();
+                                        let user_id = created.user_id.to_string();
+                                        tokio::task::spawn_blocking(move || {
+                                            store.chatter_messages(
+                                                &broadcaster_id,
+                                                &user_id,
+                                                unban::RECENT_MESSAGE_LIMIT,
+                                            )
+                                        })
+                                        .await
+                                        .map_err(eyre::Report::from)
+                                        .and_then(|result| result)
+                                        .inspect_err(|e| {
+                                            warn!("failed to read requester history: {:#}", e)
+                                        })
+                                        .unwrap_or_default()
+                                    }
+                                    None => Vec::new(),
+                                };
+                                let request = unban::UnbanRequest::new(&created, recent_messages);
+                                unban_inbox.add(request.clone());
+                                emit_unban_request(&app_ref, &request);
+                            }
+                            twitch_api::eventsub::Event::ChannelUnbanRequestResolveV1(P {
+                                message: M::Notification(resolution),
+                                ..
+                            }) => {
+                                if let Some(request) = unban_inbox.resolve_from_event(&resolution) {
+                                    emit_unban_request(&app_ref, &request);
+                                }
+                            }
+                            twitch_api::eventsub::Event::UserWhisperMessageV1(P {
+                                message: M::Notification(received),
+                                ..
+                            }) => {
+                                whispers.receive(&received);
                             }
                             _ => debug!("event notification: {:?}", notification.event),
                         }
@@ -970,8 +2324,13 @@ async fn login(
     Ok(user_token)
 }
 
+/// Clears auth state, channel cache, and background auth tasks.
+/// Used by both explicit logout and automatic auth expiration handling.
 async fn clear_auth_async(app_handle: &AppHandle, abort_poll: bool) {
     let state_syncer = app_handle.state::<StateSyncer>();
+    app_handle
+        .state::<filter::MessageFilters>()
+        .set_blocked_user_ids(Default::default());
 
     {
         let poll_handle_state = app_handle.state::<SharedPollHandle>();
@@ -989,6 +2348,7 @@ async fn clear_auth_async(app_handle: &AppHandle, abort_poll: bool) {
             handle.abort();
         }
     }
+    *app_handle.state::<SharedEventQueue>().lock().await = None;
 
     if let Some(token_manager) = app_handle.try_state::<TokenManager>() {
         if let Err(err) = token_manager.remove_active_token().await {
@@ -1008,12 +2368,6 @@ async fn clear_auth_async(app_handle: &AppHandle, abort_poll: bool) {
     }
 }
 
-/// Clears auth state, channel cache, and background auth tasks.
-/// Used by both explicit logout and automatic auth expiration handling.
-fn clear_auth(app_handle: &AppHandle) {
-    tauri::async_runtime::block_on(clear_auth_async(app_handle, true));
-}
-
 fn persist_authorized_token(state_syncer: &StateSyncer, token: types::UserToken) {
     state_syncer.update::<AuthState>(
         "auth_state",
@@ -1188,9 +2542,12 @@ impl inbound::Scrubber<tauri::Wry> for PepoScrubber {
 
 #[tauri::command]
 #[specta::specta]
-fn logout(app_handle: AppHandle, _state_syncer: State<'_, StateSyncer>) -> Result<(), String> {
+async fn logout(
+    app_handle: AppHandle,
+    _state_syncer: State<'_, StateSyncer>,
+) -> Result<(), PepoError> {
     info!("logout");
-    clear_auth(&app_handle);
+    clear_auth_async(&app_handle, true).await;
     Ok(())
 }
 
@@ -1255,6 +2612,15 @@ pub fn run() {
     let builder = internal::apply_plugins(builder, internal_build);
 
     let _builder = builder
+        .register_uri_scheme_protocol(LOCAL_EMOTE_SCHEME, |ctx, request| {
+            match ctx.app_handle().try_state::<LocalEmoteFiles>() {
+                Some(files) => emote::providers::local::serve(files.inner(), &request),
+                None => tauri::http::Response::builder()
+                    .status(404)
+                    .body(Vec::new())
+                    .unwrap(),
+            }
+        })
         .invoke_handler(public_handlers.invoke_handler())
         .setup(move |app| {
             #[cfg(target_os = "linux")]
@@ -1389,10 +2755,55 @@ pub fn run() {
             state_syncer.set("internal_state", internal_state);
             state_syncer.set("channel_cache", ChannelCache::default());
             state_syncer.set(APP_SETTINGS_KEY, settings);
+            match open_history_store(app.handle(), state_syncer.clone()) {
+                Ok(store) => {
+                    app.manage(store);
+                }
+                Err(e) => error!("chat history disabled: {:#}", e),
+            }
+            match start_chat_logger(app.handle(), state_syncer.clone()) {
+                Ok(logger) => {
+                    app.manage(logger);
+                }
+                Err(e) => error!("chat logging disabled: {:#}", e),
+            }
             app.manage::<StateSyncer>(state_syncer);
             app.manage::<SharedPollHandle>(Mutex::new(None));
             app.manage::<RefreshLock>(Mutex::new(()));
             app.manage::<SharedEventSubHandles>(Mutex::new(Vec::new()));
+            app.manage::<SharedEventQueue>(Mutex::new(None));
+            app.manage(LocalEmoteFiles::default());
+            app.manage(backfill::SeenMessageIds::default());
+            app.manage(backfill::BackfillClient::default());
+            app.manage(usercard::UserCardCache::default());
+            app.manage(automod::AutomodQueue::default());
+            app.manage(trust::TrustStatuses::default());
+            app.manage(filter::MessageFilters::default());
+            app.manage(unban::UnbanInbox::open(Arc::new(
+                unban::TauriUnbanRequestStore::new(app.handle().clone()),
+            )));
+            let whisper_handle = app.handle().clone();
+            app.manage(whisper::WhisperInbox::open(
+                Arc::new(whisper::TauriWhisperStore::new(app.handle().clone())),
+                move |event: &whisper::WhisperEvent| {
+                    if let Err(e) = whisper_handle.emit("whisper", event) {
+                        error!(
+                            "failed to emit whisper from {}: {}",
+                            event.thread.peer_user_login, e
+                        );
+                    }
+                },
+            ));
+
+            let delivery_handle = app.handle().clone();
+            app.manage(outgoing::OutgoingQueue::new(
+                client.clone(),
+                move |update: &outgoing::DeliveryUpdate| {
+                    if let Err(e) = delivery_handle.emit("message_delivery", update) {
+                        error!("failed to emit delivery status for {}: {}", update.id, e);
+                    }
+                },
+            ));
 
             let token_manager = TokenManager::new(client.clone(), app.handle().clone());
             app.manage(token_manager);
@@ -9,6 +9,8 @@ const CHAT_PARSE_SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
 
 static REPEATED_LOGS: OnceLock<Coalescer> = OnceLock::new();
 static CHAT_PARSE_STATS: OnceLock<Mutex<ChatParseStats>> = OnceLock::new();
+static CONDITIONAL_FETCH_SAVINGS: OnceLock<Mutex<HashMap<String, ConditionalFetchSavings>>> =
+    OnceLock::new();
 
 pub(crate) fn duration_ms(duration: Duration) -> f64 {
     duration.as_secs_f64() * 1_000.0
@@ -95,6 +97,30 @@ pub(crate) fn record_chat_parse(
     }
 }
 
+/// Records a 304 for `provider`, logging the body bytes it avoided along with
+/// the running total for that provider since startup.
+pub(crate) fn record_conditional_fetch_saved(provider: &str, scope: &str, bytes_saved: u64) {
+    let savings = CONDITIONAL_FETCH_SAVINGS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut savings = savings.lock().unwrap();
+    let totals = savings.entry(provider.to_owned()).or_default();
+    totals.not_modified += 1;
+    totals.bytes_saved += bytes_saved;
+    info!(
+        provider,
+        scope,
+        bytes_saved,
+        total_bytes_saved = totals.bytes_saved,
+        total_not_modified = totals.not_modified,
+        "provider metadata not modified"
+    );
+}
+
+#[derive(Default)]
+struct ConditionalFetchSavings {
+    not_modified: u64,
+    bytes_saved: u64,
+}
+
 fn repeated_logs() -> &'static Coalescer {
     REPEATED_LOGS.get_or_init(Coalescer::default)
 }
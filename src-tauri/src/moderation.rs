@@ -0,0 +1,921 @@
+use std::fmt;
+
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use twitch_api::helix::{
+    chat::{ChatSettings, UpdateChatSettingsBody, UpdateChatSettingsRequest},
+    moderation::{
+        AddBlockedTermBody, AddBlockedTermRequest, GetBlockedTermsRequest,
+        ManageHeldAutoModMessagesBody, ManageHeldAutoModMessagesRequest, RemoveBlockedTermRequest,
+        ResolveUnbanRequest,
+    },
+    ClientRequestError, EmptyBody,
+};
+use twitch_oauth2::{Scope, TwitchToken, UserToken};
+
+use crate::{
+    token::{MODERATOR_MANAGE_SUSPICIOUS_USERS, MODERATOR_SCOPES},
+    trust::Treatment,
+    types::BadgeRef,
+};
+
+type HelixClient = twitch_api::HelixClient<'static, reqwest::Client>;
+
+/// Twitch caps timeouts at two weeks.
+pub const MAX_TIMEOUT_SECS: u32 = 1_209_600;
+const MAX_REASON_LEN: usize = 500;
+/// Helix rejects blocked terms outside this many characters.
+const BLOCKED_TERM_LEN: std::ops::RangeInclusive<usize> = 2..=500;
+/// Slow mode waits, in seconds.
+const SLOW_MODE_WAIT: std::ops::RangeInclusive<u32> = 3..=120;
+/// Followers-only minimum follow age, in minutes (up to three months).
+const FOLLOWER_MODE_DURATION: std::ops::RangeInclusive<u32> = 0..=129_600;
+/// The only delays Twitch offers for non-moderator messages, in seconds.
+const CHAT_DELAYS: [u32; 3] = [2, 4, 6];
+
+/// The active user's standing in a channel.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelRole {
+    Broadcaster,
+    LeadModerator,
+    Moderator,
+    Viewer,
+}
+
+impl ChannelRole {
+    /// `moderated` is whether Helix lists the channel among the user's moderated
+    /// channels, or `None` when the token can't ask; badges from the user's own
+    /// latest message fill the gap and are the only hint of lead moderators.
+    pub fn detect(
+        user_id: &str,
+        broadcaster_id: &str,
+        moderated: Option<bool>,
+        badges: &[BadgeRef],
+    ) -> Self {
+        if user_id == broadcaster_id {
+            return ChannelRole::Broadcaster;
+        }
+        let has_badge = |set_id: &str| badges.iter().any(|badge| badge.set_id == set_id);
+        let is_moderator =
+            moderated.unwrap_or_else(|| has_badge("moderator") || has_badge("lead_moderator"));
+        match (is_moderator, has_badge("lead_moderator")) {
+            (true, true) => ChannelRole::LeadModerator,
+            (true, false) => ChannelRole::Moderator,
+            (false, _) => ChannelRole::Viewer,
+        }
+    }
+
+    pub fn can_moderate(self) -> bool {
+        self != ChannelRole::Viewer
+    }
+}
+
+/// Which moderation actions will succeed for the active user in a channel.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct ModerationCapabilities {
+    pub role: ChannelRole,
+    /// Moderator scopes the active token lacks; `request_moderator_scopes`
+    /// re-authorizes with them.
+    pub missing_scopes: Vec<String>,
+    /// Ban, timeout and unban.
+    pub can_ban: bool,
+    /// Delete single messages and clear chat.
+    pub can_delete_messages: bool,
+    /// See and resolve messages held by AutoMod.
+    pub can_manage_automod: bool,
+    /// Approve or deny unban requests.
+    pub can_resolve_unban_requests: bool,
+    /// Mark chatters monitored or restricted.
+    pub can_manage_suspicious_users: bool,
+    /// Change slow mode, followers-only mode and the other chat settings.
+    pub can_manage_chat_settings: bool,
+    /// List, add and remove blocked terms.
+    pub can_manage_blocked_terms: bool,
+}
+
+impl ModerationCapabilities {
+    pub fn new(role: ChannelRole, token_scopes: &[Scope]) -> Self {
+        let granted = |scope: Scope| role.can_moderate() && token_scopes.contains(&scope);
+        ModerationCapabilities {
+            role,
+            missing_scopes: missing_scopes(token_scopes, &MODERATOR_SCOPES)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            can_ban: granted(Scope::ModeratorManageBannedUsers),
+            can_delete_messages: granted(Scope::ModeratorManageChatMessages),
+            can_manage_automod: granted(Scope::ModeratorManageAutoMod),
+            can_resolve_unban_requests: granted(Scope::ModeratorManageUnbanRequests),
+            can_manage_suspicious_users: granted(MODERATOR_MANAGE_SUSPICIOUS_USERS.clone()),
+            can_manage_chat_settings: granted(Scope::ModeratorManageChatSettings),
+            can_manage_blocked_terms: granted(Scope::ModeratorManageBlockedTerms),
+        }
+    }
+}
+
+/// A term AutoMod blocks in the channel.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct BlockedTerm {
+    pub id: String,
+    pub text: String,
+    /// Who added it.
+    pub moderator_id: String,
+    pub created_at: String,
+    /// Set for terms AutoMod added temporarily.
+    pub expires_at: Option<String>,
+}
+
+impl From<twitch_api::helix::moderation::BlockedTerm> for BlockedTerm {
+    fn from(term: twitch_api::helix::moderation::BlockedTerm) -> Self {
+        BlockedTerm {
+            id: term.id.to_string(),
+            text: term.text,
+            moderator_id: term.moderator_id.to_string(),
+            created_at: term.created_at.to_string(),
+            expires_at: term.expires_at.map(|at| at.to_string()),
+        }
+    }
+}
+
+/// A channel's chat settings as a moderator sees them.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct ChannelChatSettings {
+    pub emote_mode: bool,
+    pub follower_mode: bool,
+    /// Minutes.
+    pub follower_mode_duration: Option<u32>,
+    pub non_moderator_chat_delay: bool,
+    /// Seconds.
+    pub non_moderator_chat_delay_duration: Option<u32>,
+    pub slow_mode: bool,
+    /// Seconds.
+    pub slow_mode_wait_time: Option<u32>,
+    pub subscriber_mode: bool,
+    pub unique_chat_mode: bool,
+}
+
+impl From<ChatSettings> for ChannelChatSettings {
+    fn from(settings: ChatSettings) -> Self {
+        let narrow = |value: Option<u64>| value.map(|value| value.min(u32::MAX as u64) as u32);
+        ChannelChatSettings {
+            emote_mode: settings.emote_mode,
+            follower_mode: settings.follower_mode,
+            follower_mode_duration: narrow(settings.follower_mode_duration),
+            non_moderator_chat_delay: settings.non_moderator_chat_delay.unwrap_or_default(),
+            non_moderator_chat_delay_duration: narrow(settings.non_moderator_chat_delay_duration),
+            slow_mode: settings.slow_mode,
+            slow_mode_wait_time: narrow(settings.slow_mode_wait_time),
+            subscriber_mode: settings.subscriber_mode,
+            unique_chat_mode: settings.unique_chat_mode,
+        }
+    }
+}
+
+/// Chat settings to change; `None` leaves a setting as it is. Setting a
+/// duration turns its mode on.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct ChatSettingsUpdate {
+    pub emote_mode: Option<bool>,
+    pub follower_mode: Option<bool>,
+    /// Minutes someone must have followed for, 0 to 129600.
+    pub follower_mode_duration: Option<u32>,
+    pub non_moderator_chat_delay: Option<bool>,
+    /// 2, 4 or 6 seconds.
+    pub non_moderator_chat_delay_duration: Option<u32>,
+    pub slow_mode: Option<bool>,
+    /// Seconds between messages, 3 to 120.
+    pub slow_mode_wait_time: Option<u32>,
+    pub subscriber_mode: Option<bool>,
+    pub unique_chat_mode: Option<bool>,
+}
+
+impl ChatSettingsUpdate {
+    fn validate(&self) -> Result<(), ModerationError> {
+        let invalid = |message: String| Err(ModerationError::InvalidInput { message });
+        if *self == ChatSettingsUpdate::default() {
+            return invalid("no chat settings to change".to_owned());
+        }
+        if let Some(wait) = self.slow_mode_wait_time {
+            if !SLOW_MODE_WAIT.contains(&wait) {
+                return invalid(format!(
+                    "slow mode must be between {} and {} seconds",
+                    SLOW_MODE_WAIT.start(),
+                    SLOW_MODE_WAIT.end()
+                ));
+            }
+        }
+        if let Some(duration) = self.follower_mode_duration {
+            if !FOLLOWER_MODE_DURATION.contains(&duration) {
+                return invalid(format!(
+                    "followers-only duration must be at most {} minutes",
+                    FOLLOWER_MODE_DURATION.end()
+                ));
+            }
+        }
+        if let Some(delay) = self.non_moderator_chat_delay_duration {
+            if !CHAT_DELAYS.contains(&delay) {
+                return invalid("chat delay must be 2, 4 or 6 seconds".to_owned());
+            }
+        }
+        for (mode, enabled, duration) in [
+            (
+                "slow mode",
+                self.slow_mode,
+                self.slow_mode_wait_time.is_some(),
+            ),
+            (
+                "followers-only mode",
+                self.follower_mode,
+                self.follower_mode_duration.is_some(),
+            ),
+            (
+                "chat delay",
+                self.non_moderator_chat_delay,
+                self.non_moderator_chat_delay_duration.is_some(),
+            ),
+        ] {
+            if enabled == Some(false) && duration {
+                return invalid(format!("can't set a duration while turning off {mode}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn into_body(self) -> UpdateChatSettingsBody {
+        // Helix wants the mode on whenever its duration is set.
+        let mode = |enabled: Option<bool>, duration: Option<u32>| match (enabled, duration) {
+            (None, Some(_)) => Some(true),
+            (enabled, _) => enabled,
+        };
+        let mut body = UpdateChatSettingsBody::default();
+        body.emote_mode = self.emote_mode;
+        body.follower_mode = mode(self.follower_mode, self.follower_mode_duration);
+        body.follower_mode_duration = self.follower_mode_duration.map(u64::from);
+        body.non_moderator_chat_delay = mode(
+            self.non_moderator_chat_delay,
+            self.non_moderator_chat_delay_duration,
+        );
+        body.non_moderator_chat_delay_duration =
+            self.non_moderator_chat_delay_duration.map(u64::from);
+        body.slow_mode = mode(self.slow_mode, self.slow_mode_wait_time);
+        body.slow_mode_wait_time = self.slow_mode_wait_time.map(u64::from);
+        body.subscriber_mode = self.subscriber_mode;
+        body.unique_chat_mode = self.unique_chat_mode;
+        body
+    }
+}
+
+/// Why a moderation action didn't happen.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ModerationError {
+    NotLoggedIn,
+    /// The token was never granted these scopes.
+    MissingScopes {
+        scopes: Vec<String>,
+    },
+    /// Rejected before calling Helix.
+    InvalidInput {
+        message: String,
+    },
+    /// Helix 400, e.g. the target is already banned or is a moderator.
+    Rejected {
+        message: String,
+    },
+    /// Helix 401; the token is invalid or lacks a scope Twitch requires.
+    Unauthorized {
+        message: String,
+    },
+    /// Helix 403; the user doesn't moderate this channel.
+    NotModerator {
+        message: String,
+    },
+    /// Helix 404, e.g. the message is gone or too old to delete.
+    NotFound {
+        message: String,
+    },
+    /// Helix 409; another moderator is changing the same user.
+    Conflict {
+        message: String,
+    },
+    /// Helix 429; `retry_after_secs` is known when the response said when the
+    /// rate limit bucket refills.
+    RateLimited {
+        message: String,
+        retry_after_secs: Option<u64>,
+    },
+    /// Twitch couldn't be reached.
+    Network {
+        message: String,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl ModerationError {
+    pub(crate) fn from_status(status: u16, message: String) -> Self {
+        match status {
+            400 => ModerationError::Rejected { message },
+            401 => ModerationError::Unauthorized { message },
+            403 => ModerationError::NotModerator { message },
+            404 => ModerationError::NotFound { message },
+            409 => ModerationError::Conflict { message },
+            429 => ModerationError::RateLimited {
+                message,
+                retry_after_secs: None,
+            },
+            _ => ModerationError::Failed { message },
+        }
+    }
+
+    pub fn with_retry_after(self, retry_after_secs: Option<u64>) -> Self {
+        match self {
+            ModerationError::RateLimited { message, .. } => ModerationError::RateLimited {
+                message,
+                retry_after_secs,
+            },
+            other => other,
+        }
+    }
+
+    pub fn from_client_error<RE>(err: ClientRequestError<RE>) -> Self
+    where
+        RE: std::error::Error + Send + Sync + 'static,
+    {
+        use twitch_api::helix::{
+            HelixRequestDeleteError, HelixRequestGetError, HelixRequestPatchError,
+            HelixRequestPostError, HelixRequestPutError,
+        };
+
+        let refused = match &err {
+            ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                status,
+                message,
+                ..
+            })
+            | ClientRequestError::HelixRequestPutError(HelixRequestPutError::Error {
+                status,
+                message,
+                ..
+            })
+            | ClientRequestError::HelixRequestPostError(HelixRequestPostError::Error {
+                status,
+                message,
+                ..
+            })
+            | ClientRequestError::HelixRequestPatchError(HelixRequestPatchError::Error {
+                status,
+                message,
+                ..
+            })
+            | ClientRequestError::HelixRequestDeleteError(HelixRequestDeleteError::Error {
+                status,
+                message,
+                ..
+            }) => Some((status.as_u16(), message.clone())),
+            _ => None,
+        };
+        match refused {
+            Some((status, message)) => ModerationError::from_status(status, message),
+            None if matches!(err, ClientRequestError::RequestError(_)) => {
+                ModerationError::Network {
+                    message: err.to_string(),
+                }
+            }
+            None => ModerationError::Failed {
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ModerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModerationError::NotLoggedIn => write!(f, "no active token"),
+            ModerationError::MissingScopes { scopes } => {
+                write!(f, "missing scopes: {}", scopes.join(", "))
+            }
+            ModerationError::InvalidInput { message }
+            | ModerationError::Rejected { message }
+            | ModerationError::Unauthorized { message }
+            | ModerationError::NotModerator { message }
+            | ModerationError::NotFound { message }
+            | ModerationError::Conflict { message }
+            | ModerationError::RateLimited { message, .. }
+            | ModerationError::Network { message }
+            | ModerationError::Failed { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+pub fn missing_scopes(granted: &[Scope], required: &[Scope]) -> Vec<Scope> {
+    required
+        .iter()
+        .filter(|scope| !granted.contains(scope))
+        .cloned()
+        .collect()
+}
+
+pub fn require_scopes(token: &UserToken, required: &[Scope]) -> Result<(), ModerationError> {
+    let missing = missing_scopes(token.scopes(), required);
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(ModerationError::MissingScopes {
+            scopes: missing.iter().map(ToString::to_string).collect(),
+        })
+    }
+}
+
+fn validate_reason(reason: &str) -> Result<(), ModerationError> {
+    if reason.chars().count() > MAX_REASON_LEN {
+        return Err(ModerationError::InvalidInput {
+            message: format!("reason must be at most {MAX_REASON_LEN} characters"),
+        });
+    }
+    Ok(())
+}
+
+fn validate_blocked_term(text: &str) -> Result<(), ModerationError> {
+    if !BLOCKED_TERM_LEN.contains(&text.chars().count()) {
+        return Err(ModerationError::InvalidInput {
+            message: format!(
+                "blocked terms must be {} to {} characters",
+                BLOCKED_TERM_LEN.start(),
+                BLOCKED_TERM_LEN.end()
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn validate_timeout(duration_secs: u32) -> Result<(), ModerationError> {
+    if !(1..=MAX_TIMEOUT_SECS).contains(&duration_secs) {
+        return Err(ModerationError::InvalidInput {
+            message: format!("timeout must be between 1 and {MAX_TIMEOUT_SECS} seconds"),
+        });
+    }
+    Ok(())
+}
+
+/// Whether Helix lists `broadcaster_id` among the channels the token's user
+/// moderates, or `None` without `user:read:moderated_channels`.
+pub async fn is_moderated_channel(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+) -> Result<Option<bool>, ModerationError> {
+    if !token.scopes().contains(&Scope::UserReadModeratedChannels) {
+        return Ok(None);
+    }
+    let channels: Vec<_> = client
+        .get_moderated_channels(token.user_id.as_str(), token)
+        .try_collect()
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    Ok(Some(channels.iter().any(|channel| {
+        channel.broadcaster_id.as_str() == broadcaster_id
+    })))
+}
+
+/// Bans `user_id`, or times them out when `duration_secs` is set.
+pub async fn ban(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    user_id: &str,
+    reason: &str,
+    duration_secs: Option<u32>,
+) -> Result<(), ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageBannedUsers])?;
+    validate_reason(reason)?;
+    if let Some(duration_secs) = duration_secs {
+        validate_timeout(duration_secs)?;
+    }
+    client
+        .ban_user(
+            user_id,
+            reason,
+            duration_secs,
+            broadcaster_id,
+            token.user_id.as_str(),
+            token,
+        )
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    Ok(())
+}
+
+pub async fn unban(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    user_id: &str,
+) -> Result<(), ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageBannedUsers])?;
+    client
+        .unban_user(user_id, broadcaster_id, token.user_id.as_str(), token)
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    Ok(())
+}
+
+/// Deletes `message_id`, or every message in the channel when it's `None`.
+pub async fn delete_messages(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    message_id: Option<&str>,
+) -> Result<(), ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageChatMessages])?;
+    let moderator_id = token.user_id.as_str();
+    match message_id {
+        Some(message_id) => client
+            .delete_chat_message(broadcaster_id, moderator_id, message_id, token)
+            .await
+            .map(drop),
+        None => client
+            .delete_all_chat_message(broadcaster_id, moderator_id, token)
+            .await
+            .map(drop),
+    }
+    .map_err(ModerationError::from_client_error)
+}
+
+/// Allows or denies a message AutoMod is holding.
+pub async fn resolve_held_message(
+    client: &HelixClient,
+    token: &UserToken,
+    message_id: &str,
+    allow: bool,
+) -> Result<(), ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageAutoMod])?;
+    let body = ManageHeldAutoModMessagesBody::new(token.user_id.as_str(), message_id, allow);
+    client
+        .req_post(ManageHeldAutoModMessagesRequest::new(), body, token)
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    Ok(())
+}
+
+/// Approves or denies an unban request, telling the requester `resolution_text`.
+pub async fn resolve_unban_request(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    request_id: &str,
+    approve: bool,
+    resolution_text: &str,
+) -> Result<(), ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageUnbanRequests])?;
+    validate_reason(resolution_text)?;
+    let moderator_id = token.user_id.as_str();
+    let mut request = if approve {
+        ResolveUnbanRequest::approve(broadcaster_id, moderator_id, request_id)
+    } else {
+        ResolveUnbanRequest::deny(broadcaster_id, moderator_id, request_id)
+    };
+    if !resolution_text.is_empty() {
+        request = request.resolution_text(resolution_text);
+    }
+    client
+        .req_patch(request, EmptyBody, token)
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    Ok(())
+}
+
+/// The channel's blocked terms, oldest first.
+pub async fn list_blocked_terms(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+) -> Result<Vec<BlockedTerm>, ModerationError> {
+    // Either scope lets a moderator read the list.
+    if !token.scopes().contains(&Scope::ModeratorManageBlockedTerms) {
+        require_scopes(token, &[Scope::ModeratorReadBlockedTerms])?;
+    }
+    let mut request = GetBlockedTermsRequest::new(broadcaster_id, token.user_id.as_str());
+    request.first = Some(100);
+    let terms: Vec<_> =
+        twitch_api::helix::make_stream(request, token, client, std::collections::VecDeque::from)
+            .try_collect()
+            .await
+            .map_err(ModerationError::from_client_error)?;
+    let mut terms: Vec<BlockedTerm> = terms.into_iter().map(BlockedTerm::from).collect();
+    terms.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(terms)
+}
+
+pub async fn add_blocked_term(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    text: &str,
+) -> Result<BlockedTerm, ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageBlockedTerms])?;
+    let text = text.trim();
+    validate_blocked_term(text)?;
+    let response = client
+        .req_post(
+            AddBlockedTermRequest::new(broadcaster_id, token.user_id.as_str()),
+            AddBlockedTermBody::new(text),
+            token,
+        )
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    response
+        .data
+        .into_iter()
+        .next()
+        .map(BlockedTerm::from)
+        .ok_or_else(|| ModerationError::Failed {
+            message: "Helix returned no blocked term".to_owned(),
+        })
+}
+
+pub async fn remove_blocked_term(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    term_id: &str,
+) -> Result<(), ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageBlockedTerms])?;
+    client
+        .req_delete(
+            RemoveBlockedTermRequest::new(broadcaster_id, token.user_id.as_str(), term_id),
+            token,
+        )
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    Ok(())
+}
+
+pub async fn get_chat_settings(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+) -> Result<ChannelChatSettings, ModerationError> {
+    client
+        .get_chat_settings(broadcaster_id, Some(&*token.user_id), token)
+        .await
+        .map(ChannelChatSettings::from)
+        .map_err(ModerationError::from_client_error)
+}
+
+/// Applies `update` after checking it against the ranges Helix accepts,
+/// returning the channel's settings afterwards.
+pub async fn update_chat_settings(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    update: ChatSettingsUpdate,
+) -> Result<ChannelChatSettings, ModerationError> {
+    require_scopes(token, &[Scope::ModeratorManageChatSettings])?;
+    update.validate()?;
+    let response = client
+        .req_patch(
+            UpdateChatSettingsRequest::new(broadcaster_id, token.user_id.as_str()),
+            update.into_body(),
+            token,
+        )
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    Ok(response.data.into())
+}
+
+/// Marks `user_id` monitored or restricted in the channel, or clears the
+/// treatment when `treatment` is `None`.
+///
+/// `twitch_api` has no request type for this endpoint yet, so it's called
+/// directly with the Helix client's HTTP client.
+pub async fn set_suspicious_status(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    user_id: &str,
+    treatment: Option<Treatment>,
+) -> Result<(), ModerationError> {
+    require_scopes(token, &[MODERATOR_MANAGE_SUSPICIOUS_USERS.clone()])?;
+    let mut url = twitch_api::TWITCH_HELIX_URL
+        .join("moderation/suspicious_users")
+        .map_err(|e| ModerationError::Failed {
+            message: e.to_string(),
+        })?;
+    url.query_pairs_mut()
+        .append_pair("broadcaster_id", broadcaster_id)
+        .append_pair("moderator_id", token.user_id.as_str());
+
+    let http = client.clone_client();
+    let request = match treatment {
+        Some(treatment) => http.post(url.as_str()).json(&serde_json::json!({
+            "user_id": user_id,
+            "status": treatment.helix_status(),
+        })),
+        None => {
+            url.query_pairs_mut().append_pair("user_id", user_id);
+            http.delete(url.as_str())
+        }
+    };
+    let response = request
+        .bearer_auth(token.access_token.secret())
+        .header("Client-Id", token.client_id().as_str())
+        .send()
+        .await
+        .map_err(|e| ModerationError::Network {
+            message: e.to_string(),
+        })?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let retry_after_secs = rate_limit_reset_in(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    Err(ModerationError::from_status(
+        status.as_u16(),
+        helix_error_message(&body).unwrap_or_else(|| status.to_string()),
+    )
+    .with_retry_after(retry_after_secs))
+}
+
+/// Seconds until the rate limit bucket refills, from Helix's
+/// `Ratelimit-Reset` header (a unix timestamp).
+fn rate_limit_reset_in(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let reset: u64 = headers
+        .get("Ratelimit-Reset")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(reset.saturating_sub(now))
+}
+
+/// The `message` of a Helix error body.
+fn helix_error_message(body: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()?
+        .get("message")?
+        .as_str()
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn badge(set_id: &str) -> BadgeRef {
+        BadgeRef {
+            set_id: set_id.to_string(),
+            id: "1".to_string(),
+            info: String::new(),
+            badge: Default::default(),
+        }
+    }
+
+    #[test]
+    fn detects_role_from_helix_and_badges() {
+        assert_eq!(
+            ChannelRole::detect("1", "1", Some(false), &[]),
+            ChannelRole::Broadcaster
+        );
+        assert_eq!(
+            ChannelRole::detect("2", "1", Some(true), &[]),
+            ChannelRole::Moderator
+        );
+        assert_eq!(
+            ChannelRole::detect("2", "1", Some(true), &[badge("lead_moderator")]),
+            ChannelRole::LeadModerator
+        );
+        assert_eq!(
+            ChannelRole::detect("2", "1", None, &[badge("moderator")]),
+            ChannelRole::Moderator
+        );
+        // A stale badge from before the user was unmodded doesn't win over Helix.
+        assert_eq!(
+            ChannelRole::detect("2", "1", Some(false), &[badge("moderator")]),
+            ChannelRole::Viewer
+        );
+        assert_eq!(
+            ChannelRole::detect("2", "1", None, &[badge("subscriber")]),
+            ChannelRole::Viewer
+        );
+    }
+
+    #[test]
+    fn capabilities_need_both_role_and_scope() {
+        let capabilities = ModerationCapabilities::new(
+            ChannelRole::Moderator,
+            &[Scope::ModeratorManageBannedUsers],
+        );
+        assert!(capabilities.can_ban);
+        assert!(!capabilities.can_delete_messages);
+        assert!(capabilities
+            .missing_scopes
+            .contains(&Scope::ModeratorManageChatMessages.to_string()));
+
+        let viewer = ModerationCapabilities::new(ChannelRole::Viewer, &MODERATOR_SCOPES);
+        assert!(viewer.missing_scopes.is_empty());
+        assert!(!viewer.can_ban);
+    }
+
+    #[test]
+    fn reads_helix_error_messages() {
+        assert_eq!(
+            helix_error_message(
+                r#"{"error":"Bad Request","status":400,"message":"user is a moderator"}"#
+            )
+            .as_deref(),
+            Some("user is a moderator")
+        );
+        assert_eq!(helix_error_message("<html>"), None);
+    }
+
+    #[test]
+    fn maps_helix_refusals() {
+        assert_eq!(
+            ModerationError::from_status(400, "already banned".to_string()),
+            ModerationError::Rejected {
+                message: "already banned".to_string()
+            }
+        );
+        assert!(matches!(
+            ModerationError::from_status(403, String::new()),
+            ModerationError::NotModerator { .. }
+        ));
+        assert!(matches!(
+            ModerationError::from_status(429, String::new()),
+            ModerationError::RateLimited { .. }
+        ));
+        assert!(matches!(
+            ModerationError::from_status(500, String::new()),
+            ModerationError::Failed { .. }
+        ));
+    }
+
+    #[test]
+    fn validates_timeouts_and_reasons() {
+        assert!(validate_timeout(1).is_ok());
+        assert!(validate_timeout(MAX_TIMEOUT_SECS).is_ok());
+        assert!(validate_timeout(0).is_err());
+        assert!(validate_timeout(MAX_TIMEOUT_SECS + 1).is_err());
+        assert!(validate_reason(&"x".repeat(MAX_REASON_LEN)).is_ok());
+        assert!(validate_reason(&"x".repeat(MAX_REASON_LEN + 1)).is_err());
+        assert!(validate_blocked_term("ab").is_ok());
+        assert!(validate_blocked_term("a").is_err());
+        assert!(validate_blocked_term(&"x".repeat(501)).is_err());
+    }
+
+    #[test]
+    fn validates_chat_settings_ranges() {
+        assert!(ChatSettingsUpdate::default().validate().is_err());
+        let slow = |wait| ChatSettingsUpdate {
+            slow_mode_wait_time: Some(wait),
+            ..Default::default()
+        };
+        assert!(slow(3).validate().is_ok());
+        assert!(slow(120).validate().is_ok());
+        assert!(slow(2).validate().is_err());
+        assert!(slow(121).validate().is_err());
+        assert!(ChatSettingsUpdate {
+            follower_mode_duration: Some(129_601),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(ChatSettingsUpdate {
+            non_moderator_chat_delay_duration: Some(3),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(ChatSettingsUpdate {
+            slow_mode: Some(false),
+            slow_mode_wait_time: Some(30),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn durations_turn_their_mode_on() {
+        let body = ChatSettingsUpdate {
+            slow_mode_wait_time: Some(30),
+            emote_mode: Some(false),
+            ..Default::default()
+        }
+        .into_body();
+        assert_eq!(body.slow_mode, Some(true));
+        assert_eq!(body.slow_mode_wait_time, Some(30));
+        assert_eq!(body.emote_mode, Some(false));
+        assert_eq!(body.follower_mode, None);
+    }
+}
@@ -0,0 +1,789 @@
+use rusqlite::{types::Value, Connection};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use twitch_api::eventsub::channel::moderate::{
+    self, ActionV2, AutomodTermAction, AutomodTermList, ChannelModerateV2Payload,
+};
+
+use crate::history::{HistoryEntry, HistoryStore};
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Columns the history table keeps next to each moderator action so the audit
+/// log can be filtered without decoding payloads.
+pub(crate) struct AuditColumns {
+    pub action: &'static str,
+    pub moderator_id: String,
+    pub moderator_login: String,
+    pub target_id: Option<String>,
+    pub target_login: Option<String>,
+}
+
+impl AuditColumns {
+    pub fn new(entry: &ModLogEntry) -> Self {
+        let target = entry.action.target();
+        AuditColumns {
+            action: entry.action.kind(),
+            moderator_id: entry.moderator.user_id.clone(),
+            moderator_login: entry.moderator.user_login.to_lowercase(),
+            target_id: target.map(|target| target.user_id.clone()),
+            target_login: target.map(|target| target.user_login.to_lowercase()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct ModUser {
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+}
+
+impl ModUser {
+    fn new(
+        user_id: &twitch_api::types::UserIdRef,
+        user_login: &twitch_api::types::UserNameRef,
+        user_name: &twitch_api::types::DisplayNameRef,
+    ) -> Self {
+        ModUser {
+            user_id: user_id.to_string(),
+            user_login: user_login.to_string(),
+            user_name: user_name.to_string(),
+        }
+    }
+}
+
+/// One `channel.moderate` event: who did what, to whom.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct ModLogEntry {
+    pub ts: String,
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    /// The channel it happened in when that's another channel of a shared
+    /// chat session.
+    pub source_broadcaster_user_login: Option<String>,
+    pub moderator: ModUser,
+    pub action: ModAction,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatMode {
+    EmoteOnly,
+    FollowersOnly,
+    Slow,
+    SubscribersOnly,
+    UniqueChat,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TermList {
+    Blocked,
+    Permitted,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModAction {
+    Ban {
+        target: ModUser,
+        reason: Option<String>,
+    },
+    Unban {
+        target: ModUser,
+    },
+    Timeout {
+        target: ModUser,
+        reason: Option<String>,
+        expires_at: String,
+    },
+    Untimeout {
+        target: ModUser,
+    },
+    DeleteMessage {
+        target: ModUser,
+        message_id: String,
+        text: String,
+    },
+    Warn {
+        target: ModUser,
+        reason: Option<String>,
+        chat_rules_cited: Vec<String>,
+    },
+    Raid {
+        target: ModUser,
+        viewer_count: u64,
+    },
+    Unraid {
+        target: ModUser,
+    },
+    Vip {
+        target: ModUser,
+    },
+    Unvip {
+        target: ModUser,
+    },
+    Mod {
+        target: ModUser,
+    },
+    Unmod {
+        target: ModUser,
+    },
+    ApproveUnbanRequest {
+        target: ModUser,
+        message: String,
+    },
+    DenyUnbanRequest {
+        target: ModUser,
+        message: String,
+    },
+    ChatMode {
+        mode: ChatMode,
+        enabled: bool,
+        /// Minimum follow age for followers-only mode, or the wait between
+        /// messages for slow mode.
+        duration_seconds: Option<u64>,
+    },
+    Terms {
+        list: TermList,
+        added: bool,
+        terms: Vec<String>,
+        /// Whether the change came from approving or denying a held message.
+        from_automod: bool,
+    },
+}
+
+impl ModAction {
+    /// The serialized `type`, which is also what the audit log filters on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ModAction::Ban { .. } => "ban",
+            ModAction::Unban { .. } => "unban",
+            ModAction::Timeout { .. } => "timeout",
+            ModAction::Untimeout { .. } => "untimeout",
+            ModAction::DeleteMessage { .. } => "delete_message",
+            ModAction::Warn { .. } => "warn",
+            ModAction::Raid { .. } => "raid",
+            ModAction::Unraid { .. } => "unraid",
+            ModAction::Vip { .. } => "vip",
+            ModAction::Unvip { .. } => "unvip",
+            ModAction::Mod { .. } => "mod",
+            ModAction::Unmod { .. } => "unmod",
+            ModAction::ApproveUnbanRequest { .. } => "approve_unban_request",
+            ModAction::DenyUnbanRequest { .. } => "deny_unban_request",
+            ModAction::ChatMode { .. } => "chat_mode",
+            ModAction::Terms { .. } => "terms",
+        }
+    }
+
+    pub fn target(&self) -> Option<&ModUser> {
+        match self {
+            ModAction::Ban { target, .. }
+            | ModAction::Unban { target }
+            | ModAction::Timeout { target, .. }
+            | ModAction::Untimeout { target }
+            | ModAction::DeleteMessage { target, .. }
+            | ModAction::Warn { target, .. }
+            | ModAction::Raid { target, .. }
+            | ModAction::Unraid { target }
+            | ModAction::Vip { target }
+            | ModAction::Unvip { target }
+            | ModAction::Mod { target }
+            | ModAction::Unmod { target }
+            | ModAction::ApproveUnbanRequest { target, .. }
+            | ModAction::DenyUnbanRequest { target, .. } => Some(target),
+            ModAction::ChatMode { .. } | ModAction::Terms { .. } => None,
+        }
+    }
+}
+
+macro_rules! target {
+    ($action:expr) => {
+        ModUser::new(&$action.user_id, &$action.user_login, &$action.user_name)
+    };
+}
+
+impl ModLogEntry {
+    /// Returns `None` for actions Twitch added after this was written.
+    pub fn new(value: &ChannelModerateV2Payload, ts: String) -> Option<Self> {
+        let mode = |mode, enabled, duration_seconds| ModAction::ChatMode {
+            mode,
+            enabled,
+            duration_seconds,
+        };
+        let action = match &value.action {
+            ActionV2::Ban(ban) | ActionV2::SharedChatBan(moderate::SharedChatBan(ban)) => {
+                ModAction::Ban {
+                    target: target!(ban),
+                    reason: ban.reason.clone(),
+                }
+            }
+            ActionV2::Unban(unban)
+            | ActionV2::SharedChatUnban(moderate::SharedChatUnban(unban)) => ModAction::Unban {
+                target: target!(unban),
+            },
+            ActionV2::Timeout(timeout)
+            | ActionV2::SharedChatTimeout(moderate::SharedChatTimeout(timeout)) => {
+                ModAction::Timeout {
+                    target: target!(timeout),
+                    reason: timeout.reason.clone(),
+                    expires_at: timeout.expires_at.to_string(),
+                }
+            }
+            ActionV2::Untimeout(untimeout)
+            | ActionV2::SharedChatUntimeout(moderate::SharedChatUntimeout(untimeout)) => {
+                ModAction::Untimeout {
+                    target: target!(untimeout),
+                }
+            }
+            ActionV2::Delete(delete)
+            | ActionV2::SharedChatDelete(moderate::SharedChatDelete(delete)) => {
+                ModAction::DeleteMessage {
+                    target: target!(delete),
+                    message_id: delete.message_id.to_string(),
+                    text: delete.message_body.clone(),
+                }
+            }
+            ActionV2::Warn(warn) => ModAction::Warn {
+                target: target!(warn),
+                reason: warn.reason.clone(),
+                chat_rules_cited: warn.chat_rules_cited.clone().unwrap_or_default(),
+            },
+            ActionV2::Raid(raid) => ModAction::Raid {
+                target: target!(raid),
+                viewer_count: raid.viewer_count as u64,
+            },
+            ActionV2::Unraid(unraid) => ModAction::Unraid {
+                target: target!(unraid),
+            },
+            ActionV2::Vip(vip) => ModAction::Vip {
+                target: target!(vip),
+            },
+            ActionV2::Unvip(unvip) => ModAction::Unvip {
+                target: target!(unvip),
+            },
+            ActionV2::Mod(promoted) => ModAction::Mod {
+                target: target!(promoted),
+            },
+            ActionV2::Unmod(demoted) => ModAction::Unmod {
+                target: target!(demoted),
+            },
+            ActionV2::ApproveUnbanRequest(request) => ModAction::ApproveUnbanRequest {
+                target: target!(request),
+                message: request.moderator_message.clone(),
+            },
+            ActionV2::DenyUnbanRequest(request) => ModAction::DenyUnbanRequest {
+                target: target!(request),
+                message: request.moderator_message.clone(),
+            },
+            ActionV2::EmoteOnly => mode(ChatMode::EmoteOnly, true, None),
+            ActionV2::EmoteOnlyOff => mode(ChatMode::EmoteOnly, false, None),
+            ActionV2::Followers(followers) => mode(
+                ChatMode::FollowersOnly,
+                true,
+                Some(followers.follow_duration_minutes as u64 * 60),
+            ),
+            ActionV2::FollowersOff => mode(ChatMode::FollowersOnly, false, None),
+            ActionV2::Slow(slow) => mode(ChatMode::Slow, true, Some(slow.wait_time_seconds as u64)),
+            ActionV2::SlowOff => mode(ChatMode::Slow, false, None),
+            ActionV2::Subscribers => mode(ChatMode::SubscribersOnly, true, None),
+            ActionV2::SubscribersOff => mode(ChatMode::SubscribersOnly, false, None),
+            ActionV2::Uniquechat => mode(ChatMode::UniqueChat, true, None),
+            ActionV2::UniquechatOff => mode(ChatMode::UniqueChat, false, None),
+            ActionV2::AddBlockedTerm(terms)
+            | ActionV2::AddPermittedTerm(terms)
+            | ActionV2::RemoveBlockedTerm(terms)
+            | ActionV2::RemovePermittedTerm(terms) => ModAction::Terms {
+                list: match &terms.list {
+                    AutomodTermList::Blocked => TermList::Blocked,
+                    AutomodTermList::Permitted => TermList::Permitted,
+                    other => {
+                        warn!("ignoring automod terms change on unknown list {other:?}");
+                        return None;
+                    }
+                },
+                added: matches!(terms.action, AutomodTermAction::Add),
+                terms: terms.terms.clone(),
+                from_automod: terms.from_automod,
+            },
+            other => {
+                warn!("ignoring unknown moderator action {other:?}");
+                return None;
+            }
+        };
+
+        Some(ModLogEntry {
+            ts,
+            broadcaster_user_id: value.broadcaster_user_id.to_string(),
+            broadcaster_user_login: value.broadcaster_user_login.to_string(),
+            source_broadcaster_user_login: value
+                .source_broadcaster_user_login
+                .as_ref()
+                .filter(|source| *source != &value.broadcaster_user_login)
+                .map(ToString::to_string),
+            moderator: ModUser::new(
+                &value.moderator_user_id,
+                &value.moderator_user_login,
+                &value.moderator_user_name,
+            ),
+            action,
+        })
+    }
+
+    /// What chat should show on the messages this action removed, if any.
+    pub fn annotation(&self) -> Option<MessageAnnotation> {
+        let (target, message_id, verb) = match &self.action {
+            ModAction::DeleteMessage {
+                target, message_id, ..
+            } => (target, Some(message_id.clone()), "deleted"),
+            ModAction::Ban { target, .. } => (target, None, "banned"),
+            ModAction::Timeout { target, .. } => (target, None, "timed out"),
+            _ => return None,
+        };
+        Some(MessageAnnotation {
+            broadcaster_user_login: self.broadcaster_user_login.clone(),
+            target_user_id: target.user_id.clone(),
+            message_id,
+            moderation: MessageModeration {
+                action: self.action.kind().to_owned(),
+                moderator_user_id: self.moderator.user_id.clone(),
+                moderator_user_name: self.moderator.user_name.clone(),
+                label: format!("{verb} by {}", self.moderator.user_name),
+            },
+        })
+    }
+}
+
+/// A one-line summary for plain-text chat logs.
+pub fn describe(entry: &ModLogEntry) -> String {
+    let moderator = &entry.moderator.user_name;
+    let with_reason = |line: String, reason: &Option<String>| match reason {
+        Some(reason) => format!("{line}: {reason}"),
+        None => line,
+    };
+    match &entry.action {
+        ModAction::Ban { target, reason } => {
+            with_reason(format!("{moderator} banned {}", target.user_name), reason)
+        }
+        ModAction::Unban { target } => format!("{moderator} unbanned {}", target.user_name),
+        ModAction::Timeout {
+            target,
+            reason,
+            expires_at,
+        } => with_reason(
+            format!(
+                "{moderator} timed out {} until {expires_at}",
+                target.user_name
+            ),
+            reason,
+        ),
+        ModAction::Untimeout { target } => {
+            format!("{moderator} removed the timeout on {}", target.user_name)
+        }
+        ModAction::DeleteMessage { target, text, .. } => {
+            format!(
+                "{moderator} deleted a message from {}: {text}",
+                target.user_name
+            )
+        }
+        ModAction::Warn { target, reason, .. } => {
+            with_reason(format!("{moderator} warned {}", target.user_name), reason)
+        }
+        ModAction::Raid {
+            target,
+            viewer_count,
+        } => format!(
+            "{moderator} started a raid to {} with {viewer_count} viewers",
+            target.user_name
+        ),
+        ModAction::Unraid { target } => {
+            format!("{moderator} canceled the raid to {}", target.user_name)
+        }
+        ModAction::Vip { target } => format!("{moderator} added {} as a VIP", target.user_name),
+        ModAction::Unvip { target } => {
+            format!("{moderator} removed {} as a VIP", target.user_name)
+        }
+        ModAction::Mod { target } => {
+            format!("{moderator} added {} as a moderator", target.user_name)
+        }
+        ModAction::Unmod { target } => {
+            format!("{moderator} removed {} as a moderator", target.user_name)
+        }
+        ModAction::ApproveUnbanRequest { target, .. } => {
+            format!(
+                "{moderator} approved the unban request from {}",
+                target.user_name
+            )
+        }
+        ModAction::DenyUnbanRequest { target, .. } => {
+            format!(
+                "{moderator} denied the unban request from {}",
+                target.user_name
+            )
+        }
+        ModAction::ChatMode {
+            mode,
+            enabled,
+            duration_seconds,
+        } => {
+            let mode = match mode {
+                ChatMode::EmoteOnly => "emote-only mode",
+                ChatMode::FollowersOnly => "followers-only mode",
+                ChatMode::Slow => "slow mode",
+                ChatMode::SubscribersOnly => "subscribers-only mode",
+                ChatMode::UniqueChat => "unique chat",
+            };
+            match (enabled, duration_seconds) {
+                (false, _) => format!("{moderator} turned off {mode}"),
+                (true, Some(seconds)) => format!("{moderator} turned on {mode} ({seconds}s)"),
+                (true, None) => format!("{moderator} turned on {mode}"),
+            }
+        }
+        ModAction::Terms {
+            list, added, terms, ..
+        } => format!(
+            "{moderator} {} {} term(s): {}",
+            if *added { "added" } else { "removed" },
+            match list {
+                TermList::Blocked => "blocked",
+                TermList::Permitted => "permitted",
+            },
+            terms.join(", ")
+        ),
+    }
+}
+
+/// Attached to a `ChannelMessage` once a moderator removed it.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct MessageModeration {
+    /// The audit log action type, e.g. `delete_message` or `ban`.
+    pub action: String,
+    pub moderator_user_id: String,
+    pub moderator_user_name: String,
+    /// Ready to display, e.g. `deleted by SomeMod`.
+    pub label: String,
+}
+
+/// Emitted on `message_moderated:{login}` to annotate messages already in chat.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct MessageAnnotation {
+    pub broadcaster_user_login: String,
+    pub target_user_id: String,
+    /// The one message affected; `None` means every earlier message from
+    /// `target_user_id`, as for bans and timeouts.
+    pub message_id: Option<String>,
+    pub moderation: MessageModeration,
+}
+
+/// Writes `annotation` into the stored payloads of the messages it covers
+/// that were sent up to `sent_at_ms`.
+pub(crate) fn annotate_history(
+    conn: &Connection,
+    broadcaster_id: &str,
+    annotation: &MessageAnnotation,
+    sent_at_ms: i64,
+) -> eyre::Result<usize> {
+    let moderation = serde_json::to_string(&annotation.moderation)?;
+    Ok(conn.execute(
+        "UPDATE history SET payload = json_set(payload, '$.moderation', json(?1))
+         WHERE kind = 'message' AND broadcaster_id = ?2 AND chatter_id = ?3
+           AND sent_at_ms <= ?4 AND (?5 IS NULL OR message_id = ?5)",
+        rusqlite::params![
+            moderation,
+            broadcaster_id,
+            annotation.target_user_id,
+            sent_at_ms,
+            annotation.message_id,
+        ],
+    )?)
+}
+
+/// Narrows the audit log. Users match by id or login.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, specta::Type)]
+pub struct ModLogFilter {
+    pub moderator: Option<String>,
+    pub target: Option<String>,
+    /// An action type as in `ModAction`, e.g. `timeout`.
+    pub action: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct ModLogRecord {
+    /// History row id; pass the last one back as `cursor` for the next page.
+    pub id: i64,
+    pub sent_at_ms: i64,
+    pub entry: ModLogEntry,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct ModLogPage {
+    /// Newest first.
+    pub records: Vec<ModLogRecord>,
+    /// Set when more results may follow.
+    pub next_cursor: Option<i64>,
+}
+
+/// Moderator actions recorded for `broadcaster_id`, newest first, starting
+/// below the row `cursor` when given.
+pub fn query(
+    store: &HistoryStore,
+    broadcaster_id: &str,
+    filter: &ModLogFilter,
+    cursor: Option<i64>,
+    limit: usize,
+) -> eyre::Result<ModLogPage> {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let mut sql = String::from(
+        "SELECT id, sent_at_ms, payload FROM history WHERE kind = 'mod_action' AND broadcaster_id = ?1",
+    );
+    let mut values = vec![Value::Text(broadcaster_id.to_owned())];
+    let mut bind = |sql: &mut String, clause: &str, value: Value| {
+        values.push(value);
+        sql.push_str(&clause.replace('?', &format!("?{}", values.len())));
+    };
+
+    if let Some(moderator) = non_empty(&filter.moderator) {
+        bind(
+            &mut sql,
+            " AND (moderator_id = ? OR moderator_login = lower(?))",
+            Value::Text(moderator.to_owned()),
+        );
+    }
+    if let Some(target) = non_empty(&filter.target) {
+        bind(
+            &mut sql,
+            " AND (target_id = ? OR target_login = lower(?))",
+            Value::Text(target.to_owned()),
+        );
+    }
+    if let Some(action) = non_empty(&filter.action) {
+        bind(&mut sql, " AND action = ?", Value::Text(action.to_owned()));
+    }
+    if let Some(cursor) = cursor {
+        bind(
+            &mut sql,
+            " AND (sent_at_ms, id) < (SELECT sent_at_ms, id FROM history WHERE id = ?)",
+            Value::Integer(cursor),
+        );
+    }
+    bind(
+        &mut sql,
+        " ORDER BY sent_at_ms DESC, id DESC LIMIT ?",
+        Value::Integer(limit as i64),
+    );
+
+    let records = store.with_reader(|conn| -> eyre::Result<Vec<ModLogRecord>> {
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(rusqlite::params_from_iter(values), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut records = Vec::new();
+        for row in rows {
+            let (id, sent_at_ms, payload) = row?;
+            match serde_json::from_str::<HistoryEntry>(&payload) {
+                Ok(HistoryEntry::ModAction(entry)) => records.push(ModLogRecord {
+                    id,
+                    sent_at_ms,
+                    entry,
+                }),
+                Ok(_) => {}
+                Err(err) => warn!(id, "skipping undecodable moderation log entry: {err}"),
+            }
+        }
+        Ok(records)
+    })?;
+
+    let next_cursor = (records.len() == limit)
+        .then(|| records.last().map(|record| record.id))
+        .flatten();
+    Ok(ModLogPage {
+        records,
+        next_cursor,
+    })
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        history::{unix_now_ms, HistorySettingsReader, HISTORY_FILE},
+        types::{ChannelMessage, HistorySettings},
+    };
+    use std::sync::Arc;
+
+    fn open_store(dir: &tempfile::TempDir) -> HistoryStore {
+        let settings = HistorySettings::default().normalized();
+        let reader: HistorySettingsReader = Arc::new(move || settings.clone());
+        HistoryStore::open(&dir.path().join(HISTORY_FILE), reader).unwrap()
+    }
+
+    fn moderated(moderator: &str, action: serde_json::Value) -> ModLogEntry {
+        let mut event = serde_json::json!({
+            "broadcaster_user_id": "forsen-id",
+            "broadcaster_user_login": "forsen",
+            "broadcaster_user_name": "forsen",
+            "source_broadcaster_user_id": null,
+            "source_broadcaster_user_login": null,
+            "source_broadcaster_user_name": null,
+            "moderator_user_id": format!("{moderator}-id"),
+            "moderator_user_login": moderator.to_lowercase(),
+            "moderator_user_name": moderator,
+        });
+        event
+            .as_object_mut()
+            .unwrap()
+            .extend(action.as_object().unwrap().clone());
+        let payload: ChannelModerateV2Payload = serde_json::from_value(event).unwrap();
+        ModLogEntry::new(&payload, String::new()).unwrap()
+    }
+
+    fn target(id: &str) -> serde_json::Value {
+        serde_json::json!({"user_id": id, "user_login": format!("user{id}"), "user_name": format!("User{id}")})
+    }
+
+    fn ban(moderator: &str, user_id: &str) -> ModLogEntry {
+        let mut user = target(user_id);
+        user["reason"] = serde_json::json!("");
+        moderated(moderator, serde_json::json!({"action": "ban", "ban": user}))
+    }
+
+    fn message(id: &str, chatter_id: &str) -> ChannelMessage {
+        ChannelMessage {
+            chatter_user_id: chatter_id.to_string(),
+            ..ChannelMessage::test(id, "forsen", &format!("User{chatter_id}"), "hello")
+        }
+    }
+
+    #[test]
+    fn parses_actions_with_actor_and_target() {
+        let entry = ban("SomeMod", "42");
+        assert_eq!(
+            entry.action,
+            ModAction::Ban {
+                target: ModUser {
+                    user_id: "42".to_string(),
+                    user_login: "user42".to_string(),
+                    user_name: "User42".to_string(),
+                },
+                reason: None,
+            }
+        );
+        assert_eq!(entry.moderator.user_name, "SomeMod");
+
+        let slow = moderated(
+            "SomeMod",
+            serde_json::json!({"action": "slow", "slow": {"wait_time_seconds": 30}}),
+        );
+        assert_eq!(
+            slow.action,
+            ModAction::ChatMode {
+                mode: ChatMode::Slow,
+                enabled: true,
+                duration_seconds: Some(30),
+            }
+        );
+        assert!(slow.annotation().is_none());
+        assert_eq!(describe(&slow), "SomeMod turned on slow mode (30s)");
+    }
+
+    #[test]
+    fn filters_by_moderator_target_and_action() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(&dir);
+        let now = unix_now_ms();
+        store.record_at(HistoryEntry::ModAction(ban("ModA", "1")), now);
+        store.record_at(HistoryEntry::ModAction(ban("ModB", "2")), now + 1);
+        store.record_at(
+            HistoryEntry::ModAction(moderated(
+                "ModA",
+                serde_json::json!({"action": "vip", "vip": target("1")}),
+            )),
+            now + 2,
+        );
+        store.flush();
+
+        let run = |filter: ModLogFilter| {
+            query(&store, "forsen-id", &filter, None, 10)
+                .unwrap()
+                .records
+                .into_iter()
+                .map(|record| {
+                    format!(
+                        "{} {}",
+                        record.entry.moderator.user_name,
+                        record.entry.action.kind()
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            run(ModLogFilter::default()),
+            vec!["ModA vip", "ModB ban", "ModA ban"]
+        );
+        assert_eq!(
+            run(ModLogFilter {
+                moderator: Some("MODA".to_string()),
+                ..Default::default()
+            }),
+            vec!["ModA vip", "ModA ban"]
+        );
+        assert_eq!(
+            run(ModLogFilter {
+                target: Some("1".to_string()),
+                action: Some("ban".to_string()),
+                ..Default::default()
+            }),
+            vec!["ModA ban"]
+        );
+        assert!(query(&store, "xqc-id", &ModLogFilter::default(), None, 10)
+            .unwrap()
+            .records
+            .is_empty());
+    }
+
+    #[test]
+    fn annotates_the_removed_messages_in_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(&dir);
+        let now = unix_now_ms();
+        store.record_at(HistoryEntry::Message(message("m1", "42")), now);
+        store.record_at(HistoryEntry::Message(message("m2", "7")), now + 1);
+        store.record_at(HistoryEntry::ModAction(ban("SomeMod", "42")), now + 2);
+        store.record_at(HistoryEntry::Message(message("m3", "42")), now + 3);
+        store.flush();
+
+        let labels: Vec<_> = store
+            .channel_history("forsen", None, 10)
+            .unwrap()
+            .into_iter()
+            .filter_map(|record| match record.entry {
+                HistoryEntry::Message(message) => Some((
+                    message.message_id,
+                    message.moderation.map(|moderation| moderation.label),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("m1".to_string(), Some("banned by SomeMod".to_string())),
+                ("m2".to_string(), None),
+                ("m3".to_string(), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use twitch_oauth2::UserToken;
+
+use crate::{
+    error::PepoError,
+    moderation::{ChannelRole, ModerationError},
+    types::BadgeRef,
+};
+
+type HelixClient = twitch_api::HelixClient<'static, reqwest::Client>;
+
+/// Twitch counts chat messages over a sliding 30 seconds.
+const RATE_WINDOW: Duration = Duration::from_secs(30);
+/// The same text to the same channel this soon after is taken as a double send.
+const DEDUPE_WINDOW: Duration = Duration::from_secs(2);
+/// How long a channel's rate class is trusted before it's looked up again.
+const CLASS_TTL: Duration = Duration::from_secs(300);
+/// Helix 429s are retried this many times, this far apart, before giving up.
+const RATE_LIMIT_RETRIES: u32 = 3;
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Which of Twitch's chat limits applies to the sender in a channel.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateClass {
+    Regular,
+    /// Broadcaster, moderator or VIP.
+    Elevated,
+    VerifiedBot,
+}
+
+impl RateClass {
+    pub fn detect(role: ChannelRole, badges: &[BadgeRef], verified_bot: bool) -> Self {
+        if verified_bot {
+            RateClass::VerifiedBot
+        } else if role.can_moderate() || badges.iter().any(|badge| badge.set_id == "vip") {
+            RateClass::Elevated
+        } else {
+            RateClass::Regular
+        }
+    }
+
+    /// Messages allowed per `RATE_WINDOW`.
+    fn limit(self) -> usize {
+        match self {
+            RateClass::Regular => 20,
+            RateClass::Elevated => 100,
+            RateClass::VerifiedBot => 7500,
+        }
+    }
+}
+
+/// Where a message from the chat input is on its way to Twitch.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for room under the rate limit.
+    Queued,
+    Sent {
+        message_id: Option<String>,
+    },
+    /// Twitch accepted the request but didn't post the message, e.g. AutoMod
+    /// held it or the channel is in followers-only mode.
+    Dropped {
+        code: String,
+        message: String,
+    },
+    Failed {
+        error: PepoError,
+    },
+}
+
+/// Emitted on `message_delivery` each time a message changes status.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct DeliveryUpdate {
+    pub id: String,
+    pub broadcaster_id: String,
+    pub text: String,
+    pub status: DeliveryStatus,
+}
+
+/// What `send_chat_message` hands back for a chat line.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct QueuedMessage {
+    /// Matches `DeliveryUpdate::id`.
+    pub id: String,
+    /// An identical message was queued a moment ago; `id` is that one's and
+    /// nothing new was sent.
+    pub duplicate: bool,
+}
+
+/// Send times for one account.
+#[derive(Default)]
+struct RateWindow {
+    sent: VecDeque<Instant>,
+}
+
+impl RateWindow {
+    /// The earliest a message limited by `class` may go out.
+    fn next_slot(&mut self, now: Instant, class: RateClass) -> Instant {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= RATE_WINDOW)
+        {
+            self.sent.pop_front();
+        }
+        match self.sent.len().checked_sub(class.limit()) {
+            Some(over) => self.sent[over] + RATE_WINDOW,
+            None => now,
+        }
+    }
+
+    fn record(&mut self, at: Instant) {
+        self.sent.push_back(at);
+    }
+}
+
+struct Recent {
+    user_id: String,
+    broadcaster_id: String,
+    text: String,
+    id: String,
+    queued_at: Instant,
+}
+
+#[derive(Default)]
+struct Dedupe {
+    recent: VecDeque<Recent>,
+}
+
+impl Dedupe {
+    /// The id of an identical message queued within `DEDUPE_WINDOW`, or
+    /// `None` after remembering this one as `id`.
+    fn check(
+        &mut self,
+        now: Instant,
+        user_id: &str,
+        broadcaster_id: &str,
+        text: &str,
+        id: &str,
+    ) -> Option<String> {
+        self.recent
+            .retain(|recent| now.duration_since(recent.queued_at) < DEDUPE_WINDOW);
+        let existing = self.recent.iter().find(|recent| {
+            recent.user_id == user_id
+                && recent.broadcaster_id == broadcaster_id
+                && recent.text == text
+        });
+        if let Some(recent) = existing {
+            return Some(recent.id.clone());
+        }
+        self.recent.push_back(Recent {
+            user_id: user_id.to_owned(),
+            broadcaster_id: broadcaster_id.to_owned(),
+            text: text.to_owned(),
+            id: id.to_owned(),
+            queued_at: now,
+        });
+        None
+    }
+}
+
+struct Outgoing {
+    id: String,
+    token: UserToken,
+    broadcaster_id: String,
+    text: String,
+    class: RateClass,
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_id: u64,
+    accounts: HashMap<String, mpsc::UnboundedSender<Outgoing>>,
+    dedupe: Dedupe,
+    /// (account id, broadcaster id) to the class looked up at that time.
+    classes: HashMap<(String, String), (RateClass, Instant)>,
+}
+
+type Notify = Arc<dyn Fn(&DeliveryUpdate) + Send + Sync>;
+
+/// Paces chat messages per account under Twitch's limits. Each account gets
+/// its own task so one busy account can't hold up another.
+#[derive(Clone)]
+pub struct OutgoingQueue {
+    client: HelixClient,
+    notify: Notify,
+    state: Arc<Mutex<QueueState>>,
+}
+
+impl OutgoingQueue {
+    pub fn new(
+        client: HelixClient,
+        notify: impl Fn(&DeliveryUpdate) + Send + Sync + 'static,
+    ) -> Self {
+        OutgoingQueue {
+            client,
+            notify: Arc::new(notify),
+            state: Arc::default(),
+        }
+    }
+
+    /// The rate class last looked up for the account in this channel, unless
+    /// it's gone stale.
+    pub fn cached_class(&self, user_id: &str, broadcaster_id: &str) -> Option<RateClass> {
+        let state = self.state.lock().unwrap();
+        state
+            .classes
+            .get(&(user_id.to_owned(), broadcaster_id.to_owned()))
+            .filter(|(_, looked_up)| looked_up.elapsed() < CLASS_TTL)
+            .map(|(class, _)| *class)
+    }
+
+    pub fn remember_class(&self, user_id: &str, broadcaster_id: &str, class: RateClass) {
+        self.state.lock().unwrap().classes.insert(
+            (user_id.to_owned(), broadcaster_id.to_owned()),
+            (class, Instant::now()),
+        );
+    }
+
+    pub fn enqueue(
+        &self,
+        token: UserToken,
+        broadcaster_id: &str,
+        text: String,
+        class: RateClass,
+    ) -> QueuedMessage {
+        let mut state = self.state.lock().unwrap();
+        let user_id = token.user_id.to_string();
+        let id = format!("{user_id}-{}", state.next_id);
+        if let Some(id) = state
+            .dedupe
+            .check(Instant::now(), &user_id, broadcaster_id, &text, &id)
+        {
+            debug!("dropping double send of {id}");
+            return QueuedMessage {
+                id,
+                duplicate: true,
+            };
+        }
+        state.next_id += 1;
+
+        (self.notify)(&DeliveryUpdate {
+            id: id.clone(),
+            broadcaster_id: broadcaster_id.to_owned(),
+            text: text.clone(),
+            status: DeliveryStatus::Queued,
+        });
+        let mut message = Outgoing {
+            id: id.clone(),
+            token,
+            broadcaster_id: broadcaster_id.to_owned(),
+            text,
+            class,
+        };
+        if let Some(sender) = state.accounts.get(&user_id) {
+            match sender.send(message) {
+                Ok(()) => {
+                    return QueuedMessage {
+                        id,
+                        duplicate: false,
+                    }
+                }
+                // The task is gone; start another below.
+                Err(mpsc::error::SendError(returned)) => message = returned,
+            }
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        sender
+            .send(message)
+            .unwrap_or_else(|_| unreachable!("receiver is alive"));
+        state.accounts.insert(user_id, sender);
+        tauri::async_runtime::spawn(drain(self.client.clone(), self.notify.clone(), receiver));
+        QueuedMessage {
+            id,
+            duplicate: false,
+        }
+    }
+}
+
+async fn drain(
+    client: HelixClient,
+    notify: Notify,
+    mut messages: mpsc::UnboundedReceiver<Outgoing>,
+) {
+    let mut window = RateWindow::default();
+    while let Some(message) = messages.recv().await {
+        let mut retries = 0;
+        let status = loop {
+            let slot = window.next_slot(Instant::now(), message.class);
+            tokio::time::sleep(slot.saturating_duration_since(Instant::now())).await;
+            window.record(Instant::now());
+            match send(&client, &message).await {
+                Err(ModerationError::RateLimited {
+                    message: reason,
+                    retry_after_secs,
+                }) if retries < RATE_LIMIT_RETRIES => {
+                    warn!("rate limited sending {}: {}", message.id, reason);
+                    retries += 1;
+                    let backoff = retry_after_secs
+                        .map(Duration::from_secs)
+                        .unwrap_or(RATE_LIMIT_BACKOFF);
+                    tokio::time::sleep(backoff).await;
+                }
+                Ok(status) => break status,
+                Err(error) => {
+                    break DeliveryStatus::Failed {
+                        error: error.into(),
+                    }
+                }
+            }
+        };
+        notify(&DeliveryUpdate {
+            id: message.id,
+            broadcaster_id: message.broadcaster_id,
+            text: message.text,
+            status,
+        });
+    }
+}
+
+async fn send(client: &HelixClient, message: &Outgoing) -> Result<DeliveryStatus, ModerationError> {
+    let response = client
+        .send_chat_message(
+            message.broadcaster_id.as_str(),
+            message.token.user_id.as_str(),
+            message.text.as_str(),
+            &message.token,
+        )
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    if response.is_sent {
+        return Ok(DeliveryStatus::Sent {
+            message_id: response.message_id.map(|id| id.to_string()),
+        });
+    }
+    Ok(match response.drop_reason {
+        Some(reason) => DeliveryStatus::Dropped {
+            code: reason.code.to_string(),
+            message: reason.message,
+        },
+        None => DeliveryStatus::Dropped {
+            code: "unknown".to_owned(),
+            message: "Twitch didn't post the message".to_owned(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paces_to_the_class_limit() {
+        let start = Instant::now();
+        let mut window = RateWindow::default();
+        for i in 0..20 {
+            let at = start + Duration::from_millis(i * 100);
+            assert_eq!(window.next_slot(at, RateClass::Regular), at);
+            window.record(at);
+        }
+
+        let now = start + Duration::from_secs(3);
+        // The 21st regular message waits for the first to leave the window.
+        assert_eq!(
+            window.next_slot(now, RateClass::Regular),
+            start + RATE_WINDOW
+        );
+        // A moderator's message goes straight out.
+        assert_eq!(window.next_slot(now, RateClass::Elevated), now);
+
+        let later = start + RATE_WINDOW + Duration::from_millis(50);
+        assert_eq!(window.next_slot(later, RateClass::Regular), later);
+        assert_eq!(window.sent.len(), 19);
+    }
+
+    #[test]
+    fn collapses_double_sends() {
+        let start = Instant::now();
+        let mut dedupe = Dedupe::default();
+        assert_eq!(dedupe.check(start, "me", "forsen", "hi", "me-0"), None);
+        assert_eq!(
+            dedupe.check(
+                start + Duration::from_millis(300),
+                "me",
+                "forsen",
+                "hi",
+                "me-1"
+            ),
+            Some("me-0".to_owned())
+        );
+        assert_eq!(dedupe.check(start, "me", "xqc", "hi", "me-2"), None);
+        assert_eq!(
+            dedupe.check(start + DEDUPE_WINDOW, "me", "forsen", "hi", "me-3"),
+            None
+        );
+    }
+
+    #[test]
+    fn vips_and_moderators_get_the_higher_limit() {
+        let vip = [BadgeRef {
+            set_id: "vip".to_owned(),
+            id: "1".to_owned(),
+            info: String::new(),
+            badge: Default::default(),
+        }];
+        assert_eq!(
+            RateClass::detect(ChannelRole::Viewer, &[], false),
+            RateClass::Regular
+        );
+        assert_eq!(
+            RateClass::detect(ChannelRole::Viewer, &vip, false),
+            RateClass::Elevated
+        );
+        assert_eq!(
+            RateClass::detect(ChannelRole::Moderator, &[], false),
+            RateClass::Elevated
+        );
+        assert_eq!(
+            RateClass::detect(ChannelRole::Viewer, &[], true),
+            RateClass::VerifiedBot
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::error::PepoError;
+
+/// Keeps a pathological pattern from eating memory on every message.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// What highlight and filter rules share: an id, an optional regex and the
+/// channels they are scoped to.
+pub trait Rule: Sized {
+    /// Names the rule in validation errors, e.g. `"filter"`.
+    const NOUN: &'static str;
+
+    fn id(&self) -> &str;
+
+    /// Trims the rule, or drops it when it has no id or lacks the pattern its
+    /// kind needs.
+    fn normalized(self) -> Option<Self>;
+
+    /// The pattern that must compile with [`text_regex`], for kinds taking a
+    /// regular expression.
+    fn regex(&self) -> Option<(&str, bool)> {
+        None
+    }
+}
+
+/// Normalizes `rules` for saving, or explains the first one that can't be.
+pub fn validate<R: Rule>(rules: Vec<R>) -> Result<Vec<R>, PepoError> {
+    let noun = R::NOUN;
+    let mut seen = HashSet::new();
+    rules
+        .into_iter()
+        .map(|rule| {
+            let id = rule.id().trim().to_owned();
+            if id.is_empty() {
+                return Err(PepoError::invalid_input(format!(
+                    "every {noun} rule needs an id"
+                )));
+            }
+            if !seen.insert(id.clone()) {
+                return Err(PepoError::invalid_input(format!(
+                    "{noun} rule id \"{id}\" is used more than once"
+                )));
+            }
+            let rule = rule.normalized().ok_or_else(|| {
+                PepoError::invalid_input(format!("{noun} rule \"{id}\" needs a pattern"))
+            })?;
+            if let Some((pattern, case_sensitive)) = rule.regex() {
+                text_regex(pattern, case_sensitive).map_err(|err| {
+                    PepoError::invalid_input(format!(
+                        "{noun} rule \"{id}\" has an invalid regex: {err}"
+                    ))
+                })?;
+            }
+            Ok(rule)
+        })
+        .collect()
+}
+
+/// Drops rules that no longer normalize and repeats of an id, for settings
+/// saved before [`validate`] guarded them.
+pub fn normalize_saved<R: Rule>(rules: Vec<R>) -> Vec<R> {
+    let mut seen = HashSet::new();
+    rules
+        .into_iter()
+        .filter_map(R::normalized)
+        .filter(|rule| seen.insert(rule.id().to_owned()))
+        .collect()
+}
+
+pub fn text_regex(pattern: &str, case_sensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+/// Matches `word` on its own, without eating the characters around it.
+pub fn word_regex(word: &str, case_sensitive: bool) -> Result<Regex, regex::Error> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let boundary = |c: Option<char>| {
+        if c.is_some_and(is_word_char) {
+            r"\b"
+        } else {
+            ""
+        }
+    };
+    text_regex(
+        &format!(
+            "{}{}{}",
+            boundary(word.chars().next()),
+            regex::escape(word),
+            boundary(word.chars().last())
+        ),
+        case_sensitive,
+    )
+}
+
+/// Lowercases channel logins and drops the `#` and blanks users type.
+pub fn normalize_channels(channels: Vec<String>) -> Vec<String> {
+    channels
+        .into_iter()
+        .map(|login| login.trim().trim_start_matches('#').to_lowercase())
+        .filter(|login| !login.is_empty())
+        .collect()
+}
+
+/// Whether a rule scoped to `channels` applies in `channel_login`; no
+/// channels means every channel.
+pub fn applies_to(channels: &[String], channel_login: &str) -> bool {
+    channels.is_empty() || channels.iter().any(|login| login == channel_login)
+}
+
+/// Compiles the saved rules once and again only after they change.
+pub struct CompileCache<R, T> {
+    cache: Arc<Mutex<Option<(Vec<R>, Arc<T>)>>>,
+}
+
+impl<R, T> Clone for CompileCache<R, T> {
+    fn clone(&self) -> Self {
+        CompileCache {
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<R, T> Default for CompileCache<R, T> {
+    fn default() -> Self {
+        CompileCache {
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<R: Clone + PartialEq, T> CompileCache<R, T> {
+    pub fn get(&self, rules: &[R], compile: impl FnOnce(&[R]) -> T) -> Arc<T> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some((cached_rules, compiled)) = cache.as_ref() {
+            if cached_rules.as_slice() == rules {
+                return compiled.clone();
+            }
+        }
+        let compiled = Arc::new(compile(rules));
+        *cache = Some((rules.to_vec(), compiled.clone()));
+        compiled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_channels_and_scopes_rules() {
+        let channels = normalize_channels(vec![" #Luna ".to_owned(), "#".to_owned()]);
+        assert_eq!(channels, ["luna"]);
+        assert!(applies_to(&channels, "luna"));
+        assert!(!applies_to(&channels, "maya"));
+        assert!(applies_to(&[], "maya"));
+    }
+
+    #[test]
+    fn word_regex_matches_whole_words_only() {
+        let gg = word_regex("gg", false).unwrap();
+        assert!(gg.is_match("POGGERS GG"));
+        assert!(!gg.is_match("ggwp"));
+        assert_eq!(gg.find("(gg)").map(|m| m.as_str()), Some("gg"));
+        assert!(word_regex("?!", true).unwrap().is_match("what?!"));
+    }
+
+    #[test]
+    fn recompiles_only_after_the_rules_change() {
+        let cache = CompileCache::<String, usize>::default();
+        let first = cache.get(&["a".to_owned()], |rules| rules.len());
+        let same = cache.get(&["a".to_owned()], |_| unreachable!());
+        assert!(Arc::ptr_eq(&first, &same));
+        assert_eq!(*cache.get(&[], |rules| rules.len()), 0);
+    }
+}
@@ -0,0 +1,525 @@
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    date::{self, DAY_MS},
+    history::{HistoryEntry, HistoryStore},
+    message,
+    types::{AppearanceTheme, ChannelMessage},
+};
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Columns the history table keeps next to each message so filters and the
+/// full-text index don't need to decode payloads.
+pub(crate) struct SearchColumns {
+    pub text: String,
+    /// Lowercase login, matched by `from:`.
+    pub chatter_login: String,
+    pub chatter_id: String,
+    pub has_emote: bool,
+    pub has_link: bool,
+    /// Badge set ids wrapped in commas (`,moderator,subscriber,`) so a single
+    /// `LIKE` matches whole ids.
+    pub badges: String,
+}
+
+impl SearchColumns {
+    pub fn new(message: &ChannelMessage) -> Self {
+        let badges = message
+            .badges
+            .iter()
+            .map(|badge| badge.set_id.to_lowercase())
+            .collect::<Vec<_>>();
+        SearchColumns {
+            text: message.text.clone(),
+            chatter_login: message.chatter_user_login.to_lowercase(),
+            chatter_id: message.chatter_user_id.clone(),
+            has_emote: message
+                .fragments
+                .iter()
+                .any(|fragment| matches!(fragment, message::Fragment::Emote(_))),
+            has_link: message.text.split_whitespace().any(is_link),
+            badges: if badges.is_empty() {
+                String::new()
+            } else {
+                format!(",{},", badges.join(","))
+            },
+        }
+    }
+}
+
+fn is_link(word: &str) -> bool {
+    let word = word.to_ascii_lowercase();
+    word.starts_with("http://") || word.starts_with("https://") || word.starts_with("www.")
+}
+
+/// A parsed `search_chat_history` query, e.g.
+/// `"that link" from:viewer in:forsen has:link badge:moderator after:2024-01-01`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Free-text terms and quoted phrases; all must match.
+    pub terms: Vec<String>,
+    pub from: Option<String>,
+    pub channel: Option<String>,
+    pub has_emote: bool,
+    pub has_link: bool,
+    pub badges: Vec<String>,
+    /// Inclusive lower bound in unix milliseconds.
+    pub after_ms: Option<i64>,
+    /// Exclusive upper bound in unix milliseconds.
+    pub before_ms: Option<i64>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut query = SearchQuery::default();
+        for token in tokenize(input) {
+            let Token { text, quoted } = token;
+            let filter = (!quoted)
+                .then(|| text.split_once(':'))
+                .flatten()
+                .filter(|(_, value)| !value.is_empty());
+            let Some((key, value)) = filter else {
+                query.terms.push(text);
+                continue;
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "from" => query.from = Some(value.trim_start_matches('@').to_lowercase()),
+                "in" => query.channel = Some(value.trim_start_matches('#').to_lowercase()),
+                "has" => match value.to_ascii_lowercase().as_str() {
+                    "emote" => query.has_emote = true,
+                    "link" => query.has_link = true,
+                    other => return Err(format!("unknown has: filter '{other}'")),
+                },
+                "badge" => query.badges.push(value.to_lowercase()),
+                "after" => query.after_ms = Some(parse_date(value)?),
+                "before" => query.before_ms = Some(parse_date(value)?),
+                "on" => {
+                    let day = parse_date(value)?;
+                    query.after_ms = Some(day);
+                    query.before_ms = Some(day + DAY_MS);
+                }
+                _ => query.terms.push(text),
+            }
+        }
+        Ok(query)
+    }
+
+    /// FTS5 match expression: every term as a quoted prefix query.
+    fn match_expression(&self) -> Option<String> {
+        let terms = self
+            .terms
+            .iter()
+            .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+            .collect::<Vec<_>>();
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(Token {
+                        text: std::mem::take(&mut current),
+                        quoted,
+                    });
+                }
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(Token {
+            text: current,
+            quoted,
+        });
+    }
+    tokens
+}
+
+fn parse_date(value: &str) -> Result<i64, String> {
+    date::parse_ymd(value).ok_or_else(|| format!("invalid date '{value}', expected YYYY-MM-DD"))
+}
+
+/// A matched span of a message's text, in UTF-16 code units so it indexes
+/// JavaScript strings directly.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct MatchHighlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct SearchHit {
+    /// History row id; pass the last one back as `cursor` for the next page.
+    pub id: i64,
+    pub sent_at_ms: i64,
+    pub message: ChannelMessage,
+    pub highlights: Vec<MatchHighlight>,
+}
+
+impl SearchHit {
+    pub fn with_emote_theme(mut self, theme: &AppearanceTheme) -> Self {
+        self.message = self.message.with_emote_theme(theme);
+        self
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct SearchPage {
+    /// Newest first.
+    pub hits: Vec<SearchHit>,
+    /// Set when more results may follow.
+    pub next_cursor: Option<i64>,
+}
+
+/// Runs `query` against stored chat messages, newest first, starting below
+/// the row `cursor` when given.
+pub fn search(
+    store: &HistoryStore,
+    query: &SearchQuery,
+    cursor: Option<i64>,
+    limit: usize,
+) -> eyre::Result<SearchPage> {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let mut sql = String::from(
+        "SELECT h.id, h.sent_at_ms, h.payload FROM history h WHERE h.kind = 'message'",
+    );
+    let mut values: Vec<Value> = Vec::new();
+    let mut bind = |sql: &mut String, clause: &str, value: Value| {
+        values.push(value);
+        sql.push_str(&clause.replace('?', &format!("?{}", values.len())));
+    };
+
+    if let Some(expression) = query.match_expression() {
+        bind(
+            &mut sql,
+            " AND h.id IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ?)",
+            Value::Text(expression),
+        );
+    }
+    if let Some(from) = &query.from {
+        bind(
+            &mut sql,
+            " AND h.chatter_login = ?",
+            Value::Text(from.clone()),
+        );
+    }
+    if let Some(channel) = &query.channel {
+        bind(
+            &mut sql,
+            " AND h.channel_login = ?",
+            Value::Text(channel.clone()),
+        );
+    }
+    if query.has_emote {
+        sql.push_str(" AND h.has_emote = 1");
+    }
+    if query.has_link {
+        sql.push_str(" AND h.has_link = 1");
+    }
+    for badge in &query.badges {
+        bind(
+            &mut sql,
+            " AND h.badges LIKE ? ESCAPE '\\'",
+            Value::Text(format!("%,{},%", escape_like(badge))),
+        );
+    }
+    if let Some(after_ms) = query.after_ms {
+        bind(&mut sql, " AND h.sent_at_ms >= ?", Value::Integer(after_ms));
+    }
+    if let Some(before_ms) = query.before_ms {
+        bind(&mut sql, " AND h.sent_at_ms < ?", Value::Integer(before_ms));
+    }
+    if let Some(cursor) = cursor {
+        bind(
+            &mut sql,
+            " AND (h.sent_at_ms, h.id) < (SELECT sent_at_ms, id FROM history WHERE id = ?)",
+            Value::Integer(cursor),
+        );
+    }
+    bind(
+        &mut sql,
+        " ORDER BY h.sent_at_ms DESC, h.id DESC LIMIT ?",
+        Value::Integer(limit as i64),
+    );
+
+    let hits = store.with_reader(|conn| -> eyre::Result<Vec<SearchHit>> {
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(rusqlite::params_from_iter(values), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut hits = Vec::new();
+        for row in rows {
+            let (id, sent_at_ms, payload) = row?;
+            match serde_json::from_str::<HistoryEntry>(&payload) {
+                Ok(HistoryEntry::Message(message)) => hits.push(SearchHit {
+                    id,
+                    sent_at_ms,
+                    highlights: highlights(&message.text, &query.terms),
+                    message: message.with_fresh_index(),
+                }),
+                Ok(_) => {}
+                Err(err) => warn!(id, "skipping undecodable chat history entry: {err}"),
+            }
+        }
+        Ok(hits)
+    })?;
+
+    let next_cursor = (hits.len() == limit)
+        .then(|| hits.last().map(|hit| hit.id))
+        .flatten();
+    Ok(SearchPage { hits, next_cursor })
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Case-insensitive occurrences of any term in `text`, merged where they
+/// overlap.
+fn highlights(text: &str, terms: &[String]) -> Vec<MatchHighlight> {
+    let chars: Vec<char> = text.chars().collect();
+    let folded: Vec<String> = chars.iter().map(|c| c.to_lowercase().collect()).collect();
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for term in terms {
+        let term: Vec<String> = term.chars().map(|c| c.to_lowercase().collect()).collect();
+        if term.is_empty() || term.len() > chars.len() {
+            continue;
+        }
+        for start in 0..=chars.len() - term.len() {
+            if folded[start..start + term.len()] == term[..] {
+                spans.push((start, start + term.len()));
+            }
+        }
+    }
+    spans.sort_unstable();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let mut utf16_offsets = Vec::with_capacity(chars.len() + 1);
+    let mut offset = 0;
+    utf16_offsets.push(0);
+    for c in &chars {
+        offset += c.len_utf16();
+        utf16_offsets.push(offset);
+    }
+    merged
+        .into_iter()
+        .map(|(start, end)| MatchHighlight {
+            start: utf16_offsets[start],
+            end: utf16_offsets[end],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        emote::Emote,
+        history::{HistorySettingsReader, HISTORY_FILE},
+        types::{BadgeRef, HistorySettings},
+    };
+
+    // 2023-11-14T22:13:20Z
+    const SENT_AT_MS: i64 = 1_700_000_000_000;
+
+    fn message(id: &str, channel: &str, chatter: &str, text: &str) -> ChannelMessage {
+        ChannelMessage::test(id, channel, chatter, text)
+    }
+
+    fn open_store(dir: &tempfile::TempDir) -> HistoryStore {
+        let settings = HistorySettings {
+            retention_days: 36_500,
+            ..Default::default()
+        };
+        let reader: HistorySettingsReader = Arc::new(move || settings.clone());
+        HistoryStore::open(&dir.path().join(HISTORY_FILE), reader).unwrap()
+    }
+
+    fn seeded_store(dir: &tempfile::TempDir) -> HistoryStore {
+        let store = open_store(dir);
+        let mut moderator = message("m1", "forsen", "ModUser", "check https://example.com/clip");
+        moderator.badges.push(BadgeRef {
+            set_id: "moderator".to_string(),
+            id: "1".to_string(),
+            info: String::new(),
+            badge: Default::default(),
+        });
+        let mut emote = message("m2", "forsen", "Viewer", "Kappa clip");
+        emote.fragments = vec![message::Fragment::Emote(message::EmoteFragment {
+            index: 0,
+            emote: Emote {
+                name: "Kappa".to_string(),
+                ..Default::default()
+            },
+        })];
+        let entries = [
+            (moderator, SENT_AT_MS),
+            (emote, SENT_AT_MS + 1_000),
+            (
+                message("m3", "xqc", "Viewer", "another clip"),
+                SENT_AT_MS + DAY_MS,
+            ),
+        ];
+        for (message, sent_at_ms) in entries {
+            store.record_at(HistoryEntry::Message(message), sent_at_ms);
+        }
+        store.flush();
+        store
+    }
+
+    fn ids(page: &SearchPage) -> Vec<&str> {
+        page.hits
+            .iter()
+            .map(|hit| hit.message.message_id.as_str())
+            .collect()
+    }
+
+    fn run(store: &HistoryStore, query: &str) -> SearchPage {
+        search(store, &SearchQuery::parse(query).unwrap(), None, 50).unwrap()
+    }
+
+    #[test]
+    fn parses_filters_and_phrases() {
+        let query = SearchQuery::parse(
+            "\"that link\" from:@Viewer in:#Forsen has:link badge:Moderator on:2023-11-14 clip",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query,
+            SearchQuery {
+                terms: vec!["that link".to_string(), "clip".to_string()],
+                from: Some("viewer".to_string()),
+                channel: Some("forsen".to_string()),
+                has_emote: false,
+                has_link: true,
+                badges: vec!["moderator".to_string()],
+                after_ms: Some(1_699_920_000_000),
+                before_ms: Some(1_699_920_000_000 + DAY_MS),
+            }
+        );
+        assert!(SearchQuery::parse("has:video").is_err());
+        assert!(SearchQuery::parse("after:2023-13-01").is_err());
+        assert!(SearchQuery::parse("on:2024-02-31").is_err());
+    }
+
+    #[test]
+    fn free_text_matches_prefixes_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = seeded_store(&dir);
+
+        assert_eq!(ids(&run(&store, "cli")), vec!["m3", "m2", "m1"]);
+        assert_eq!(ids(&run(&store, "example clip")), vec!["m1"]);
+        assert!(run(&store, "nothing").hits.is_empty());
+    }
+
+    #[test]
+    fn structured_filters_narrow_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = seeded_store(&dir);
+
+        assert_eq!(ids(&run(&store, "from:viewer")), vec!["m3", "m2"]);
+        assert_eq!(ids(&run(&store, "in:forsen clip")), vec!["m2", "m1"]);
+        assert_eq!(ids(&run(&store, "has:emote")), vec!["m2"]);
+        assert_eq!(ids(&run(&store, "has:link")), vec!["m1"]);
+        assert_eq!(ids(&run(&store, "badge:moderator")), vec!["m1"]);
+        assert_eq!(ids(&run(&store, "after:2023-11-15")), vec!["m3"]);
+        assert_eq!(ids(&run(&store, "before:2023-11-15")), vec!["m2", "m1"]);
+    }
+
+    #[test]
+    fn pages_with_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = seeded_store(&dir);
+        let query = SearchQuery::parse("clip").unwrap();
+
+        let first = search(&store, &query, None, 2).unwrap();
+        assert_eq!(ids(&first), vec!["m3", "m2"]);
+        let second = search(&store, &query, first.next_cursor, 2).unwrap();
+        assert_eq!(ids(&second), vec!["m1"]);
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[test]
+    fn pruned_messages_leave_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = HistorySettings {
+            max_entries_per_channel: 1,
+            retention_days: 36_500,
+            ..Default::default()
+        };
+        let reader: HistorySettingsReader = Arc::new(move || settings.clone());
+        let store = HistoryStore::open(&dir.path().join(HISTORY_FILE), reader).unwrap();
+        store.record_at(
+            HistoryEntry::Message(message("m1", "forsen", "a", "old clip")),
+            SENT_AT_MS,
+        );
+        store.record_at(
+            HistoryEntry::Message(message("m2", "forsen", "a", "new clip")),
+            SENT_AT_MS + 1,
+        );
+        store.flush();
+
+        assert_eq!(ids(&run(&store, "clip")), vec!["m2"]);
+        assert!(run(&store, "old").hits.is_empty());
+    }
+
+    #[test]
+    fn from_matches_logins_rather_than_display_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut localized = message("m1", "forsen", "viewer", "hello clip");
+        localized.chatter_user_name = "시청자".to_string();
+        let store = open_store(&dir);
+        store.record_at(HistoryEntry::Message(localized), SENT_AT_MS);
+        store.flush();
+        assert_eq!(ids(&run(&store, "from:viewer")), vec!["m1"]);
+    }
+
+    #[test]
+    fn highlights_use_utf16_offsets_and_merge_overlaps() {
+        assert_eq!(
+            highlights("😀 Clip clipper", &["clip".to_string(), "pper".to_string()]),
+            vec![
+                MatchHighlight { start: 3, end: 7 },
+                MatchHighlight { start: 8, end: 15 },
+            ]
+        );
+    }
+}
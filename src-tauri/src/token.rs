@@ -1,4 +1,4 @@
-use crate::types;
+use crate::{error::PepoError, types};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -24,6 +24,44 @@ lazy_static! {
         Scope::UserReadBlockedUsers,
         Scope::UserReadSubscriptions,
     ];
+    /// Requested on demand, the first time the user reaches for a moderation
+    /// tool, so viewers never see a consent screen full of moderator scopes.
+    pub static ref MODERATOR_SCOPES: Vec<Scope> = vec![
+        Scope::UserReadModeratedChannels,
+        Scope::ModeratorManageBannedUsers,
+        Scope::ModeratorManageChatMessages,
+        Scope::ModeratorManageAutoMod,
+        Scope::ModeratorManageUnbanRequests,
+        Scope::ModeratorManageChatSettings,
+        Scope::ModeratorManageBlockedTerms,
+        // The rest of what `channel.moderate` needs to see every action.
+        Scope::ModeratorReadBlockedTerms,
+        Scope::ModeratorReadChatSettings,
+        Scope::ModeratorReadModerators,
+        Scope::ModeratorReadVips,
+        Scope::ModeratorReadWarnings,
+        Scope::ModeratorReadSuspiciousUsers,
+        MODERATOR_MANAGE_SUSPICIOUS_USERS.clone(),
+        // Behind chat commands: /announce and /shoutout.
+        Scope::ModeratorManageAnnouncements,
+        Scope::ModeratorManageShoutouts,
+    ];
+    /// Requested by broadcasters and their editors for /raid, /unraid and
+    /// /marker, which moderators can't run.
+    pub static ref BROADCASTER_SCOPES: Vec<Scope> = vec![
+        Scope::ChannelManageRaids,
+        Scope::ChannelManageBroadcast,
+    ];
+    /// Requested the first time anyone changes their name colour with /color.
+    pub static ref COLOR_SCOPES: Vec<Scope> = vec![Scope::UserManageChatColor];
+    /// Requested only when the user turns whispers on.
+    pub static ref WHISPER_SCOPES: Vec<Scope> = vec![
+        Scope::UserReadWhispers,
+        Scope::UserManageWhispers,
+    ];
+    /// Newer than `twitch_oauth2`, so it can only be spelled out.
+    pub static ref MODERATOR_MANAGE_SUSPICIOUS_USERS: Scope =
+        Scope::Other("moderator:manage:suspicious_users".into());
 }
 
 #[derive(Clone)]
@@ -123,11 +161,20 @@ fn public_from_twitch_token(
     public
 }
 
-fn new_device_builder() -> twitch_oauth2::DeviceUserTokenBuilder {
-    twitch_oauth2::tokens::DeviceUserTokenBuilder::new(
-        CLIENT_ID.to_string(),
-        DEFAULT_SCOPES.clone(),
-    )
+fn device_flow_error(context: &str, err: impl std::fmt::Display) -> PepoError {
+    PepoError::Provider {
+        message: format!("{context}: {err}"),
+    }
+}
+
+fn new_device_builder(extra_scopes: &[Scope]) -> twitch_oauth2::DeviceUserTokenBuilder {
+    let mut scopes = DEFAULT_SCOPES.clone();
+    for scope in extra_scopes {
+        if !scopes.contains(scope) {
+            scopes.push(scope.clone());
+        }
+    }
+    twitch_oauth2::tokens::DeviceUserTokenBuilder::new(CLIENT_ID.to_string(), scopes)
 }
 
 #[derive(Clone)]
@@ -147,7 +194,7 @@ impl TokenManager {
             state: Arc::new(Mutex::new(TokenManagerState::default())),
             client,
             app_handle,
-            builder: Arc::new(Mutex::new(new_device_builder())),
+            builder: Arc::new(Mutex::new(new_device_builder(&[]))),
         }
     }
 
@@ -206,28 +253,61 @@ impl TokenManager {
         Ok(())
     }
 
-    pub async fn start_device_code_flow(&self) -> twitch_oauth2::id::DeviceCodeResponse {
+    pub async fn start_device_code_flow(
+        &self,
+    ) -> Result<twitch_oauth2::id::DeviceCodeResponse, PepoError> {
         debug!("starting device flow");
 
         let mut build_guard = self.builder.lock().await;
-        build_guard.start(&self.client).await.unwrap().clone()
+        build_guard
+            .start(&self.client)
+            .await
+            .cloned()
+            .map_err(|e| device_flow_error("failed to start authorization", e))
+    }
+
+    /// Starts a device flow asking for `extra_scopes` on top of the defaults,
+    /// keeping whatever the active token was already granted so re-authorizing
+    /// never drops a scope.
+    pub async fn start_scope_upgrade_flow(
+        &self,
+        extra_scopes: &[Scope],
+    ) -> Result<twitch_oauth2::id::DeviceCodeResponse, PepoError> {
+        debug!(?extra_scopes, "starting device flow for additional scopes");
+
+        let mut scopes = self
+            .active_twitch_token()
+            .await
+            .map(|token| token.scopes().to_vec())
+            .unwrap_or_default();
+        scopes.extend(extra_scopes.iter().cloned());
+
+        let mut build_guard = self.builder.lock().await;
+        *build_guard = new_device_builder(&scopes);
+        build_guard
+            .start(&self.client)
+            .await
+            .cloned()
+            .map_err(|e| device_flow_error("failed to start authorization", e))
     }
 
-    pub async fn finish_device_code_flow(&self) -> twitch_oauth2::UserToken {
+    /// Waits for the user to approve the device code; denying it or letting
+    /// it expire is an error, and the active token is left as it was.
+    pub async fn finish_device_code_flow(&self) -> Result<twitch_oauth2::UserToken, PepoError> {
         debug!("finishing device flow");
 
         let mut build_guard = self.builder.lock().await;
         let token = build_guard
             .wait_for_code(&self.client, tokio::time::sleep)
             .await
-            .unwrap();
+            .map_err(|e| device_flow_error("authorization failed", e))?;
         drop(build_guard);
 
         if let Err(err) = self.set_active_twitch_token(token.clone(), None).await {
             error!("failed to persist completed device flow token: {}", err);
         }
 
-        token
+        Ok(token)
     }
 
     pub async fn is_active_token_valid(&self) -> bool {
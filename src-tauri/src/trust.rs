@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use twitch_api::eventsub::channel::suspicious_user::{
+    BanEvasionEvaluation, ChannelSuspiciousUserMessageV1Payload,
+    ChannelSuspiciousUserUpdateV1Payload, LowTrustStatus,
+};
+
+/// How a moderator chose to treat a suspicious user.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Treatment {
+    /// Their messages show up with a flag for moderators.
+    Monitored,
+    /// Only moderators see their messages.
+    Restricted,
+}
+
+impl Treatment {
+    /// The value Helix expects for `status`.
+    pub fn helix_status(self) -> &'static str {
+        match self {
+            Treatment::Monitored => "ACTIVE_MONITORING",
+            Treatment::Restricted => "RESTRICTED",
+        }
+    }
+
+    fn from_low_trust(status: &LowTrustStatus) -> Option<Self> {
+        match status {
+            LowTrustStatus::ActiveMonitoring => Some(Treatment::Monitored),
+            LowTrustStatus::Restricted => Some(Treatment::Restricted),
+            LowTrustStatus::None => None,
+            other => {
+                warn!("unknown low trust status {other:?}");
+                None
+            }
+        }
+    }
+}
+
+/// Twitch's estimate of whether someone is evading a ban in the channel.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BanEvasion {
+    Possible,
+    Likely,
+}
+
+/// Attached to messages from chatters Twitch or a moderator flagged as low
+/// trust in the channel.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct TrustStatus {
+    pub treatment: Option<Treatment>,
+    pub ban_evasion: Option<BanEvasion>,
+    /// Channels sharing ban info with this one that have banned the user.
+    pub shared_ban_channel_ids: Vec<String>,
+}
+
+impl TrustStatus {
+    fn is_flagged(&self) -> bool {
+        self.treatment.is_some()
+            || self.ban_evasion.is_some()
+            || !self.shared_ban_channel_ids.is_empty()
+    }
+}
+
+/// Emitted on `trust_status:{login}` whenever a chatter's status changes, so
+/// their messages already in chat can be re-flagged.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct TrustStatusUpdate {
+    pub user_id: String,
+    pub user_login: String,
+    /// `None` once the user is no longer flagged.
+    pub trust_status: Option<TrustStatus>,
+}
+
+/// Known low-trust chatters per broadcaster id. Only flagged users are kept.
+#[derive(Clone, Default)]
+pub struct TrustStatuses(Arc<Mutex<HashMap<String, HashMap<String, TrustStatus>>>>);
+
+impl TrustStatuses {
+    pub fn get(&self, broadcaster_id: &str, user_id: &str) -> Option<TrustStatus> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(broadcaster_id)
+            .and_then(|users| users.get(user_id))
+            .cloned()
+    }
+
+    /// Takes the full picture Twitch sends with each of the user's messages.
+    pub fn record_message(
+        &self,
+        message: &ChannelSuspiciousUserMessageV1Payload,
+    ) -> TrustStatusUpdate {
+        let status = TrustStatus {
+            treatment: Treatment::from_low_trust(&message.low_trust_status),
+            ban_evasion: match &message.ban_evasion_evaluation {
+                BanEvasionEvaluation::Possible => Some(BanEvasion::Possible),
+                BanEvasionEvaluation::Likely => Some(BanEvasion::Likely),
+                _ => None,
+            },
+            shared_ban_channel_ids: message
+                .shared_ban_channel_ids
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        };
+        TrustStatusUpdate {
+            user_id: message.user_id.to_string(),
+            user_login: message.user_login.to_string(),
+            trust_status: self.store(
+                message.broadcaster_user_id.as_str(),
+                message.user_id.as_str(),
+                status,
+            ),
+        }
+    }
+
+    /// A moderator changed the user's treatment; the rest of what we know
+    /// about them still holds.
+    pub fn record_update(
+        &self,
+        update: &ChannelSuspiciousUserUpdateV1Payload,
+    ) -> TrustStatusUpdate {
+        let broadcaster_id = update.broadcaster_user_id.as_str();
+        let user_id = update.user_id.as_str();
+        let status = TrustStatus {
+            treatment: Treatment::from_low_trust(&update.low_trust_status),
+            ..self.get(broadcaster_id, user_id).unwrap_or_default()
+        };
+        TrustStatusUpdate {
+            user_id: user_id.to_owned(),
+            user_login: update.user_login.to_string(),
+            trust_status: self.store(broadcaster_id, user_id, status),
+        }
+    }
+
+    fn store(
+        &self,
+        broadcaster_id: &str,
+        user_id: &str,
+        status: TrustStatus,
+    ) -> Option<TrustStatus> {
+        let mut channels = self.0.lock().unwrap();
+        let users = channels.entry(broadcaster_id.to_owned()).or_default();
+        if status.is_flagged() {
+            users.insert(user_id.to_owned(), status.clone());
+            Some(status)
+        } else {
+            users.remove(user_id);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suspicious_message(
+        low_trust_status: &str,
+        evaluation: &str,
+    ) -> ChannelSuspiciousUserMessageV1Payload {
+        serde_json::from_value(serde_json::json!({
+            "broadcaster_user_id": "forsen-id",
+            "broadcaster_user_login": "forsen",
+            "broadcaster_user_name": "forsen",
+            "user_id": "42",
+            "user_login": "viewer",
+            "user_name": "Viewer",
+            "low_trust_status": low_trust_status,
+            "shared_ban_channel_ids": null,
+            "types": ["ban_evader"],
+            "ban_evasion_evaluation": evaluation,
+            "message": {"message_id": "m1", "text": "hi", "fragments": []}
+        }))
+        .unwrap()
+    }
+
+    fn update(low_trust_status: &str) -> ChannelSuspiciousUserUpdateV1Payload {
+        serde_json::from_value(serde_json::json!({
+            "broadcaster_user_id": "forsen-id",
+            "broadcaster_user_login": "forsen",
+            "broadcaster_user_name": "forsen",
+            "moderator_user_id": "7",
+            "moderator_user_login": "somemod",
+            "moderator_user_name": "SomeMod",
+            "user_id": "42",
+            "user_login": "viewer",
+            "user_name": "Viewer",
+            "low_trust_status": low_trust_status
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn flags_ban_evaders_from_their_messages() {
+        let statuses = TrustStatuses::default();
+        let recorded = statuses.record_message(&suspicious_message("active_monitoring", "likely"));
+
+        let expected = TrustStatus {
+            treatment: Some(Treatment::Monitored),
+            ban_evasion: Some(BanEvasion::Likely),
+            shared_ban_channel_ids: Vec::new(),
+        };
+        assert_eq!(recorded.user_login, "viewer");
+        assert_eq!(recorded.trust_status, Some(expected.clone()));
+        assert_eq!(statuses.get("forsen-id", "42"), Some(expected));
+        assert_eq!(statuses.get("xqc-id", "42"), None);
+    }
+
+    #[test]
+    fn treatment_updates_keep_the_evasion_estimate() {
+        let statuses = TrustStatuses::default();
+        statuses.record_message(&suspicious_message("none", "possible"));
+
+        let restricted = statuses
+            .record_update(&update("restricted"))
+            .trust_status
+            .unwrap();
+        assert_eq!(restricted.treatment, Some(Treatment::Restricted));
+        assert_eq!(restricted.ban_evasion, Some(BanEvasion::Possible));
+
+        statuses.record_message(&suspicious_message("none", "unknown"));
+        assert_eq!(statuses.get("forsen-id", "42"), None);
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use twitch_api::{client::CompatError, HelixClient};
@@ -8,9 +8,10 @@ use twitch_oauth2::TwitchToken;
 
 use crate::{
     badgemanager::{Badge, BadgeManager},
+    batch, commands,
     emote::{cache::EmoteCacheTrait, Emote},
     emotemanager::EmoteManager,
-    message,
+    filter, highlight, message, modlog, rule, trust,
 };
 
 pub const APP_SETTINGS_SCHEMA_VERSION: u32 = 1;
@@ -50,6 +51,11 @@ pub struct AppSettings {
     pub auth: AuthSettings,
     pub eventsub: EventSubSettings,
     pub providers: ProviderSettings,
+    pub history: HistorySettings,
+    pub backfill: BackfillSettings,
+    pub chat_log: ChatLogSettings,
+    pub highlights: HighlightSettings,
+    pub filters: FilterSettings,
 }
 
 impl Default for AppSettings {
@@ -64,6 +70,11 @@ impl Default for AppSettings {
             auth: AuthSettings::default(),
             eventsub: EventSubSettings::default(),
             providers: ProviderSettings::default(),
+            history: HistorySettings::default(),
+            backfill: BackfillSettings::default(),
+            chat_log: ChatLogSettings::default(),
+            highlights: HighlightSettings::default(),
+            filters: FilterSettings::default(),
         }
     }
 }
@@ -77,6 +88,11 @@ impl AppSettings {
         self.auth = self.auth.normalized();
         self.eventsub = self.eventsub.normalized();
         self.providers = self.providers.normalized();
+        self.history = self.history.normalized();
+        self.backfill = self.backfill.normalized();
+        self.chat_log = self.chat_log.normalized();
+        self.highlights = self.highlights.normalized();
+        self.filters = self.filters.normalized();
         self
     }
 }
@@ -196,6 +212,27 @@ pub struct EventSubSettings {
     pub repeated_log_throttle_enabled: bool,
     pub unparseable_warning_throttle_secs: u64,
     pub subscription_error_throttle_secs: u64,
+    /// Events held across all channels before chat messages start being shed.
+    pub queue_capacity: usize,
+    pub overload_policy: OverloadPolicy,
+}
+
+/// What to shed once the EventSub queue is full. Only chat messages are ever
+/// dropped.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverloadPolicy {
+    /// Drop the oldest queued message of the busiest channel the user isn't
+    /// looking at.
+    ShedBusiestUnfocused,
+    /// Drop the message that just arrived.
+    ShedIncoming,
+}
+
+impl Default for OverloadPolicy {
+    fn default() -> Self {
+        Self::ShedBusiestUnfocused
+    }
 }
 
 impl Default for EventSubSettings {
@@ -209,6 +246,8 @@ impl Default for EventSubSettings {
             repeated_log_throttle_enabled: true,
             unparseable_warning_throttle_secs: 60,
             subscription_error_throttle_secs: 300,
+            queue_capacity: 5000,
+            overload_policy: OverloadPolicy::default(),
         }
     }
 }
@@ -234,6 +273,7 @@ impl EventSubSettings {
             self.subscription_error_throttle_secs,
             defaults.subscription_error_throttle_secs,
         );
+        self.queue_capacity = usize_or_default(self.queue_capacity, defaults.queue_capacity);
         self
     }
 
@@ -268,6 +308,15 @@ pub struct ProviderSettings {
     pub http_request_timeout_secs: u64,
     pub metadata_retention_enabled: bool,
     pub metadata_retention_secs: u64,
+    /// Total attempts per fetch, including the first; only transient
+    /// failures (network errors, 5xx, 408, 429) are retried.
+    pub fetch_retry_attempts: u32,
+    pub fetch_retry_base_ms: u64,
+    pub fetch_retry_max_ms: u64,
+    /// Consecutive transient failures before a provider's breaker opens.
+    pub circuit_breaker_threshold: u32,
+    pub circuit_breaker_cooldown_secs: u64,
+    pub background_retry_enabled: bool,
 }
 
 impl Default for ProviderSettings {
@@ -277,6 +326,12 @@ impl Default for ProviderSettings {
             http_request_timeout_secs: 15,
             metadata_retention_enabled: true,
             metadata_retention_secs: 30 * 24 * 60 * 60,
+            fetch_retry_attempts: 3,
+            fetch_retry_base_ms: 250,
+            fetch_retry_max_ms: 4_000,
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown_secs: 60,
+            background_retry_enabled: true,
         }
     }
 }
@@ -296,6 +351,20 @@ impl ProviderSettings {
             self.metadata_retention_secs,
             defaults.metadata_retention_secs,
         );
+        if self.fetch_retry_attempts == 0 {
+            self.fetch_retry_attempts = defaults.fetch_retry_attempts;
+        }
+        self.fetch_retry_base_ms =
+            u64_or_default(self.fetch_retry_base_ms, defaults.fetch_retry_base_ms);
+        self.fetch_retry_max_ms =
+            u64_or_default(self.fetch_retry_max_ms, defaults.fetch_retry_max_ms);
+        if self.circuit_breaker_threshold == 0 {
+            self.circuit_breaker_threshold = defaults.circuit_breaker_threshold;
+        }
+        self.circuit_breaker_cooldown_secs = u64_or_default(
+            self.circuit_breaker_cooldown_secs,
+            defaults.circuit_breaker_cooldown_secs,
+        );
         self
     }
 
@@ -306,6 +375,208 @@ impl ProviderSettings {
     pub fn http_request_timeout(self) -> Duration {
         Duration::from_secs(self.http_request_timeout_secs)
     }
+
+    pub fn fetch_retry_base(self) -> Duration {
+        Duration::from_millis(self.fetch_retry_base_ms)
+    }
+
+    pub fn fetch_retry_max(self) -> Duration {
+        Duration::from_millis(self.fetch_retry_max_ms)
+    }
+
+    pub fn circuit_breaker_cooldown(self) -> Duration {
+        Duration::from_secs(self.circuit_breaker_cooldown_secs)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct HistorySettings {
+    pub enabled: bool,
+    /// Newest entries kept per channel, unless overridden below.
+    pub max_entries_per_channel: usize,
+    /// Per-channel overrides of `max_entries_per_channel`, keyed by login.
+    pub channel_max_entries: HashMap<String, usize>,
+    /// Newest entries kept across all channels.
+    pub max_total_entries: usize,
+    pub retention_days: u64,
+    pub write_batch_size: usize,
+    pub write_flush_ms: u64,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries_per_channel: 5_000,
+            channel_max_entries: HashMap::new(),
+            max_total_entries: 250_000,
+            retention_days: 30,
+            write_batch_size: 200,
+            write_flush_ms: 500,
+        }
+    }
+}
+
+impl HistorySettings {
+    pub fn normalized(mut self) -> Self {
+        let defaults = Self::default();
+        self.max_entries_per_channel = usize_or_default(
+            self.max_entries_per_channel,
+            defaults.max_entries_per_channel,
+        );
+        self.channel_max_entries = self
+            .channel_max_entries
+            .into_iter()
+            .filter(|(_, limit)| *limit > 0)
+            .map(|(login, limit)| (login.trim().to_lowercase(), limit))
+            .filter(|(login, _)| !login.is_empty())
+            .collect();
+        self.max_total_entries =
+            usize_or_default(self.max_total_entries, defaults.max_total_entries);
+        self.retention_days = u64_or_default(self.retention_days, defaults.retention_days);
+        self.write_batch_size = usize_or_default(self.write_batch_size, defaults.write_batch_size);
+        self.write_flush_ms = u64_or_default(self.write_flush_ms, defaults.write_flush_ms);
+        self
+    }
+
+    pub fn max_entries_for(&self, channel_login: &str) -> usize {
+        self.channel_max_entries
+            .get(channel_login)
+            .copied()
+            .unwrap_or(self.max_entries_per_channel)
+    }
+
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_days.saturating_mul(24 * 60 * 60))
+    }
+
+    pub fn write_flush_interval(&self) -> Duration {
+        Duration::from_millis(self.write_flush_ms)
+    }
+}
+
+/// Recent-messages backfill when joining a channel.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct BackfillSettings {
+    /// Off until the user opts in, since every joined channel is sent to
+    /// `base_url`.
+    pub enabled: bool,
+    /// Service base URL; the channel login is appended as the last path
+    /// segment, as with the public recent-messages API.
+    pub base_url: String,
+    pub message_limit: usize,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for BackfillSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            base_url: "https://recent-messages.robotty.de/api/v2/recent-messages".to_string(),
+            message_limit: 100,
+            request_timeout_secs: 5,
+        }
+    }
+}
+
+impl BackfillSettings {
+    /// The public service keeps at most this many messages per channel.
+    const MAX_MESSAGE_LIMIT: usize = 800;
+
+    pub fn normalized(mut self) -> Self {
+        let defaults = Self::default();
+        if self.base_url.trim().is_empty() {
+            self.base_url = defaults.base_url;
+        } else {
+            self.base_url = self.base_url.trim().to_string();
+        }
+        self.message_limit = usize_or_default(self.message_limit, defaults.message_limit)
+            .min(Self::MAX_MESSAGE_LIMIT);
+        self.request_timeout_secs =
+            u64_or_default(self.request_timeout_secs, defaults.request_timeout_secs);
+        self
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatLogFormat {
+    /// One JSON-encoded `ChannelMessage` per line.
+    Jsonl,
+    /// `[date time] name: text`, with emotes as their names.
+    Plain,
+    /// Chatterino's `Twitch/Channels/<channel>/<channel>-<date>.log` layout.
+    Chatterino,
+}
+
+impl Default for ChatLogFormat {
+    fn default() -> Self {
+        Self::Chatterino
+    }
+}
+
+/// Which daily log files to keep, mirroring the log plugin's `RotationStrategy`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatLogRotation {
+    KeepAll,
+    /// Delete files older than `keep_days`.
+    KeepDays,
+}
+
+impl Default for ChatLogRotation {
+    fn default() -> Self {
+        Self::KeepAll
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct ChatLogSettings {
+    /// Append every live message to a daily per-channel file.
+    pub always_log: bool,
+    /// Where logs and exports are written; empty means the app data directory.
+    pub directory: String,
+    pub format: ChatLogFormat,
+    pub rotation: ChatLogRotation,
+    pub keep_days: u32,
+}
+
+impl Default for ChatLogSettings {
+    fn default() -> Self {
+        Self {
+            always_log: false,
+            directory: String::new(),
+            format: ChatLogFormat::default(),
+            rotation: ChatLogRotation::default(),
+            keep_days: 30,
+        }
+    }
+}
+
+impl ChatLogSettings {
+    pub fn normalized(mut self) -> Self {
+        let defaults = Self::default();
+        self.directory = self.directory.trim().to_string();
+        if self.keep_days == 0 {
+            self.keep_days = defaults.keep_days;
+        }
+        self
+    }
+
+    /// Days of files to keep, or `None` to keep everything.
+    pub fn retention_days(&self) -> Option<u32> {
+        match self.rotation {
+            ChatLogRotation::KeepAll => None,
+            ChatLogRotation::KeepDays => Some(self.keep_days),
+        }
+    }
 }
 
 fn u64_or_default(value: u64, fallback: u64) -> u64 {
@@ -376,6 +647,17 @@ pub struct ChatSettings {
     pub show_badges: bool,
     pub show_emotes: bool,
     pub alternate_backgrounds: bool,
+    /// Local slash commands, expanded before the built-in ones run.
+    pub command_aliases: Vec<commands::CommandAlias>,
+    /// Accounts Twitch verified as bots, which get a much higher chat limit.
+    pub verified_bot_user_ids: Vec<String>,
+    /// Send incoming messages to the chat view in batches on
+    /// `chat_messages:{login}` instead of one `chat_message:{login}` event each.
+    pub batch_messages: bool,
+    /// How long a channel's messages are held before a batch goes out.
+    pub batch_window_ms: u64,
+    /// A batch goes out early once this many messages are waiting.
+    pub batch_max_messages: usize,
 }
 
 impl Default for ChatSettings {
@@ -390,6 +672,11 @@ impl Default for ChatSettings {
             show_badges: true,
             show_emotes: true,
             alternate_backgrounds: true,
+            command_aliases: Vec::new(),
+            verified_bot_user_ids: Vec::new(),
+            batch_messages: true,
+            batch_window_ms: 16,
+            batch_max_messages: 100,
         }
     }
 }
@@ -406,6 +693,55 @@ impl ChatSettings {
         if self.timestamp_locale.trim().is_empty() {
             self.timestamp_locale = defaults.timestamp_locale;
         }
+        self.batch_window_ms = u64_or_default(self.batch_window_ms, defaults.batch_window_ms);
+        self.batch_max_messages =
+            usize_or_default(self.batch_max_messages, defaults.batch_max_messages);
+        let mut seen = HashSet::new();
+        self.command_aliases = self
+            .command_aliases
+            .into_iter()
+            .filter_map(commands::CommandAlias::normalized)
+            .filter(|alias| seen.insert(alias.name.clone()))
+            .collect();
+        self
+    }
+
+    pub fn batch_limits(&self) -> batch::BatchLimits {
+        batch::BatchLimits {
+            window: Duration::from_millis(self.batch_window_ms),
+            max_messages: self.batch_max_messages,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Default)]
+#[serde(default)]
+pub struct HighlightSettings {
+    /// Saved through `set_highlight_rules`, which rejects broken regexes.
+    pub rules: Vec<highlight::HighlightRule>,
+}
+
+impl HighlightSettings {
+    pub fn normalized(mut self) -> Self {
+        self.rules = rule::normalize_saved(self.rules);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Default)]
+#[serde(default)]
+pub struct FilterSettings {
+    /// Saved through `set_filter_rules`, which rejects broken regexes.
+    pub rules: Vec<filter::FilterRule>,
+    /// Also filter chatters the account blocked on Twitch. Turned on through
+    /// `set_honor_block_list`, which loads the list.
+    pub honor_block_list: bool,
+    pub block_list_action: filter::FilterAction,
+}
+
+impl FilterSettings {
+    pub fn normalized(mut self) -> Self {
+        self.rules = rule::normalize_saved(self.rules);
         self
     }
 }
@@ -453,6 +789,10 @@ pub struct EmoteSettings {
     pub picker_max_height_px: u32,
     pub inline_emote_px: u32,
     pub inline_badge_px: u32,
+    /// Folder of images or JSON manifest for the local emote provider.
+    /// Empty disables it.
+    pub local_emotes_path: String,
+    pub local_emotes_poll_ms: u64,
 }
 
 impl Default for EmoteSettings {
@@ -468,6 +808,8 @@ impl Default for EmoteSettings {
             picker_max_height_px: 192,
             inline_emote_px: 28,
             inline_badge_px: 20,
+            local_emotes_path: String::new(),
+            local_emotes_poll_ms: 2_000,
         }
     }
 }
@@ -518,10 +860,17 @@ impl EmoteSettings {
         if self.inline_badge_px == 0 {
             self.inline_badge_px = defaults.inline_badge_px;
         }
+        if self.local_emotes_poll_ms == 0 {
+            self.local_emotes_poll_ms = defaults.local_emotes_poll_ms;
+        }
 
         self
     }
 
+    pub fn local_emotes_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.local_emotes_poll_ms)
+    }
+
     pub fn provider_enabled(&self, id: EmoteProviderId) -> bool {
         self.providers
             .iter()
@@ -552,6 +901,7 @@ pub enum EmoteProviderId {
     Bttv,
     Ffz,
     Seventv,
+    Local,
 }
 
 impl EmoteProviderId {
@@ -573,6 +923,10 @@ impl EmoteProviderId {
                 id: EmoteProviderId::Seventv,
                 enabled: true,
             },
+            EmoteProviderPreference {
+                id: EmoteProviderId::Local,
+                enabled: true,
+            },
         ]
     }
 
@@ -582,6 +936,7 @@ impl EmoteProviderId {
             EmoteProviderId::Bttv => "BttvProvider",
             EmoteProviderId::Ffz => "FfzProvider",
             EmoteProviderId::Seventv => "SeventvProvider",
+            EmoteProviderId::Local => "LocalProvider",
         }
     }
 }
@@ -948,6 +1303,9 @@ pub struct ChannelMessage {
     pub chatter_user_id: String,
     /// The user name of the user that sent the message.
     pub chatter_user_name: String,
+    /// The login of the user that sent the message.
+    #[serde(default)]
+    pub chatter_user_login: String,
     /// A UUID that identifies the message.
     pub message_id: String,
     pub text: String,
@@ -969,18 +1327,67 @@ pub struct ChannelMessage {
     pub translation: Option<ChannelMessageTranslation>,
     /// A stable message int that can be used by the UI
     pub index: u64,
+    /// Set on messages loaded from the recent-messages service on join.
+    #[serde(default)]
+    pub is_backfill: bool,
+    /// Who removed the message, once a moderator deletes it or bans or times
+    /// out its sender.
+    #[serde(default)]
+    pub moderation: Option<modlog::MessageModeration>,
+    /// The user's highlight rules this message matched.
+    #[serde(default)]
+    pub highlight: Option<highlight::MessageHighlight>,
+    /// Set when a filter collapsed the message instead of dropping it.
+    #[serde(default)]
+    pub filtered: Option<filter::FilterReason>,
+    /// Set in channels we moderate when the chatter is monitored, restricted
+    /// or suspected of ban evasion.
+    #[serde(default)]
+    pub trust_status: Option<trust::TrustStatus>,
 }
 
+#[cfg(test)]
 impl ChannelMessage {
-    pub fn new(
+    /// A plain text message from `chatter` in `channel`, for tests.
+    pub(crate) fn test(id: &str, channel: &str, chatter: &str, text: &str) -> Self {
+        ChannelMessage {
+            ts: String::new(),
+            broadcaster_user_id: format!("{channel}-id"),
+            broadcaster_user_name: channel.to_string(),
+            broadcaster_user_login: channel.to_lowercase(),
+            chatter_user_id: format!("{chatter}-id"),
+            chatter_user_name: chatter.to_string(),
+            chatter_user_login: chatter.to_lowercase(),
+            message_id: id.to_string(),
+            text: text.to_string(),
+            fragments: vec![message::Fragment::Text(message::TextFragment {
+                index: 0,
+                text: text.to_string(),
+            })],
+            message_type: ChannelMessageType::Text,
+            badges: Vec::new(),
+            color: String::new(),
+            translation: None,
+            index: 0,
+            is_backfill: false,
+            moderation: None,
+            highlight: None,
+            filtered: None,
+            trust_status: None,
+        }
+    }
+}
+
+impl ChannelMessage {
+    pub async fn new(
         value: twitch_api::eventsub::channel::ChannelChatMessageV1Payload,
         ts: String,
         bm: BadgeManager,
         em: EmoteManager,
         emote_settings: EmoteSettings,
+        highlights: &highlight::HighlightSet,
         app_handle: tauri::AppHandle,
     ) -> Self {
-        let bm_ref = bm.clone();
         let emote_settings = emote_settings.normalized();
         let broadcaster_id = value.broadcaster_user_id.to_string();
         let broadcaster_login = value.broadcaster_user_login.to_string();
@@ -989,29 +1396,74 @@ impl ChannelMessage {
         let emote_cache = em.get_emote_cache(broadcaster_id.clone(), &emote_settings);
 
         if emote_settings.provider_enabled(EmoteProviderId::Twitch) {
-            let _: Vec<_> = value
-                .message
-                .fragments
-                .iter()
-                .map(|f| {
-                    if let twitch_api::eventsub::channel::chat::Fragment::Emote { text, emote } = f
-                    {
-                        if !emote_cache.has_emote(text.to_string()) {
-                            let scope = em
-                                .resolve_user_name(&emote.owner_id.to_string())
-                                .unwrap_or_else(|| "Channel".to_string());
+            for fragment in &value.message.fragments {
+                let twitch_api::eventsub::channel::chat::Fragment::Emote { text, emote } = fragment
+                else {
+                    continue;
+                };
+                if emote_cache.has_emote(text.to_string()) {
+                    continue;
+                }
+                // Looking up the owner would stall every message behind a Helix
+                // call, so use "Channel" now and fix the scope once it resolves.
+                let owner_id = emote.owner_id.to_string();
+                let scope = em.cached_user_name(&owner_id).unwrap_or_else(|| {
+                    let em = em.clone();
+                    let broadcaster_id = broadcaster_id.clone();
+                    let name = text.to_string();
+                    let fragment = emote.clone();
+                    let emote_settings = emote_settings.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Some(scope) = em.resolve_user_name(&owner_id).await {
                             em.insert_twitch_fragment_emote(
-                                broadcaster_id.clone(),
-                                text.to_string(),
-                                Emote::from_emote_fragment(text.to_string(), emote, scope),
+                                broadcaster_id,
+                                name.clone(),
+                                Emote::from_emote_fragment(name, &fragment, scope),
                                 &emote_settings,
                             );
                         }
-                    }
-                })
-                .collect();
+                    });
+                    "Channel".to_string()
+                });
+                em.insert_twitch_fragment_emote(
+                    broadcaster_id.clone(),
+                    text.to_string(),
+                    Emote::from_emote_fragment(text.to_string(), emote, scope),
+                    &emote_settings,
+                );
+            }
+        }
+
+        let mut badges = Vec::with_capacity(value.badges.len());
+        for v in &value.badges {
+            let badge = match bm
+                .clone()
+                .get(v.set_id.to_string(), broadcaster_id.clone())
+                .await
+            {
+                Some(b_set) => b_set.version(v.id.to_string()),
+                None => None,
+            };
+            badges.push(badge_ref_or_fallback(
+                v.set_id.to_string(),
+                v.id.to_string(),
+                v.info.clone(),
+                badge,
+            ));
         }
 
+        let badge_set_ids: Vec<_> = value.badges.iter().map(|b| b.set_id.to_string()).collect();
+        let highlight = highlights.evaluate(&highlight::HighlightInput {
+            channel_login: &broadcaster_login,
+            chatter_login: value.chatter_user_login.as_str(),
+            text: &message_text,
+            badge_set_ids: &badge_set_ids,
+            first_message: matches!(
+                value.message_type,
+                twitch_api::eventsub::channel::chat::message::MessageType::UserIntro
+            ),
+        });
+
         let fragments = message::Parser::parse(message_text.clone(), &emote_cache);
         let translation_text = translation_input_from_fragments(&fragments);
         crate::internal::detect_language(
@@ -1028,35 +1480,109 @@ impl ChannelMessage {
             broadcaster_user_login: broadcaster_login,
             chatter_user_id: value.chatter_user_id.to_string(),
             chatter_user_name: value.chatter_user_name.to_string(),
+            chatter_user_login: value.chatter_user_login.to_string(),
             message_id,
             text: message_text.clone(),
             message_type: value.message_type.into(),
             color: value.color.to_string(),
             translation: None,
             index: next_index!(),
-            badges: value
-                .badges
-                .iter()
-                .map(|v| {
-                    let bm_ref = bm_ref.clone();
-                    let badge = match tauri::async_runtime::block_on(
-                        bm_ref.get(v.set_id.to_string(), value.broadcaster_user_id.to_string()),
-                    ) {
-                        Some(b_set) => b_set.version(v.id.to_string()),
-                        None => None,
-                    };
-
-                    badge_ref_or_fallback(
-                        v.set_id.to_string(),
-                        v.id.to_string(),
-                        v.info.clone(),
-                        badge,
-                    )
-                })
-                .collect(),
+            is_backfill: false,
+            moderation: None,
+            highlight,
+            filtered: None,
+            trust_status: None,
+            badges,
             fragments,
         }
     }
+
+    /// Builds a backfilled message from an IRC `PRIVMSG`, resolving badges,
+    /// Twitch emotes and highlights the same way live messages do.
+    pub async fn from_privmsg(
+        value: crate::backfill::Privmsg,
+        bm: BadgeManager,
+        em: EmoteManager,
+        emote_settings: &EmoteSettings,
+        highlights: &highlight::HighlightSet,
+    ) -> Self {
+        let emote_settings = emote_settings.clone().normalized();
+        let emote_cache = em.get_emote_cache(value.broadcaster_user_id.clone(), &emote_settings);
+
+        if emote_settings.provider_enabled(EmoteProviderId::Twitch) {
+            for (id, name) in &value.emotes {
+                if !emote_cache.has_emote(name.clone()) {
+                    em.insert_twitch_fragment_emote(
+                        value.broadcaster_user_id.clone(),
+                        name.clone(),
+                        Emote::from_irc_emote(id.clone(), name.clone(), "Channel".to_string()),
+                        &emote_settings,
+                    );
+                }
+            }
+        }
+
+        let badge_set_ids: Vec<_> = value.badges.iter().map(|b| b.0.clone()).collect();
+        let highlight = highlights.evaluate(&highlight::HighlightInput {
+            channel_login: &value.broadcaster_user_login,
+            chatter_login: &value.chatter_user_login,
+            text: &value.text,
+            badge_set_ids: &badge_set_ids,
+            first_message: matches!(value.message_type, ChannelMessageType::UserIntro),
+        });
+
+        let mut badges = Vec::with_capacity(value.badges.len());
+        for (set_id, id, info) in value.badges {
+            let badge = match bm
+                .clone()
+                .get(set_id.clone(), value.broadcaster_user_id.clone())
+                .await
+            {
+                Some(b_set) => b_set.version(id.clone()),
+                None => None,
+            };
+            badges.push(badge_ref_or_fallback(set_id, id, info, badge));
+        }
+
+        ChannelMessage {
+            ts: crate::date::rfc3339_from_unix_ms(value.sent_at_ms),
+            broadcaster_user_name: value.broadcaster_user_login.clone(),
+            broadcaster_user_id: value.broadcaster_user_id,
+            broadcaster_user_login: value.broadcaster_user_login,
+            chatter_user_id: value.chatter_user_id,
+            chatter_user_name: value.chatter_user_name,
+            chatter_user_login: value.chatter_user_login,
+            message_id: value.message_id,
+            fragments: message::Parser::parse(value.text.clone(), &emote_cache),
+            text: value.text,
+            message_type: value.message_type,
+            color: value.color,
+            translation: None,
+            index: next_index!(),
+            is_backfill: true,
+            moderation: None,
+            highlight,
+            filtered: None,
+            trust_status: None,
+            badges,
+        }
+    }
+
+    /// Assigns a new UI index, for messages replayed from storage.
+    pub fn with_fresh_index(mut self) -> Self {
+        self.index = next_index!();
+        self
+    }
+
+    /// Points every emote fragment at the image variant for `theme`.
+    pub fn with_emote_theme(mut self, theme: &AppearanceTheme) -> Self {
+        for fragment in &mut self.fragments {
+            if let message::Fragment::Emote(emote_fragment) = fragment {
+                emote_fragment.emote = std::mem::take(&mut emote_fragment.emote).themed(theme);
+            }
+        }
+        self
+    }
 }
 
 fn badge_ref_or_fallback(
@@ -1188,7 +1714,8 @@ mod tests {
                 EmoteProviderId::Twitch,
                 EmoteProviderId::Bttv,
                 EmoteProviderId::Ffz,
-                EmoteProviderId::Seventv
+                EmoteProviderId::Seventv,
+                EmoteProviderId::Local
             ]
         );
         assert!(settings.channel_cache.recurring_poll_enabled);
@@ -1208,6 +1735,11 @@ mod tests {
         assert!(settings.eventsub.repeated_log_throttle_enabled);
         assert_eq!(settings.eventsub.unparseable_warning_throttle_secs, 60);
         assert_eq!(settings.eventsub.subscription_error_throttle_secs, 300);
+        assert_eq!(settings.eventsub.queue_capacity, 5000);
+        assert_eq!(
+            settings.eventsub.overload_policy,
+            OverloadPolicy::ShedBusiestUnfocused
+        );
         assert_eq!(settings.providers.http_connect_timeout_secs, 5);
         assert_eq!(settings.providers.http_request_timeout_secs, 15);
         assert!(settings.providers.metadata_retention_enabled);
@@ -1215,6 +1747,16 @@ mod tests {
             settings.providers.metadata_retention_secs,
             30 * 24 * 60 * 60
         );
+        assert_eq!(settings.providers.fetch_retry_attempts, 3);
+        assert_eq!(settings.providers.fetch_retry_base_ms, 250);
+        assert_eq!(settings.providers.fetch_retry_max_ms, 4_000);
+        assert_eq!(settings.providers.circuit_breaker_threshold, 5);
+        assert_eq!(settings.providers.circuit_breaker_cooldown_secs, 60);
+        assert!(settings.providers.background_retry_enabled);
+        assert!(settings.history.enabled);
+        assert_eq!(settings.history.max_entries_per_channel, 5_000);
+        assert_eq!(settings.history.retention_days, 30);
+        assert!(!settings.backfill.enabled);
     }
 
     #[test]
@@ -1266,6 +1808,10 @@ mod tests {
                     id: EmoteProviderId::Seventv,
                     enabled: true,
                 },
+                EmoteProviderPreference {
+                    id: EmoteProviderId::Local,
+                    enabled: true,
+                },
             ]
         );
         assert_eq!(
@@ -1273,7 +1819,8 @@ mod tests {
             vec![
                 EmoteProviderId::Twitch,
                 EmoteProviderId::Ffz,
-                EmoteProviderId::Seventv
+                EmoteProviderId::Seventv,
+                EmoteProviderId::Local
             ]
         );
     }
@@ -1342,12 +1889,45 @@ mod tests {
                 repeated_log_throttle_enabled: false,
                 unparseable_warning_throttle_secs: 0,
                 subscription_error_throttle_secs: 0,
+                queue_capacity: 0,
+                overload_policy: OverloadPolicy::ShedIncoming,
             },
             providers: ProviderSettings {
                 http_connect_timeout_secs: 0,
                 http_request_timeout_secs: 0,
                 metadata_retention_enabled: false,
                 metadata_retention_secs: 0,
+                fetch_retry_attempts: 0,
+                fetch_retry_base_ms: 0,
+                fetch_retry_max_ms: 0,
+                circuit_breaker_threshold: 0,
+                circuit_breaker_cooldown_secs: 0,
+                background_retry_enabled: false,
+            },
+            history: HistorySettings {
+                enabled: false,
+                max_entries_per_channel: 0,
+                channel_max_entries: HashMap::from([
+                    (" Forsen ".to_string(), 100),
+                    ("xqc".to_string(), 0),
+                ]),
+                max_total_entries: 0,
+                retention_days: 0,
+                write_batch_size: 0,
+                write_flush_ms: 0,
+            },
+            backfill: BackfillSettings {
+                enabled: false,
+                base_url: "  ".to_string(),
+                message_limit: 0,
+                request_timeout_secs: 0,
+            },
+            chat_log: ChatLogSettings {
+                always_log: true,
+                directory: " /tmp/logs ".to_string(),
+                format: ChatLogFormat::Plain,
+                rotation: ChatLogRotation::KeepDays,
+                keep_days: 0,
             },
             ..Default::default()
         }
@@ -1370,6 +1950,11 @@ mod tests {
         assert!(!settings.eventsub.repeated_log_throttle_enabled);
         assert_eq!(settings.eventsub.unparseable_warning_throttle_secs, 60);
         assert_eq!(settings.eventsub.subscription_error_throttle_secs, 300);
+        assert_eq!(settings.eventsub.queue_capacity, 5000);
+        assert_eq!(
+            settings.eventsub.overload_policy,
+            OverloadPolicy::ShedIncoming
+        );
         assert_eq!(settings.providers.http_connect_timeout_secs, 5);
         assert_eq!(settings.providers.http_request_timeout_secs, 15);
         assert!(!settings.providers.metadata_retention_enabled);
@@ -1377,5 +1962,42 @@ mod tests {
             settings.providers.metadata_retention_secs,
             30 * 24 * 60 * 60
         );
+        assert_eq!(settings.providers.fetch_retry_attempts, 3);
+        assert_eq!(settings.providers.fetch_retry_base_ms, 250);
+        assert_eq!(settings.providers.fetch_retry_max_ms, 4_000);
+        assert_eq!(settings.providers.circuit_breaker_threshold, 5);
+        assert_eq!(settings.providers.circuit_breaker_cooldown_secs, 60);
+        assert!(!settings.providers.background_retry_enabled);
+        assert!(!settings.history.enabled);
+        assert_eq!(settings.history.max_entries_per_channel, 5_000);
+        assert_eq!(settings.history.max_entries_for("forsen"), 100);
+        assert_eq!(settings.history.max_entries_for("xqc"), 5_000);
+        assert_eq!(settings.history.max_total_entries, 250_000);
+        assert_eq!(settings.history.retention_days, 30);
+        assert_eq!(settings.history.write_batch_size, 200);
+        assert_eq!(settings.history.write_flush_ms, 500);
+        assert!(!settings.backfill.enabled);
+        assert_eq!(
+            settings.backfill.base_url,
+            "https://recent-messages.robotty.de/api/v2/recent-messages"
+        );
+        assert_eq!(settings.backfill.message_limit, 100);
+        assert_eq!(settings.backfill.request_timeout_secs, 5);
+        assert!(settings.chat_log.always_log);
+        assert_eq!(settings.chat_log.directory, "/tmp/logs");
+        assert_eq!(settings.chat_log.retention_days(), Some(30));
+    }
+
+    #[test]
+    fn backfill_settings_trim_url_and_cap_limit() {
+        let settings = BackfillSettings {
+            base_url: " https://rm.example/api/ ".to_string(),
+            message_limit: 5_000,
+            ..Default::default()
+        }
+        .normalized();
+
+        assert_eq!(settings.base_url, "https://rm.example/api/");
+        assert_eq!(settings.message_limit, 800);
     }
 }
@@ -0,0 +1,335 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use tracing::{error, warn};
+use twitch_api::eventsub::channel::unban_request::{
+    ChannelUnbanRequestCreateV1Payload, ChannelUnbanRequestResolveV1Payload,
+    UnbanRequestStatus as EventStatus,
+};
+
+use crate::types::ChannelMessage;
+
+const STORE_FILE: &str = "unban-requests.json";
+const STORE_KEY: &str = "unban_requests:v1";
+/// Resolved requests are kept for reference until the inbox grows past this.
+const MAX_REQUESTS: usize = 500;
+/// How many of the requester's messages are snapshotted from chat history.
+pub const RECENT_MESSAGE_LIMIT: usize = 20;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnbanRequestStatus {
+    Pending,
+    Approved,
+    Denied,
+    /// Withdrawn by the requester, or closed by Twitch because they were
+    /// unbanned some other way.
+    Canceled,
+}
+
+/// A banned user's request to be unbanned from a channel we moderate.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct UnbanRequest {
+    pub id: String,
+    pub broadcaster_user_id: String,
+    pub broadcaster_user_login: String,
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+    pub text: String,
+    pub created_at: String,
+    pub status: UnbanRequestStatus,
+    /// Display name of the moderator who resolved it.
+    pub resolved_by: Option<String>,
+    pub resolution_text: Option<String>,
+    /// The requester's last messages in the channel before the ban, oldest
+    /// first, snapshotted when the request arrived so pruning doesn't lose them.
+    #[serde(default)]
+    pub recent_messages: Vec<ChannelMessage>,
+}
+
+impl UnbanRequest {
+    pub fn new(
+        request: &ChannelUnbanRequestCreateV1Payload,
+        recent_messages: Vec<ChannelMessage>,
+    ) -> Self {
+        UnbanRequest {
+            id: request.id.to_string(),
+            broadcaster_user_id: request.broadcaster_user_id.to_string(),
+            broadcaster_user_login: request.broadcaster_user_login.to_string(),
+            user_id: request.user_id.to_string(),
+            user_login: request.user_login.to_string(),
+            user_name: request.user_name.to_string(),
+            text: request.text.clone(),
+            created_at: request.created_at.to_string(),
+            status: UnbanRequestStatus::Pending,
+            resolved_by: None,
+            resolution_text: None,
+            recent_messages,
+        }
+    }
+}
+
+pub(crate) trait UnbanRequestStore: Send + Sync {
+    fn load(&self) -> Vec<UnbanRequest>;
+    fn save(&self, requests: &[UnbanRequest]);
+}
+
+pub(crate) struct TauriUnbanRequestStore {
+    app: AppHandle,
+}
+
+impl TauriUnbanRequestStore {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl UnbanRequestStore for TauriUnbanRequestStore {
+    fn load(&self) -> Vec<UnbanRequest> {
+        let store = match self.app.store(STORE_FILE) {
+            Ok(store) => store,
+            Err(err) => {
+                error!("failed to open unban request store: {err}");
+                return Vec::new();
+            }
+        };
+        let Some(value) = store.get(STORE_KEY) else {
+            return Vec::new();
+        };
+        serde_json::from_value(value).unwrap_or_else(|err| {
+            warn!("ignoring undecodable unban request inbox: {err}");
+            Vec::new()
+        })
+    }
+
+    fn save(&self, requests: &[UnbanRequest]) {
+        let store = match self.app.store(STORE_FILE) {
+            Ok(store) => store,
+            Err(err) => {
+                error!("failed to open unban request store: {err}");
+                return;
+            }
+        };
+        store.set(STORE_KEY, serde_json::json!(requests));
+        if let Err(err) = store.save() {
+            error!("failed to save unban request store: {err}");
+        }
+    }
+}
+
+/// Unban requests for every moderated channel, oldest first, written through
+/// to disk on each change.
+#[derive(Clone)]
+pub struct UnbanInbox {
+    requests: Arc<Mutex<Vec<UnbanRequest>>>,
+    store: Arc<dyn UnbanRequestStore>,
+}
+
+impl UnbanInbox {
+    pub(crate) fn open(store: Arc<dyn UnbanRequestStore>) -> Self {
+        UnbanInbox {
+            requests: Arc::new(Mutex::new(store.load())),
+            store,
+        }
+    }
+
+    pub fn add(&self, request: UnbanRequest) {
+        let mut requests = self.requests.lock().unwrap();
+        requests.retain(|existing| existing.id != request.id);
+        requests.push(request);
+        while requests.len() > MAX_REQUESTS {
+            // Drop the oldest resolved request, or the oldest outright if every
+            // one is still pending.
+            let index = requests
+                .iter()
+                .position(|request| request.status != UnbanRequestStatus::Pending)
+                .unwrap_or(0);
+            requests.remove(index);
+        }
+        self.store.save(&requests);
+    }
+
+    /// Records a resolution from any moderator, returning the updated request
+    /// if it's in the inbox.
+    pub fn resolve(
+        &self,
+        request_id: &str,
+        status: UnbanRequestStatus,
+        resolved_by: Option<String>,
+        resolution_text: Option<String>,
+    ) -> Option<UnbanRequest> {
+        let mut requests = self.requests.lock().unwrap();
+        let request = requests
+            .iter_mut()
+            .find(|request| request.id == request_id)?;
+        request.status = status;
+        request.resolved_by = resolved_by;
+        request.resolution_text = resolution_text;
+        let resolved = request.clone();
+        self.store.save(&requests);
+        Some(resolved)
+    }
+
+    pub fn resolve_from_event(
+        &self,
+        resolution: &ChannelUnbanRequestResolveV1Payload,
+    ) -> Option<UnbanRequest> {
+        let status = match &resolution.status {
+            EventStatus::Approved => UnbanRequestStatus::Approved,
+            EventStatus::Denied => UnbanRequestStatus::Denied,
+            EventStatus::Canceled => UnbanRequestStatus::Canceled,
+            other => {
+                warn!(id = %resolution.id, "unknown unban request status {other:?}");
+                return None;
+            }
+        };
+        self.resolve(
+            resolution.id.as_str(),
+            status,
+            resolution
+                .moderator_user_name
+                .as_ref()
+                .map(ToString::to_string),
+            resolution.resolution_text.clone(),
+        )
+    }
+
+    pub fn get(&self, request_id: &str) -> Option<UnbanRequest> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|request| request.id == request_id)
+            .cloned()
+    }
+
+    /// The channel's requests, newest first.
+    pub fn list(&self, broadcaster_id: &str, include_resolved: bool) -> Vec<UnbanRequest> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|request| request.broadcaster_user_id == broadcaster_id)
+            .filter(|request| include_resolved || request.status == UnbanRequestStatus::Pending)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MemoryUnbanRequestStore {
+    saved: Mutex<Vec<UnbanRequest>>,
+}
+
+#[cfg(test)]
+impl UnbanRequestStore for MemoryUnbanRequestStore {
+    fn load(&self) -> Vec<UnbanRequest> {
+        self.saved.lock().unwrap().clone()
+    }
+
+    fn save(&self, requests: &[UnbanRequest]) {
+        *self.saved.lock().unwrap() = requests.to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(channel: &str, id: &str) -> UnbanRequest {
+        let payload: ChannelUnbanRequestCreateV1Payload =
+            serde_json::from_value(serde_json::json!({
+                "id": id,
+                "broadcaster_user_id": format!("{channel}-id"),
+                "broadcaster_user_login": channel,
+                "broadcaster_user_name": channel,
+                "user_id": "42",
+                "user_login": "viewer",
+                "user_name": "Viewer",
+                "text": "sorry",
+                "created_at": "2024-02-23T21:18:45.520Z"
+            }))
+            .unwrap();
+        UnbanRequest::new(&payload, Vec::new())
+    }
+
+    fn ids(requests: &[UnbanRequest]) -> Vec<&str> {
+        requests.iter().map(|request| request.id.as_str()).collect()
+    }
+
+    #[test]
+    fn survives_a_restart() {
+        let store = Arc::new(MemoryUnbanRequestStore::default());
+        let inbox = UnbanInbox::open(store.clone());
+        inbox.add(request("forsen", "a"));
+        inbox.resolve(
+            "a",
+            UnbanRequestStatus::Denied,
+            Some("Mod".to_string()),
+            Some("no".to_string()),
+        );
+
+        let reopened = UnbanInbox::open(store);
+        let restored = reopened.get("a").unwrap();
+        assert_eq!(restored.status, UnbanRequestStatus::Denied);
+        assert_eq!(restored.resolved_by.as_deref(), Some("Mod"));
+    }
+
+    #[test]
+    fn lists_per_channel_newest_first() {
+        let inbox = UnbanInbox::open(Arc::new(MemoryUnbanRequestStore::default()));
+        inbox.add(request("forsen", "a"));
+        inbox.add(request("forsen", "b"));
+        inbox.add(request("xqc", "c"));
+        inbox.resolve("a", UnbanRequestStatus::Approved, None, None);
+
+        assert_eq!(ids(&inbox.list("forsen-id", false)), vec!["b"]);
+        assert_eq!(ids(&inbox.list("forsen-id", true)), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn resolves_from_events_by_other_moderators() {
+        let inbox = UnbanInbox::open(Arc::new(MemoryUnbanRequestStore::default()));
+        inbox.add(request("forsen", "a"));
+        let resolution: ChannelUnbanRequestResolveV1Payload =
+            serde_json::from_value(serde_json::json!({
+                "id": "a",
+                "broadcaster_user_id": "forsen-id",
+                "broadcaster_user_login": "forsen",
+                "broadcaster_user_name": "forsen",
+                "moderator_user_id": "7",
+                "moderator_user_login": "othermod",
+                "moderator_user_name": "OtherMod",
+                "user_id": "42",
+                "user_login": "viewer",
+                "user_name": "Viewer",
+                "resolution_text": "welcome back",
+                "status": "approved"
+            }))
+            .unwrap();
+
+        let resolved = inbox.resolve_from_event(&resolution).unwrap();
+        assert_eq!(resolved.status, UnbanRequestStatus::Approved);
+        assert_eq!(resolved.resolved_by.as_deref(), Some("OtherMod"));
+        assert_eq!(resolved.resolution_text.as_deref(), Some("welcome back"));
+    }
+
+    #[test]
+    fn evicts_resolved_requests_first() {
+        let inbox = UnbanInbox::open(Arc::new(MemoryUnbanRequestStore::default()));
+        inbox.add(request("forsen", "pending"));
+        inbox.add(request("forsen", "resolved"));
+        inbox.resolve("resolved", UnbanRequestStatus::Denied, None, None);
+        for i in 0..MAX_REQUESTS - 1 {
+            inbox.add(request("forsen", &i.to_string()));
+        }
+
+        assert!(inbox.get("pending").is_some());
+        assert!(inbox.get("resolved").is_none());
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use twitch_api::{
+    helix::channels::{GetChannelFollowersRequest, GetFollowedChannelsRequest},
+    types::BroadcasterType,
+};
+use twitch_oauth2::{Scope, TwitchToken, UserToken};
+
+use crate::{
+    error::PepoError,
+    types::{BadgeRef, ChannelMessage},
+};
+
+type HelixClient = twitch_api::HelixClient<'static, reqwest::Client>;
+
+/// How many of the user's messages in the channel a card shows.
+pub const RECENT_MESSAGE_LIMIT: usize = 50;
+/// Profiles rarely change while a card is open and closed again, so repeated
+/// opens reuse the Helix lookup for this long.
+const PROFILE_TTL: Duration = Duration::from_secs(120);
+
+/// Everything shown when clicking a chatter's name.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct UserCard {
+    pub user_id: String,
+    pub login: String,
+    pub display_name: String,
+    pub profile_image_url: String,
+    pub description: String,
+    /// RFC 3339 account creation time.
+    pub created_at: String,
+    /// `"partner"`, `"affiliate"` or empty.
+    pub broadcaster_type: String,
+    /// When they followed the broadcaster; `None` if they don't, or the active
+    /// token isn't allowed to see it.
+    pub followed_at: Option<String>,
+    /// Badges on their latest message in this channel.
+    pub badges: Vec<BadgeRef>,
+    /// Their latest messages in this channel, oldest first.
+    pub recent_messages: Vec<ChannelMessage>,
+}
+
+impl UserCard {
+    pub fn new(profile: UserProfile, recent_messages: Vec<ChannelMessage>) -> Self {
+        UserCard {
+            user_id: profile.user_id,
+            login: profile.login,
+            display_name: profile.display_name,
+            profile_image_url: profile.profile_image_url,
+            description: profile.description,
+            created_at: profile.created_at,
+            broadcaster_type: profile.broadcaster_type,
+            followed_at: profile.followed_at,
+            badges: recent_messages
+                .last()
+                .map(|message| message.badges.clone())
+                .unwrap_or_default(),
+            recent_messages,
+        }
+    }
+}
+
+/// The Helix part of a card, cached per broadcaster since the follow date
+/// depends on the channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserProfile {
+    pub user_id: String,
+    pub login: String,
+    pub display_name: String,
+    pub profile_image_url: String,
+    pub description: String,
+    pub created_at: String,
+    pub broadcaster_type: String,
+    pub followed_at: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct UserCardCache(Arc<Mutex<HashMap<(String, String), CachedProfile>>>);
+
+struct CachedProfile {
+    fetched_at: Instant,
+    profile: UserProfile,
+}
+
+impl UserCardCache {
+    pub fn get(&self, broadcaster_id: &str, user_id: &str, now: Instant) -> Option<UserProfile> {
+        let profiles = self.0.lock().unwrap();
+        profiles
+            .get(&(broadcaster_id.to_owned(), user_id.to_owned()))
+            .filter(|cached| now.saturating_duration_since(cached.fetched_at) < PROFILE_TTL)
+            .map(|cached| cached.profile.clone())
+    }
+
+    pub fn insert(&self, broadcaster_id: &str, profile: UserProfile, now: Instant) {
+        let mut profiles = self.0.lock().unwrap();
+        profiles.retain(|_, cached| now.saturating_duration_since(cached.fetched_at) < PROFILE_TTL);
+        profiles.insert(
+            (broadcaster_id.to_owned(), profile.user_id.clone()),
+            CachedProfile {
+                fetched_at: now,
+                profile,
+            },
+        );
+    }
+}
+
+/// Looks up `user_id` on Helix, returning `None` if the user doesn't exist.
+pub async fn fetch_profile(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    user_id: &str,
+) -> Result<Option<UserProfile>, PepoError> {
+    let Some(user) = client
+        .get_user_from_id(user_id, token)
+        .await
+        .map_err(PepoError::from_client_error)?
+    else {
+        return Ok(None);
+    };
+    let followed_at = follow_date(client, token, broadcaster_id, user_id).await;
+
+    Ok(Some(UserProfile {
+        user_id: user.id.to_string(),
+        login: user.login.to_string(),
+        display_name: user.display_name.to_string(),
+        profile_image_url: user.profile_image_url.unwrap_or_default(),
+        description: user.description.unwrap_or_default(),
+        created_at: user.created_at.to_string(),
+        broadcaster_type: match user.broadcaster_type {
+            Some(BroadcasterType::Partner) => "partner",
+            Some(BroadcasterType::Affiliate) => "affiliate",
+            Some(BroadcasterType::None) | None => "",
+        }
+        .to_owned(),
+        followed_at,
+    }))
+}
+
+/// Anyone's follow date needs `moderator:read:followers` in the channel; without
+/// it only the token owner's own follow is visible.
+async fn follow_date(
+    client: &HelixClient,
+    token: &UserToken,
+    broadcaster_id: &str,
+    user_id: &str,
+) -> Option<String> {
+    let result = if token.scopes().contains(&Scope::ModeratorReadFollowers) {
+        let request = GetChannelFollowersRequest::broadcaster_id(broadcaster_id).user_id(user_id);
+        client.req_get(request, token).await.map(|response| {
+            response
+                .data
+                .into_iter()
+                .next()
+                .map(|follower| follower.followed_at.to_string())
+        })
+    } else if token.user_id.as_str() == user_id && token.scopes().contains(&Scope::UserReadFollows)
+    {
+        let request = GetFollowedChannelsRequest::user_id(user_id).broadcaster_id(broadcaster_id);
+        client.req_get(request, token).await.map(|response| {
+            response
+                .data
+                .into_iter()
+                .next()
+                .map(|channel| channel.followed_at.to_string())
+        })
+    } else {
+        return None;
+    };
+
+    result.unwrap_or_else(|err| {
+        // Typically a 401 because the token isn't a moderator here.
+        debug!(broadcaster_id, user_id, "follow date unavailable: {err}");
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(user_id: &str) -> UserProfile {
+        UserProfile {
+            user_id: user_id.to_string(),
+            login: "viewer".to_string(),
+            display_name: "Viewer".to_string(),
+            profile_image_url: String::new(),
+            description: String::new(),
+            created_at: "2015-01-01T00:00:00Z".to_string(),
+            broadcaster_type: String::new(),
+            followed_at: None,
+        }
+    }
+
+    #[test]
+    fn cached_profiles_expire_and_are_per_channel() {
+        let cache = UserCardCache::default();
+        let start = Instant::now();
+        cache.insert("forsen-id", profile("42"), start);
+
+        assert_eq!(
+            cache.get("forsen-id", "42", start + Duration::from_secs(5)),
+            Some(profile("42"))
+        );
+        assert_eq!(cache.get("xqc-id", "42", start), None);
+        assert_eq!(cache.get("forsen-id", "42", start + PROFILE_TTL), None);
+    }
+
+    #[test]
+    fn expired_profiles_are_dropped_on_insert() {
+        let cache = UserCardCache::default();
+        let start = Instant::now();
+        cache.insert("forsen-id", profile("1"), start);
+        cache.insert("forsen-id", profile("2"), start + PROFILE_TTL);
+
+        assert_eq!(cache.0.lock().unwrap().len(), 1);
+    }
+}
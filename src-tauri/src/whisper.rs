@@ -0,0 +1,372 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use tracing::{error, warn};
+use twitch_api::eventsub::user::UserWhisperMessageV1Payload;
+use twitch_oauth2::{Scope, UserToken};
+
+use crate::{
+    history::unix_now_ms,
+    moderation::{self, ModerationError},
+};
+
+type HelixClient = twitch_api::HelixClient<'static, reqwest::Client>;
+
+const STORE_FILE: &str = "whispers.json";
+const STORE_KEY: &str = "whisper_threads:v1";
+/// Older whispers in a conversation are dropped past this many.
+const MAX_THREAD_MESSAGES: usize = 200;
+/// The least recently active conversations are dropped past this many.
+const MAX_THREADS: usize = 200;
+
+/// One whisper in a conversation.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type, PartialEq, Eq)]
+pub struct Whisper {
+    /// Twitch's whisper id; `None` for whispers we sent, which Helix doesn't
+    /// identify.
+    pub id: Option<String>,
+    pub text: String,
+    /// Sent by the account rather than the peer.
+    pub outgoing: bool,
+    pub ts: i64,
+}
+
+/// A conversation between one of our accounts and another user.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct WhisperThread {
+    pub account_user_id: String,
+    pub peer_user_id: String,
+    pub peer_user_login: String,
+    pub peer_user_name: String,
+    /// Oldest first.
+    pub messages: Vec<Whisper>,
+    /// Received whispers not yet seen in the conversation view.
+    pub unread: u32,
+}
+
+/// What `list_whisper_threads` shows per conversation.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct WhisperThreadSummary {
+    pub peer_user_id: String,
+    pub peer_user_login: String,
+    pub peer_user_name: String,
+    pub last_message: Option<Whisper>,
+    pub unread: u32,
+}
+
+impl WhisperThread {
+    fn summary(&self) -> WhisperThreadSummary {
+        WhisperThreadSummary {
+            peer_user_id: self.peer_user_id.clone(),
+            peer_user_login: self.peer_user_login.clone(),
+            peer_user_name: self.peer_user_name.clone(),
+            last_message: self.messages.last().cloned(),
+            unread: self.unread,
+        }
+    }
+}
+
+/// Emitted on `whisper` for each whisper received or sent.
+#[derive(Clone, Debug, Deserialize, Serialize, specta::Type)]
+pub struct WhisperEvent {
+    pub account_user_id: String,
+    pub whisper: Whisper,
+    pub thread: WhisperThreadSummary,
+}
+
+/// The other side of a conversation.
+pub struct Peer {
+    pub user_id: String,
+    pub login: String,
+    pub name: String,
+}
+
+pub(crate) trait WhisperStore: Send + Sync {
+    fn load(&self) -> Vec<WhisperThread>;
+    fn save(&self, threads: &[WhisperThread]);
+}
+
+pub(crate) struct TauriWhisperStore {
+    app: AppHandle,
+}
+
+impl TauriWhisperStore {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl WhisperStore for TauriWhisperStore {
+    fn load(&self) -> Vec<WhisperThread> {
+        let store = match self.app.store(STORE_FILE) {
+            Ok(store) => store,
+            Err(err) => {
+                error!("failed to open whisper store: {err}");
+                return Vec::new();
+            }
+        };
+        let Some(value) = store.get(STORE_KEY) else {
+            return Vec::new();
+        };
+        serde_json::from_value(value).unwrap_or_else(|err| {
+            warn!("ignoring undecodable whisper threads: {err}");
+            Vec::new()
+        })
+    }
+
+    fn save(&self, threads: &[WhisperThread]) {
+        let store = match self.app.store(STORE_FILE) {
+            Ok(store) => store,
+            Err(err) => {
+                error!("failed to open whisper store: {err}");
+                return;
+            }
+        };
+        store.set(STORE_KEY, serde_json::json!(threads));
+        if let Err(err) = store.save() {
+            error!("failed to save whisper store: {err}");
+        }
+    }
+}
+
+type Notify = Arc<dyn Fn(&WhisperEvent) + Send + Sync>;
+
+/// Whisper conversations for every account, least recently active first,
+/// written through to disk and announced through `notify` on each whisper.
+#[derive(Clone)]
+pub struct WhisperInbox {
+    threads: Arc<Mutex<Vec<WhisperThread>>>,
+    store: Arc<dyn WhisperStore>,
+    notify: Notify,
+}
+
+impl WhisperInbox {
+    pub(crate) fn open(
+        store: Arc<dyn WhisperStore>,
+        notify: impl Fn(&WhisperEvent) + Send + Sync + 'static,
+    ) -> Self {
+        WhisperInbox {
+            threads: Arc::new(Mutex::new(store.load())),
+            store,
+            notify: Arc::new(notify),
+        }
+    }
+
+    pub fn receive(&self, payload: &UserWhisperMessageV1Payload) -> WhisperEvent {
+        let peer = Peer {
+            user_id: payload.from_user_id.to_string(),
+            login: payload.from_user_login.to_string(),
+            name: payload.from_user_name.to_string(),
+        };
+        let whisper = Whisper {
+            id: Some(payload.whisper_id.to_string()),
+            text: payload.whisper.text.clone(),
+            outgoing: false,
+            ts: unix_now_ms(),
+        };
+        self.append(payload.to_user_id.as_str(), peer, whisper)
+    }
+
+    pub fn record_sent(&self, account_user_id: &str, peer: Peer, text: &str) -> WhisperEvent {
+        let whisper = Whisper {
+            id: None,
+            text: text.to_owned(),
+            outgoing: true,
+            ts: unix_now_ms(),
+        };
+        self.append(account_user_id, peer, whisper)
+    }
+
+    fn append(&self, account_user_id: &str, peer: Peer, whisper: Whisper) -> WhisperEvent {
+        let mut threads = self.threads.lock().unwrap();
+        let existing = threads.iter().position(|thread| {
+            thread.account_user_id == account_user_id && thread.peer_user_id == peer.user_id
+        });
+        let mut thread = match existing {
+            Some(index) => threads.remove(index),
+            None => WhisperThread {
+                account_user_id: account_user_id.to_owned(),
+                peer_user_id: peer.user_id,
+                peer_user_login: String::new(),
+                peer_user_name: String::new(),
+                messages: Vec::new(),
+                unread: 0,
+            },
+        };
+        // Renames show up on the next whisper.
+        thread.peer_user_login = peer.login;
+        thread.peer_user_name = peer.name;
+        if whisper.outgoing {
+            // Replying means the conversation has been read.
+            thread.unread = 0;
+        } else {
+            thread.unread += 1;
+        }
+        thread.messages.push(whisper.clone());
+        if thread.messages.len() > MAX_THREAD_MESSAGES {
+            let excess = thread.messages.len() - MAX_THREAD_MESSAGES;
+            thread.messages.drain(..excess);
+        }
+        let summary = thread.summary();
+
+        threads.push(thread);
+        if threads.len() > MAX_THREADS {
+            threads.remove(0);
+        }
+        self.store.save(&threads);
+        drop(threads);
+
+        let event = WhisperEvent {
+            account_user_id: account_user_id.to_owned(),
+            whisper,
+            thread: summary,
+        };
+        (self.notify)(&event);
+        event
+    }
+
+    /// The account's conversations, most recently active first.
+    pub fn list(&self, account_user_id: &str) -> Vec<WhisperThreadSummary> {
+        self.threads
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|thread| thread.account_user_id == account_user_id)
+            .map(WhisperThread::summary)
+            .collect()
+    }
+
+    /// The conversation's whispers, oldest first, marking it read.
+    pub fn open_thread(&self, account_user_id: &str, peer_user_id: &str) -> Vec<Whisper> {
+        let mut threads = self.threads.lock().unwrap();
+        let Some(thread) = threads.iter_mut().find(|thread| {
+            thread.account_user_id == account_user_id && thread.peer_user_id == peer_user_id
+        }) else {
+            return Vec::new();
+        };
+        let messages = thread.messages.clone();
+        if thread.unread > 0 {
+            thread.unread = 0;
+            self.store.save(&threads);
+        }
+        messages
+    }
+}
+
+/// Whispers `to_login` as the token's user and records it in the
+/// conversation. Twitch only lets accounts with a verified phone number
+/// whisper.
+pub async fn send(
+    client: &HelixClient,
+    token: &UserToken,
+    inbox: &WhisperInbox,
+    to_login: &str,
+    text: &str,
+) -> Result<WhisperEvent, ModerationError> {
+    moderation::require_scopes(token, &[Scope::UserManageWhispers])?;
+    if text.trim().is_empty() {
+        return Err(ModerationError::InvalidInput {
+            message: "whispers can't be empty".to_owned(),
+        });
+    }
+    let user = client
+        .get_user_from_login(to_login, token)
+        .await
+        .map_err(ModerationError::from_client_error)?
+        .ok_or_else(|| ModerationError::NotFound {
+            message: format!("no such user {to_login}"),
+        })?;
+    client
+        .send_whisper(token.user_id.as_str(), user.id.as_str(), text, token)
+        .await
+        .map_err(ModerationError::from_client_error)?;
+    let peer = Peer {
+        user_id: user.id.to_string(),
+        login: user.login.to_string(),
+        name: user.display_name.to_string(),
+    };
+    Ok(inbox.record_sent(token.user_id.as_str(), peer, text))
+}
+
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MemoryWhisperStore {
+    saved: Mutex<Vec<WhisperThread>>,
+}
+
+#[cfg(test)]
+impl WhisperStore for MemoryWhisperStore {
+    fn load(&self) -> Vec<WhisperThread> {
+        self.saved.lock().unwrap().clone()
+    }
+
+    fn save(&self, threads: &[WhisperThread]) {
+        *self.saved.lock().unwrap() = threads.to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received(from: &str, text: &str) -> UserWhisperMessageV1Payload {
+        serde_json::from_value(serde_json::json!({
+            "from_user_id": format!("{from}-id"),
+            "from_user_login": from,
+            "from_user_name": from,
+            "to_user_id": "me-id",
+            "to_user_login": "me",
+            "to_user_name": "Me",
+            "whisper_id": format!("{from}-{text}"),
+            "whisper": {"text": text}
+        }))
+        .unwrap()
+    }
+
+    fn peer(login: &str) -> Peer {
+        Peer {
+            user_id: format!("{login}-id"),
+            login: login.to_owned(),
+            name: login.to_owned(),
+        }
+    }
+
+    #[test]
+    fn threads_by_peer_with_unread_counts() {
+        let store = Arc::new(MemoryWhisperStore::default());
+        let inbox = WhisperInbox::open(store.clone(), |_| {});
+        inbox.receive(&received("somemod", "ban him?"));
+        inbox.receive(&received("somemod", "the spammer"));
+        let event = inbox.receive(&received("othermod", "brb"));
+        assert_eq!(event.thread.unread, 1);
+
+        let threads = WhisperInbox::open(store, |_| {}).list("me-id");
+        let unread: Vec<_> = threads
+            .iter()
+            .map(|thread| (thread.peer_user_login.as_str(), thread.unread))
+            .collect();
+        assert_eq!(unread, vec![("othermod", 1), ("somemod", 2)]);
+        assert!(inbox.list("someone-else").is_empty());
+    }
+
+    #[test]
+    fn reading_or_replying_clears_unread() {
+        let inbox = WhisperInbox::open(Arc::new(MemoryWhisperStore::default()), |_| {});
+        inbox.receive(&received("somemod", "ban him?"));
+        inbox.receive(&received("othermod", "brb"));
+
+        let reply = inbox.record_sent("me-id", peer("somemod"), "done");
+        assert_eq!(reply.thread.unread, 0);
+        assert_eq!(
+            reply.thread.last_message.map(|whisper| whisper.outgoing),
+            Some(true)
+        );
+
+        let messages = inbox.open_thread("me-id", "othermod-id");
+        assert_eq!(messages.len(), 1);
+        assert!(inbox.list("me-id").iter().all(|thread| thread.unread == 0));
+    }
+}
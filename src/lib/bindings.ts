@@ -4,7 +4,7 @@
 /** user-defined commands **/
 
 export const commands = {
-	async getFollowedStreams(): Promise<Result<Stream[], string>> {
+	async getFollowedStreams(): Promise<Result<Stream[], PepoError>> {
 		try {
 			return { status: 'ok', data: await TAURI_INVOKE('get_followed_streams') };
 		} catch (e) {
@@ -12,7 +12,7 @@ export const commands = {
 			else return { status: 'error', error: e as any };
 		}
 	},
-	async getFollowedChannels(): Promise<Result<Broadcaster[], string>> {
+	async getFollowedChannels(): Promise<Result<Broadcaster[], PepoError>> {
 		try {
 			return { status: 'ok', data: await TAURI_INVOKE('get_followed_channels') };
 		} catch (e) {
@@ -20,7 +20,7 @@ export const commands = {
 			else return { status: 'error', error: e as any };
 		}
 	},
-	async getChannelInfo(channelName: string): Promise<Result<ChannelInfo, string>> {
+	async getChannelInfo(channelName: string): Promise<Result<ChannelInfo, PepoError>> {
 		try {
 			return { status: 'ok', data: await TAURI_INVOKE('get_channel_info', { channelName }) };
 		} catch (e) {
@@ -28,7 +28,7 @@ export const commands = {
 			else return { status: 'error', error: e as any };
 		}
 	},
-	async joinChat(channelName: string): Promise<Result<ChannelInfo, string>> {
+	async joinChat(channelName: string): Promise<Result<ChannelInfo, PepoError>> {
 		try {
 			return { status: 'ok', data: await TAURI_INVOKE('join_chat', { channelName }) };
 		} catch (e) {
@@ -36,7 +36,7 @@ export const commands = {
 			else return { status: 'error', error: e as any };
 		}
 	},
-	async leaveChat(channelName: string): Promise<Result<null, string>> {
+	async leaveChat(channelName: string): Promise<Result<null, PepoError>> {
 		try {
 			return { status: 'ok', data: await TAURI_INVOKE('leave_chat', { channelName }) };
 		} catch (e) {
@@ -44,7 +44,7 @@ export const commands = {
 			else return { status: 'error', error: e as any };
 		}
 	},
-	async login(quick: boolean): Promise<Result<UserToken, string>> {
+	async login(quick: boolean): Promise<Result<UserToken, PepoError>> {
 		try {
 			return { status: 'ok', data: await TAURI_INVOKE('login', { quick }) };
 		} catch (e) {
@@ -52,7 +52,7 @@ export const commands = {
 			else return { status: 'error', error: e as any };
 		}
 	},
-	async logout(): Promise<Result<null, string>> {
+	async logout(): Promise<Result<null, PepoError>> {
 		try {
 			return { status: 'ok', data: await TAURI_INVOKE('logout') };
 		} catch (e) {
@@ -60,7 +60,10 @@ export const commands = {
 			else return { status: 'error', error: e as any };
 		}
 	},
-	async sendChatMessage(broadcasterId: string, message: string): Promise<Result<null, string>> {
+	async sendChatMessage(
+		broadcasterId: string,
+		message: string
+	): Promise<Result<QueuedMessage | null, PepoError>> {
 		try {
 			return {
 				status: 'ok',
@@ -81,7 +84,7 @@ export const commands = {
 		query: string,
 		broadcasterId: string,
 		limit: number | null
-	): Promise<Result<Emote[], string>> {
+	): Promise<Result<Emote[], PepoError>> {
 		try {
 			return {
 				status: 'ok',
@@ -91,371 +94,1546 @@ export const commands = {
 			if (e instanceof Error) throw e;
 			else return { status: 'error', error: e as any };
 		}
-	}
+	},
+	async getChannelHistory(
+		channel: string,
+		before: number | null,
+		limit: number | null
+	): Promise<Result<HistoryRecord[], PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('get_channel_history', { channel, before, limit })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async exportChatLog(
+		channel: string,
+		from: number,
+		to: number,
+		format: ChatLogFormat
+	): Promise<Result<string, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('export_chat_log', { channel, from, to, format })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async searchChatHistory(
+		query: string,
+		cursor: number | null,
+		limit: number | null
+	): Promise<Result<SearchPage, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('search_chat_history', { query, cursor, limit })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getUserCard(broadcasterId: string, userId: string): Promise<Result<UserCard, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('get_user_card', { broadcasterId, userId }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async requestModeratorScopes(): Promise<Result<UserToken, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('request_moderator_scopes') };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async requestBroadcasterScopes(): Promise<Result<UserToken, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('request_broadcaster_scopes') };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async requestColorScopes(): Promise<Result<UserToken, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('request_color_scopes') };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getChannelModeration(
+		broadcasterId: string
+	): Promise<Result<ModerationCapabilities, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('get_channel_moderation', { broadcasterId })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async banUser(
+		broadcasterId: string,
+		userId: string,
+		reason: string | null
+	): Promise<Result<null, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('ban_user', { broadcasterId, userId, reason })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async timeoutUser(
+		broadcasterId: string,
+		userId: string,
+		durationSecs: number,
+		reason: string | null
+	): Promise<Result<null, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('timeout_user', { broadcasterId, userId, durationSecs, reason })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async unbanUser(broadcasterId: string, userId: string): Promise<Result<null, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('unban_user', { broadcasterId, userId }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async deleteChatMessage(
+		broadcasterId: string,
+		messageId: string
+	): Promise<Result<null, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('delete_chat_message', { broadcasterId, messageId })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async clearChat(broadcasterId: string): Promise<Result<null, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('clear_chat', { broadcasterId }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getAutomodQueue(broadcasterId: string): Promise<HeldMessage[]> {
+		return await TAURI_INVOKE('get_automod_queue', { broadcasterId });
+	},
+	async resolveAutomodMessage(messageId: string, allow: boolean): Promise<Result<null, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('resolve_automod_message', { messageId, allow })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async listUnbanRequests(
+		broadcasterId: string,
+		includeResolved: boolean | null
+	): Promise<UnbanRequest[]> {
+		return await TAURI_INVOKE('list_unban_requests', { broadcasterId, includeResolved });
+	},
+	async approveUnbanRequest(
+		requestId: string,
+		resolutionText: string | null
+	): Promise<Result<UnbanRequest, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('approve_unban_request', { requestId, resolutionText })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async denyUnbanRequest(
+		requestId: string,
+		resolutionText: string | null
+	): Promise<Result<UnbanRequest, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('deny_unban_request', { requestId, resolutionText })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getModerationLog(
+		broadcasterId: string,
+		filter: ModLogFilter | null,
+		cursor: number | null,
+		limit: number | null
+	): Promise<Result<ModLogPage, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('get_moderation_log', { broadcasterId, filter, cursor, limit })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async setUserTrustStatus(
+		broadcasterId: string,
+		userId: string,
+		treatment: Treatment | null
+	): Promise<Result<null, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('set_user_trust_status', { broadcasterId, userId, treatment })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async listBlockedTerms(broadcasterId: string): Promise<Result<BlockedTerm[], PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('list_blocked_terms', { broadcasterId }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async addBlockedTerm(
+		broadcasterId: string,
+		text: string
+	): Promise<Result<BlockedTerm, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('add_blocked_term', { broadcasterId, text })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async removeBlockedTerm(broadcasterId: string, termId: string): Promise<Result<null, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('remove_blocked_term', { broadcasterId, termId })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getChatSettings(broadcasterId: string): Promise<Result<ChannelChatSettings, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('get_chat_settings', { broadcasterId }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async updateChatSettings(
+		broadcasterId: string,
+		update: ChatSettingsUpdate
+	): Promise<Result<ChannelChatSettings, PepoError>> {
+		try {
+			return {
+				status: 'ok',
+				data: await TAURI_INVOKE('update_chat_settings', { broadcasterId, update })
+			};
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async listCommands(): Promise<CommandInfo[]> {
+		return await TAURI_INVOKE('list_commands');
+	},
+	async requestWhisperScopes(): Promise<Result<UserToken, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('request_whisper_scopes') };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async sendWhisper(
+		toLogin: string,
+		message: string
+	): Promise<Result<WhisperThreadSummary, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('send_whisper', { toLogin, message }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async listWhisperThreads(): Promise<Result<WhisperThreadSummary[], PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('list_whisper_threads') };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getWhisperThread(peerUserId: string): Promise<Result<Whisper[], PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('get_whisper_thread', { peerUserId }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async setFocusedChannels(logins: string[]): Promise<Result<null, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('set_focused_channels', { logins }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getEventQueueMetrics(): Promise<Result<EventQueueMetrics | null, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('get_event_queue_metrics') };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getHighlightRules(): Promise<HighlightRule[]> {
+		return await TAURI_INVOKE('get_highlight_rules');
+	},
+	async setHighlightRules(rules: HighlightRule[]): Promise<Result<HighlightRule[], PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('set_highlight_rules', { rules }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async getFilterRules(): Promise<FilterRule[]> {
+		return await TAURI_INVOKE('get_filter_rules');
+	},
+	async setFilterRules(rules: FilterRule[]): Promise<Result<FilterRule[], PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('set_filter_rules', { rules }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async setHonorBlockList(honor: boolean): Promise<Result<number, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('set_honor_block_list', { honor }) };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	},
+	async refreshBlockList(): Promise<Result<number, PepoError>> {
+		try {
+			return { status: 'ok', data: await TAURI_INVOKE('refresh_block_list') };
+		} catch (e) {
+			if (e instanceof Error) throw e;
+			else return { status: 'error', error: e as any };
+		}
+	}
+};
+
+/** user-defined events **/
+
+/** user-defined constants **/
+
+/** user-defined types **/
+
+export type AppSettings = {
+	schema_version: number;
+	appearance: AppearanceSettings;
+	layout: LayoutSettings;
+	chat: ChatSettings;
+	emotes: EmoteSettings;
+	channel_cache: ChannelCacheSettings;
+	auth: AuthSettings;
+	eventsub: EventSubSettings;
+	providers: ProviderSettings;
+	history: HistorySettings;
+	backfill: BackfillSettings;
+	chat_log: ChatLogSettings;
+	highlights: HighlightSettings;
+	filters: FilterSettings;
+};
+export type AppearanceSettings = { theme: AppearanceTheme };
+export type AppearanceTheme = 'system' | 'light' | 'dark';
+export type AuthPhase =
+	| 'unauthorized'
+	| 'waitingForDeviceCode'
+	| 'waitingForAuth'
+	| 'failedAuth'
+	| 'authorized';
+export type AuthSettings = {
+	login_activation_delay_ms: number;
+	refresh_supervisor_tick_secs: number;
+	validation_interval_secs: number;
+	refresh_if_remaining_lt_secs: number;
+};
+export type AuthState = { phase: AuthPhase; device_code: string; token: UserToken | null };
+/**
+ * Recent-messages backfill when joining a channel.
+ */
+export type BackfillSettings = {
+	/**
+	 * Off until the user opts in, since every joined channel is sent to
+	 * `base_url`.
+	 */
+	enabled: boolean;
+	/**
+	 * Service base URL; the channel login is appended as the last path
+	 * segment, as with the public recent-messages API.
+	 */
+	base_url: string;
+	message_limit: number;
+	request_timeout_secs: number;
+};
+export type Badge = {
+	/**
+	 * An ID that identifies this set of chat badges. For example, Bits or Subscriber.
+	 */
+	set_id: string;
+	/**
+	 * An ID that identifies this version of the badge. The ID can be any value.
+	 * For example, for Bits, the ID is the Bits tier level, but for World of Warcraft, it could be Alliance or Horde.
+	 */
+	id: string;
+	/**
+	 * URL to png of size 28x28
+	 */
+	image_url_1x: string;
+	/**
+	 * URL to png of size 56x56
+	 */
+	image_url_2x: string;
+	/**
+	 * URL to png of size 112x112
+	 */
+	image_url_4x: string;
+	/**
+	 * Title of the badge
+	 */
+	title: string;
+	/**
+	 * Descrition of the badge
+	 */
+	description: string;
+};
+export type BadgeRef = {
+	/**
+	 * An ID that identifies this set of chat badges. For example, Bits or Subscriber.
+	 */
+	set_id: string;
+	/**
+	 * An ID that identifies this version of the badge. The ID can be any value.
+	 * For example, for Bits, the ID is the Bits tier level, but for World of Warcraft, it could be Alliance or Horde.
+	 */
+	id: string;
+	/**
+	 * Contains metadata related to the chat badges in the badges tag.
+	 * Currently, this tag contains metadata only for subscriber badges, to indicate the number of months the user has been a subscriber.
+	 */
+	info: string;
+	/**
+	 * The info for displaying the badge
+	 */
+	badge: Badge;
+};
+/**
+ * Twitch's estimate of whether someone is evading a ban in the channel.
+ */
+export type BanEvasion = 'possible' | 'likely';
+/**
+ * A term AutoMod blocks in the channel.
+ */
+export type BlockedTerm = {
+	id: string;
+	text: string;
+	/**
+	 * Who added it.
+	 */
+	moderator_id: string;
+	created_at: string;
+	/**
+	 * Set for terms AutoMod added temporarily.
+	 */
+	expires_at: string | null;
+};
+export type Broadcaster = {
+	/**
+	 * An ID that uniquely identifies the broadcaster that this user is following.
+	 */
+	id: string;
+	/**
+	 * The broadcaster’s login name.
+	 */
+	login: string;
+	/**
+	 * The broadcaster’s display name.
+	 */
+	display_name: string;
+	profile_image_url: string;
+	offline_image_url: string;
+	description: string;
+	created_at: string;
+};
+export type ChannelCache = { channels: Partial<{ [key in string]: ChannelStatus }> };
+export type ChannelCacheSettings = {
+	recurring_poll_enabled: boolean;
+	poll_interval_secs: number;
+	error_log_throttle_enabled: boolean;
+	error_log_throttle_secs: number;
+	user_lookup_chunk_size: number;
+};
+/**
+ * A channel's chat settings as a moderator sees them.
+ */
+export type ChannelChatSettings = {
+	emote_mode: boolean;
+	follower_mode: boolean;
+	/**
+	 * Minutes.
+	 */
+	follower_mode_duration: number | null;
+	non_moderator_chat_delay: boolean;
+	/**
+	 * Seconds.
+	 */
+	non_moderator_chat_delay_duration: number | null;
+	slow_mode: boolean;
+	/**
+	 * Seconds.
+	 */
+	slow_mode_wait_time: number | null;
+	subscriber_mode: boolean;
+	unique_chat_mode: boolean;
+};
+export type ChannelInfo = {
+	/**
+	 * Twitch User ID of this channel owner
+	 */
+	broadcaster_id: string;
+	/**
+	 * Twitch User login of this channel owner
+	 */
+	broadcaster_login: string;
+	/**
+	 * Twitch user display name of this channel owner
+	 */
+	broadcaster_name: string;
+	/**
+	 * Current game ID being played on the channel
+	 */
+	game_id: string;
+	/**
+	 * Name of the game being played on the channel
+	 */
+	game_name: string;
+	/**
+	 * Language of the channel
+	 */
+	broadcaster_language: string;
+	/**
+	 * Title of the stream
+	 */
+	title: string;
+	/**
+	 * Description of the stream
+	 */
+	description?: string;
+	/**
+	 * Stream delay in seconds
+	 *
+	 * # Notes
+	 *
+	 * This value may not be accurate, it'll only be accurate when the token belongs to the broadcaster and they are partnered.
+	 */
+	delay?: number;
+	/**
+	 * The tags applied to the channel.
+	 */
+	tags: string[];
+	/**
+	 * Boolean flag indicating if the channel has branded content.
+	 */
+	is_branded_content: boolean;
+	/**
+	 * Profile image URL of the broadcaster
+	 */
+	profile_image_url?: string;
+};
+export type ChannelMessage = {
+	ts: string;
+	/**
+	 * The broadcaster user ID.
+	 */
+	broadcaster_user_id: string;
+	/**
+	 * The broadcaster display name.
+	 */
+	broadcaster_user_name: string;
+	/**
+	 * The broadcaster login.
+	 */
+	broadcaster_user_login: string;
+	/**
+	 * The user ID of the user that sent the message.
+	 */
+	chatter_user_id: string;
+	/**
+	 * The user name of the user that sent the message.
+	 */
+	chatter_user_name: string;
+	/**
+	 * The login of the user that sent the message.
+	 */
+	chatter_user_login?: string;
+	/**
+	 * A UUID that identifies the message.
+	 */
+	message_id: string;
+	text: string;
+	/**
+	 * The parsed fragments of the text field for rendering
+	 */
+	fragments: Fragment[];
+	/**
+	 * The type of message.
+	 */
+	message_type: ChannelMessageType;
+	/**
+	 * List of chat badges.
+	 */
+	badges: BadgeRef[];
+	/**
+	 * Metadata if this message is a cheer.
+	 * The color of the user's name in the chat room.
+	 * This is a hexadecimal RGB color code in the form, `#<RGB>`.
+	 * This may be empty if it is never set.
+	 */
+	color: string;
+	/**
+	 * Translated message text when available.
+	 */
+	translation: ChannelMessageTranslation | null;
+	/**
+	 * A stable message int that can be used by the UI
+	 */
+	index: number;
+	/**
+	 * Set on messages loaded from the recent-messages service on join.
+	 */
+	is_backfill?: boolean;
+	/**
+	 * Who removed the message, once a moderator deletes it or bans or times
+	 * out its sender.
+	 */
+	moderation?: MessageModeration | null;
+	/**
+	 * The user's highlight rules this message matched.
+	 */
+	highlight?: MessageHighlight | null;
+	/**
+	 * Set when a filter collapsed the message instead of dropping it.
+	 */
+	filtered?: FilterReason | null;
+	/**
+	 * Set in channels we moderate when the chatter is monitored, restricted
+	 * or suspected of ban evasion.
+	 */
+	trust_status?: TrustStatus | null;
+};
+export type ChannelMessageTranslation = {
+	source_language: string;
+	target_language: string;
+	translated_text: string;
+};
+export type ChannelMessageTranslationUpdate = {
+	message_id: string;
+	translation: ChannelMessageTranslation;
+};
+export type ChannelMessageType =
+	/**
+	 * An Unknown Message Type
+	 */
+	| 'unknown'
+	/**
+	 * A regular text message
+	 */
+	| 'text'
+	/**
+	 * A highlighted message with channel points
+	 */
+	| 'channel_points_highlighted'
+	/**
+	 * A message sent with channel points during sub-only mode
+	 */
+	| 'channel_points_sub_only'
+	/**
+	 * A first message from a user
+	 */
+	| 'user_intro'
+	/**
+	 * A gigantified emote
+	 */
+	| 'power_ups_gigantified_emote'
+	/**
+	 * A message sent with effects
+	 */
+	| 'power_ups_message_effect';
+/**
+ * A `channel.chat.notification` event: subs, raids, announcements and the like.
+ */
+export type ChannelNotice = {
+	ts: string;
+	broadcaster_user_id: string;
+	broadcaster_user_login: string;
+	/**
+	 * Empty when the notice was triggered anonymously.
+	 */
+	chatter_user_id: string;
+	chatter_user_name: string;
+	message_id: string;
+	/**
+	 * Twitch's `notice_type`, e.g. `sub`, `raid` or `announcement`.
+	 */
+	notice_type: string;
+	system_message: string;
+	/**
+	 * The chatter's own message, if they attached one.
+	 */
+	text: string;
 };
-
-/** user-defined events **/
-
-/** user-defined constants **/
-
-/** user-defined types **/
-
-export type AppSettings = {
-	schema_version: number;
-	appearance: AppearanceSettings;
-	layout: LayoutSettings;
-	chat: ChatSettings;
-	emotes: EmoteSettings;
-	channel_cache: ChannelCacheSettings;
-	auth: AuthSettings;
-	eventsub: EventSubSettings;
-	providers: ProviderSettings;
+/**
+ * Queue depth for one channel.
+ */
+export type ChannelQueueMetrics = {
+	login: string;
+	depth: number;
+	/**
+	 * Chat messages shed from this channel since login.
+	 */
+	dropped: number;
+	focused: boolean;
 };
-export type AppearanceSettings = { theme: AppearanceTheme };
-export type AppearanceTheme = 'system' | 'light' | 'dark';
-export type AuthPhase =
-	| 'unauthorized'
-	| 'waitingForDeviceCode'
-	| 'waitingForAuth'
-	| 'failedAuth'
-	| 'authorized';
-export type AuthSettings = {
-	login_activation_delay_ms: number;
-	refresh_supervisor_tick_secs: number;
-	validation_interval_secs: number;
-	refresh_if_remaining_lt_secs: number;
+/**
+ * The active user's standing in a channel.
+ */
+export type ChannelRole = 'broadcaster' | 'lead_moderator' | 'moderator' | 'viewer';
+export type ChannelStatus = {
+	broadcaster_id: string;
+	login: string;
+	display_name: string;
+	profile_image_url: string;
+	is_live: boolean;
+	stream: Stream | null;
 };
-export type AuthState = { phase: AuthPhase; device_code: string; token: UserToken | null };
-export type Badge = {
+export type ChatLogFormat =
 	/**
-	 * An ID that identifies this set of chat badges. For example, Bits or Subscriber.
+	 * One JSON-encoded `ChannelMessage` per line.
 	 */
-	set_id: string;
+	| 'jsonl'
 	/**
-	 * An ID that identifies this version of the badge. The ID can be any value.
-	 * For example, for Bits, the ID is the Bits tier level, but for World of Warcraft, it could be Alliance or Horde.
+	 * `[date time] name: text`, with emotes as their names.
 	 */
-	id: string;
+	| 'plain'
 	/**
-	 * URL to png of size 28x28
+	 * Chatterino's `Twitch/Channels/<channel>/<channel>-<date>.log` layout.
 	 */
-	image_url_1x: string;
+	| 'chatterino';
+/**
+ * Which daily log files to keep, mirroring the log plugin's `RotationStrategy`.
+ */
+export type ChatLogRotation =
+	| 'keep_all'
 	/**
-	 * URL to png of size 56x56
+	 * Delete files older than `keep_days`.
 	 */
-	image_url_2x: string;
+	| 'keep_days';
+export type ChatLogSettings = {
 	/**
-	 * URL to png of size 112x112
+	 * Append every live message to a daily per-channel file.
 	 */
-	image_url_4x: string;
+	always_log: boolean;
 	/**
-	 * Title of the badge
+	 * Where logs and exports are written; empty means the app data directory.
 	 */
-	title: string;
+	directory: string;
+	format: ChatLogFormat;
+	rotation: ChatLogRotation;
+	keep_days: number;
+};
+export type ChatMode =
+	| 'emote_only'
+	| 'followers_only'
+	| 'slow'
+	| 'subscribers_only'
+	| 'unique_chat';
+export type ChatSettings = {
+	message_limit: number;
+	autoscroll_threshold_px: number;
+	show_timestamps: boolean;
+	timestamp_locale: string;
+	timestamp_style: TimestampStyle;
+	translation_layout: ChatTranslationLayout;
+	show_badges: boolean;
+	show_emotes: boolean;
+	alternate_backgrounds: boolean;
 	/**
-	 * Descrition of the badge
+	 * Local slash commands, expanded before the built-in ones run.
+	 */
+	command_aliases: CommandAlias[];
+	/**
+	 * Accounts Twitch verified as bots, which get a much higher chat limit.
+	 */
+	verified_bot_user_ids: string[];
+	/**
+	 * Send incoming messages to the chat view in batches on
+	 * `chat_messages:{login}` instead of one `chat_message:{login}` event each.
+	 */
+	batch_messages: boolean;
+	/**
+	 * How long a channel's messages are held before a batch goes out.
+	 */
+	batch_window_ms: number;
+	/**
+	 * A batch goes out early once this many messages are waiting.
+	 */
+	batch_max_messages: number;
+};
+/**
+ * Chat settings to change; `None` leaves a setting as it is. Setting a
+ * duration turns its mode on.
+ */
+export type ChatSettingsUpdate = {
+	emote_mode: boolean | null;
+	follower_mode: boolean | null;
+	/**
+	 * Minutes someone must have followed for, 0 to 129600.
 	 */
+	follower_mode_duration: number | null;
+	non_moderator_chat_delay: boolean | null;
+	/**
+	 * 2, 4 or 6 seconds.
+	 */
+	non_moderator_chat_delay_duration: number | null;
+	slow_mode: boolean | null;
+	/**
+	 * Seconds between messages, 3 to 120.
+	 */
+	slow_mode_wait_time: number | null;
+	subscriber_mode: boolean | null;
+	unique_chat_mode: boolean | null;
+};
+export type ChatTranslationLayout = 'language_tag' | 'message_text' | 'timestamp_end' | 'connector';
+export type CheerFragment = { index: number; text: string };
+/**
+ * A local shortcut: `/{name} args` runs `{expansion} args`.
+ */
+export type CommandAlias = {
+	name: string;
+	/**
+	 * A command line such as `/timeout` or `/announce Stream starts soon`,
+	 * or plain text to send.
+	 */
+	expansion: string;
+};
+/**
+ * One entry in the chat input's command autocomplete.
+ */
+export type CommandInfo = {
+	name: string;
+	aliases: string[];
+	usage: string;
 	description: string;
+	permission: Permission;
+	/**
+	 * Set for local aliases.
+	 */
+	expansion: string | null;
 };
-export type BadgeRef = {
+/**
+ * Where a message from the chat input is on its way to Twitch.
+ */
+export type DeliveryStatus =
 	/**
-	 * An ID that identifies this set of chat badges. For example, Bits or Subscriber.
+	 * Waiting for room under the rate limit.
 	 */
-	set_id: string;
+	| { status: 'queued' }
+	| { status: 'sent'; message_id: string | null }
 	/**
-	 * An ID that identifies this version of the badge. The ID can be any value.
-	 * For example, for Bits, the ID is the Bits tier level, but for World of Warcraft, it could be Alliance or Horde.
+	 * Twitch accepted the request but didn't post the message, e.g. AutoMod
+	 * held it or the channel is in followers-only mode.
 	 */
+	| { status: 'dropped'; code: string; message: string }
+	| { status: 'failed'; error: PepoError };
+/**
+ * Emitted on `message_delivery` each time a message changes status.
+ */
+export type DeliveryUpdate = {
 	id: string;
+	broadcaster_id: string;
+	text: string;
+	status: DeliveryStatus;
+};
+export type Emote = {
 	/**
-	 * Contains metadata related to the chat badges in the badges tag.
-	 * Currently, this tag contains metadata only for subscriber badges, to indicate the number of months the user has been a subscriber.
+	 * ID of the emote.
 	 */
-	info: string;
+	id: string;
 	/**
-	 * The info for displaying the badge
+	 * Name of the emote a viewer types into Twitch chat for the image to appear.
 	 */
-	badge: Badge;
+	name: string;
+	tier: string;
+	/**
+	 * If the emote_type is "subscriptions", this indicates the subscriber tier at which the emote is unlocked. Set to an empty string otherwise.
+	 * The type of emote.
+	 *
+	 * The most common values for custom channel emotes are
+	 *
+	 * `subscriptions`: Indicates a custom subscriber emote.
+	 *
+	 * `bitstier`: Indicates a custom Bits tier emote.
+	 *
+	 * `follower`: Indicates a custom follower emote.
+	 */
+	emote_type: string;
+	/**
+	 * ID of the emote set the emote belongs to.
+	 */
+	emote_set_id: string;
+	/**
+	 * The formats that the emote is available in.
+	 */
+	format: string[];
+	/**
+	 * The sizes that the emote is available in.
+	 */
+	scale: string[];
+	/**
+	 * The background themes that the emote is available in.
+	 */
+	theme_mode: string[];
+	/**
+	 * Fully constructed URL for the emote image.
+	 */
+	url: string;
+	/**
+	 * The provider this emote comes from (e.g. "Twitch", "BTTV").
+	 */
+	provider: string;
+	/**
+	 * The scope of the emote (e.g. "Global", "Channel").
+	 */
+	scope: string;
+	/**
+	 * Whether the emote image is animated.
+	 */
+	animated?: boolean;
+	/**
+	 * Intrinsic width at 1x, in CSS pixels, when the provider reports it.
+	 */
+	width?: number | null;
+	/**
+	 * Intrinsic height at 1x, in CSS pixels, when the provider reports it.
+	 */
+	height?: number | null;
+	/**
+	 * Per-density image URLs, ordered from smallest to largest scale.
+	 */
+	images?: EmoteImage[];
 };
-export type Broadcaster = {
+export type EmoteFragment = { index: number; emote: Emote };
+/**
+ * One rendition of an emote at a given display density.
+ */
+export type EmoteImage = {
 	/**
-	 * An ID that uniquely identifies the broadcaster that this user is following.
+	 * Display density multiplier: 1, 2 or 4.
+	 */
+	scale: number;
+	/**
+	 * URL for dark backgrounds, or the only URL when the provider has no
+	 * themed variants.
+	 */
+	url: string;
+	/**
+	 * URL for light backgrounds when the provider serves a separate image.
+	 */
+	light_url: string | null;
+};
+export type EmoteProviderId = 'twitch' | 'bttv' | 'ffz' | 'seventv' | 'local';
+export type EmoteProviderPreference = { id: EmoteProviderId; enabled: boolean };
+export type EmoteSettings = {
+	providers: EmoteProviderPreference[];
+	autocomplete_enabled: boolean;
+	autocomplete_min_chars: number;
+	search_debounce_ms: number;
+	autocomplete_result_limit: number;
+	picker_result_limit: number;
+	picker_columns: number;
+	picker_max_height_px: number;
+	inline_emote_px: number;
+	inline_badge_px: number;
+	/**
+	 * Folder of images or JSON manifest for the local emote provider.
+	 * Empty disables it.
+	 */
+	local_emotes_path: string;
+	local_emotes_poll_ms: number;
+};
+/**
+ * A snapshot of the EventSub queue, busiest channel first.
+ */
+export type EventQueueMetrics = {
+	capacity: number;
+	/**
+	 * Events waiting across every channel.
+	 */
+	depth: number;
+	channels: ChannelQueueMetrics[];
+};
+export type EventSubSettings = {
+	socket_idle_timeout_secs: number;
+	retry_base_secs: number;
+	retry_max_secs: number;
+	debug_cost_watcher_enabled: boolean;
+	debug_cost_watcher_interval_secs: number;
+	repeated_log_throttle_enabled: boolean;
+	unparseable_warning_throttle_secs: number;
+	subscription_error_throttle_secs: number;
+	/**
+	 * Events held across all channels before chat messages start being shed.
+	 */
+	queue_capacity: number;
+	overload_policy: OverloadPolicy;
+};
+/**
+ * What happens to a message an ignore or badge rule catches.
+ */
+export type FilterAction =
+	/**
+	 * Never reaches the chat view or the history.
+	 */
+	| 'drop'
+	/**
+	 * Kept with `filtered` set, for the UI to show as a placeholder.
+	 */
+	| 'collapse';
+export type FilterKind =
+	/**
+	 * Messages from the user whose login is `pattern`.
+	 */
+	| 'ignore_user'
+	/**
+	 * Messages whose text matches the regular expression `pattern`.
+	 */
+	| 'ignore_pattern'
+	/**
+	 * Rewrites the word `pattern` to `replacement`, e.g. to blur spoilers.
+	 */
+	| 'replace_word'
+	/**
+	 * Messages from chatters with a badge from the `pattern` set.
 	 */
+	| 'hide_badge';
+/**
+ * Why a message was collapsed. `rule_id` is unset for the Twitch block list.
+ */
+export type FilterReason = { rule_id: string | null; reason: string };
+export type FilterRule = {
 	id: string;
+	enabled: boolean;
+	kind: FilterKind;
+	pattern: string;
+	case_sensitive: boolean;
 	/**
-	 * The broadcaster’s login name.
+	 * Only used by `replace_word`.
 	 */
-	login: string;
+	replacement: string;
 	/**
-	 * The broadcaster’s display name.
+	 * Ignored by `replace_word`.
 	 */
-	display_name: string;
-	profile_image_url: string;
-	offline_image_url: string;
-	description: string;
-	created_at: string;
+	action: FilterAction;
+	/**
+	 * Channel logins the rule applies to; empty means every channel.
+	 */
+	channels: string[];
 };
-export type ChannelCache = { channels: Partial<{ [key in string]: ChannelStatus }> };
-export type ChannelCacheSettings = {
-	recurring_poll_enabled: boolean;
-	poll_interval_secs: number;
-	error_log_throttle_enabled: boolean;
-	error_log_throttle_secs: number;
-	user_lookup_chunk_size: number;
+export type FilterSettings = {
+	/**
+	 * Saved through `set_filter_rules`, which rejects broken regexes.
+	 */
+	rules: FilterRule[];
+	/**
+	 * Also filter chatters the account blocked on Twitch. Turned on through
+	 * `set_honor_block_list`, which loads the list.
+	 */
+	honor_block_list: boolean;
+	block_list_action: FilterAction;
 };
-export type ChannelInfo = {
+export type Fragment = { Text: TextFragment } | { Emote: EmoteFragment } | { Cheer: CheerFragment };
+export type HeldFragment =
+	| { type: 'text'; text: string }
+	| { type: 'emote'; text: string; id: string }
+	| { type: 'cheermote'; text: string };
+/**
+ * A chat message AutoMod is holding for review.
+ */
+export type HeldMessage = {
+	message_id: string;
+	broadcaster_user_id: string;
+	broadcaster_user_login: string;
+	user_id: string;
+	user_login: string;
+	user_name: string;
+	text: string;
+	fragments: HeldFragment[];
 	/**
-	 * Twitch User ID of this channel owner
+	 * The AutoMod category that flagged it, e.g. `swearing`.
 	 */
-	broadcaster_id: string;
+	category: string;
 	/**
-	 * Twitch User login of this channel owner
+	 * How strongly it matched the category, 0 (weakest) to 4.
 	 */
-	broadcaster_login: string;
+	level: number;
+	held_at: string;
+};
+export type HighlightKind =
+	/**
+	 * `pattern` appears anywhere in the text.
+	 */
+	| 'keyword'
+	/**
+	 * `pattern` is a regular expression matched against the text.
+	 */
+	| 'regex'
+	/**
+	 * `pattern` appears as a word of its own.
+	 */
+	| 'whole_word'
+	/**
+	 * The chatter's login is `pattern`.
+	 */
+	| 'username'
 	/**
-	 * Twitch user display name of this channel owner
+	 * The chatter has a badge from the `pattern` set, e.g. `moderator` or `vip`.
 	 */
-	broadcaster_name: string;
+	| 'badge'
 	/**
-	 * Current game ID being played on the channel
+	 * Twitch marked the message as the chatter's first in the channel.
 	 */
-	game_id: string;
+	| 'first_time_chatter'
 	/**
-	 * Name of the game being played on the channel
+	 * The text mentions the logged-in account.
 	 */
-	game_name: string;
+	| 'self_mention';
+/**
+ * Emitted on `highlight_notification` when a live message matches a rule
+ * with `notify` set.
+ */
+export type HighlightNotification = {
+	channel_login: string;
+	chatter_user_name: string;
+	message_id: string;
+	text: string;
+	rule_ids: string[];
+};
+export type HighlightRule = {
+	id: string;
+	enabled: boolean;
+	kind: HighlightKind;
+	pattern: string;
+	case_sensitive: boolean;
 	/**
-	 * Language of the channel
+	 * CSS colour for matching messages.
 	 */
-	broadcaster_language: string;
+	color: string;
 	/**
-	 * Title of the stream
+	 * When several rules match, the highest priority picks the colour.
 	 */
-	title: string;
+	priority: number;
 	/**
-	 * Description of the stream
+	 * Ask for a desktop notification when a live message matches.
 	 */
-	description?: string;
+	notify: boolean;
 	/**
-	 * Stream delay in seconds
-	 *
-	 * # Notes
-	 *
-	 * This value may not be accurate, it'll only be accurate when the token belongs to the broadcaster and they are partnered.
+	 * Channel logins the rule applies to; empty means every channel.
 	 */
-	delay?: number;
+	channels: string[];
+};
+export type HighlightSettings = {
 	/**
-	 * The tags applied to the channel.
+	 * Saved through `set_highlight_rules`, which rejects broken regexes.
 	 */
-	tags: string[];
+	rules: HighlightRule[];
+};
+/**
+ * Everything the history database records for a channel.
+ */
+export type HistoryEntry =
+	| ({ kind: 'message' } & ChannelMessage)
+	| ({ kind: 'notice' } & ChannelNotice)
+	| ({ kind: 'moderation' } & ModerationEvent)
 	/**
-	 * Boolean flag indicating if the channel has branded content.
+	 * A `channel.moderate` event, only delivered in channels we moderate.
 	 */
-	is_branded_content: boolean;
+	| ({ kind: 'mod_action' } & ModLogEntry);
+export type HistoryRecord = {
 	/**
-	 * Profile image URL of the broadcaster
+	 * Row id; pass it back as `before` to page further into the past.
 	 */
-	profile_image_url?: string;
+	id: number;
+	sent_at_ms: number;
+	entry: HistoryEntry;
 };
-export type ChannelMessage = {
-	ts: string;
+export type HistorySettings = {
+	enabled: boolean;
 	/**
-	 * The broadcaster user ID.
+	 * Newest entries kept per channel, unless overridden below.
 	 */
-	broadcaster_user_id: string;
+	max_entries_per_channel: number;
 	/**
-	 * The broadcaster display name.
+	 * Per-channel overrides of `max_entries_per_channel`, keyed by login.
 	 */
-	broadcaster_user_name: string;
+	channel_max_entries: Partial<{ [key in string]: number }>;
 	/**
-	 * The broadcaster login.
+	 * Newest entries kept across all channels.
 	 */
+	max_total_entries: number;
+	retention_days: number;
+	write_batch_size: number;
+	write_flush_ms: number;
+};
+export type InternalState = { version: string; name: string; sidebar_open: boolean };
+export type LayoutSettings = { sidebar_open: boolean };
+/**
+ * A matched span of a message's text, in UTF-16 code units so it indexes
+ * JavaScript strings directly.
+ */
+export type MatchHighlight = { start: number; end: number };
+/**
+ * Emitted on `message_moderated:{login}` to annotate messages already in chat.
+ */
+export type MessageAnnotation = {
 	broadcaster_user_login: string;
+	target_user_id: string;
 	/**
-	 * The user ID of the user that sent the message.
+	 * The one message affected; `None` means every earlier message from
+	 * `target_user_id`, as for bans and timeouts.
 	 */
-	chatter_user_id: string;
+	message_id: string | null;
+	moderation: MessageModeration;
+};
+/**
+ * The matching rules of a message, attached as `ChannelMessage::highlight`.
+ */
+export type MessageHighlight = {
 	/**
-	 * The user name of the user that sent the message.
+	 * Highest priority first.
 	 */
-	chatter_user_name: string;
+	rule_ids: string[];
+	color: string;
+	notify: boolean;
+};
+/**
+ * Attached to a `ChannelMessage` once a moderator removed it.
+ */
+export type MessageModeration = {
 	/**
-	 * A UUID that identifies the message.
+	 * The audit log action type, e.g. `delete_message` or `ban`.
 	 */
-	message_id: string;
-	text: string;
+	action: string;
+	moderator_user_id: string;
+	moderator_user_name: string;
 	/**
-	 * The parsed fragments of the text field for rendering
+	 * Ready to display, e.g. `deleted by SomeMod`.
 	 */
-	fragments: Fragment[];
+	label: string;
+};
+export type ModAction =
+	| { type: 'ban'; target: ModUser; reason: string | null }
+	| { type: 'unban'; target: ModUser }
+	| { type: 'timeout'; target: ModUser; reason: string | null; expires_at: string }
+	| { type: 'untimeout'; target: ModUser }
+	| { type: 'delete_message'; target: ModUser; message_id: string; text: string }
+	| { type: 'warn'; target: ModUser; reason: string | null; chat_rules_cited: string[] }
+	| { type: 'raid'; target: ModUser; viewer_count: number }
+	| { type: 'unraid'; target: ModUser }
+	| { type: 'vip'; target: ModUser }
+	| { type: 'unvip'; target: ModUser }
+	| { type: 'mod'; target: ModUser }
+	| { type: 'unmod'; target: ModUser }
+	| { type: 'approve_unban_request'; target: ModUser; message: string }
+	| { type: 'deny_unban_request'; target: ModUser; message: string }
+	| { type: 'chat_mode'; mode: ChatMode; enabled: boolean; duration_seconds: number | null }
+	| { type: 'terms'; list: TermList; added: boolean; terms: string[]; from_automod: boolean };
+/**
+ * One `channel.moderate` event: who did what, to whom.
+ */
+export type ModLogEntry = {
+	ts: string;
+	broadcaster_user_id: string;
+	broadcaster_user_login: string;
 	/**
-	 * The type of message.
+	 * The channel it happened in when that's another channel of a shared
+	 * chat session.
 	 */
-	message_type: ChannelMessageType;
+	source_broadcaster_user_login: string | null;
+	moderator: ModUser;
+	action: ModAction;
+};
+/**
+ * Narrows the audit log. Users match by id or login.
+ */
+export type ModLogFilter = {
+	moderator: string | null;
+	target: string | null;
 	/**
-	 * List of chat badges.
+	 * An action type as in `ModAction`, e.g. `timeout`.
 	 */
-	badges: BadgeRef[];
+	action: string | null;
+};
+export type ModLogPage = {
 	/**
-	 * Metadata if this message is a cheer.
-	 * The color of the user's name in the chat room.
-	 * This is a hexadecimal RGB color code in the form, `#<RGB>`.
-	 * This may be empty if it is never set.
+	 * Newest first.
 	 */
-	color: string;
+	records: ModLogRecord[];
 	/**
-	 * Translated message text when available.
+	 * Set when more results may follow.
 	 */
-	translation: ChannelMessageTranslation | null;
+	next_cursor: number | null;
+};
+export type ModLogRecord = {
 	/**
-	 * A stable message int that can be used by the UI
+	 * History row id; pass the last one back as `cursor` for the next page.
 	 */
-	index: number;
-};
-export type ChannelMessageTranslation = {
-	source_language: string;
-	target_language: string;
-	translated_text: string;
-};
-export type ChannelMessageTranslationUpdate = {
-	message_id: string;
-	translation: ChannelMessageTranslation;
+	id: number;
+	sent_at_ms: number;
+	entry: ModLogEntry;
 };
-export type ChannelMessageType =
+export type ModUser = { user_id: string; user_login: string; user_name: string };
+export type ModerationAction =
 	/**
-	 * An Unknown Message Type
+	 * A moderator cleared the whole chat.
 	 */
-	| 'unknown'
+	| { type: 'clear_chat' }
 	/**
-	 * A regular text message
+	 * A user's messages were purged, e.g. by a ban or timeout.
 	 */
-	| 'text'
+	| { type: 'clear_user_messages'; target_user_id: string; target_user_login: string }
+	| {
+			type: 'delete_message';
+			target_user_id: string;
+			target_user_login: string;
+			message_id: string;
+	  };
+/**
+ * Which moderation actions will succeed for the active user in a channel.
+ */
+export type ModerationCapabilities = {
+	role: ChannelRole;
 	/**
-	 * A highlighted message with channel points
+	 * Moderator scopes the active token lacks; `request_moderator_scopes`
+	 * re-authorizes with them.
 	 */
-	| 'channel_points_highlighted'
+	missing_scopes: string[];
 	/**
-	 * A message sent with channel points during sub-only mode
+	 * Ban, timeout and unban.
 	 */
-	| 'channel_points_sub_only'
+	can_ban: boolean;
 	/**
-	 * A first message from a user
+	 * Delete single messages and clear chat.
 	 */
-	| 'user_intro'
+	can_delete_messages: boolean;
 	/**
-	 * A gigantified emote
+	 * See and resolve messages held by AutoMod.
 	 */
-	| 'power_ups_gigantified_emote'
+	can_manage_automod: boolean;
 	/**
-	 * A message sent with effects
+	 * Approve or deny unban requests.
 	 */
-	| 'power_ups_message_effect';
-export type ChannelStatus = {
-	broadcaster_id: string;
-	login: string;
-	display_name: string;
-	profile_image_url: string;
-	is_live: boolean;
-	stream: Stream | null;
+	can_resolve_unban_requests: boolean;
+	/**
+	 * Mark chatters monitored or restricted.
+	 */
+	can_manage_suspicious_users: boolean;
+	/**
+	 * Change slow mode, followers-only mode and the other chat settings.
+	 */
+	can_manage_chat_settings: boolean;
+	/**
+	 * List, add and remove blocked terms.
+	 */
+	can_manage_blocked_terms: boolean;
 };
-export type ChatSettings = {
-	message_limit: number;
-	autoscroll_threshold_px: number;
-	show_timestamps: boolean;
-	timestamp_locale: string;
-	timestamp_style: TimestampStyle;
-	translation_layout: ChatTranslationLayout;
-	show_badges: boolean;
-	show_emotes: boolean;
-	alternate_backgrounds: boolean;
+export type ModerationEvent = {
+	ts: string;
+	broadcaster_user_id: string;
+	broadcaster_user_login: string;
+	action: ModerationAction;
 };
-export type ChatTranslationLayout = 'language_tag' | 'message_text' | 'timestamp_end' | 'connector';
-export type CheerFragment = { index: number; text: string };
-export type Emote = {
+/**
+ * Emitted on `chat_overloaded:{login}` where messages were shed.
+ */
+export type OverloadMarker = { dropped: number };
+/**
+ * What to shed once the EventSub queue is full. Only chat messages are ever
+ * dropped.
+ */
+export type OverloadPolicy =
 	/**
-	 * ID of the emote.
+	 * Drop the oldest queued message of the busiest channel the user isn't
+	 * looking at.
 	 */
-	id: string;
+	| 'shed_busiest_unfocused'
 	/**
-	 * Name of the emote a viewer types into Twitch chat for the image to appear.
+	 * Drop the message that just arrived.
 	 */
-	name: string;
-	tier: string;
+	| 'shed_incoming';
+/**
+ * Why a command failed, for the frontend to react to by `kind`.
+ */
+export type PepoError =
 	/**
-	 * If the emote_type is "subscriptions", this indicates the subscriber tier at which the emote is unlocked. Set to an empty string otherwise.
-	 * The type of emote.
-	 *
-	 * The most common values for custom channel emotes are
-	 *
-	 * `subscriptions`: Indicates a custom subscriber emote.
-	 *
-	 * `bitstier`: Indicates a custom Bits tier emote.
-	 *
-	 * `follower`: Indicates a custom follower emote.
+	 * Nobody is logged in.
 	 */
-	emote_type: string;
+	| { kind: 'auth_missing' }
 	/**
-	 * ID of the emote set the emote belongs to.
+	 * Twitch no longer accepts the token; logging in again fixes it.
 	 */
-	emote_set_id: string;
+	| { kind: 'auth_expired'; message: string }
+	| { kind: 'not_found'; message: string }
 	/**
-	 * The formats that the emote is available in.
+	 * `missing_scopes` lists scopes the token lacks when re-authorizing would
+	 * help; it is empty when the user simply isn't allowed.
 	 */
-	format: string[];
+	| { kind: 'permission_denied'; message: string; missing_scopes: string[] }
 	/**
-	 * The sizes that the emote is available in.
+	 * `retry_after_secs` is set when Twitch said when to try again.
 	 */
-	scale: string[];
+	| { kind: 'rate_limited'; message: string; retry_after_secs: number | null }
 	/**
-	 * The background themes that the emote is available in.
+	 * Rejected before anything was sent, or refused by Twitch as malformed.
 	 */
-	theme_mode: string[];
+	| { kind: 'invalid_input'; message: string }
 	/**
-	 * Fully constructed URL for the emote image.
+	 * Twitch couldn't be reached.
 	 */
-	url: string;
+	| { kind: 'network'; message: string }
 	/**
-	 * The provider this emote comes from (e.g. "Twitch", "BTTV").
+	 * Twitch or another provider answered with an error.
 	 */
-	provider: string;
+	| { kind: 'provider'; message: string }
 	/**
-	 * The scope of the emote (e.g. "Global", "Channel").
+	 * Something went wrong on our side.
 	 */
-	scope: string;
-};
-export type EmoteFragment = { index: number; emote: Emote };
-export type EmoteProviderId = 'twitch' | 'bttv' | 'ffz' | 'seventv';
-export type EmoteProviderPreference = { id: EmoteProviderId; enabled: boolean };
-export type EmoteSettings = {
-	providers: EmoteProviderPreference[];
-	autocomplete_enabled: boolean;
-	autocomplete_min_chars: number;
-	search_debounce_ms: number;
-	autocomplete_result_limit: number;
-	picker_result_limit: number;
-	picker_columns: number;
-	picker_max_height_px: number;
-	inline_emote_px: number;
-	inline_badge_px: number;
-};
-export type EventSubSettings = {
-	socket_idle_timeout_secs: number;
-	retry_base_secs: number;
-	retry_max_secs: number;
-	debug_cost_watcher_enabled: boolean;
-	debug_cost_watcher_interval_secs: number;
-	repeated_log_throttle_enabled: boolean;
-	unparseable_warning_throttle_secs: number;
-	subscription_error_throttle_secs: number;
-};
-export type Fragment = { Text: TextFragment } | { Emote: EmoteFragment } | { Cheer: CheerFragment };
-export type InternalState = { version: string; name: string; sidebar_open: boolean };
-export type LayoutSettings = { sidebar_open: boolean };
+	| { kind: 'internal'; message: string };
+/**
+ * Who may run a command in a channel.
+ */
+export type Permission =
+	| 'everyone'
+	| 'moderator'
+	/**
+	 * The broadcaster or one of their editors. Helix doesn't tell editors
+	 * they are one, so this is left for Twitch to enforce.
+	 */
+	| 'editor'
+	| 'broadcaster';
 export type ProviderSettings = {
 	http_connect_timeout_secs: number;
 	http_request_timeout_secs: number;
 	metadata_retention_enabled: boolean;
 	metadata_retention_secs: number;
+	/**
+	 * Total attempts per fetch, including the first; only transient
+	 * failures (network errors, 5xx, 408, 429) are retried.
+	 */
+	fetch_retry_attempts: number;
+	fetch_retry_base_ms: number;
+	fetch_retry_max_ms: number;
+	/**
+	 * Consecutive transient failures before a provider's breaker opens.
+	 */
+	circuit_breaker_threshold: number;
+	circuit_breaker_cooldown_secs: number;
+	background_retry_enabled: boolean;
+};
+/**
+ * What `send_chat_message` hands back for a chat line.
+ */
+export type QueuedMessage = {
+	/**
+	 * Matches `DeliveryUpdate::id`.
+	 */
+	id: string;
+	/**
+	 * An identical message was queued a moment ago; `id` is that one's and
+	 * nothing new was sent.
+	 */
+	duplicate: boolean;
+};
+export type SearchHit = {
+	/**
+	 * History row id; pass the last one back as `cursor` for the next page.
+	 */
+	id: number;
+	sent_at_ms: number;
+	message: ChannelMessage;
+	highlights: MatchHighlight[];
+};
+export type SearchPage = {
+	/**
+	 * Newest first.
+	 */
+	hits: SearchHit[];
+	/**
+	 * Set when more results may follow.
+	 */
+	next_cursor: number | null;
 };
 export type StateUpdate = { version: number | null; name: string; value: string };
 export type Stream = {
@@ -509,8 +1687,109 @@ export type Stream = {
 	 */
 	viewer_count: number;
 };
+export type TermList = 'blocked' | 'permitted';
 export type TextFragment = { index: number; text: string };
 export type TimestampStyle = 'short' | 'medium' | 'long' | 'full';
+/**
+ * How a moderator chose to treat a suspicious user.
+ */
+export type Treatment =
+	/**
+	 * Their messages show up with a flag for moderators.
+	 */
+	| 'monitored'
+	/**
+	 * Only moderators see their messages.
+	 */
+	| 'restricted';
+/**
+ * Attached to messages from chatters Twitch or a moderator flagged as low
+ * trust in the channel.
+ */
+export type TrustStatus = {
+	treatment: Treatment | null;
+	ban_evasion: BanEvasion | null;
+	/**
+	 * Channels sharing ban info with this one that have banned the user.
+	 */
+	shared_ban_channel_ids: string[];
+};
+/**
+ * Emitted on `trust_status:{login}` whenever a chatter's status changes, so
+ * their messages already in chat can be re-flagged.
+ */
+export type TrustStatusUpdate = {
+	user_id: string;
+	user_login: string;
+	/**
+	 * `None` once the user is no longer flagged.
+	 */
+	trust_status: TrustStatus | null;
+};
+/**
+ * A banned user's request to be unbanned from a channel we moderate.
+ */
+export type UnbanRequest = {
+	id: string;
+	broadcaster_user_id: string;
+	broadcaster_user_login: string;
+	user_id: string;
+	user_login: string;
+	user_name: string;
+	text: string;
+	created_at: string;
+	status: UnbanRequestStatus;
+	/**
+	 * Display name of the moderator who resolved it.
+	 */
+	resolved_by: string | null;
+	resolution_text: string | null;
+	/**
+	 * The requester's last messages in the channel before the ban, oldest
+	 * first, snapshotted when the request arrived so pruning doesn't lose them.
+	 */
+	recent_messages?: ChannelMessage[];
+};
+export type UnbanRequestStatus =
+	| 'pending'
+	| 'approved'
+	| 'denied'
+	/**
+	 * Withdrawn by the requester, or closed by Twitch because they were
+	 * unbanned some other way.
+	 */
+	| 'canceled';
+/**
+ * Everything shown when clicking a chatter's name.
+ */
+export type UserCard = {
+	user_id: string;
+	login: string;
+	display_name: string;
+	profile_image_url: string;
+	description: string;
+	/**
+	 * RFC 3339 account creation time.
+	 */
+	created_at: string;
+	/**
+	 * `"partner"`, `"affiliate"` or empty.
+	 */
+	broadcaster_type: string;
+	/**
+	 * When they followed the broadcaster; `None` if they don't, or the active
+	 * token isn't allowed to see it.
+	 */
+	followed_at: string | null;
+	/**
+	 * Badges on their latest message in this channel.
+	 */
+	badges: BadgeRef[];
+	/**
+	 * Their latest messages in this channel, oldest first.
+	 */
+	recent_messages: ChannelMessage[];
+};
 export type UserToken = {
 	/**
 	 * The access token used to authenticate requests with
@@ -532,6 +1811,40 @@ export type UserToken = {
 	expires_in: number;
 	profile_image_url?: string;
 };
+/**
+ * One whisper in a conversation.
+ */
+export type Whisper = {
+	/**
+	 * Twitch's whisper id; `None` for whispers we sent, which Helix doesn't
+	 * identify.
+	 */
+	id: string | null;
+	text: string;
+	/**
+	 * Sent by the account rather than the peer.
+	 */
+	outgoing: boolean;
+	ts: number;
+};
+/**
+ * Emitted on `whisper` for each whisper received or sent.
+ */
+export type WhisperEvent = {
+	account_user_id: string;
+	whisper: Whisper;
+	thread: WhisperThreadSummary;
+};
+/**
+ * What `list_whisper_threads` shows per conversation.
+ */
+export type WhisperThreadSummary = {
+	peer_user_id: string;
+	peer_user_login: string;
+	peer_user_name: string;
+	last_message: Whisper | null;
+	unread: number;
+};
 
 /** tauri-specta globals **/
 
@@ -0,0 +1,31 @@
+import { describe, expect, it } from 'vitest';
+import { describeError } from './errors';
+
+describe('describeError', () => {
+	it('explains errors without a message', () => {
+		expect(describeError({ kind: 'auth_missing' })).toBe('You are not logged in');
+		expect(describeError({ kind: 'auth_expired', message: 'invalid token' })).toBe(
+			'Your session expired, please log in again'
+		);
+	});
+
+	it('adds missing scopes and retry hints', () => {
+		expect(
+			describeError({
+				kind: 'permission_denied',
+				message: 'not allowed',
+				missing_scopes: ['moderator:manage:banned_users']
+			})
+		).toBe('not allowed (missing moderator:manage:banned_users)');
+		expect(
+			describeError({ kind: 'permission_denied', message: 'not allowed', missing_scopes: [] })
+		).toBe('not allowed');
+		expect(describeError({ kind: 'rate_limited', message: 'slow down', retry_after_secs: 5 })).toBe(
+			'slow down (try again in 5s)'
+		);
+	});
+
+	it('passes other messages through', () => {
+		expect(describeError({ kind: 'network', message: 'timed out' })).toBe('timed out');
+	});
+});
@@ -0,0 +1,24 @@
+import type { PepoError } from '$lib/bindings';
+
+export function describeError(error: PepoError): string {
+	switch (error.kind) {
+		case 'auth_missing':
+			return 'You are not logged in';
+		case 'auth_expired':
+			return 'Your session expired, please log in again';
+		case 'permission_denied':
+			return error.missing_scopes.length > 0
+				? `${error.message} (missing ${error.missing_scopes.join(', ')})`
+				: error.message;
+		case 'rate_limited':
+			return error.retry_after_secs !== null
+				? `${error.message} (try again in ${error.retry_after_secs}s)`
+				: error.message;
+		case 'not_found':
+		case 'invalid_input':
+		case 'network':
+		case 'provider':
+		case 'internal':
+			return error.message;
+	}
+}
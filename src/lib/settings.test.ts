@@ -20,7 +20,8 @@ describe('settings helpers', () => {
 			{ id: 'bttv', enabled: false },
 			{ id: 'twitch', enabled: true },
 			{ id: 'ffz', enabled: true },
-			{ id: 'seventv', enabled: true }
+			{ id: 'seventv', enabled: true },
+			{ id: 'local', enabled: true }
 		]);
 	});
 
@@ -17,14 +17,20 @@ export const DEFAULT_APP_SETTINGS: AppSettings = {
 		translation_layout: 'message_text',
 		show_badges: true,
 		show_emotes: true,
-		alternate_backgrounds: true
+		alternate_backgrounds: true,
+		command_aliases: [],
+		verified_bot_user_ids: [],
+		batch_messages: true,
+		batch_window_ms: 16,
+		batch_max_messages: 100
 	},
 	emotes: {
 		providers: [
 			{ id: 'twitch', enabled: true },
 			{ id: 'bttv', enabled: true },
 			{ id: 'ffz', enabled: true },
-			{ id: 'seventv', enabled: true }
+			{ id: 'seventv', enabled: true },
+			{ id: 'local', enabled: true }
 		],
 		autocomplete_enabled: true,
 		autocomplete_min_chars: 2,
@@ -34,7 +40,9 @@ export const DEFAULT_APP_SETTINGS: AppSettings = {
 		picker_columns: 8,
 		picker_max_height_px: 192,
 		inline_emote_px: 28,
-		inline_badge_px: 20
+		inline_badge_px: 20,
+		local_emotes_path: '',
+		local_emotes_poll_ms: 2000
 	},
 	channel_cache: {
 		recurring_poll_enabled: true,
@@ -57,18 +65,54 @@ export const DEFAULT_APP_SETTINGS: AppSettings = {
 		debug_cost_watcher_interval_secs: 30,
 		repeated_log_throttle_enabled: true,
 		unparseable_warning_throttle_secs: 60,
-		subscription_error_throttle_secs: 300
+		subscription_error_throttle_secs: 300,
+		queue_capacity: 5000,
+		overload_policy: 'shed_busiest_unfocused'
 	},
 	providers: {
 		http_connect_timeout_secs: 5,
 		http_request_timeout_secs: 15,
 		metadata_retention_enabled: true,
-		metadata_retention_secs: 30 * 24 * 60 * 60
+		metadata_retention_secs: 30 * 24 * 60 * 60,
+		fetch_retry_attempts: 3,
+		fetch_retry_base_ms: 250,
+		fetch_retry_max_ms: 4000,
+		circuit_breaker_threshold: 5,
+		circuit_breaker_cooldown_secs: 60,
+		background_retry_enabled: true
+	},
+	history: {
+		enabled: true,
+		max_entries_per_channel: 5000,
+		channel_max_entries: {},
+		max_total_entries: 250000,
+		retention_days: 30,
+		write_batch_size: 200,
+		write_flush_ms: 500
+	},
+	backfill: {
+		enabled: false,
+		base_url: 'https://recent-messages.robotty.de/api/v2/recent-messages',
+		message_limit: 100,
+		request_timeout_secs: 5
+	},
+	chat_log: {
+		always_log: false,
+		directory: '',
+		format: 'chatterino',
+		rotation: 'keep_all',
+		keep_days: 30
+	},
+	highlights: {
+		rules: []
+	},
+	filters: {
+		rules: [],
+		honor_block_list: false,
+		block_list_action: 'drop'
 	}
 };
 
-const PROVIDER_ORDER: EmoteProviderId[] = ['twitch', 'bttv', 'ffz', 'seventv'];
-
 export function normalizeAppSettings(settings: AppSettings): AppSettings {
 	const source = settings ?? DEFAULT_APP_SETTINGS;
 	const appearance = source.appearance ?? DEFAULT_APP_SETTINGS.appearance;
@@ -89,9 +133,9 @@ export function normalizeAppSettings(settings: AppSettings): AppSettings {
 		}
 	}
 
-	for (const id of PROVIDER_ORDER) {
-		if (!seen.has(id)) {
-			providers.push({ id, enabled: true });
+	for (const provider of DEFAULT_APP_SETTINGS.emotes.providers) {
+		if (!seen.has(provider.id)) {
+			providers.push({ ...provider });
 		}
 	}
 